@@ -0,0 +1,211 @@
+//! Input files that may live on an HTTP server, or come from stdin (`-`), and
+//! may be compressed.
+//!
+//! Downloads are kept in memory for the rest of the run, so programming many
+//! boards with `--all` fetches the image once. If the URL pins the content with
+//! a `#sha256=<hex>` suffix, the download is checked against it and stored in
+//! an on-disk cache, keyed by that hash.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use eyre::{Result, WrapErr};
+use sha2::{Digest, Sha256};
+use smol::lock::OnceCell;
+
+use crate::compress;
+
+#[derive(Clone, Debug)]
+pub enum Source {
+    Path(PathBuf),
+    /// `-`, read once and kept like a download.
+    Stdin,
+    Url {
+        url: String,
+        sha256: Option<String>,
+    },
+}
+
+impl FromStr for Source {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(Self::Stdin);
+        }
+        if !(s.starts_with("http://") || s.starts_with("https://")) {
+            return Ok(Self::Path(s.into()));
+        }
+        match s.split_once("#sha256=") {
+            Some((url, hash)) => {
+                let valid = hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+                if !valid {
+                    return Err(eyre::eyre!("invalid sha256 {hash:?}"));
+                }
+                Ok(Self::Url {
+                    url: url.to_owned(),
+                    sha256: Some(hash.to_ascii_lowercase()),
+                })
+            }
+            None => Ok(Self::Url {
+                url: s.to_owned(),
+                sha256: None,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct CacheArgs {
+    /// Where to keep downloads pinned with `#sha256=`. Defaults to
+    /// `$XDG_CACHE_HOME/nafa`.
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Download again even if the file is cached, replacing the cached copy.
+    #[arg(long)]
+    refresh_cache: bool,
+}
+
+impl CacheArgs {
+    fn dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.cache_dir {
+            return Some(dir.clone());
+        }
+        let base = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        Some(base.join("nafa"))
+    }
+}
+
+/// Contents loaded by this process, already decompressed, by URL, or `-` for
+/// stdin. The map is only
+/// locked to find the cell of a URL, so loads of different URLs don't wait on
+/// each other, while loads of the same one wait for the first.
+static LOADED: LazyLock<Mutex<HashMap<String, Loaded>>> = LazyLock::new(Default::default);
+
+type Loaded = Arc<OnceCell<Arc<[u8]>>>;
+
+fn loaded(key: &str) -> Loaded {
+    let mut loaded = LOADED.lock().expect("not poisoned");
+    loaded.entry(key.to_owned()).or_default().clone()
+}
+
+/// The contents of `source`, decompressed if it's a gzip or zstd file.
+pub async fn load(source: &Source, cache: &CacheArgs) -> Result<Arc<[u8]>> {
+    let (url, sha256) = match source {
+        Source::Path(path) => {
+            let data = std::fs::read(path)
+                .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+            return decompress(data.into());
+        }
+        Source::Stdin => return load_stdin().await,
+        Source::Url { url, sha256 } => (url, sha256.as_deref()),
+    };
+
+    let cell = loaded(url);
+    let data = cell
+        .get_or_try_init(async || decompress(fetch(url, sha256, cache).await?))
+        .await?;
+    Ok(data.clone())
+}
+
+fn decompress(data: Arc<[u8]>) -> Result<Arc<[u8]>> {
+    match compress::decompress(&data)? {
+        Some(decompressed) => {
+            tracing::info!(from = data.len(), to = decompressed.len(), "decompressed");
+            Ok(decompressed.into())
+        }
+        None => Ok(data),
+    }
+}
+
+async fn fetch(url: &str, sha256: Option<&str>, cache: &CacheArgs) -> Result<Arc<[u8]>> {
+    let cached = sha256.zip(cache.dir()).map(|(hash, dir)| dir.join(hash));
+    let data = match &cached {
+        Some(path) if !cache.refresh_cache && path.exists() => {
+            tracing::info!(path = %path.display(), "using cached download");
+            let data = std::fs::read(path)?;
+            verify(&data, sha256).wrap_err_with(|| {
+                format!(
+                    "cached file {} is corrupt, use --refresh-cache",
+                    path.display()
+                )
+            })?;
+            data
+        }
+        _ => {
+            let data = download(url).await?;
+            verify(&data, sha256).wrap_err_with(|| format!("while downloading {url}"))?;
+            if let Some(path) = &cached {
+                store(path, &data)?;
+            }
+            data
+        }
+    };
+
+    Ok(data.into())
+}
+
+/// Stdin can only be read once, but the command may run again (`--all`, or
+/// after a reconnect).
+async fn load_stdin() -> Result<Arc<[u8]>> {
+    let cell = loaded("-");
+    let data = cell
+        .get_or_try_init(async || {
+            let data = smol::unblock(|| {
+                let mut data = Vec::new();
+                std::io::Read::read_to_end(&mut std::io::stdin(), &mut data).map(|_| data)
+            })
+            .await
+            .wrap_err("failed to read stdin")?;
+            decompress(data.into())
+        })
+        .await?;
+    Ok(data.clone())
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    tracing::info!(url, "downloading");
+    let url = url.to_owned();
+    smol::unblock(move || {
+        let mut response = ureq::get(&url).call()?;
+        let data = response
+            .body_mut()
+            .with_config()
+            .limit(u64::MAX)
+            .read_to_vec()?;
+        Ok(data)
+    })
+    .await
+}
+
+fn verify(data: &[u8], sha256: Option<&str>) -> Result<()> {
+    let Some(expected) = sha256 else {
+        return Ok(());
+    };
+    let actual = hex::encode(Sha256::digest(data));
+    if actual != expected {
+        return Err(eyre::eyre!(
+            "sha256 mismatch: expected {expected}, got {actual}"
+        ));
+    }
+    Ok(())
+}
+
+/// Write via a temporary file, so an interrupted write never leaves a partial
+/// file under the final name.
+fn store(path: &Path, data: &[u8]) -> Result<()> {
+    let dir = path.parent().expect("cache path has a parent");
+    std::fs::create_dir_all(dir)
+        .wrap_err_with(|| format!("failed to create cache dir {}", dir.display()))?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -1,9 +1,11 @@
 use std::{
     fmt::{Display, Formatter},
     str::FromStr,
+    time::Duration,
 };
 
 use color_eyre::eyre::OptionExt;
+use nafa_io::{ftdi::devices::Interface, jtag::IdCode, units::Bytes};
 
 #[derive(Debug, Clone, Copy)]
 pub struct UsbAddr {
@@ -28,6 +30,67 @@ impl Display for UsbAddr {
     }
 }
 
+/// A single channel of a multi-channel cable, i.e. `ft4232h:A` or
+/// `ft4232h:0`.
+#[derive(Debug, Clone)]
+pub struct CableChannel {
+    pub name: String,
+    pub channel: Interface,
+}
+
+impl FromStr for CableChannel {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, channel) = s.rsplit_once(':').ok_or_eyre("no ':'")?;
+        Ok(Self {
+            name: name.to_owned(),
+            channel: channel.parse()?,
+        })
+    }
+}
+
+/// Which cable to open: a serial number, or `NAME:CHANNEL` for one channel of
+/// a multi-channel cable.
+///
+/// Serial numbers may contain ':' themselves, so only a valid channel after
+/// the last ':' selects a channel. Anything else is taken as a serial.
+#[derive(Debug, Clone)]
+pub enum CableSelector {
+    Serial(String),
+    Channel(CableChannel),
+}
+
+impl FromStr for CableSelector {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(channel) => Ok(Self::Channel(channel)),
+            Err(_) => Ok(Self::Serial(s.to_owned())),
+        }
+    }
+}
+
+/// `CHANNEL=VALUE`, i.e. `B=fifo`.
+#[derive(Debug, Clone, Copy)]
+pub struct PerChannel<T> {
+    pub channel: Interface,
+    pub value: T,
+}
+
+impl<T: FromStr<Err = nafa_io::Error>> FromStr for PerChannel<T> {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (channel, value) = s.split_once('=').ok_or_eyre("no '='")?;
+        Ok(Self {
+            channel: channel.parse()?,
+            value: value.parse()?,
+        })
+    }
+}
+
 #[repr(transparent)]
 #[derive(Clone)]
 pub struct Hex<const N: usize>(pub [u8; N]);
@@ -41,3 +104,77 @@ impl<const N: usize> FromStr for Hex<N> {
         Ok(Self(ret))
     }
 }
+
+/// A size for `--dr-chunk`, which can't be 0.
+pub fn parse_dr_chunk(s: &str) -> color_eyre::Result<Bytes<usize>> {
+    match s.parse()? {
+        Bytes(0) => Err(color_eyre::eyre::eyre!("chunk size must not be 0")),
+        chunk => Ok(chunk),
+    }
+}
+
+/// A 32-bit value, decimal or `0x` hex.
+pub fn parse_u32(s: &str) -> color_eyre::Result<u32> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => Ok(u32::from_str_radix(hex, 16)?),
+        None => Ok(s.parse()?),
+    }
+}
+
+/// A 16-bit value, decimal or `0x` hex.
+pub fn parse_u16(s: &str) -> color_eyre::Result<u16> {
+    Ok(u16::try_from(parse_u32(s)?)?)
+}
+
+/// An IDCODE in hex, with or without `0x`.
+pub fn parse_idcode(s: &str) -> color_eyre::Result<IdCode> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    Ok(IdCode::new(u32::from_str_radix(hex, 16)?))
+}
+
+/// A duration: a number of seconds, or a number followed by `ms`, `s`, or
+/// `m`.
+pub fn parse_duration(s: &str) -> color_eyre::Result<Duration> {
+    let (number, scale) = match s {
+        _ if s.ends_with("ms") => (&s[..s.len() - 2], 1e-3),
+        _ if s.ends_with('s') => (&s[..s.len() - 1], 1.0),
+        _ if s.ends_with('m') => (&s[..s.len() - 1], 60.0),
+        _ => (s, 1.0),
+    };
+    Ok(Duration::try_from_secs_f64(number.parse::<f64>()? * scale)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cable_selector() {
+        let serial = "FT5ABCDE".parse::<CableSelector>().unwrap();
+        assert!(matches!(serial, CableSelector::Serial(s) if s == "FT5ABCDE"));
+        let channel = "ft4232h:B".parse::<CableSelector>().unwrap();
+        assert!(matches!(channel, CableSelector::Channel(c) if c.name == "ft4232h"));
+        let channel = "usb:ft4232h:2".parse::<CableSelector>().unwrap();
+        assert!(matches!(
+            channel,
+            CableSelector::Channel(c) if c.name == "usb:ft4232h" && c.channel == Interface::C
+        ));
+        let serial = "ft4232h:Z".parse::<CableSelector>().unwrap();
+        assert!(matches!(serial, CableSelector::Serial(s) if s == "ft4232h:Z"));
+        let serial = "AB:CD:12".parse::<CableSelector>().unwrap();
+        assert!(matches!(serial, CableSelector::Serial(s) if s == "AB:CD:12"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1s").unwrap(), Duration::from_secs(1));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("1.5").unwrap(), Duration::from_millis(1500));
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("fast").is_err());
+    }
+}
@@ -1,3 +1,16 @@
 pub mod flash;
 pub mod xilinx32;
 pub mod microchip;
+pub mod cable_reset;
+pub mod cpld;
+pub mod cpu;
+pub mod dna;
+pub mod eeprom;
+pub mod identify;
+pub mod info;
+pub mod mem;
+pub mod prom;
+#[cfg(feature = "top")]
+pub mod top;
+pub mod virtex;
+pub mod xilinx16;
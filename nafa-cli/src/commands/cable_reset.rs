@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use nafa_io::cables;
+
+use crate::cli_helpers::UsbAddr;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub async fn run(device: nusb::DeviceInfo, options: &cables::Options) -> Result<(), eyre::Error> {
+    let usb = UsbAddr {
+        vid: device.vendor_id(),
+        pid: device.product_id(),
+    };
+
+    match cables::recover(&device, TIMEOUT, options).await {
+        Ok(_) => {
+            println!("cable {usb} reset");
+            Ok(())
+        }
+        Err(errs) => {
+            let errs: Vec<_> = errs.into_iter().map(eyre::Report::from).collect();
+            Err(eyre::eyre!("failed to init cable after reset: {errs:?}"))
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use eyre::{OptionExt, Result, bail};
+use nafa_io::Controller;
+use nafa_xilinx::cpld::{
+    isp::{self, ROWS},
+    jedec::Jedec,
+};
+
+/// XC9500XL CPLDs.
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    /// Erase the device, program it from a JEDEC file, and verify it.
+    Program {
+        input_file: PathBuf,
+        /// Skip reading the fuses back after programming.
+        #[arg(long)]
+        no_verify: bool,
+    },
+    /// Compare the fuses of the device with a JEDEC file.
+    Verify { input_file: PathBuf },
+    /// Erase every fuse, leaving the device blank.
+    Erase,
+}
+
+impl Command {
+    pub fn wants_progress(&self) -> bool {
+        matches!(self, Self::Program { .. } | Self::Verify { .. })
+    }
+}
+
+/// Read a JEDEC file, checking it was fitted for the device `name`.
+fn load(path: &PathBuf, name: &str) -> Result<Jedec> {
+    let jed = Jedec::parse(&std::fs::read_to_string(path)?)?;
+    if let Some(device) = &jed.device
+        && !device.to_lowercase().starts_with(name)
+    {
+        bail!("JEDEC file is for {device}, active device is {name}");
+    }
+    Ok(jed)
+}
+
+pub async fn run(
+    cont: &mut Controller,
+    pb: Option<&indicatif::ProgressBar>,
+    command: Command,
+) -> Result<()> {
+    let name = cont.info().name.to_string();
+    let mut cont = cont
+        .typed()
+        .ok_or_eyre("cannot call cpld method with non-cpld active device")?;
+    let progress = |n: usize| {
+        if let Some(pb) = pb {
+            pb.set_position(n as _);
+        }
+    };
+    match command {
+        Command::Program {
+            input_file,
+            no_verify,
+        } => {
+            let jed = load(&input_file, &name)?;
+            if let Some(pb) = pb {
+                pb.set_length(if no_verify { ROWS } else { 2 * ROWS } as _);
+            }
+            isp::run(cont, &jed, progress, !no_verify).await?;
+        }
+        Command::Verify { input_file } => {
+            let jed = load(&input_file, &name)?;
+            if let Some(pb) = pb {
+                pb.set_length(ROWS as _);
+            }
+            isp::enable(cont.reborrow()).await?;
+            let ret = isp::verify(cont.reborrow(), &jed, progress).await;
+            isp::disable(cont).await?;
+            ret?;
+            println!("verified");
+        }
+        Command::Erase => {
+            isp::enable(cont.reborrow()).await?;
+            let ret = isp::erase(cont.reborrow()).await;
+            isp::disable(cont).await?;
+            ret?;
+        }
+    }
+    Ok(())
+}
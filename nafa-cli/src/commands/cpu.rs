@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use eyre::{OptionExt, Result};
+use facet::Facet;
+use nafa_io::{Controller, units::Bytes};
+use nafa_xilinx::dap::{
+    Dap,
+    boot::{self, Image},
+    cpu::{self, CoreState, Ps},
+};
+
+use crate::output;
+
+/// Application cores of a Zynq PS, through the ARM DAP. Without `--core`,
+/// every core is affected (skipping those that are off).
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    /// Print whether each core is running, halted, or off.
+    Status,
+    /// Halt the cores in debug state, i.e. before reconfiguring the PL.
+    Halt {
+        #[arg(long)]
+        core: Option<u8>,
+    },
+    /// Let halted cores run again.
+    Resume {
+        #[arg(long)]
+        core: Option<u8>,
+    },
+    /// Reset the cores, which then start again from their reset vector.
+    Reset {
+        #[arg(long)]
+        core: Option<u8>,
+    },
+    /// Load an ELF or raw binary into OCM or DDR and start a core on it,
+    /// without an FSBL. DDR has to be initialized already.
+    Load {
+        input_file: PathBuf,
+        /// Load address for a raw binary, which is also where it's started.
+        #[arg(long, default_value = "0")]
+        addr: Bytes<u32>,
+        #[arg(long, default_value_t = 0)]
+        core: u8,
+        /// Only load the image, leaving the core in reset.
+        #[arg(long)]
+        no_start: bool,
+    },
+}
+
+/// One core in `cpu status`, for `--format`.
+#[derive(Facet)]
+struct CoreStatus {
+    core: u8,
+    state: String,
+}
+
+impl Command {
+    pub fn wants_progress(&self) -> bool {
+        matches!(self, Self::Load { .. })
+    }
+}
+
+pub async fn run(
+    cont: &mut Controller,
+    pb: Option<&indicatif::ProgressBar>,
+    command: Command,
+    format: Option<output::Format>,
+) -> Result<()> {
+    let ps = Ps::detect(cont).ok_or_eyre("no Zynq on the chain")?;
+    let dap = &mut Dap::new(cont).await?;
+    match command {
+        Command::Status => {
+            let mut cores = Vec::new();
+            for core in 0..ps.cores() {
+                let state = cpu::state(dap, ps, core).await?.to_string();
+                cores.push(CoreStatus { core, state });
+            }
+            match format {
+                Some(format) => output::print(&cores, format)?,
+                None => {
+                    for CoreStatus { core, state } in cores {
+                        println!("core {core}: {state}");
+                    }
+                }
+            }
+        }
+        Command::Halt { core: Some(core) } => cpu::halt(dap, ps, core).await?,
+        Command::Halt { core: None } => {
+            let halted = cpu::halt_all(dap, ps).await?;
+            println!("halted cores {halted:?}");
+        }
+        Command::Resume { core: Some(core) } => cpu::resume(dap, ps, core).await?,
+        Command::Resume { core: None } => {
+            for core in 0..ps.cores() {
+                if cpu::state(dap, ps, core).await? != CoreState::Off {
+                    cpu::resume(dap, ps, core).await?;
+                }
+            }
+        }
+        Command::Reset { core: Some(core) } => cpu::reset(dap, ps, core).await?,
+        Command::Reset { core: None } => {
+            for core in 0..ps.cores() {
+                cpu::reset(dap, ps, core).await?;
+            }
+        }
+        Command::Load {
+            input_file,
+            addr,
+            core,
+            no_start,
+        } => {
+            let data = std::fs::read(input_file)?;
+            let image = match Image::is_elf(&data) {
+                true => Image::from_elf(&data)?,
+                false => Image::from_bin(&data, addr.0),
+            };
+            if let Some(pb) = pb {
+                pb.set_length(image.len() as _);
+            }
+            boot::hold(dap, ps, core).await?;
+            let mut done = 0;
+            for segment in &image.segments {
+                boot::write_segment(dap, segment, |n| {
+                    if let Some(pb) = pb {
+                        pb.set_position((done + n) as _);
+                    }
+                })
+                .await?;
+                done += segment.data.len();
+            }
+            if !no_start {
+                boot::start(dap, ps, core, &image).await?;
+                println!("core {core} started at {:#010x}", image.entry);
+            }
+        }
+    }
+    Ok(())
+}
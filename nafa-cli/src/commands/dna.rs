@@ -0,0 +1,16 @@
+use eyre::Result;
+use nafa_io::Controller;
+use nafa_xilinx::dna;
+
+pub async fn run(cont: &mut Controller) -> Result<()> {
+    let dnas = dna::read(cont).await?;
+    match &dnas[..] {
+        [dna] => println!("{dna}"),
+        dnas => {
+            for (slr, dna) in dnas.iter().enumerate() {
+                println!("slr {slr}: {dna}");
+            }
+        }
+    }
+    Ok(())
+}
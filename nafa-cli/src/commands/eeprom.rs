@@ -0,0 +1,100 @@
+use nafa_io::ftdi::{
+    devices::Interface,
+    eeprom::{Channel, ChannelMode, Driver, Eeprom},
+};
+
+use crate::cli_helpers::PerChannel;
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Print the USB strings and channel modes.
+    Read {
+        /// Also dump the raw EEPROM contents.
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Change fields, keeping everything not given as is.
+    Write(WriteArgs),
+}
+
+#[derive(clap::Args)]
+pub struct WriteArgs {
+    #[arg(long)]
+    manufacturer: Option<String>,
+    #[arg(long)]
+    product: Option<String>,
+    #[arg(long)]
+    serial: Option<String>,
+    /// Channel mode, i.e. `B=fifo`. One of uart, fifo, cpu-fifo, or
+    /// fast-serial. FT2232H only.
+    #[arg(long, value_name = "CHANNEL=MODE")]
+    channel: Vec<PerChannel<ChannelMode>>,
+    /// Driver bound on Windows, i.e. `A=d2xx`. Either d2xx or vcp.
+    #[arg(long, value_name = "CHANNEL=DRIVER")]
+    driver: Vec<PerChannel<Driver>>,
+}
+
+pub async fn run(device: nusb::DeviceInfo, command: Command) -> Result<(), eyre::Error> {
+    let device = device.open().await?;
+    let mut eeprom = Eeprom::read(&device).await?;
+
+    match command {
+        Command::Read { raw } => {
+            print(&eeprom)?;
+            if raw {
+                println!("{}", nafa_io::HexDump::new(eeprom.as_bytes()).ascii(true));
+            }
+        }
+        Command::Write(args) => {
+            let manufacturer = args
+                .manufacturer
+                .map_or_else(|| eeprom.manufacturer(), Ok)?;
+            let product = args.product.map_or_else(|| eeprom.product(), Ok)?;
+            let serial = args.serial.map_or_else(|| eeprom.serial(), Ok)?;
+            eeprom.set_strings(&manufacturer, &product, &serial)?;
+
+            for PerChannel { channel, value } in args.channel {
+                let driver = eeprom.channel(channel).map_or(Driver::D2xx, |c| c.driver);
+                let mode = Some(value);
+                eeprom.set_channel(channel, Channel { mode, driver })?;
+            }
+            for PerChannel { channel, value } in args.driver {
+                let channel_config = Channel {
+                    mode: None,
+                    driver: value,
+                };
+                eeprom.set_channel(channel, channel_config)?;
+            }
+
+            eeprom.write(&device).await?;
+            println!("written, replug the cable for changes to take effect");
+            print(&Eeprom::read(&device).await?)?;
+        }
+    }
+    Ok(())
+}
+
+fn print(eeprom: &Eeprom) -> Result<(), eyre::Error> {
+    println!("chip         {:?}", eeprom.chip());
+    println!(
+        "vid:pid      {:04X}:{:04X}",
+        eeprom.vendor_id(),
+        eeprom.product_id()
+    );
+    println!("manufacturer {}", eeprom.manufacturer()?);
+    println!("product      {}", eeprom.product()?);
+    println!("serial       {}", eeprom.serial()?);
+    for interface in [Interface::A, Interface::B, Interface::C, Interface::D] {
+        match eeprom.channel(interface) {
+            Some(Channel {
+                mode: Some(mode),
+                driver,
+            }) => println!("channel {interface:?}    {mode:?} ({driver:?})"),
+            Some(Channel { mode: None, driver }) => {
+                println!("channel {interface:?}    {driver:?}")
+            }
+            None => (),
+        }
+    }
+    Ok(())
+}
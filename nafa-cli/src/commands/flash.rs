@@ -1,7 +1,5 @@
 use nafa_io::{usb_blaster, xpc};
 
-use crate::cli_helpers::UsbAddr;
-
 #[derive(clap::Args)]
 pub struct Args {
     firmware: Firmware,
@@ -13,13 +11,8 @@ pub enum Firmware {
     UsbBlasterII,
 }
 
-pub async fn run(usb: UsbAddr, args: Args) -> Result<(), eyre::Error> {
-    let device = nusb::list_devices()
-        .await?
-        .find(|d| d.vendor_id() == usb.vid && d.product_id() == usb.pid)
-        .ok_or_else(|| eyre::eyre!("failed to open device {usb}"))?
-        .open()
-        .await?;
+pub async fn run(device: nusb::DeviceInfo, args: Args) -> Result<(), eyre::Error> {
+    let device = device.open().await?;
 
     let firmware = match args.firmware {
         Firmware::XP2 => xpc::firmware::XP2,
@@ -0,0 +1,41 @@
+use nafa_io::{cables, ftdi::devices::Pin};
+
+use crate::cli_helpers::UsbAddr;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Pin the LED is on, i.e. `CBUS3`. Defaults to the known pin for the
+    /// cable.
+    #[arg(long)]
+    pin: Option<Pin>,
+    /// How many times to blink.
+    #[arg(long, default_value_t = 5)]
+    count: usize,
+}
+
+/// Blink the cable with `serial`, picked with `--cable SERIAL`. Only needed if
+/// more than one cable with the same VID:PID is plugged in.
+pub async fn run(usb: UsbAddr, serial: Option<&str>, args: Args) -> Result<(), eyre::Error> {
+    let candidates: Vec<_> = nusb::list_devices()
+        .await?
+        .filter(|d| d.vendor_id() == usb.vid && d.product_id() == usb.pid)
+        .collect();
+    let device = match serial {
+        Some(serial) => candidates
+            .iter()
+            .find(|d| d.serial_number() == Some(serial))
+            .ok_or_else(|| eyre::eyre!("no cable {usb} with serial {serial}"))?,
+        None => match &candidates[..] {
+            [] => return Err(eyre::eyre!("failed to open device {usb}")),
+            [single] => single,
+            multiple => {
+                let serials: Vec<_> = multiple.iter().map(|d| d.serial_number()).collect();
+                return Err(eyre::eyre!(
+                    "multiple cables {usb} plugged in, pick one with --cable SERIAL: {serials:?}"
+                ));
+            }
+        },
+    };
+
+    Ok(cables::identify(device, args.pin, args.count).await?)
+}
@@ -0,0 +1,102 @@
+use eyre::{OptionExt as _, Result, bail};
+use facet::Facet;
+use nafa_io::{Controller, devices::Specific};
+use nafa_xilinx::zynq::actions::info::ZP;
+
+use super::{microchip, virtex, xilinx16, xilinx32};
+use crate::output;
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    /// Read every register of the device, as the `info` command of its family
+    /// does, instead of only what the chain scan found.
+    #[arg(long)]
+    pub full: bool,
+    #[command(flatten)]
+    pub output: output::Args,
+}
+
+/// What is known about the active device without talking to it.
+#[derive(Facet)]
+struct Summary {
+    idcode: String,
+    name: String,
+    irlen: u8,
+    family: &'static str,
+}
+
+pub async fn run(cont: &mut Controller, args: Args, format: Option<output::Format>) -> Result<()> {
+    let family = family(&cont.info().specific);
+    if !args.full {
+        let summary = Summary {
+            idcode: format!("{:#010x}", cont.idcode().code()),
+            name: cont.info().name.to_string(),
+            irlen: cont.info().irlen.0,
+            family,
+        };
+        return args.output.print(&summary, format);
+    }
+
+    match cont.info().specific {
+        Specific::Xilinx32(_) => {
+            let cont = cont.typed().ok_or_eyre("not a xilinx32 device")?;
+            let args = xilinx32::info::Args {
+                output: args.output,
+                verbose: false,
+            };
+            xilinx32::info::run(cont, args, format).await
+        }
+        Specific::Xilinx16(_) => {
+            let cont = cont.typed().ok_or_eyre("not a Spartan-6")?;
+            xilinx16::info::run(
+                cont,
+                xilinx16::info::Args {
+                    output: args.output,
+                },
+                format,
+            )
+            .await
+        }
+        Specific::XilinxVirtex(_) => {
+            let cont = cont.typed().ok_or_eyre("not a Virtex")?;
+            virtex::info::run(
+                cont,
+                virtex::info::Args {
+                    output: args.output,
+                },
+                format,
+            )
+            .await
+        }
+        Specific::XilinxZynq(_) => {
+            let cont = cont.typed().ok_or_eyre("not a Zynq UltraScale+")?;
+            args.output.print(&ZP::read(cont).await?, format)
+        }
+        Specific::Microchip => {
+            microchip::info::run(
+                cont,
+                microchip::info::Args {
+                    output: args.output,
+                },
+                format,
+            )
+            .await
+        }
+        _ => bail!("nothing more to read from a {family} device, leave out --full"),
+    }
+}
+
+fn family(specific: &Specific) -> &'static str {
+    match specific {
+        Specific::Unknown => "unknown",
+        Specific::Xilinx32(_) => "xilinx32",
+        Specific::Xilinx16(_) => "xilinx16",
+        Specific::XilinxVirtex(_) => "virtex",
+        Specific::XilinxCpld(_) => "cpld",
+        Specific::XilinxProm(_) => "prom",
+        Specific::XilinxZynq(_) => "zynq",
+        Specific::XilinxVersal(_) => "versal",
+        Specific::Intel => "intel",
+        Specific::Microchip => "microchip",
+    }
+}
@@ -0,0 +1,47 @@
+use eyre::Result;
+use nafa_io::{Controller, units::Bytes};
+use nafa_xilinx::dap::{Dap, SYSTEM_AP};
+
+use crate::cli_helpers::parse_u32;
+
+/// PS memory and registers of a Zynq, through the ARM DAP. The DAP is found
+/// on the chain by itself, `--jtag-idx` isn't needed.
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    /// Print `count` 32-bit words starting at `addr`.
+    Read {
+        addr: Bytes<u32>,
+        #[arg(default_value_t = 1)]
+        count: usize,
+        /// MEM-AP to go through. Defaults to the system bus.
+        #[arg(long, default_value_t = SYSTEM_AP)]
+        ap: u8,
+    },
+    /// Write 32-bit words starting at `addr`.
+    Write {
+        addr: Bytes<u32>,
+        #[arg(required = true, value_parser = parse_u32)]
+        values: Vec<u32>,
+        /// MEM-AP to go through. Defaults to the system bus.
+        #[arg(long, default_value_t = SYSTEM_AP)]
+        ap: u8,
+    },
+}
+
+pub async fn run(cont: &mut Controller, command: Command) -> Result<()> {
+    let mut dap = Dap::new(cont).await?;
+    match command {
+        Command::Read { addr, count, ap } => {
+            let mut words = vec![0; count];
+            dap.mem_ap(ap).await?.read(addr.0, &mut words).await?;
+            for (i, word) in words.into_iter().enumerate() {
+                let addr = addr.0.wrapping_add(4 * i as u32);
+                println!("{addr:08X}: {word:08X}");
+            }
+        }
+        Command::Write { addr, values, ap } => {
+            dap.mem_ap(ap).await?.write(addr.0, &values).await?;
+        }
+    }
+    Ok(())
+}
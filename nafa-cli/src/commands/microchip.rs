@@ -1,16 +1,22 @@
 use eyre::Result;
 use nafa_io::Controller;
 
-mod info;
+use crate::output;
 
-#[derive(clap::Subcommand)]
+pub mod info;
+
+#[derive(Clone, clap::Subcommand)]
 pub enum Command {
     Info(info::Args),
 }
 
-pub async fn run(cont: &mut Controller, command: Command) -> Result<Option<Box<dyn FnOnce()>>> {
+pub async fn run(
+    cont: &mut Controller,
+    command: Command,
+    format: Option<output::Format>,
+) -> Result<Option<Box<dyn FnOnce()>>> {
     let no_action = |()| None;
     match command {
-        Command::Info(args) => info::run(cont, args).await.map(no_action),
+        Command::Info(args) => info::run(cont, args, format).await.map(no_action),
     }
 }
@@ -2,24 +2,19 @@ use eyre::Result;
 use nafa_io::Controller;
 use nafa_microchip::read;
 
-#[derive(clap::Args)]
+use crate::output;
+
+#[derive(Clone, clap::Args)]
 pub struct Args {
-    #[arg(short, long)]
-    pub pretty: bool,
+    #[command(flatten)]
+    pub output: output::Args,
 }
 
-pub async fn run(cont: &mut Controller, args: Args) -> Result<(), eyre::Error> {
-    use facet_pretty::FacetPretty;
-
-    fn print<'a, F: facet::Facet<'a>>(info: F, pretty: bool) -> Result<(), eyre::Error> {
-        if pretty {
-            println!("{}", info.pretty());
-        } else {
-            facet_json::to_writer_std(std::io::stdout(), &info)?;
-        }
-        Ok(())
-    }
-
+pub async fn run(
+    cont: &mut Controller,
+    args: Args,
+    format: Option<output::Format>,
+) -> Result<(), eyre::Error> {
     let info = read(cont).await?;
-    print(info, args.pretty)
+    args.output.print(&info, format)
 }
@@ -0,0 +1,93 @@
+use eyre::{OptionExt, Result};
+use nafa_io::{Controller, units::Bytes};
+use nafa_xilinx::{
+    bitstream::{self, Format},
+    prom,
+};
+
+use crate::artifact::{self, CacheArgs, Source};
+
+/// XCFxxS platform flash and XC18V00 ISP PROMs.
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    /// Erase the PROM, program it with a bitstream, and verify it.
+    Program {
+        #[command(flatten)]
+        input: Input,
+        /// Skip reading the PROM back after programming.
+        #[arg(long)]
+        no_verify: bool,
+    },
+    /// Compare the contents of the PROM with a bitstream.
+    Verify {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Erase the whole PROM.
+    Erase,
+}
+
+#[derive(Clone, clap::Args)]
+pub struct Input {
+    /// PROM image (`.mcs`, `.bit`, or `.bin`). May be an `http(s)://`
+    /// URL, as for `xilinx32 program`.
+    input_file: Source,
+    /// Format of the image, instead of guessing from its contents.
+    #[arg(long, value_name = "FORMAT")]
+    input_format: Option<Format>,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+impl Input {
+    async fn load(&self, block: Bytes<usize>) -> Result<Vec<u8>> {
+        let data = artifact::load(&self.input_file, &self.cache).await?;
+        let data = bitstream::load(&data, self.input_format)?;
+        Ok(prom::to_wire_order(&data, block))
+    }
+}
+
+impl Command {
+    pub fn wants_progress(&self) -> bool {
+        matches!(self, Self::Program { .. } | Self::Verify { .. })
+    }
+}
+
+pub async fn run(
+    cont: &mut Controller,
+    pb: Option<&indicatif::ProgressBar>,
+    command: Command,
+) -> Result<()> {
+    let mut cont = cont
+        .typed()
+        .ok_or_eyre("cannot call prom method with non-prom active device")?;
+    let block = prom::block(cont.info());
+    match command {
+        Command::Program { input, no_verify } => {
+            let data = input.load(block).await?;
+            if let Some(pb) = pb {
+                let passes = if no_verify { 1 } else { 2 };
+                pb.set_length((passes * data.len()) as _);
+            }
+            prom::run(cont, &data, !no_verify).await?;
+        }
+        Command::Verify { input } => {
+            let data = input.load(block).await?;
+            if let Some(pb) = pb {
+                pb.set_length(data.len() as _);
+            }
+            prom::enable(cont.reborrow()).await?;
+            let ret = prom::verify(cont.reborrow(), &data).await;
+            prom::disable(cont).await?;
+            ret?;
+            println!("verified");
+        }
+        Command::Erase => {
+            prom::enable(cont.reborrow()).await?;
+            let ret = prom::erase(cont.reborrow()).await;
+            prom::disable(cont).await?;
+            ret?;
+        }
+    }
+    Ok(())
+}
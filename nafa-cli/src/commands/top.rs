@@ -0,0 +1,234 @@
+//! A live dashboard of the XADC / SYSMON sensors and the configuration
+//! status, for keeping an eye on a board over a long session in the lab.
+
+use std::time::{Duration, Instant};
+
+use eyre::{OptionExt as _, Result};
+use nafa_io::Controller;
+use nafa_xilinx::_32bit::{
+    Controller as XilinxController,
+    actions::{self, seu::SeuStatus},
+    drp::{Addr, Flags},
+    status::Stat,
+};
+use ratatui::{
+    Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize as _},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table},
+};
+
+use crate::{cli_helpers::parse_duration, commands::xilinx32::xadc};
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    /// Time between readings, i.e. `500ms`, `1s`, or `2m`.
+    #[arg(long, default_value = "1s", value_parser = parse_duration)]
+    interval: Duration,
+}
+
+struct Sensor {
+    name: &'static str,
+    addr: Addr,
+    unit: &'static str,
+    now: Option<f32>,
+    /// Lowest and highest value since the start, or since the last reset.
+    range: Option<(f32, f32)>,
+}
+
+struct State {
+    name: String,
+    idcode: u32,
+    interval: Duration,
+    sensors: Vec<Sensor>,
+    flags: Flags,
+    seu: Option<SeuStatus>,
+    readings: usize,
+    start: Instant,
+    /// Why the last reading failed. The next one is tried anyway, the cable
+    /// may just have been bumped.
+    error: Option<String>,
+}
+
+enum Key {
+    Quit,
+    ResetRange,
+    Other,
+}
+
+pub async fn run(cont: &mut Controller, args: Args) -> Result<()> {
+    let mut cont = cont
+        .typed()
+        .ok_or_eyre("cannot call xilinx method with non-xilinx active device")?;
+    let sensors = (xadc::sensors(&mut cont).into_iter())
+        .map(|(name, addr, unit)| Sensor {
+            name: name.trim(),
+            addr,
+            unit,
+            now: None,
+            range: None,
+        })
+        .collect();
+    let mut state = State {
+        name: cont.borrow().info().name.to_string(),
+        idcode: cont.borrow().idcode().code(),
+        interval: args.interval,
+        sensors,
+        flags: Flags::empty(),
+        seu: None,
+        readings: 0,
+        start: Instant::now(),
+        error: None,
+    };
+
+    let mut terminal = ratatui::init();
+    let ret = async {
+        let mut next = Instant::now();
+        loop {
+            if Instant::now() >= next {
+                state.error = state
+                    .read(cont.reborrow())
+                    .await
+                    .err()
+                    .map(|e| e.to_string());
+                next += args.interval;
+            }
+            terminal.draw(|frame| state.draw(frame))?;
+            let timeout = next.saturating_duration_since(Instant::now());
+            match next_key(timeout).await? {
+                Some(Key::Quit) => return Ok(()),
+                Some(Key::ResetRange) => state.sensors.iter_mut().for_each(|s| s.range = None),
+                Some(Key::Other) | None => (),
+            }
+        }
+    }
+    .await;
+    ratatui::restore();
+    ret
+}
+
+/// Wait up to `timeout` for a key press or a resize, `None` on timeout.
+async fn next_key(timeout: Duration) -> Result<Option<Key>> {
+    // crossterm only has a blocking poll
+    let event = smol::unblock(move || event::poll(timeout)?.then(event::read).transpose());
+    let key = match event.await? {
+        None => return Ok(None),
+        Some(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+        Some(_) => return Ok(Some(Key::Other)),
+    };
+    let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+    Ok(Some(match key.code {
+        _ if ctrl_c => Key::Quit,
+        KeyCode::Char('q') | KeyCode::Esc => Key::Quit,
+        KeyCode::Char('r') => Key::ResetRange,
+        _ => Key::Other,
+    }))
+}
+
+impl State {
+    /// Read the sensors and the flag register in one batch, then the
+    /// configuration status.
+    async fn read(&mut self, mut cont: XilinxController<'_>) -> Result<()> {
+        let family = cont.info().family;
+        let addrs = self.sensors.iter().map(|s| s.addr).chain([Addr::Flag]);
+        let raw = actions::xadc::run(cont.reborrow(), addrs.map(xadc::read)).await?;
+        let (flags, raw) = raw.split_last().ok_or_eyre("no XADC readings")?;
+        self.flags = Flags::from_bits_retain(*flags);
+        for (sensor, &raw) in self.sensors.iter_mut().zip(raw) {
+            sensor.now = xadc::value(family, sensor.addr, raw);
+            if let Some(now) = sensor.now {
+                let (min, max) = sensor.range.get_or_insert((now, now));
+                (*min, *max) = (min.min(now), max.max(now));
+            }
+        }
+        self.seu = Some(actions::seu::status(cont, 0).await?);
+        self.readings += 1;
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, sensors, status, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(self.sensors.len() as u16 + 3),
+            Constraint::Min(6),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let elapsed = self.start.elapsed().as_secs();
+        let header_text = format!(
+            "{} ({:#010x}), {} readings in {}:{:02}:{:02}, every {:?}",
+            self.name,
+            self.idcode,
+            self.readings,
+            elapsed / 3600,
+            elapsed / 60 % 60,
+            elapsed % 60,
+            self.interval,
+        );
+        frame.render_widget(Line::from(header_text).bold(), header);
+        frame.render_widget(self.sensor_table(), sensors);
+        frame.render_widget(self.status(), status);
+        frame.render_widget(Line::from("q: quit, r: reset min/max").dark_gray(), help);
+    }
+
+    fn sensor_table(&self) -> Table<'_> {
+        let value = |v: Option<f32>, unit| match v {
+            Some(v) => format!("{v:>8.3} {unit}"),
+            None => format!("{:>8}", "-"),
+        };
+        let rows = self.sensors.iter().map(|s| {
+            let alarm = self.flags.alarmed(s.addr);
+            let row = Row::new([
+                s.name.to_owned(),
+                value(s.now, s.unit),
+                value(s.range.map(|r| r.0), s.unit),
+                value(s.range.map(|r| r.1), s.unit),
+                if alarm { "ALARM" } else { "" }.to_owned(),
+            ]);
+            match alarm {
+                true => row.style(Style::new().fg(Color::Red)),
+                false => row,
+            }
+        });
+        let widths = [
+            Constraint::Length(8),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(6),
+        ];
+        Table::new(rows, widths)
+            .header(Row::new(["", "now", "min", "max", ""]).bold())
+            .block(Block::bordered().title(" sensors "))
+    }
+
+    fn status(&self) -> Paragraph<'_> {
+        let mut lines = Vec::new();
+        let alarms: Vec<_> = self.flags.iter_names().map(|(name, _)| name).collect();
+        lines.push(match &alarms[..] {
+            [] => Line::from("alarms: none"),
+            alarms => Line::from(format!("alarms: {}", alarms.join(", "))).red(),
+        });
+        if let Some(seu) = self.seu {
+            let stat = Line::from(format!("  STAT: {}", seu.stat));
+            lines.push(match seu.stat.started() {
+                true => stat,
+                false => stat.yellow(),
+            });
+            lines.push(Line::from(match seu.rbcrc_enabled() {
+                true => format!("readback CRC: enabled, {} on error", seu.rbcrc_action()),
+                false => "readback CRC: disabled".to_owned(),
+            }));
+            if seu.stat.contains(Stat::CRC_ERROR) {
+                lines.push(Line::from("CRC error").red());
+            }
+        }
+        if let Some(error) = &self.error {
+            lines.push(Line::from(format!("last reading failed: {error}")).red());
+        }
+        Paragraph::new(lines).block(Block::bordered().title(" status "))
+    }
+}
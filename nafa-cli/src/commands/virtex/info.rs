@@ -0,0 +1,16 @@
+use eyre::Result;
+use nafa_xilinx::virtex::{Controller, actions::info::Virtex};
+
+use crate::output;
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    pub output: output::Args,
+}
+
+pub async fn run(cont: Controller<'_>, args: Args, format: Option<output::Format>) -> Result<()> {
+    let info = Virtex::read(cont).await?;
+    args.output.print(&info, format)?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use eyre::{OptionExt as _, Result};
+use nafa_io::units::{Bytes, Words32};
+use nafa_xilinx::virtex::{Controller, actions};
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    pub output_file: PathBuf,
+    /// Number of 32-bit words to read, for devices without a known
+    /// bitstream length.
+    #[arg(long)]
+    pub words: Option<usize>,
+}
+
+pub async fn run(
+    cont: Controller<'_>,
+    pb: Option<&indicatif::ProgressBar>,
+    args: Args,
+) -> Result<()> {
+    let words = args.words.map(Words32).or(cont.info().readback);
+    let words = words.ok_or_eyre("unknown readback length for device, pass --words")?;
+
+    if let Some(pb) = pb {
+        pb.set_length(Bytes::from(words).0 as u64);
+    }
+
+    let data = actions::readback::run(cont, words).await?;
+    std::fs::write(args.output_file, data)?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use eyre::{OptionExt, Result};
+use nafa_io::Controller;
+
+use crate::output;
+
+pub mod info;
+mod program;
+mod readback;
+
+/// Spartan-6.
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    Info(info::Args),
+    Readback(readback::Args),
+    Program(program::Args),
+}
+
+impl Command {
+    pub fn wants_progress(&self) -> bool {
+        matches!(self, Command::Readback(_) | Command::Program(_))
+    }
+}
+
+pub async fn run(
+    cont: &mut Controller,
+    pb: Option<&indicatif::ProgressBar>,
+    command: Command,
+    format: Option<output::Format>,
+) -> Result<Option<Box<dyn FnOnce()>>> {
+    let no_action = |()| None;
+    let cont = cont
+        .typed()
+        .ok_or_eyre("cannot call spartan-6 method with non-spartan-6 active device")?;
+    match command {
+        Command::Info(args) => info::run(cont, args, format).await.map(no_action),
+        Command::Readback(args) => readback::run(cont, pb, args).await.map(no_action),
+        Command::Program(args) => program::run(cont, pb, args).await,
+    }
+}
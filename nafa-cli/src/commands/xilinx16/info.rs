@@ -0,0 +1,20 @@
+use eyre::Result;
+use nafa_xilinx::_16bit::{Controller, actions::info::S6, registers::Stat};
+
+use crate::output;
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    pub output: output::Args,
+}
+
+pub async fn run(cont: Controller<'_>, args: Args, format: Option<output::Format>) -> Result<()> {
+    let info = S6::read(cont).await?;
+    args.output.print(&info, format)?;
+    let stat = Stat::from_bits_retain(info.registers.stat);
+    if !stat.started() {
+        tracing::warn!("device is not configured, STAT {stat}");
+    }
+    Ok(())
+}
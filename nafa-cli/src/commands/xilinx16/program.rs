@@ -0,0 +1,24 @@
+use eyre::Result;
+use nafa_xilinx::_16bit::{Controller, actions};
+
+use crate::program::{self, Input};
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    pub input: Input,
+}
+
+pub async fn run(
+    cont: Controller<'_>,
+    pb: Option<&indicatif::ProgressBar>,
+    args: Args,
+) -> Result<Option<Box<dyn FnOnce()>>> {
+    let data = args.input.load(pb).await?;
+
+    let stats = actions::program::run(cont, &data).await?;
+    Ok(Some(Box::new(move || {
+        program::print_times(stats.time_shutdown, stats.time_program, stats.time_startup);
+        println!("    stat: {}", stats.stat);
+    })))
+}
@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+use eyre::{OptionExt as _, Result};
+use nafa_xilinx::_16bit::{Controller, actions};
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    pub output_file: PathBuf,
+}
+
+pub async fn run(
+    cont: Controller<'_>,
+    pb: Option<&indicatif::ProgressBar>,
+    args: Args,
+) -> Result<()> {
+    let words = cont.info().readback;
+    let words = words.ok_or_eyre("unsupported device for readback")?;
+
+    if let Some(pb) = pb {
+        pb.set_length(2 * words as u64);
+    }
+
+    let data = actions::readback::run(cont, words).await?;
+    std::fs::write(args.output_file, data)?;
+    Ok(())
+}
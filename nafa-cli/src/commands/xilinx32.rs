@@ -1,24 +1,59 @@
 use eyre::{OptionExt, Result};
 use nafa_io::Controller;
 
-mod info;
+use crate::output;
+
+mod bram;
+pub mod info;
 mod program;
 mod program_bbram;
 mod readback;
-mod xadc;
+pub mod reset;
+mod seu;
+mod spi_flash;
+mod verify;
+mod vio;
+pub mod xadc;
 
-#[derive(clap::Subcommand)]
+#[derive(Clone, clap::Subcommand)]
 pub enum Command {
     Info(info::Args),
     Xadc(xadc::Args),
     Readback(readback::Args),
+    /// Readback after GCAPTURE, with the current flip-flop and block RAM
+    /// values in place of their initial values.
+    Capture(readback::Args),
+    /// Read back the configuration and compare it against a bitstream.
+    Verify(verify::Args),
     Program(program::Args),
     ProgramBbramKey(program_bbram::Args),
+    /// Clear the configuration with JPROGRAM, as if PROG_B was pulsed.
+    Reset(reset::Args),
+    Vio(vio::Args),
+    #[command(subcommand)]
+    Bram(bram::Command),
+    /// Configuration memory upsets: readback CRC status, or scrubbing.
+    #[command(subcommand)]
+    Seu(seu::Command),
+    /// Read, erase, or verify the configuration flash.
+    #[command(subcommand)]
+    SpiFlash(spi_flash::Command),
 }
 
 impl Command {
     pub fn wants_progress(&self) -> bool {
-        matches!(self, Command::Readback(_) | Command::Program(_))
+        matches!(
+            self,
+            Command::Readback(_)
+                | Command::Capture(_)
+                | Command::Verify(_)
+                | Command::Program(_)
+                | Command::SpiFlash(
+                    spi_flash::Command::Read { .. }
+                        | spi_flash::Command::Erase { .. }
+                        | spi_flash::Command::Verify { .. }
+                )
+        )
     }
 }
 
@@ -26,16 +61,24 @@ pub async fn run(
     cont: &mut Controller,
     pb: Option<&indicatif::ProgressBar>,
     command: Command,
+    format: Option<output::Format>,
 ) -> Result<Option<Box<dyn FnOnce()>>> {
     let no_action = |()| None;
     let cont = cont
         .typed()
         .ok_or_eyre("cannot call xilinx method with non-xilinx active device")?;
     match command {
-        Command::Info(args) => info::run(cont, args).await.map(no_action),
-        Command::Xadc(args) => xadc::run(cont, args).await.map(no_action),
-        Command::Readback(args) => readback::run(cont, pb, args).await,
+        Command::Info(args) => info::run(cont, args, format).await.map(no_action),
+        Command::Xadc(args) => xadc::run(cont, args, format).await.map(no_action),
+        Command::Readback(args) => readback::run(cont, pb, args, false).await,
+        Command::Capture(args) => readback::run(cont, pb, args, true).await,
+        Command::Verify(args) => verify::run(cont, pb, args).await.map(no_action),
         Command::Program(args) => program::run(cont, pb, args).await,
         Command::ProgramBbramKey(args) => program_bbram::run(cont, args).await.map(no_action),
+        Command::Reset(args) => reset::run(cont, args).await.map(no_action),
+        Command::Vio(args) => vio::run(cont, args).await.map(no_action),
+        Command::Bram(command) => bram::run(cont, command).await.map(no_action),
+        Command::Seu(command) => seu::run(cont, command, format).await.map(no_action),
+        Command::SpiFlash(command) => spi_flash::run(cont, pb, command).await.map(no_action),
     }
 }
@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use eyre::{OptionExt as _, Result, bail};
+use nafa_io::devices::Xilinx32Family;
+use nafa_xilinx::_32bit::{
+    Controller,
+    actions::readback,
+    bram::{self, LogicLocation},
+};
+
+use crate::cli_helpers::parse_u32;
+
+/// Block RAM contents, read from the configuration frames. Stop the design
+/// from using the RAM first, reading can disturb it.
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    /// Save the contents of one block RAM.
+    ///
+    /// With `--ll`, the data bits of `--block` are written in address order.
+    /// Otherwise (7-series only), the bits of RAMB36 `--site` of the column
+    /// at `--far` are written in the order they are in the frames.
+    Dump {
+        output_file: PathBuf,
+        /// Frame address of the first content frame of the column.
+        #[arg(long, value_parser = parse_u32, required_unless_present = "ll")]
+        far: Option<u32>,
+        /// RAMB36 in the column, counted from the start of the frames.
+        #[arg(long, default_value_t = 0, conflicts_with = "ll")]
+        site: usize,
+        /// Logic location file (`.ll`) of the design.
+        #[arg(long, requires = "block")]
+        ll: Option<PathBuf>,
+        /// Site of the RAM in the `.ll`, i.e. `RAMB36_X0Y12`.
+        #[arg(long, requires = "ll")]
+        block: Option<String>,
+        /// Also save the parity bits, with `--ll`.
+        #[arg(long, requires = "ll")]
+        parity_file: Option<PathBuf>,
+    },
+}
+
+pub async fn run(cont: Controller<'_>, command: Command) -> Result<()> {
+    let family = cont.info().family;
+    let frame = family.frame_words();
+    match command {
+        Command::Dump {
+            output_file,
+            far,
+            ll: Some(ll),
+            block,
+            parity_file,
+            ..
+        } => {
+            let block = block.ok_or_eyre("--ll needs --block")?;
+            let ll = LogicLocation::parse(&std::fs::read_to_string(ll)?)?;
+            let (first, count) = ll.frames(&block, family)?;
+            if let Some(far) = far
+                && far != first
+            {
+                bail!("{block} starts at frame {first:#010x}, not {far:#010x}");
+            }
+            let frames = readback::read_frames(cont, first, count).await?;
+            let contents = bram::extract(&ll, &block, first, &frames, frame.0)?;
+            std::fs::write(output_file, contents.data)?;
+            if let Some(path) = parity_file {
+                std::fs::write(path, contents.parity)?;
+            }
+        }
+        Command::Dump {
+            output_file,
+            far,
+            site,
+            ll: None,
+            ..
+        } => {
+            let far = far.ok_or_eyre("--far is needed without --ll")?;
+            if !matches!(family, Xilinx32Family::S7) {
+                bail!("the RAMB36 layout is only known for 7-series, use --ll");
+            }
+            if bram::block_type(far) != bram::CONTENT_BLOCK_TYPE {
+                bail!("{far:#010x} is not the address of a block RAM content frame");
+            }
+            if far & bram::minor_mask(family) != 0 {
+                bail!("{far:#010x} is not the first frame of its column");
+            }
+            let frames = readback::read_frames(cont, far, bram::S7_CONTENT_FRAMES).await?;
+            std::fs::write(output_file, bram::s7_site(&frames, site)?)?;
+        }
+    }
+    Ok(())
+}
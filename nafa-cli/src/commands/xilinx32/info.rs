@@ -1,24 +1,69 @@
 use eyre::Result;
-use nafa_xilinx::_32bit::{Controller, actions};
+use nafa_io::HexDump;
+use nafa_xilinx::_32bit::{
+    Controller,
+    actions::{
+        self,
+        info::{S7, UP, US, XilinxInfo},
+    },
+    status::BootSts,
+};
 
-#[derive(clap::Args)]
+use crate::output;
+
+#[derive(Clone, clap::Args)]
 pub struct Args {
+    #[command(flatten)]
+    pub output: output::Args,
+    /// Also dump the eFUSE registers to stderr, with offsets and as text.
     #[arg(short, long)]
-    pub pretty: bool,
+    pub verbose: bool,
 }
 
-pub async fn run(cont: Controller<'_>, args: Args) -> Result<()> {
-    use facet_pretty::FacetPretty;
-
-    fn print<'a, F: facet::Facet<'a>>(info: F, pretty: bool) -> Result<(), eyre::Error> {
-        if pretty {
-            println!("{}", info.pretty());
-        } else {
-            facet_json::to_writer_std(std::io::stdout(), &info)?;
+pub async fn run(cont: Controller<'_>, args: Args, format: Option<output::Format>) -> Result<()> {
+    let info = actions::info::run(cont).await?;
+    args.output.print(&info, format)?;
+    for (slr, bootsts) in bootsts(&info).into_iter().enumerate() {
+        if let Some(problem) = bootsts.problem() {
+            eprintln!("slr {slr}: {problem}");
         }
-        Ok(())
     }
+    if args.verbose {
+        for (slr, name, data) in fuses(&info) {
+            eprintln!("slr {slr} {name}:\n{}", HexDump::new(data).ascii(true));
+        }
+    }
+    Ok(())
+}
 
-    let info = actions::info::run(cont).await?;
-    print(info, args.pretty)
+fn bootsts(info: &XilinxInfo) -> Vec<BootSts> {
+    let registers = match info {
+        XilinxInfo::S7(S7 { registers, .. })
+        | XilinxInfo::US(US { registers, .. })
+        | XilinxInfo::UP(UP { registers, .. }) => registers,
+    };
+    (registers.slrs.iter())
+        .map(|r| BootSts(r.bootsts))
+        .collect()
+}
+
+/// The eFUSE registers of each SLR, which are too long to read as numbers.
+fn fuses(info: &XilinxInfo) -> Vec<(usize, &'static str, &[u8])> {
+    let mut ret = Vec::new();
+    match info {
+        XilinxInfo::S7(s7) => {
+            for (slr, jtag) in s7.jtag.slrs.iter().enumerate() {
+                ret.push((slr, "fuse_dna", &jtag.fuse_dna[..]));
+                ret.push((slr, "fuse_key", &jtag.fuse_key[..]));
+            }
+        }
+        XilinxInfo::US(US { jtag, .. }) | XilinxInfo::UP(UP { jtag, .. }) => {
+            for (slr, jtag) in jtag.slrs.iter().enumerate() {
+                ret.push((slr, "fuse_dna", &jtag.fuse_dna[..]));
+                ret.push((slr, "fuse_key", &jtag.fuse_key[..]));
+                ret.push((slr, "fuse_rsa", &jtag.fuse_rsa[..]));
+            }
+        }
+    }
+    ret
 }
@@ -1,43 +1,252 @@
 use std::path::PathBuf;
 
-use eyre::Result;
-use nafa_xilinx::_32bit::{Controller, actions};
+use eyre::{OptionExt, Result, WrapErr, bail};
+use nafa_io::{Controller, devices, units::Bytes};
+use nafa_xilinx::{
+    _32bit::{self, actions},
+    bitstream::{self, BitHeader},
+    dap::{
+        Dap,
+        cpu::{self, Ps},
+    },
+};
 
-#[derive(clap::Args)]
+use crate::program::{self, Input};
+
+#[derive(Clone, clap::Args)]
 pub struct Args {
-    pub input_file: PathBuf,
+    #[command(flatten)]
+    pub input: Input,
+    /// Program these identical devices (chain indices, i.e. `0,1,2`) in one
+    /// pass, instead of only the `--jtag-idx` device.
+    #[arg(long, value_delimiter = ',')]
+    pub broadcast: Vec<usize>,
+    /// Run the shutdown sequence (JSHUTDOWN) before clearing the
+    /// configuration, so the I/Os are tristated in an orderly way.
+    #[arg(long)]
+    pub shutdown_first: bool,
+    /// On a Zynq, halt the application cores through the ARM DAP first, so
+    /// they don't access the PL while it's being reconfigured.
+    #[arg(long)]
+    pub halt_ps: bool,
+    /// Program without checking the CRCs in the bitstream first.
+    #[arg(long)]
+    pub no_crc_check: bool,
+    /// Program a `.bit` even if its header names another part than the
+    /// device.
+    #[arg(long)]
+    pub force: bool,
+    /// Read the die temperature and VCCINT first, and warn or refuse to
+    /// program if they are out of the datasheet operating range.
+    #[arg(long, value_name = "ACTION")]
+    pub check_supply: Option<SupplyCheck>,
+    /// Check the device after programming, so a bad cable or a truncated
+    /// file fails here. `--verify` only checks STAT, `--verify=readback`
+    /// also reads back the configuration.
+    #[arg(long, value_name = "CHECK", num_args = 0..=1, require_equals = true,
+          default_missing_value = "status")]
+    pub verify: Option<Verify>,
+    /// Mask file for `--verify readback`, as for `verify --mask`.
+    #[arg(long, value_name = "FILE", requires = "verify")]
+    pub verify_mask: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Verify {
+    /// STAT shows the device started, without a CRC error.
+    Status,
+    /// Also read the configuration back and compare it to the bitstream,
+    /// like `verify`.
+    Readback,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SupplyCheck {
+    Warn,
+    Refuse,
 }
 
 pub async fn run(
-    cont: Controller<'_>,
+    mut cont: _32bit::Controller<'_>,
     pb: Option<&indicatif::ProgressBar>,
     args: Args,
 ) -> Result<Option<Box<dyn FnOnce()>>> {
-    let mut data = std::fs::read(&args.input_file)?;
-    for d in &mut data {
-        *d = d.reverse_bits();
+    let data = args.input.fetch().await?;
+    match BitHeader::parse(&data) {
+        Ok(Some(header)) => check_part(cont.borrow(), &header, args.force)?,
+        Ok(None) => (),
+        Err(e) => tracing::warn!("can't read the .bit header, not checking the part: {e:#}"),
     }
-    if let Some(pb) = pb {
-        pb.set_length(data.len() as _)
+    let data = args.input.decode(&data)?;
+    if !args.no_crc_check {
+        let checked = bitstream::check_crc(&data)
+            .wrap_err("bitstream failed validation, use --no-crc-check to program it anyway")?;
+        tracing::info!(checked, "bitstream CRC ok");
     }
+    let golden = match args.verify {
+        Some(Verify::Readback) => Some(bitstream::frame_data(&data)?),
+        _ => None,
+    };
+    let mask = match &args.verify_mask {
+        Some(path) => Some(bitstream::frame_data(&bitstream::load(
+            &std::fs::read(path)?,
+            None,
+        )?)?),
+        None => None,
+    };
+    let data = program::to_wire_order(&data, pb);
 
-    let stats = actions::program::run(cont, &data).await?;
+    if let Some(action) = args.check_supply {
+        check_supply(cont.reborrow(), action).await?;
+    }
+
+    if args.halt_ps {
+        halt_ps(cont.borrow()).await?;
+    }
+
+    if args.shutdown_first {
+        for &idx in &args.broadcast {
+            cont.borrow().select(idx)?;
+            actions::program::shutdown(cont.reborrow()).await?;
+        }
+        if args.broadcast.is_empty() {
+            actions::program::shutdown(cont.reborrow()).await?;
+        }
+    }
+
+    let (stats, stats_each) = match &args.broadcast[..] {
+        [] => (actions::program::run(cont.reborrow(), &data).await?, vec![]),
+        targets => actions::program::broadcast(cont.reborrow(), targets, &data).await?,
+    };
+    let targets = args.broadcast;
+    let verified = match args.verify {
+        Some(_) => verify(cont, pb, &targets, golden.as_deref(), mask.as_deref()).await?,
+        None => Vec::new(),
+    };
 
-    let digits = as_millis(stats.time_program)
-        .max(as_millis(stats.time_shutdown))
-        .max(as_millis(stats.time_verify))
-        .log10()
-        .ceil() as usize;
-    let width = digits + 4;
     Ok(Some(Box::new(move || {
-        println!("shutdown: {:>width$.3}ms", as_millis(stats.time_shutdown));
-        println!(" program: {:>width$.3}ms", as_millis(stats.time_program));
-        println!("  verify: {:>width$.3}ms", as_millis(stats.time_verify));
-        println!(" success: {}", stats.success);
+        program::print_times(stats.time_shutdown, stats.time_program, stats.time_startup);
+        if targets.is_empty() {
+            println!("    stat: {}", stats.stat);
+        }
+        for (idx, stat) in targets.iter().zip(stats_each) {
+            match stat {
+                Some(stat) => println!("  dev {idx:>2}: {stat}"),
+                None => println!("  dev {idx:>2}: STAT could not be read"),
+            }
+        }
+        for line in verified {
+            println!("  verify: {line}");
+        }
     })))
 }
 
-fn as_millis(d: std::time::Duration) -> f32 {
-    const NANOS_PER_MILLI: u32 = 1_000_000;
-    (d.as_nanos() as f32) / (NANOS_PER_MILLI as f32)
+/// Check each programmed device started, and with `golden`, that its
+/// configuration reads back as the bitstream. Returns a line to print for
+/// each device.
+async fn verify(
+    mut cont: _32bit::Controller<'_>,
+    pb: Option<&indicatif::ProgressBar>,
+    targets: &[usize],
+    golden: Option<&[u32]>,
+    mask: Option<&[u32]>,
+) -> Result<Vec<String>> {
+    let targets: Vec<_> = match targets {
+        [] => vec![None],
+        targets => targets.iter().copied().map(Some).collect(),
+    };
+    let mut lines = Vec::with_capacity(targets.len());
+    for &idx in &targets {
+        let dev = match idx {
+            Some(idx) => {
+                cont.borrow().select(idx)?;
+                format!("dev {idx}: ")
+            }
+            None => String::new(),
+        };
+        let stat = actions::program::verify_started(cont.reborrow())
+            .await
+            .wrap_err_with(|| format!("{dev}verify failed"))?;
+        let Some(golden) = golden else {
+            lines.push(format!("{dev}started, STAT {stat}"));
+            continue;
+        };
+
+        if let (Some(pb), Some(len)) = (pb, cont.info().readback) {
+            pb.reset();
+            pb.set_length(Bytes::from(len).0 as _);
+        }
+        let report = actions::verify::run(cont.reborrow(), golden, mask, 0).await?;
+        if report.mismatched != 0 {
+            bail!(
+                "{dev}readback does not match the bitstream, {} of {} frames differ",
+                report.mismatched,
+                report.frames
+            );
+        }
+        lines.push(format!(
+            "{dev}{} frames read back as programmed",
+            report.frames
+        ));
+    }
+    if let Some(&Some(first)) = targets.first() {
+        cont.borrow().select(first)?;
+    }
+    Ok(lines)
+}
+
+/// Refuse to program a `.bit` made for another part, unless `force`.
+fn check_part(cont: &Controller, header: &BitHeader, force: bool) -> Result<()> {
+    let idcode = cont.idcode().strip_version();
+    let expected = devices::for_part(&header.part);
+    if expected.is_empty() {
+        tracing::warn!(
+            part = header.part,
+            "unknown part in bitstream header, not checking it"
+        );
+        return Ok(());
+    }
+    if expected.iter().any(|e| e.strip_version() == idcode) {
+        return Ok(());
+    }
+    let msg = format!(
+        "bitstream is for {}, but the device is {}",
+        header.part,
+        cont.info().name
+    );
+    if force {
+        tracing::warn!("{msg}");
+        return Ok(());
+    }
+    bail!("{msg}, use --force to program it anyway")
+}
+
+/// Programming a board that is browning out tends to half succeed, so check
+/// the supply before starting.
+async fn check_supply(cont: _32bit::Controller<'_>, action: SupplyCheck) -> Result<()> {
+    let family = cont.info().family;
+    let supply = actions::xadc::supply(cont).await?;
+    tracing::info!(supply.temperature, supply.vccint, "supply");
+    let problems = supply.problems(family);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let problems = problems.join(", ");
+    match action {
+        SupplyCheck::Warn => {
+            tracing::warn!("{problems}");
+            Ok(())
+        }
+        SupplyCheck::Refuse => bail!("{problems}, not programming"),
+    }
+}
+
+/// Halt the cores of the PS, then go back to the PL TAP.
+async fn halt_ps(cont: &mut Controller) -> Result<()> {
+    let idx = cont.info_before().len();
+    let ps = Ps::detect(cont).ok_or_eyre("--halt-ps needs a Zynq")?;
+    let halted = cpu::halt_all(&mut Dap::new(cont).await?, ps).await?;
+    tracing::info!(?halted, "halted PS cores");
+    cont.select(idx)?;
+    Ok(())
 }
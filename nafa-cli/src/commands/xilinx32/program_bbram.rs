@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use eyre::Result;
 use nafa_xilinx::_32bit::{Controller, actions, nky};
 
-#[derive(clap::Args)]
+#[derive(Clone, clap::Args)]
 #[group(required = true, multiple = false)]
 pub struct BbramKeySource {
     /// 32-byte hexadecimal value. Can be repeated for devices with multiple
@@ -15,7 +15,7 @@ pub struct BbramKeySource {
     pub nky: Option<PathBuf>,
 }
 
-#[derive(clap::Args)]
+#[derive(Clone, clap::Args)]
 pub struct Args {
     #[command(flatten)]
     pub key_source: BbramKeySource,
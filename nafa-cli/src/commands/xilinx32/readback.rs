@@ -1,27 +1,106 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
 
 use eyre::{OptionExt as _, Result};
-use nafa_io::units::Bytes;
-use nafa_xilinx::_32bit::{Controller, actions};
+use nafa_io::{Buffer, buffers::WriteBuffer, units::Bytes};
+use nafa_xilinx::_32bit::{Controller, actions, layout::FrameWriter};
 
-#[derive(clap::Args)]
+use crate::compress::{self, Compression};
+
+#[derive(Clone, clap::Args)]
 pub struct Args {
+    /// Where to write the readback, `-` for stdout.
     pub output_file: PathBuf,
+    /// How to write the readback.
+    #[arg(long, value_name = "FORMAT", default_value = "raw")]
+    pub output_format: OutputFormat,
+    /// Compress the output file.
+    #[arg(long, value_name = "ALGORITHM")]
+    pub compress: Option<Compression>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// As read from the device, in JTAG bit order, starting with a pad frame.
+    Raw,
+    /// Only the frames, as big-endian words like the frame data of a `.bin`.
+    /// With the frame layout of the part in the device list, every SLR,
+    /// without pad frames.
+    Frames,
+    /// The frames as ASCII `0`/`1`, one word per line, like a `.rbd`. Like
+    /// `frames`, without pad frames if the frame layout is known.
+    Rbd,
 }
 
+/// Readback, or with `capture` a readback capture of the design state.
+///
+/// The readback is written to the file as it arrives, so memory use doesn't
+/// grow with the size of the device. Except for frames without pad frames,
+/// which are only written once every SLR was read.
 pub async fn run(
-    cont: Controller<'_>,
+    mut cont: Controller<'_>,
     pb: Option<&indicatif::ProgressBar>,
     args: Args,
+    capture: bool,
 ) -> Result<Option<Box<dyn FnOnce()>>> {
-    let len = cont.info().readback;
-    let len = len.ok_or_eyre("unsupported device for readback")?;
+    let frame = cont.info().family.frame_words();
+    let part = cont.borrow().info().name.clone();
+    let layout = cont.info().layout.clone();
+    let len = (cont.info().readback)
+        .or_else(|| Some(frame * layout.as_ref()?.readback_frames(0)?))
+        .ok_or_eyre("unsupported device for readback")?;
 
     if let Some(pb) = pb {
         pb.set_length(Bytes::from(len).0 as _);
     }
 
-    let data = actions::readback::run(cont, len.into()).await?;
-    std::fs::write(args.output_file, data)?;
+    let file: Box<dyn Write + Send> = match args.output_file.as_os_str() == "-" {
+        true => Box::new(std::io::stdout()),
+        false => Box::new(File::create(&args.output_file)?),
+    };
+    let mut file = compress::Writer::new(BufWriter::new(file), args.compress)?;
+    if layout.is_some() && args.output_format != OutputFormat::Raw {
+        let readback = actions::readback::frames(cont, capture).await?;
+        match args.output_format {
+            OutputFormat::Rbd => readback.write_rbd(&mut file, &part)?,
+            _ => file.write_all(&readback.to_bytes())?,
+        }
+        file.finish()?;
+        return Ok(None);
+    }
+    let file = match args.output_format {
+        OutputFormat::Raw => {
+            let mut buf = WriteBuffer::new(file);
+            read_into(cont, len.into(), capture, &mut buf).await?;
+            buf.finish()?
+        }
+        OutputFormat::Frames => {
+            let mut buf = WriteBuffer::new(FrameWriter::frames(file, frame.0));
+            read_into(cont, len.into(), capture, &mut buf).await?;
+            buf.finish()?.finish()?
+        }
+        OutputFormat::Rbd => {
+            let rbd = FrameWriter::rbd(file, frame.0, &part, len.into())?;
+            let mut buf = WriteBuffer::new(rbd);
+            read_into(cont, len.into(), capture, &mut buf).await?;
+            buf.finish()?.finish()?
+        }
+    };
+    file.finish()?;
     Ok(None)
 }
+
+async fn read_into(
+    cont: Controller<'_>,
+    len: Bytes<usize>,
+    capture: bool,
+    buf: &mut dyn Buffer,
+) -> Result<()> {
+    match capture {
+        true => actions::readback::capture_into(cont, len, buf).await,
+        false => actions::readback::run_into(cont, len, buf).await,
+    }
+}
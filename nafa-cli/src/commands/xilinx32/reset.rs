@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use eyre::Result;
+use nafa_io::units::Bytes;
+use nafa_xilinx::_32bit::{Controller, actions};
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    /// Wait for the device to configure itself again (i.e. from flash), and
+    /// fail if it doesn't within this many milliseconds.
+    #[arg(long, value_name = "MS", num_args = 0..=1, default_missing_value = "5000")]
+    pub wait_done: Option<u64>,
+    /// Instead of clearing the configuration, reboot into the image at this
+    /// flash address with WBSTAR and IPROG (MultiBoot).
+    #[arg(long, value_name = "ADDR")]
+    pub multiboot: Option<Bytes<u32>>,
+}
+
+pub async fn run(cont: Controller<'_>, args: Args) -> Result<()> {
+    let wait_done = args.wait_done.map(Duration::from_millis);
+    let stats = match args.multiboot {
+        Some(addr) => actions::reset::multiboot(cont, addr.0, wait_done).await?,
+        None => actions::reset::run(cont, wait_done).await?,
+    };
+    println!("cleared after {:.3}ms", stats.time_init.as_secs_f32() * 1e3);
+    if let Some(done) = stats.time_done {
+        println!("done after {:.3}ms", done.as_secs_f32() * 1e3);
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use eyre::{OptionExt as _, Result};
+use facet::Facet;
+use nafa_xilinx::_32bit::{Controller, actions, layout::Frames, status::Stat};
+
+use crate::output;
+
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    /// Print the readback CRC settings and whether it found an error.
+    Status,
+    /// Read back the configuration every `--interval` seconds, and report the
+    /// frames that changed since the first readback.
+    ///
+    /// Frames the design writes, i.e. block RAM or LUT RAM that isn't masked,
+    /// show up as changed too.
+    Scrub {
+        #[arg(long, default_value_t = 10)]
+        interval: u64,
+        /// Stop after this many readbacks, instead of running until
+        /// interrupted.
+        #[arg(long)]
+        count: Option<usize>,
+    },
+}
+
+/// `seu status`, for `--format`.
+#[derive(Facet)]
+struct StatusReport {
+    rbcrc_enabled: bool,
+    rbcrc_action: &'static str,
+    crc_error: bool,
+    rbcrc_sw: u32,
+    lut_ram_masked: bool,
+    stat: u32,
+}
+
+pub async fn run(
+    mut cont: Controller<'_>,
+    command: Command,
+    format: Option<output::Format>,
+) -> Result<()> {
+    match command {
+        Command::Status => {
+            let status = actions::seu::status(cont, 0).await?;
+            if let Some(format) = format {
+                let report = StatusReport {
+                    rbcrc_enabled: status.rbcrc_enabled(),
+                    rbcrc_action: status.rbcrc_action(),
+                    crc_error: status.stat.contains(Stat::CRC_ERROR),
+                    rbcrc_sw: status.rbcrc_sw,
+                    lut_ram_masked: status.lut_ram_masked(),
+                    stat: status.stat.bits(),
+                };
+                return output::print(&report, format);
+            }
+            match status.rbcrc_enabled() {
+                true => println!("readback CRC: enabled, {} on error", status.rbcrc_action()),
+                false => println!("readback CRC: disabled"),
+            }
+            println!("   CRC error: {}", status.stat.contains(Stat::CRC_ERROR));
+            println!("    RBCRC_SW: {:#010x}", status.rbcrc_sw);
+            println!("LUT RAM mask: {}", status.lut_ram_masked());
+            println!("        stat: {}", status.stat);
+        }
+        Command::Scrub { interval, count } => {
+            let len = cont.info().readback;
+            let len = len.ok_or_eyre("unsupported device for readback")?;
+            let frame = cont.info().family.frame_words();
+            let mut golden = None;
+            for pass in (0..).take(count.unwrap_or(usize::MAX)) {
+                if pass != 0 {
+                    smol::Timer::after(Duration::from_secs(interval)).await;
+                }
+                let data = actions::readback::run(cont.reborrow(), len.into()).await?;
+                let frames = Frames::from_readback(data, frame.0)?;
+                let Some(golden) = &golden else {
+                    println!(
+                        "readback {pass}: {} frames, kept as reference",
+                        frames.len()
+                    );
+                    golden = Some(frames);
+                    continue;
+                };
+                let changed: Vec<_> = golden.differing(&frames).collect();
+                match &changed[..] {
+                    [] => println!("readback {pass}: no change"),
+                    changed => println!(
+                        "readback {pass}: {} frames changed: {changed:?}",
+                        changed.len()
+                    ),
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+use eyre::Result;
+use nafa_io::units::Bytes;
+use nafa_xilinx::_32bit::{
+    Controller,
+    spi_flash::{Flash, JtagSpi},
+};
+
+/// The flash has to be reachable through a `bscan_spi` bridge bitstream,
+/// loaded with `program` first.
+#[derive(Clone, clap::Subcommand)]
+pub enum Command {
+    /// Print the JEDEC ID and geometry of the flash.
+    Id,
+    /// Save the flash contents to a file.
+    Read {
+        output_file: PathBuf,
+        #[arg(long, default_value = "0")]
+        addr: Bytes<usize>,
+        /// Defaults to the rest of the flash.
+        #[arg(long)]
+        len: Option<Bytes<usize>>,
+    },
+    /// Erase a range, aligned to the smallest erase size.
+    Erase {
+        #[arg(long, default_value = "0")]
+        addr: Bytes<usize>,
+        /// Defaults to the rest of the flash.
+        #[arg(long)]
+        len: Option<Bytes<usize>>,
+    },
+    /// Compare the flash against a file.
+    Verify {
+        input_file: PathBuf,
+        #[arg(long, default_value = "0")]
+        addr: Bytes<usize>,
+    },
+}
+
+pub async fn run(
+    cont: Controller<'_>,
+    pb: Option<&indicatif::ProgressBar>,
+    command: Command,
+) -> Result<()> {
+    let mut flash = Flash::new(JtagSpi::new(cont)).await?;
+    let info = flash.info().clone();
+    let rest = |addr: Bytes<usize>| info.size.saturating_sub(addr.0);
+    let progress = |len: usize| {
+        if let Some(pb) = pb {
+            pb.set_length(len as _);
+        }
+        move |done: usize| {
+            if let Some(pb) = pb {
+                pb.set_position(done as _);
+            }
+        }
+    };
+
+    match command {
+        Command::Id => {
+            let [mfg, kind, capacity] = info.jedec_id;
+            println!("jedec id {mfg:02X} {kind:02X} {capacity:02X}");
+            println!("size     {}", Bytes(info.size));
+            for erase in &info.erase {
+                println!("erase    {} (0x{:02X})", Bytes(erase.size), erase.opcode);
+            }
+            let source = if info.sfdp { "sfdp" } else { "jedec id" };
+            println!("geometry from {source}");
+        }
+        Command::Read {
+            output_file,
+            addr,
+            len,
+        } => {
+            let len = len.map_or(rest(addr), |l| l.0);
+            let data = flash.read(addr.0, len, &mut progress(len)).await?;
+            std::fs::write(output_file, data)?;
+        }
+        Command::Erase { addr, len } => {
+            let len = len.map_or(rest(addr), |l| l.0);
+            flash.erase(addr.0, len, &mut progress(len)).await?;
+        }
+        Command::Verify { input_file, addr } => {
+            let expected = std::fs::read(input_file)?;
+            let len = expected.len();
+            match flash.verify(addr.0, &expected, &mut progress(len)).await? {
+                None => println!("flash matches"),
+                Some(offset) => {
+                    return Err(eyre::eyre!("flash differs at {:#x}", addr.0 + offset));
+                }
+            }
+        }
+    }
+    Ok(())
+}
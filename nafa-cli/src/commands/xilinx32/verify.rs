@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use eyre::{OptionExt as _, Result};
+use nafa_io::units::Bytes;
+use nafa_xilinx::{
+    _32bit::{Controller, actions},
+    bitstream::{self, Format},
+};
+
+use crate::artifact::{self, CacheArgs, Source};
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    /// Bitstream the device was programmed with. Same formats and URLs as
+    /// `program`.
+    pub input_file: Source,
+    #[command(flatten)]
+    pub cache: CacheArgs,
+    /// Format of the bitstream, instead of guessing from its contents.
+    #[arg(long, value_name = "FORMAT")]
+    pub input_format: Option<Format>,
+    /// Mask file (`write_bitstream -mask_file`), to skip bits that change at
+    /// runtime.
+    #[arg(long, value_name = "FILE")]
+    pub mask: Option<PathBuf>,
+    /// Mismatching frames to print.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub max_mismatches: usize,
+}
+
+pub async fn run(
+    cont: Controller<'_>,
+    pb: Option<&indicatif::ProgressBar>,
+    args: Args,
+) -> Result<()> {
+    let data = artifact::load(&args.input_file, &args.cache).await?;
+    let golden = bitstream::frame_data(&bitstream::load(&data, args.input_format)?)?;
+    let mask = match &args.mask {
+        Some(path) => Some(bitstream::frame_data(&bitstream::load(
+            &std::fs::read(path)?,
+            None,
+        )?)?),
+        None => None,
+    };
+
+    let len = cont.info().readback;
+    let len = len.ok_or_eyre("unsupported device for readback")?;
+    if let Some(pb) = pb {
+        pb.set_length(Bytes::from(len).0 as _);
+    }
+
+    let report = actions::verify::run(cont, &golden, mask.as_deref(), args.max_mismatches).await?;
+    for m in &report.first {
+        println!(
+            "frame {:>6} word {:>3}: expected {:08X}, read {:08X} ({} bits differ in frame)",
+            m.frame, m.word, m.expected, m.actual, m.bits
+        );
+    }
+    if report.mismatched > report.first.len() {
+        println!("...");
+    }
+    println!(
+        "{} of {} frames differ (readback offset {} words)",
+        report.mismatched, report.frames, report.offset
+    );
+    if report.mismatched != 0 {
+        return Err(eyre::eyre!("readback does not match the bitstream"));
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+
+use eyre::{OptionExt, Result};
+use nafa_xilinx::{
+    _32bit::{
+        Controller,
+        actions::vio::{self, Layout},
+    },
+    ltx::{CoreKind, Direction, Probe, ProbeFile},
+};
+
+/// Drive / sample a virtual I/O core behind a USER register. See
+/// `nafa_xilinx::_32bit::actions::vio` for the register format the core must
+/// implement.
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: VioCommand,
+    /// USER register the core is attached to.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=4))]
+    user: u8,
+    /// Widths of the `probe_out` ports, i.e. `1,1,8`.
+    #[arg(long, value_delimiter = ',', conflicts_with = "ltx")]
+    outputs: Vec<u32>,
+    /// Widths of the `probe_in` ports.
+    #[arg(long, value_delimiter = ',', conflicts_with = "ltx")]
+    inputs: Vec<u32>,
+    /// Take port widths and net names from the VIO core in this probe file.
+    #[arg(long)]
+    ltx: Option<PathBuf>,
+}
+
+#[derive(Clone, clap::Subcommand)]
+enum VioCommand {
+    /// Print all ports.
+    Get,
+    /// Change some outputs, i.e. `probe_out0=1`. Net names from `--ltx` work
+    /// as well.
+    Set {
+        #[arg(required = true, value_name = "NAME=VALUE")]
+        assignments: Vec<String>,
+    },
+}
+
+pub async fn run(mut cont: Controller<'_>, args: Args) -> Result<()> {
+    let probes = match &args.ltx {
+        Some(path) => vio_probes(ProbeFile::read(path)?)?,
+        None => Vec::new(),
+    };
+    let layout = match &args.ltx {
+        Some(_) => Layout {
+            outputs: port_widths(&probes, Direction::Out)?,
+            inputs: port_widths(&probes, Direction::In)?,
+        },
+        None => Layout {
+            outputs: args.outputs.clone(),
+            inputs: args.inputs.clone(),
+        },
+    };
+
+    let state = vio::read(cont.reborrow(), args.user, &layout).await?;
+    match args.command {
+        VioCommand::Get => {
+            for (idx, value) in state.outputs.iter().enumerate() {
+                println!("probe_out{idx} = {value:#x}");
+            }
+            for (idx, value) in state.inputs.iter().enumerate() {
+                println!(" probe_in{idx} = {value:#x}");
+            }
+            for probe in &probes {
+                let port = match probe.direction {
+                    Direction::Out => state.outputs[probe.port as usize],
+                    Direction::In => state.inputs[probe.port as usize],
+                };
+                let value = port >> probe.lsb & mask(probe.width());
+                println!("{} = {value:#x}", probe.name);
+            }
+        }
+        VioCommand::Set { assignments } => {
+            let mut outputs = state.outputs;
+            for assignment in &assignments {
+                let (name, value) = assignment
+                    .split_once('=')
+                    .ok_or_eyre("expected NAME=VALUE")?;
+                let value = parse_value(value)?;
+                let (port, lsb, width) = match name.strip_prefix("probe_out") {
+                    Some(idx) => (idx.parse()?, 0, 64),
+                    None => {
+                        let probe = probes
+                            .iter()
+                            .find(|p| p.name == name && p.direction == Direction::Out)
+                            .ok_or_else(|| eyre::eyre!("no vio output named {name}"))?;
+                        (probe.port as usize, probe.lsb, probe.width())
+                    }
+                };
+                let current = outputs
+                    .get_mut(port)
+                    .ok_or_else(|| eyre::eyre!("no probe_out{port}"))?;
+                if value & !mask(width) != 0 {
+                    return Err(eyre::eyre!("value {value:#x} does not fit in {name}"));
+                }
+                *current = *current & !(mask(width) << lsb) | value << lsb;
+            }
+            vio::write(cont, args.user, &layout, &outputs).await?;
+        }
+    }
+    Ok(())
+}
+
+fn vio_probes(file: ProbeFile) -> Result<Vec<Probe>> {
+    let mut vios = file.cores.into_iter().filter(|c| c.kind == CoreKind::Vio);
+    let core = vios.next().ok_or_eyre("no vio core in probe file")?;
+    if vios.next().is_some() {
+        return Err(eyre::eyre!("more than one vio core in probe file"));
+    }
+    Ok(core.probes)
+}
+
+fn port_widths(probes: &[Probe], direction: Direction) -> Result<Vec<u32>> {
+    let mut widths = Vec::new();
+    for probe in probes.iter().filter(|p| p.direction == direction) {
+        let port = probe.port as usize;
+        if widths.len() <= port {
+            widths.resize(port + 1, 0);
+        }
+        widths[port] = widths[port].max(probe.msb + 1);
+    }
+    if let Some(port) = widths.iter().position(|w| *w == 0) {
+        return Err(eyre::eyre!("no probes on {direction:?} port {port}"));
+    }
+    Ok(widths)
+}
+
+fn mask(width: u32) -> u64 {
+    u64::MAX >> (64 - width)
+}
+
+fn parse_value(s: &str) -> Result<u64> {
+    let value = if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)?
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        u64::from_str_radix(bin, 2)?
+    } else {
+        s.parse()?
+    };
+    Ok(value)
+}
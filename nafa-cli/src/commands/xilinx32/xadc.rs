@@ -1,70 +1,370 @@
+use std::{
+    fs::File,
+    io::{BufWriter, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use eyre::Result;
-use nafa_xilinx::_32bit::{
-    Controller, actions,
-    drp::{Addr, Cmd, Command, Transfer},
+use facet::Facet;
+use nafa_io::devices::Xilinx32Family as Family;
+use nafa_xilinx::{
+    _32bit::{
+        Controller, actions,
+        drp::{Addr, Cmd, Command, PsAddr, Sysmon, Transfer},
+    },
+    dap::{Dap, SYSTEM_AP},
 };
 
-#[derive(clap::Args)]
-pub struct Args {}
+use crate::{
+    cli_helpers::{parse_duration, parse_u16},
+    output,
+};
+
+#[derive(Clone, clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    pub mode: Option<Mode>,
+}
+
+#[derive(Clone, clap::Subcommand)]
+pub enum Mode {
+    /// Read every channel enabled in the sequencer, instead of the fixed set
+    /// of on-chip sensors.
+    Scan {
+        /// First make the sequencer scan all channels, including every VAUX
+        /// input. Overrides the sequencer settings of the design.
+        #[arg(long)]
+        enable_all: bool,
+    },
+    /// Write a DRP register, then print the value read back. Only
+    /// configuration, sequencer, and alarm threshold registers, unless
+    /// `--unsafe`.
+    WriteReg {
+        #[arg(value_parser = parse_u16)]
+        addr: u16,
+        #[arg(value_parser = parse_u16)]
+        value: u16,
+        /// Allow any address, including status and factory test registers.
+        #[arg(long = "unsafe")]
+        unchecked: bool,
+    },
+    /// Read the sensors again and again, showing a table of their current,
+    /// lowest, and highest values, i.e. to keep an eye on a board.
+    Watch {
+        /// Time between readings, i.e. `500ms`, `1s`, or `2m`.
+        #[arg(long, default_value = "1s", value_parser = parse_duration)]
+        interval: Duration,
+        /// Stop after this many readings, instead of running until
+        /// interrupted.
+        #[arg(long)]
+        count: Option<usize>,
+        /// Also append each reading to this file, as CSV.
+        #[arg(long, value_name = "FILE")]
+        csv: Option<PathBuf>,
+    },
+}
 
-pub async fn run(mut cont: Controller<'_>, _args: Args) -> Result<()> {
+/// The readings, for `--format`.
+#[derive(Facet)]
+struct Report {
+    idcode: String,
+    name: String,
+    readings: Vec<Reading>,
+}
+
+#[derive(Facet)]
+struct Reading {
+    name: String,
+    /// DRP address of the register, or offset in the PS SYSMON.
+    addr: u16,
+    raw: u16,
+    /// The raw value converted, with each transfer function that may apply.
+    /// Empty for registers without a unit.
+    values: Vec<f32>,
+    unit: &'static str,
+}
+
+impl Reading {
+    fn new(name: &str, addr: u16, transfer: Transfer, raw: u16, unit: &'static str) -> Self {
+        let values = match transfer {
+            Transfer::None => Vec::new(),
+            Transfer::Exactly(f) => vec![f(raw)],
+            Transfer::OneOf(many) => many.iter().map(|f| f(raw)).collect(),
+        };
+        Self {
+            name: name.trim().to_owned(),
+            addr,
+            raw,
+            values,
+            unit,
+        }
+    }
+}
+
+/// `xadc write-reg`, for `--format`.
+#[derive(Facet)]
+struct Written {
+    addr: u16,
+    read: u16,
+}
+
+pub async fn run(
+    mut cont: Controller<'_>,
+    args: Args,
+    format: Option<output::Format>,
+) -> Result<()> {
     let family = cont.info().family;
+    let idcode = cont.borrow().idcode().code();
+    let name = cont.borrow().info().name.to_string();
 
-    println!("idcode: {:04X}", cont.borrow().idcode().code());
-    println!("  name: {}", cont.borrow().info().name);
+    if format.is_none() {
+        println!("idcode: {idcode:04X}");
+        println!("  name: {name}");
+    }
 
-    let c = |addr| Command {
-        cmd: Cmd::Read,
+    if let Some(Mode::WriteReg {
         addr,
-        data: 0,
+        value,
+        unchecked,
+    }) = args.mode
+    {
+        let read = actions::xadc::write_reg(cont, addr, value, unchecked).await?;
+        match format {
+            Some(format) => output::print(&Written { addr, read }, format)?,
+            None => println!("{addr:02X}: {read:04X}"),
+        }
+        return Ok(());
+    }
+
+    if let Some(Mode::Watch {
+        interval,
+        count,
+        csv,
+    }) = args.mode
+    {
+        return watch(cont, interval, count, csv.as_deref()).await;
+    }
+
+    let mut readings = Vec::new();
+    let mut record = |name: &str, addr: u16, transfer, val: u16, unit: &'static str| match format {
+        Some(_) => readings.push(Reading::new(name, addr, transfer, val, unit)),
+        None => show(name, transfer, val, unit),
     };
 
-    let regs = [
-        c(Addr::Temperature),
-        c(Addr::VccInt),
-        c(Addr::VccAux),
-        c(Addr::VpVn),
-        c(Addr::VRefP),
-        c(Addr::VRefN),
-        c(Addr::VccBram),
-    ];
-    let xadc_regs = actions::xadc::run(cont, regs).await?;
-
-    let show = |name: &str, addr: Addr, val: u16, unit: &str| {
-        const PREC: usize = 3;
-        match addr.transfer(family) {
-            Transfer::None => println!("{name}: {val:04X}"),
-            Transfer::Exactly(f) => println!("{name}: {val:04X} => {:.PREC$}{unit}", f(val)),
-            Transfer::OneOf(many) => {
-                let mut it = many.iter();
-                if let Some(first) = it.next() {
-                    println!("{name}: {val:04X} => {:.PREC$}{unit}", first(val));
-                }
-                for f in it {
-                    println!(
-                        "{:len$}       => {:.PREC$}{unit}",
-                        "",
-                        f(val),
-                        len = name.len()
-                    );
+    if let Some(Mode::Scan { enable_all }) = args.mode {
+        for (addr, val) in actions::xadc::scan(cont, enable_all).await? {
+            let unit = match addr {
+                Addr::Temperature => "C",
+                _ => "V",
+            };
+            record(
+                &format!("{addr:?}"),
+                addr as u16,
+                addr.transfer(family),
+                val,
+                unit,
+            );
+        }
+        return print_report(format, idcode, name, readings);
+    }
+
+    let sensors = sensors(&mut cont);
+    let regs = sensors.iter().map(|s| read(s.1));
+    let xadc_regs = actions::xadc::run(cont.reborrow(), regs).await?;
+
+    for ((name, addr, unit), val) in sensors.into_iter().zip(xadc_regs) {
+        record(name, addr as u16, addr.transfer(family), val, unit);
+    }
+
+    if zynq_up(&cont) {
+        // the DAP may not be on the chain, or the PS may be off
+        match read_ps(&mut cont).await {
+            Ok(ps_regs) => {
+                for (reg, val) in PsAddr::ALL.into_iter().zip(ps_regs) {
+                    let unit = match reg {
+                        PsAddr::TempLpd | PsAddr::TempFpd => "C",
+                        _ => "V",
+                    };
+                    record(reg.name(), reg as u16, reg.transfer(), val, unit);
                 }
             }
+            Err(e) => tracing::warn!("cannot read the PS SYSMON: {e}"),
+        }
+    }
+
+    print_report(format, idcode, name, readings)
+}
+
+/// Read every register of the PS SYSMON through the ARM DAP, then go back to
+/// the PL TAP.
+async fn read_ps(cont: &mut Controller<'_>) -> Result<Vec<u16>> {
+    let idx = cont.borrow().info_before().len();
+    let ps_regs = async {
+        let mut dap = Dap::new(cont.borrow()).await?;
+        let mut mem = dap.mem_ap(SYSTEM_AP).await?;
+        actions::xadc::run_ps(&mut mem, PsAddr::ALL).await
+    }
+    .await;
+    cont.borrow().select(idx)?;
+    ps_regs
+}
+
+/// Whether the device is a Zynq UltraScale+, with a PS SYSMON of its own.
+fn zynq_up(cont: &Controller<'_>) -> bool {
+    let info = cont.info();
+    matches!(info.family, Family::UP) && info.ps_cores.is_some()
+}
+
+/// A sensor: name padded to line up, register, and unit.
+pub type Sensor = (&'static str, Addr, &'static str);
+
+/// The on-chip sensors of the device's XADC / SYSMON.
+pub fn sensors(cont: &mut Controller<'_>) -> Vec<Sensor> {
+    let family = cont.info().family;
+    let sensors = [
+        ("  temp", Addr::Temperature, "C"),
+        ("vccint", Addr::VccInt, "V"),
+        ("vccaux", Addr::VccAux, "V"),
+        ("  vpvn", Addr::VpVn, "V"),
+        (" vrefp", Addr::VRefP, "V"),
+        (" vrefn", Addr::VRefN, "V"),
+        ("  bram", Addr::VccBram, "V"),
+        (" user0", Addr::VUser0, "V"),
+        (" user1", Addr::VUser1, "V"),
+        (" user2", Addr::VUser2, "V"),
+        (" user3", Addr::VUser3, "V"),
+    ];
+    // on a Zynq UltraScale+, the PL SYSMON also measures some PS rails:
+    // VCC_PSINTLP, VCC_PSINTFP, and VCC_PSAUX
+    let ps = [
+        ("  pslp", Addr::VccPInt, "V"),
+        ("  psfp", Addr::VccPAux, "V"),
+        (" psaux", Addr::VccODdr, "V"),
+    ];
+    let zynq_up = zynq_up(cont);
+    let sysmon = Sysmon::of(family);
+    (sensors.into_iter())
+        .chain(ps.into_iter().filter(|_| zynq_up))
+        .filter(|(_, addr, _)| sysmon.has(*addr))
+        .collect()
+}
+
+pub fn read(addr: Addr) -> Command {
+    Command {
+        cmd: Cmd::Read,
+        addr,
+        data: 0,
+    }
+}
+
+/// `val` converted with the first transfer function of `addr`.
+pub fn value(family: Family, addr: Addr, val: u16) -> Option<f32> {
+    match addr.transfer(family) {
+        Transfer::None => None,
+        Transfer::Exactly(f) => Some(f(val)),
+        Transfer::OneOf(many) => many.first().map(|f| f(val)),
+    }
+}
+
+/// Read the sensors every `interval`, redrawing a table with their current,
+/// lowest, and highest values, and appending them to `csv`.
+async fn watch(
+    mut cont: Controller<'_>,
+    interval: Duration,
+    count: Option<usize>,
+    csv: Option<&Path>,
+) -> Result<()> {
+    let family = cont.info().family;
+    let sensors = sensors(&mut cont);
+    let mut csv = match csv {
+        Some(path) => {
+            let mut w = BufWriter::new(File::create(path)?);
+            let names = sensors
+                .iter()
+                .map(|(name, _, unit)| format!("{}_{unit}", name.trim()));
+            writeln!(w, "time,{}", names.collect::<Vec<_>>().join(","))?;
+            Some(w)
         }
+        None => None,
     };
+    let redraw = std::io::stdout().is_terminal();
+    let mut range: Vec<Option<(f32, f32)>> = vec![None; sensors.len()];
 
-    if let [_, temp, vccint, vccaux, vpvn, vrefp, vrefn, vcc_bram] = xadc_regs.as_chunks().0 {
-        fn x(x: &[u8; 4]) -> u16 {
-            u32::from_le_bytes(*x) as u16
+    for pass in (0..).take(count.unwrap_or(usize::MAX)) {
+        if pass != 0 {
+            smol::Timer::after(interval).await;
         }
+        let regs = sensors.iter().map(|s| read(s.1));
+        let raw = actions::xadc::run(cont.reborrow(), regs).await?;
+        let values: Vec<_> = (sensors.iter().zip(raw))
+            .map(|((_, addr, _), val)| value(family, *addr, val))
+            .collect();
 
-        show("  temp", Addr::Temperature, x(temp), "F");
-        show("vccint", Addr::VccInt, x(vccint), "V");
-        show("vccaux", Addr::VccAux, x(vccaux), "V");
-        show("  vpvn", Addr::VpVn, x(vpvn), "V");
-        show(" vrefp", Addr::VRefP, x(vrefp), "V");
-        show(" vrefn", Addr::VRefN, x(vrefn), "V");
-        show("  bram", Addr::VccBram, x(vcc_bram), "V");
-    }
+        // on a terminal, back up over the previous table and clear each line
+        // before writing over it
+        let clear = if redraw { "\x1b[2K" } else { "" };
+        if redraw && pass != 0 {
+            print!("\x1b[{}A", sensors.len() + 1);
+        }
+        println!("{clear}{:>6}  {:>9}  {:>9}  {:>9}", "", "now", "min", "max");
+        for (((name, _, unit), value), range) in sensors.iter().zip(&values).zip(&mut range) {
+            let Some(value) = *value else {
+                println!("{clear}{name}: {:>9}", "-");
+                continue;
+            };
+            let (min, max) = range.get_or_insert((value, value));
+            (*min, *max) = (min.min(value), max.max(value));
+            println!("{clear}{name}: {value:>8.3}{unit}  {min:>8.3}{unit}  {max:>8.3}{unit}");
+        }
 
+        if let Some(w) = &mut csv {
+            let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+            let values = values
+                .iter()
+                .map(|v| v.map(|v| v.to_string()).unwrap_or_default());
+            writeln!(w, "{time:.3},{}", values.collect::<Vec<_>>().join(","))?;
+            w.flush()?;
+        }
+    }
     Ok(())
 }
+
+fn print_report(
+    format: Option<output::Format>,
+    idcode: u32,
+    name: String,
+    readings: Vec<Reading>,
+) -> Result<()> {
+    let Some(format) = format else {
+        return Ok(());
+    };
+    let report = Report {
+        idcode: format!("{idcode:#010x}"),
+        name,
+        readings,
+    };
+    output::print(&report, format)
+}
+
+fn show(name: &str, transfer: Transfer, val: u16, unit: &str) {
+    const PREC: usize = 3;
+    match transfer {
+        Transfer::None => println!("{name}: {val:04X}"),
+        Transfer::Exactly(f) => println!("{name}: {val:04X} => {:.PREC$}{unit}", f(val)),
+        Transfer::OneOf(many) => {
+            let mut it = many.iter();
+            if let Some(first) = it.next() {
+                println!("{name}: {val:04X} => {:.PREC$}{unit}", first(val));
+            }
+            for f in it {
+                println!(
+                    "{:len$}       => {:.PREC$}{unit}",
+                    "",
+                    f(val),
+                    len = name.len()
+                );
+            }
+        }
+    }
+}
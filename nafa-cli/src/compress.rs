@@ -0,0 +1,108 @@
+//! gzip and zstd compressed files. Configuration images are mostly zeros, so
+//! they are often shipped compressed.
+
+use std::io::{self, Read, Write};
+
+use eyre::{Result, WrapErr};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression of `data`, from its magic bytes.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if data.starts_with(&ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// `data` decompressed, if it is compressed.
+pub fn decompress(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    let Some(compression) = Compression::detect(data) else {
+        return Ok(None);
+    };
+    let mut out = Vec::new();
+    match compression {
+        Compression::Gzip => flate2::read::MultiGzDecoder::new(data).read_to_end(&mut out),
+        Compression::Zstd => zstd::stream::read::Decoder::new(data)?.read_to_end(&mut out),
+    }
+    .wrap_err_with(|| format!("failed to decompress {compression:?} data"))?;
+    Ok(Some(out))
+}
+
+/// Writes to `W`, compressed or not. [`Writer::finish`] has to be called to
+/// end the compressed stream.
+pub enum Writer<W: Write> {
+    Plain(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(out: W, compression: Option<Compression>) -> io::Result<Self> {
+        Ok(match compression {
+            None => Self::Plain(out),
+            Some(Compression::Gzip) => {
+                Self::Gzip(flate2::write::GzEncoder::new(out, Default::default()))
+            }
+            Some(Compression::Zstd) => Self::Zstd(zstd::stream::write::Encoder::new(out, 0)?),
+        })
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        let mut out = match self {
+            Self::Plain(out) => out,
+            Self::Gzip(w) => w.finish()?,
+            Self::Zstd(w) => w.finish()?,
+        };
+        out.flush()?;
+        Ok(out)
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(w) => w.write(buf),
+            Self::Gzip(w) => w.write(buf),
+            Self::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            Self::Gzip(w) => w.flush(),
+            Self::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 7) as u8).collect();
+        assert_eq!(decompress(&data).unwrap(), None);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let mut w = Writer::new(Vec::new(), Some(compression)).unwrap();
+            w.write_all(&data).unwrap();
+            let compressed = w.finish().unwrap();
+            assert_eq!(Compression::detect(&compressed), Some(compression));
+            assert_eq!(decompress(&compressed).unwrap(), Some(data.clone()));
+        }
+    }
+}
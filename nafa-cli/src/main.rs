@@ -1,17 +1,38 @@
 use std::{
     collections::HashMap,
-    sync::atomic::{AtomicUsize, Ordering},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use clap::Parser;
 use color_eyre::Result;
-use nafa_io::{Backend, Controller, devices::DeviceInfo, jtag::IdCode};
+use eyre::WrapErr;
+use nafa_io::{
+    Backend, Controller, ProgressSink, TapSelector, Timeouts,
+    cables::{self, Edge},
+    devices::DeviceInfo,
+    hotplug::Watch,
+    jtag::IdCode,
+    units::{Bits, Bytes},
+};
 use smol::future::FutureExt;
 
-use crate::cli_helpers::UsbAddr;
+use crate::cli_helpers::{CableChannel, CableSelector, UsbAddr, parse_dr_chunk, parse_idcode};
 
+mod artifact;
 mod cli_helpers;
 mod commands;
+mod compress;
+mod output;
+mod program;
+
+/// How long to wait for the OS to report a disconnect after an IO error.
+const DISCONNECT_GRACE: Duration = Duration::from_millis(500);
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(clap::Parser)]
 struct Args {
@@ -31,13 +52,235 @@ struct Global {
     )]
     usb: UsbAddr,
 
+    /// Cable to open: its serial number, to pick one of several identical
+    /// cables, or `NAME:CHANNEL` for one channel of a multi-channel cable,
+    /// i.e. `ft4232h:A`. Each channel is its own chain, so separate
+    /// invocations can use different channels of the same cable at once.
+    #[arg(long, global = true, value_name = "SERIAL|NAME:CHANNEL")]
+    cable: Option<CableSelector>,
+
     /// Device to open if there are multiple devices on the JTAG chain.
-    #[arg(long, global = true)]
+    /// Defaults to the only device that isn't just along for the ride, i.e.
+    /// the PL of a Zynq-7000 rather than its ARM DAP.
+    #[arg(long, global = true, visible_alias = "index", value_name = "N",
+          conflicts_with_all = ["idcode", "device"])]
     jtag_idx: Option<usize>,
 
+    /// Open the device with this IDCODE, in hex, instead of by position. The
+    /// version (top 4 bits) is only compared if it isn't 0.
+    #[arg(long, global = true, value_name = "IDCODE", value_parser = parse_idcode,
+          conflicts_with = "device")]
+    idcode: Option<IdCode>,
+
+    /// Open the device with this name, i.e. `xc7a35t`, instead of by position.
+    #[arg(long, global = true, value_name = "NAME")]
+    device: Option<String>,
+
+    /// Print results as structured data, with stable field names, instead of
+    /// text: `info`, `xadc`, `detect-chain`, `seu status`, and `cpu status`.
+    /// The `info` commands print JSON without it.
+    #[arg(long, global = true, value_name = "FORMAT")]
+    format: Option<output::Format>,
+
+    /// TCK edge to change TDI on. Defaults to falling.
+    #[arg(long, global = true, value_name = "EDGE")]
+    tdi_edge: Option<Edge>,
+
+    /// TCK edge to sample TDO on. Defaults to falling. Some boards with slow
+    /// level shifters need rising.
+    #[arg(long, global = true, value_name = "EDGE")]
+    tdo_edge: Option<Edge>,
+
+    /// TCK frequency in Hz, instead of the cable's default. The default is
+    /// lowered to the slowest device on the chain, this is only warned about.
+    #[arg(long, global = true, value_name = "HZ")]
+    frequency: Option<u32>,
+
+    /// Use three-phase clocking (FTDI only), keeping data valid on both TCK
+    /// edges. Needed when the MPSSE bus is shared with I2C devices.
+    #[arg(long, global = true)]
+    three_phase: bool,
+
+    /// Slow, careful IO for long ribbon cables, isolators, or flaky
+    /// connectors: lowers TCK unless `--frequency` is given, waits after each
+    /// state transition, and sends data in small transfers.
+    #[arg(long, global = true)]
+    gentle: bool,
+
     /// Disable the progress bar
     #[arg(long, global = true)]
     no_progress_bar: bool,
+
+    /// If the cable is unplugged while running a command, wait for it to be
+    /// plugged back in, then run the command again.
+    #[arg(long, global = true)]
+    reconnect: bool,
+
+    /// Give up on a command once one of its steps (i.e. shutdown, program,
+    /// startup) hangs for this many seconds, without moving any data. The
+    /// cable is then reset and the command is started again from the
+    /// beginning.
+    #[arg(long, global = true, value_name = "SECONDS")]
+    watchdog: Option<u64>,
+
+    /// Fail when the cable does not answer a single transfer within this many
+    /// seconds, i.e. because the target is wedged, instead of waiting forever.
+    #[arg(long, global = true, value_name = "SECONDS")]
+    io_timeout: Option<u64>,
+
+    /// How many times the watchdog may reset the cable before giving up.
+    #[arg(long, global = true, default_value_t = 3)]
+    watchdog_retries: u32,
+
+    /// Run the command on every cable matching `--usb` in parallel, i.e. to
+    /// program the same bitstream to several boards.
+    #[arg(long, global = true)]
+    all: bool,
+
+    /// With `--all`, how many cables to run at once. Defaults to all of them.
+    #[arg(long, global = true, value_name = "N", requires = "all")]
+    jobs: Option<usize>,
+
+    /// How many times to try enabling the ARM DAP of a Zynq US+, waiting
+    /// longer each time. Some boards need more than the default of 4.
+    #[arg(long, global = true, value_name = "N")]
+    dap_attempts: Option<u32>,
+
+    /// Keep devices with an IDCODE missing from the device list in the chain,
+    /// in BYPASS, instead of failing. Their irlen is guessed from the IR
+    /// capture unless `--unknown-irlen` is given.
+    #[arg(long, global = true)]
+    allow_unknown: bool,
+
+    /// irlen of the devices kept by `--allow-unknown`.
+    #[arg(long, global = true, value_name = "BITS", requires = "allow_unknown",
+          value_parser = clap::value_parser!(u8).range(1..=32))]
+    unknown_irlen: Option<u8>,
+
+    /// Extra device list, in TOML or JSON, replacing builtin devices with the
+    /// same idcode. See `nafa_io::devices::from_str` for the format. Can be
+    /// given more than once.
+    #[arg(long, global = true, value_name = "PATH")]
+    device_db: Vec<PathBuf>,
+
+    /// Send large DR shifts (i.e. bitstreams) in pieces of this many bytes,
+    /// pausing in Pause-DR in between, so the progress bar moves during the
+    /// shift. Decimal or `0x` hex.
+    #[arg(long, global = true, value_name = "BYTES", value_parser = parse_dr_chunk)]
+    dr_chunk: Option<Bytes<usize>>,
+
+    /// Record every call to the cable, with the data shifted in and out, to
+    /// this file as JSON lines. Appended to if it already exists, i.e. when
+    /// reconnecting.
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "all")]
+    trace_jtag: Option<PathBuf>,
+
+    /// Write TCK, TMS, TDI, and TDO of every call to the cable to this file,
+    /// as a VCD waveform for GTKWave and friends. Started over when
+    /// reconnecting.
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "all")]
+    vcd: Option<PathBuf>,
+}
+
+impl Global {
+    fn cable_options(&self) -> cables::Options {
+        let default = cables::Options::default();
+        cables::Options {
+            tdi_edge: self.tdi_edge.unwrap_or(default.tdi_edge),
+            tdo_edge: self.tdo_edge.unwrap_or(default.tdo_edge),
+            clock_frequency: self.frequency,
+            three_phase: self.three_phase,
+            gentle: self.gentle,
+        }
+    }
+
+    fn detect_options(&self) -> nafa_io::DetectOptions {
+        let default = nafa_io::DetectOptions::default();
+        nafa_io::DetectOptions {
+            dap_attempts: self.dap_attempts.unwrap_or(default.dap_attempts),
+            unknown: match self.allow_unknown {
+                true => nafa_io::UnknownDevices::Bypass {
+                    irlen: self.unknown_irlen.map(Bits),
+                },
+                false => default.unknown,
+            },
+        }
+    }
+
+    fn tap_selector(&self) -> Option<TapSelector> {
+        match (self.idcode, &self.device) {
+            (Some(idcode), _) => Some(TapSelector::IdCode(idcode)),
+            (None, Some(name)) => Some(TapSelector::Name(name.clone())),
+            (None, None) => None,
+        }
+    }
+
+    fn serial(&self) -> Option<&str> {
+        match &self.cable {
+            Some(CableSelector::Serial(serial)) => Some(serial),
+            _ => None,
+        }
+    }
+
+    fn channel(&self) -> Option<&CableChannel> {
+        match &self.cable {
+            Some(CableSelector::Channel(channel)) => Some(channel),
+            _ => None,
+        }
+    }
+
+    fn usb_addr(&self) -> Result<UsbAddr> {
+        let Some(cable) = self.channel() else {
+            return Ok(self.usb);
+        };
+        match cables::MULTI_CHANNEL.iter().find(|c| c.name == cable.name) {
+            Some(c) => Ok(UsbAddr {
+                vid: c.vid,
+                pid: c.pid,
+            }),
+            None => Err(eyre::eyre!("unknown multi-channel cable {}", cable.name)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("command made no progress for {0:?}")]
+struct WatchdogExpired(Duration);
+
+/// Notes when the command last made progress, for `--watchdog`, and passes
+/// everything on to the progress bar, if there is one.
+struct Steps {
+    last: Arc<Mutex<Instant>>,
+    inner: Option<Box<dyn ProgressSink>>,
+}
+
+impl Steps {
+    fn step(&self) {
+        *self.last.lock().expect("not poisoned") = Instant::now();
+    }
+}
+
+impl ProgressSink for Steps {
+    fn written(&mut self, bytes: usize) {
+        self.step();
+        if let Some(inner) = &mut self.inner {
+            inner.written(bytes);
+        }
+    }
+
+    fn read(&mut self, bytes: usize) {
+        self.step();
+        if let Some(inner) = &mut self.inner {
+            inner.read(bytes);
+        }
+    }
+
+    fn phase(&mut self, name: &str) {
+        self.step();
+        if let Some(inner) = &mut self.inner {
+            inner.phase(name);
+        }
+    }
 }
 
 #[derive(clap::Subcommand)]
@@ -52,21 +295,75 @@ enum Command {
 enum StandaloneCommand {
     DetectChain,
     Flash(commands::flash::Args),
+    /// Read or change the FTDI configuration EEPROM of the cable.
+    #[command(subcommand, alias = "eeprom")]
+    FtdiEeprom(commands::eeprom::Command),
+    /// Reset the cable's USB port and re-initialize it, for when it stopped
+    /// responding.
+    CableReset,
+    /// Blink the cable's LED, to find it among identical cables.
+    Identify(commands::identify::Args),
+    /// Check the cable works by looping TDI back to TDO inside it, to tell
+    /// cable faults from target faults.
+    Selftest,
 }
 
-#[derive(clap::Subcommand)]
+#[derive(Clone, clap::Subcommand)]
 enum ControllerCommand {
+    /// Print what is known about the active device, whatever its family.
+    Info(commands::info::Args),
     #[command(subcommand)]
     Xilinx32(commands::xilinx32::Command),
+    /// Spartan-6, with its 16-bit configuration logic.
+    #[command(subcommand)]
+    Xilinx16(commands::xilinx16::Command),
+    /// Virtex-4, Virtex-5, and Virtex-6, with their 10- or 14-bit IR.
+    #[command(subcommand)]
+    Virtex(commands::virtex::Command),
+    /// XC9500XL CPLDs, programmed from JEDEC files.
+    #[command(subcommand)]
+    Cpld(commands::cpld::Command),
+    /// XCFxxS platform flash and XC18V00 PROMs, from the same images as the
+    /// FPGA.
+    #[command(subcommand)]
+    Prom(commands::prom::Command),
     #[command(subcommand)]
     Microchip(commands::microchip::Command),
+    /// Clear the configuration of the active device, returning it to an
+    /// unconfigured state or making it reload from flash. Same as
+    /// `xilinx32 reset`.
+    Reset(commands::xilinx32::reset::Args),
+    /// Print the device DNA of the active device, in hex. Devices with
+    /// several SLRs have one per SLR.
+    Dna,
+    /// Read or write PS memory of a Zynq through its ARM DAP.
+    #[command(subcommand)]
+    Mem(commands::mem::Command),
+    /// Halt, resume, or reset the application cores of a Zynq PS.
+    #[command(subcommand)]
+    Cpu(commands::cpu::Command),
+    /// A live dashboard of the XADC / SYSMON sensors, their alarms, and the
+    /// configuration status of the active device.
+    #[cfg(feature = "top")]
+    Top(commands::top::Args),
 }
 
 impl ControllerCommand {
     fn wants_progress(&self) -> bool {
         match self {
+            Self::Info(_args) => false,
             Self::Xilinx32(command) => command.wants_progress(),
+            Self::Xilinx16(command) => command.wants_progress(),
+            Self::Virtex(command) => command.wants_progress(),
+            Self::Cpld(command) => command.wants_progress(),
+            Self::Prom(command) => command.wants_progress(),
             Self::Microchip(_command) => false,
+            Self::Reset(_args) => false,
+            Self::Dna => false,
+            Self::Mem(_command) => false,
+            Self::Cpu(command) => command.wants_progress(),
+            #[cfg(feature = "top")]
+            Self::Top(_args) => false,
         }
     }
 }
@@ -81,125 +378,385 @@ async fn async_main(Args { global, command }: Args) -> Result<()> {
     // no controller
     let command = match command {
         Command::Standalone(StandaloneCommand::DetectChain) => {
-            let backend = &mut get_backend(global.usb).await?;
-            let chain = nafa_io::detect_chain(backend, &get_device_map()).await?;
-            for (idx, (idcode, info)) in chain.iter().enumerate() {
-                let code = idcode.code();
-                let info = nafa_io::controller::IdCodeInfo::new(4, *idcode, Some(info));
-                println!("{idx}: {code:08X}\n{info}");
+            let backend = &mut get_backend(&global).await?;
+            let options = global.detect_options();
+            let devices = get_device_map(&global)?;
+            let chain = nafa_io::detect_chain_with(backend, &devices, &options).await?;
+            match global.format {
+                Some(format) => output::print(&chain, format)?,
+                None => println!("{chain}"),
             }
             return Ok(());
         }
         Command::Standalone(StandaloneCommand::Flash(args)) => {
-            return commands::flash::run(global.usb, args).await;
+            return commands::flash::run(get_device(&global).await?, args).await;
+        }
+        Command::Standalone(StandaloneCommand::FtdiEeprom(command)) => {
+            return commands::eeprom::run(get_device(&global).await?, command).await;
+        }
+        Command::Standalone(StandaloneCommand::CableReset) => {
+            let device = get_device(&global).await?;
+            return commands::cable_reset::run(device, &global.cable_options()).await;
+        }
+        Command::Standalone(StandaloneCommand::Identify(args)) => {
+            return commands::identify::run(global.usb_addr()?, global.serial(), args).await;
+        }
+        Command::Standalone(StandaloneCommand::Selftest) => {
+            let backend = &mut get_backend(&global).await?;
+            backend.self_test().await?;
+            println!("cable self-test passed");
+            return Ok(());
         }
         Command::Controller(c) => c,
     };
 
-    let mut cont = get_controller(&get_device_map(), global.usb, global.jtag_idx).await?;
+    if global.all {
+        return run_all(&global, command).await;
+    }
+
+    let devices = get_device_map(&global)?;
+    let device = get_device(&global).await?;
+    let mut watch = Watch::new(device.clone())?;
+    let mut cont = get_controller(&devices, &global, device).await?;
+    let cancel = nafa_io::CancellationToken::new();
+    cont.set_cancellation(Some(cancel.clone()));
+    let mut watchdog_resets = 0;
+    let last_step = Arc::new(Mutex::new(Instant::now()));
+    let action = loop {
+        *last_step.lock().expect("not poisoned") = Instant::now();
+        let steps = global.watchdog.map(|_| &last_step);
+        let attempt = run_with_progress(&mut cont, &global, command.clone(), steps)
+            .or(async { Err(watch.disconnected().await.into()) })
+            .or(watchdog(global.watchdog, &last_step))
+            .or(ctrl_c(&cancel))
+            .await;
+        let err = match attempt {
+            Ok(action) => break action,
+            Err(err) => err,
+        };
+
+        if err.is::<WatchdogExpired>() && watchdog_resets < global.watchdog_retries {
+            watchdog_resets += 1;
+            tracing::warn!(%err, attempt = watchdog_resets, "resetting cable");
+            cont.detach();
+            let device = watch.reset_port(RECONNECT_TIMEOUT).await?;
+            cont.reconnect(init_backend(&global, device).await?, &devices)
+                .await?;
+            continue;
+        }
+        let connection_error = (err.downcast_ref::<nafa_io::Error>())
+            .is_some_and(|e| matches!(e.root(), nafa_io::Error::Connection(_)));
+        let disconnected = connection_error || watch.was_disconnected(DISCONNECT_GRACE).await;
+        if !(global.reconnect && disconnected) {
+            return Err(err);
+        }
+
+        tracing::warn!(%err, "waiting for cable to reconnect");
+        let device = watch.reconnected(RECONNECT_TIMEOUT).await?;
+        cont.reconnect(init_backend(&global, device).await?, &devices)
+            .await?;
+    };
+    if let Some(action) = action {
+        action()
+    }
+    Ok(())
+}
+
+/// Run `command` on every matching cable, at most `--jobs` at a time. Output
+/// of each cable is printed once all of them are done.
+async fn run_all(global: &Global, command: ControllerCommand) -> Result<()> {
+    let usb = global.usb_addr()?;
+    let cables = cables::find_all(usb.vid, usb.pid).await?;
+    if cables.is_empty() {
+        return Err(eyre::eyre!("failed to open device {usb}"));
+    }
+    let count = cables.len();
+    let jobs = global.jobs.unwrap_or(count);
+    let devices = get_device_map(global)?;
+    let cancel = nafa_io::CancellationToken::new();
+
+    let run_one = async |device: nusb::DeviceInfo| {
+        let name = match device.serial_number() {
+            Some(serial) => serial.to_owned(),
+            None => format!("{:?}", device.id()),
+        };
+        let result = async {
+            let mut cont = get_controller(&devices, global, device).await?;
+            cont.set_cancellation(Some(cancel.clone()));
+            run(&mut cont, None, command.clone(), global.format).await
+        };
+        (name, result.await)
+    };
+    let results = async { Ok(cables::for_each(cables, jobs, run_one).await) }
+        .or(ctrl_c(&cancel))
+        .await?;
+
+    let mut failed = 0;
+    for (name, result) in results {
+        println!("== {name} ==");
+        match result {
+            Ok(Some(action)) => action(),
+            Ok(None) => (),
+            Err(err) => {
+                failed += 1;
+                println!("failed: {err:?}");
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(eyre::eyre!("{failed} of {count} cables failed")),
+    }
+}
+
+/// Cancel the running command on Ctrl-C, so it stops with the TAP in
+/// Test-Logic-Reset rather than halfway through a shift. A second Ctrl-C exits
+/// right away, for commands that don't get to check for cancellation (i.e. a
+/// long shift without `--dr-chunk`).
+async fn ctrl_c<T>(cancel: &nafa_io::CancellationToken) -> Result<T> {
+    use async_signal::{Signal, Signals};
+    use smol::stream::StreamExt;
+
+    let mut signals = Signals::new([Signal::Int])?;
+    signals.next().await;
+    cancel.cancel();
+    tracing::warn!("cancelling, press Ctrl-C again to exit immediately");
+    signals.next().await;
+    std::process::exit(130)
+}
+
+/// Fails once the command made no progress for `seconds`: no new step, and no
+/// bytes of a transfer reported to the progress bar.
+async fn watchdog<T>(seconds: Option<u64>, last_step: &Mutex<Instant>) -> Result<T> {
+    let Some(seconds) = seconds else {
+        return std::future::pending().await;
+    };
+    let deadline = Duration::from_secs(seconds);
+    loop {
+        let due = *last_step.lock().expect("not poisoned") + deadline;
+        if Instant::now() >= due {
+            return Err(WatchdogExpired(deadline).into());
+        }
+        smol::Timer::at(due).await;
+    }
+}
+
+async fn run_with_progress(
+    cont: &mut Controller,
+    global: &Global,
+    command: ControllerCommand,
+    last_step: Option<&Arc<Mutex<Instant>>>,
+) -> Result<Option<Box<dyn FnOnce()>>> {
     let progress = !global.no_progress_bar && command.wants_progress();
-    let action = if progress {
-        let notify = AtomicUsize::new(0);
+    let notify = Arc::new(AtomicUsize::new(0));
+    let mut sink: Option<Box<dyn ProgressSink>> = match progress {
+        true => Some(Box::new(notify.clone())),
+        false => None,
+    };
+    if let Some(last) = last_step {
+        sink = Some(Box::new(Steps {
+            last: last.clone(),
+            inner: sink,
+        }));
+    }
+    let old = sink.map(|sink| cont.set_progress(Some(sink)));
+
+    let ret = if progress {
         let pb = setup_progress_bar();
+        // commands may also move the bar themselves, only overwrite it when
+        // the controller reported something new
+        let mut last = 0;
         let progress = smol::future::poll_fn(|_| {
             let pos = notify.load(Ordering::Acquire);
-            if pos == 0 {
+            if pos == 0 && pb.position() == 0 {
                 pb.reset_elapsed();
             }
-            pb.set_position(pos as _);
+            if pos != last {
+                pb.set_position(pos as _);
+                last = pos;
+            }
             std::task::Poll::Pending
         });
-        cont.with_notifications(&notify, async |cont| run(cont, Some(&pb), command).await)
+        run(cont, Some(&pb), command, global.format)
             .race(progress)
-            .await?
+            .await
     } else {
-        run(&mut cont, None, command).await?
+        run(cont, None, command, global.format).await
     };
-    if let Some(action) = action {
-        action()
+    if let Some(old) = old {
+        cont.set_progress(old);
     }
-    Ok(())
+    ret
 }
 
 async fn run(
     cont: &mut Controller,
     pb: Option<&indicatif::ProgressBar>,
     command: ControllerCommand,
+    format: Option<output::Format>,
 ) -> Result<Option<Box<dyn FnOnce()>>, eyre::Error> {
     match command {
-        ControllerCommand::Xilinx32(cmd) => commands::xilinx32::run(cont, pb, cmd).await,
-        ControllerCommand::Microchip(cmd) => commands::microchip::run(cont, cmd).await,
+        ControllerCommand::Info(args) => {
+            (commands::info::run(cont, args, format).await).map(|()| None)
+        }
+        ControllerCommand::Xilinx32(cmd) => commands::xilinx32::run(cont, pb, cmd, format).await,
+        ControllerCommand::Xilinx16(cmd) => commands::xilinx16::run(cont, pb, cmd, format).await,
+        ControllerCommand::Virtex(cmd) => commands::virtex::run(cont, pb, cmd, format).await,
+        ControllerCommand::Cpld(cmd) => commands::cpld::run(cont, pb, cmd).await.map(|()| None),
+        ControllerCommand::Prom(cmd) => commands::prom::run(cont, pb, cmd).await.map(|()| None),
+        ControllerCommand::Microchip(cmd) => commands::microchip::run(cont, cmd, format).await,
+        ControllerCommand::Reset(args) => {
+            let cmd = commands::xilinx32::Command::Reset(args);
+            commands::xilinx32::run(cont, pb, cmd, format).await
+        }
+        ControllerCommand::Dna => commands::dna::run(cont).await.map(|()| None),
+        ControllerCommand::Mem(cmd) => commands::mem::run(cont, cmd).await.map(|()| None),
+        ControllerCommand::Cpu(cmd) => {
+            (commands::cpu::run(cont, pb, cmd, format).await).map(|()| None)
+        }
+        #[cfg(feature = "top")]
+        ControllerCommand::Top(args) => commands::top::run(cont, args).await.map(|()| None),
     }
 }
 
-fn get_device_map() -> HashMap<IdCode, DeviceInfo> {
-    nafa_io::devices::builtin().collect()
+fn get_device_map(global: &Global) -> Result<HashMap<IdCode, DeviceInfo>> {
+    let mut devices: HashMap<_, _> = nafa_io::devices::all().collect();
+    for path in &global.device_db {
+        devices.extend(nafa_io::devices::from_path(path)?);
+    }
+    Ok(devices)
 }
 
-async fn get_device(addr: UsbAddr) -> Result<nusb::DeviceInfo> {
-    let Some(device) = nusb::list_devices()
-        .await?
-        .find(|d| d.vendor_id() == addr.vid && d.product_id() == addr.pid)
-    else {
-        return Err(eyre::eyre!("failed to open device {addr}"));
+/// The cable matching `--usb` and `--cable`, the first one if there are
+/// several and no serial number was given.
+async fn get_device(global: &Global) -> Result<nusb::DeviceInfo> {
+    let addr = global.usb_addr()?;
+    let serial = global.serial();
+    let Some(device) = nusb::list_devices().await?.find(|d| {
+        d.vendor_id() == addr.vid
+            && d.product_id() == addr.pid
+            && serial.is_none_or(|serial| d.serial_number() == Some(serial))
+    }) else {
+        return Err(match serial {
+            Some(serial) => eyre::eyre!("no cable {addr} with serial {serial}"),
+            None => eyre::eyre!("failed to open device {addr}"),
+        });
     };
     Ok(device)
 }
 
-async fn get_backend(addr: UsbAddr) -> Result<Box<dyn Backend>, eyre::Error> {
-    let device = get_device(addr).await?;
-    match nafa_io::cables::init(device).await {
-        Ok(b) => Ok(b),
-        Err(errs) => Err(eyre::eyre!("failed to init cable: {errs:?}")),
+async fn get_backend(global: &Global) -> Result<Box<dyn Backend>, eyre::Error> {
+    init_backend(global, get_device(global).await?).await
+}
+
+async fn init_backend(
+    global: &Global,
+    device: nusb::DeviceInfo,
+) -> Result<Box<dyn Backend>, eyre::Error> {
+    let backend = if let Some(cable) = global.channel() {
+        let options = &global.cable_options();
+        cables::init_channel(device, &cable.name, cable.channel, options).await?
+    } else {
+        match cables::init(device, &global.cable_options()).await {
+            Ok(b) => b,
+            Err(errs) => {
+                let errs: Vec<_> = errs.into_iter().map(eyre::Report::from).collect();
+                return Err(eyre::eyre!("failed to init cable: {errs:?}"));
+            }
+        }
+    };
+    wrap_backend(global, backend)
+}
+
+/// Wrap `backend` in the recorders asked for on the command line.
+fn wrap_backend(global: &Global, mut backend: Box<dyn Backend>) -> Result<Box<dyn Backend>> {
+    let open = |path: &PathBuf, append: bool| {
+        let file = std::fs::File::options()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .wrap_err_with(|| format!("failed to open {}", path.display()))?;
+        eyre::Ok(Box::new(std::io::BufWriter::new(file)))
+    };
+    if let Some(path) = &global.trace_jtag {
+        backend = Box::new(nafa_io::trace::Recorder::new(backend, open(path, true)?));
+    }
+    if let Some(path) = &global.vcd {
+        backend = Box::new(nafa_io::vcd::Vcd::new(backend, open(path, false)?)?);
     }
+    Ok(backend)
 }
 
 async fn get_controller(
     devices: &HashMap<IdCode, DeviceInfo>,
-    addr: UsbAddr,
-    jtag_idx: Option<usize>,
+    global: &Global,
+    device: nusb::DeviceInfo,
 ) -> Result<Controller> {
-    fn chain_info(devices: &[(IdCode, DeviceInfo)]) -> String {
-        let devices = devices.iter().enumerate();
-        devices.fold(String::new(), |mut acc, (idx, (idcode, info))| {
+    fn chain_info(chain: &nafa_io::Chain) -> String {
+        chain.iter().fold(String::new(), |mut acc, tap| {
             use std::fmt::Write;
-            let code = idcode.code();
-            let name = info.name;
-            write!(&mut acc, "\n    {idx:>2}: {code:08X} {name}")
+            let nafa_io::Tap {
+                position,
+                idcode,
+                name,
+                ..
+            } = tap;
+            write!(&mut acc, "\n    {position:>2}: {idcode:08X} {name}")
                 .expect("write to string cannot fail");
             acc
         })
     }
 
-    let mut backend = get_backend(addr).await?;
+    let mut backend = init_backend(global, device).await?;
 
-    let devices = nafa_io::detect_chain(&mut backend, devices).await?;
-    let (before, device, after) = match (&devices[..], jtag_idx) {
-        ([], _) => return Err(eyre::eyre!("no devices detected on jtag chain")),
+    let chain = nafa_io::detect_chain_with(&mut backend, devices, &global.detect_options()).await?;
+    let idx = match global.tap_selector() {
+        Some(selector) => match chain.select(&selector)[..] {
+            [idx] => Some(idx),
+            [] => {
+                return Err(eyre::eyre!(
+                    "no matching device on jtag chain:{}",
+                    chain_info(&chain)
+                ));
+            }
+            _ => {
+                return Err(eyre::eyre!(
+                    "multiple matching devices on jtag chain, use --jtag-idx:{}",
+                    chain_info(&chain)
+                ));
+            }
+        },
+        None => global.jtag_idx.or_else(|| chain.default_target()),
+    };
+    let (before, device, after) = match (chain.len(), idx) {
+        (0, _) => return Err(eyre::eyre!("no devices detected on jtag chain")),
 
-        ([single], Some(0) | None) => (vec![], single.clone(), vec![]),
+        (1, Some(0) | None) => chain.split(0).expect("chain has one device"),
 
-        (multiple, Some(idx)) if idx >= multiple.len() => {
+        (len, Some(idx)) if idx >= len => {
             return Err(eyre::eyre!(
                 "idx {idx} too large for chain:{}",
-                chain_info(multiple)
+                chain_info(&chain)
             ));
         }
-        (multiple, None) => {
+        (_, None) => {
             return Err(eyre::eyre!(
                 "multiple devices on jtag chain, but no index provided:{}",
-                chain_info(multiple)
+                chain_info(&chain)
             ));
         }
 
-        (multiple, Some(idx)) => {
-            let before = multiple[..idx].to_vec();
-            let chosen = multiple[idx].clone();
-            let after = multiple[idx + 1..].to_vec();
-            (before, chosen, after)
-        }
+        (_, Some(idx)) => chain.split(idx).expect("idx is in bounds"),
     };
-    Controller::new(backend, before, device, after).await
+    let mut cont = Controller::new(backend, before, device, after).await?;
+    cont.set_chunk_size(global.dr_chunk);
+    cont.set_timeouts(Timeouts {
+        run: None,
+        flush: global.io_timeout.map(Duration::from_secs),
+    });
+    Ok(cont)
 }
 
 fn init_logging() -> Result<()> {
@@ -217,7 +774,12 @@ fn init_logging() -> Result<()> {
     }
 
     tracing_subscriber::registry()
-        .with(fmt::layer().with_filter(NoNusbErrors))
+        // stderr, stdout may be a readback or structured output
+        .with(
+            fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(NoNusbErrors),
+        )
         .with(EnvFilter::from_default_env())
         .with(tracing_error::ErrorLayer::default())
         .init();
@@ -235,3 +797,15 @@ fn setup_progress_bar() -> indicatif::ProgressBar {
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
     pb
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn test_cli() {
+        Args::command().debug_assert();
+    }
+}
@@ -0,0 +1,51 @@
+//! Printing structured results (`info`), for people or for scripts.
+
+use eyre::Result;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// One line of JSON.
+    #[default]
+    Json,
+    Yaml,
+    /// Indented and colored, for reading.
+    Pretty,
+}
+
+// options of the commands printing structured results by default (not a doc
+// comment, clap would take it as their description). The format itself is
+// the global `--format`, JSON if not given.
+//
+// no group, it would clash with the `Args` of the command flattening this
+#[derive(Clone, clap::Args)]
+#[group(skip)]
+pub struct Args {
+    /// Same as `--format pretty`.
+    #[arg(short, long, conflicts_with = "format")]
+    pub pretty: bool,
+}
+
+impl Args {
+    /// `format` is the global `--format`.
+    pub fn format(&self, format: Option<Format>) -> Format {
+        match self.pretty {
+            true => Format::Pretty,
+            false => format.unwrap_or_default(),
+        }
+    }
+
+    pub fn print<'a, F: facet::Facet<'a>>(&self, value: &F, format: Option<Format>) -> Result<()> {
+        print(value, self.format(format))
+    }
+}
+
+pub fn print<'a, F: facet::Facet<'a>>(value: &F, format: Format) -> Result<()> {
+    use facet_pretty::FacetPretty;
+
+    match format {
+        Format::Json => facet_json::to_writer_std(std::io::stdout(), value)?,
+        Format::Yaml => print!("{}", facet_yaml::to_string(value)?),
+        Format::Pretty => println!("{}", value.pretty()),
+    }
+    Ok(())
+}
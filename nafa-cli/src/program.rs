@@ -0,0 +1,68 @@
+//! What the `program` command of each FPGA family shares: the bitstream
+//! argument, and the timing summary printed once it's done.
+
+use std::{sync::Arc, time::Duration};
+
+use eyre::Result;
+use nafa_xilinx::bitstream::{self, Format};
+
+use crate::artifact::{self, CacheArgs, Source};
+
+#[derive(Clone, clap::Args)]
+pub struct Input {
+    /// Bitstream to program (`.bit`, `.bin`, `.rbt`, or `.mcs`), or `-` for
+    /// stdin. May be an `http(s)://` URL, optionally pinned with
+    /// `#sha256=<hex>` to enable the download cache.
+    pub input_file: Source,
+    /// Format of the bitstream, instead of guessing from its contents.
+    #[arg(long, value_name = "FORMAT")]
+    pub input_format: Option<Format>,
+    #[command(flatten)]
+    pub cache: CacheArgs,
+}
+
+impl Input {
+    /// The file as given, i.e. with the header of a `.bit`.
+    pub async fn fetch(&self) -> Result<Arc<[u8]>> {
+        artifact::load(&self.input_file, &self.cache).await
+    }
+
+    /// Decode `data` from [`Input::fetch`] into the raw bitstream.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        bitstream::load(data, self.input_format)
+    }
+
+    /// The bitstream in the bit order it's shifted in, with `pb` sized for
+    /// it.
+    pub async fn load(&self, pb: Option<&indicatif::ProgressBar>) -> Result<Vec<u8>> {
+        let data = self.decode(&self.fetch().await?)?;
+        Ok(to_wire_order(&data, pb))
+    }
+}
+
+/// Bit-reverse each byte of `data`, which the configuration logic takes MSB
+/// first, and size `pb` for it.
+pub fn to_wire_order(data: &[u8], pb: Option<&indicatif::ProgressBar>) -> Vec<u8> {
+    if let Some(pb) = pb {
+        pb.set_length(data.len() as _)
+    }
+    data.iter().map(|d| d.reverse_bits()).collect()
+}
+
+/// Print how long each step took, lined up.
+pub fn print_times(shutdown: Duration, program: Duration, startup: Duration) {
+    let digits = as_millis(program)
+        .max(as_millis(shutdown))
+        .max(as_millis(startup))
+        .log10()
+        .ceil() as usize;
+    let width = digits + 4;
+    println!("shutdown: {:>width$.3}ms", as_millis(shutdown));
+    println!(" program: {:>width$.3}ms", as_millis(program));
+    println!(" startup: {:>width$.3}ms", as_millis(startup));
+}
+
+fn as_millis(d: Duration) -> f32 {
+    const NANOS_PER_MILLI: u32 = 1_000_000;
+    (d.as_nanos() as f32) / (NANOS_PER_MILLI as f32)
+}
@@ -1,7 +1,7 @@
-use eyre::Result;
+use std::{any::Any, time::Duration};
 
 use crate::{
-    jtag,
+    Error, Result, jtag,
     units::{Bits, Bytes},
 };
 
@@ -13,6 +13,46 @@ pub enum Data<'d> {
     ConstantTx(bool, Bytes<usize>),
 }
 
+impl<'d> Data<'d> {
+    /// Number of bytes shifted.
+    pub fn len(&self) -> usize {
+        match self {
+            Data::Tx(tdi) | Data::TxRx(tdi) => tdi.len(),
+            Data::Rx(len) | Data::ConstantTx(_, len) => len.0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The first `mid` bytes, and the rest.
+    pub fn split_at(self, mid: usize) -> (Self, Self) {
+        match self {
+            Data::Tx(tdi) => {
+                let (a, b) = tdi.split_at(mid);
+                (Data::Tx(a), Data::Tx(b))
+            }
+            Data::TxRx(tdi) => {
+                let (a, b) = tdi.split_at(mid);
+                (Data::TxRx(a), Data::TxRx(b))
+            }
+            Data::Rx(len) => {
+                assert!(mid <= len.0);
+                (Data::Rx(Bytes(mid)), Data::Rx(Bytes(len.0 - mid)))
+            }
+            Data::ConstantTx(tdi, len) => {
+                assert!(mid <= len.0);
+                let rest = Bytes(len.0 - mid);
+                (
+                    Data::ConstantTx(tdi, Bytes(mid)),
+                    Data::ConstantTx(tdi, rest),
+                )
+            }
+        }
+    }
+}
+
 /// A device that is able to talk over JTAG.
 ///
 /// The implementation is allowed to flush at any time, though only required to
@@ -53,8 +93,131 @@ pub trait Backend: Send {
         after: Option<jtag::Path>,
     ) -> Result<()>;
 
+    /// Like [`Backend::bits`], but also capture TDO.
+    ///
+    /// Reads `len.div_ceil(8)` bytes into `buf`, LSB-first. Unused high bits of
+    /// the last byte are zero.
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()>;
+
     /// Run any queud IO commands
     async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()>;
+
+    /// Clock TCK `count` times with TMS low, staying in the current state
+    /// (usually [`jtag::State::RunTestIdle`]).
+    ///
+    /// The default implementation shifts ones through TDI.
+    async fn idle_clocks(&mut self, buf: &mut dyn Buffer, count: usize) -> Result<()> {
+        let (bytes, bits) = (count / 8, count % 8);
+        if bytes != 0 {
+            let data = Data::ConstantTx(true, Bytes(bytes));
+            self.bytes(buf, None, data, None).await?;
+        }
+        if bits != 0 {
+            self.bits(buf, None, u32::MAX, Bits(bits as u8), None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Stay in the current state for at least `duration`. Anything queued is
+    /// flushed first.
+    async fn wait(&mut self, buf: &mut dyn Buffer, duration: Duration) -> Result<()> {
+        self.flush(buf).await?;
+        smol::Timer::after(duration).await;
+        Ok(())
+    }
+
+    /// Whether this backend supports [`Backend::take_template`].
+    fn supports_templates(&self) -> bool {
+        false
+    }
+
+    /// How many times the backend flushed so far, including on its own while
+    /// queueing. Backends supporting templates count these, so a template
+    /// missing the commands flushed before it can be caught.
+    fn flushes(&self) -> usize {
+        0
+    }
+
+    /// Take everything queued since the last flush as a [`Template`], instead
+    /// of sending it.
+    ///
+    /// Only useful for small batches (i.e. polling a status register), which
+    /// the backend would not have flushed on its own while queueing.
+    fn take_template(&mut self) -> Result<Template> {
+        Err(Error::Unsupported(
+            "backend does not support command templates".into(),
+        ))
+    }
+
+    /// Send a template returned by [`Backend::take_template`] on this backend.
+    /// Data is read into `buf` as if the original commands were flushed.
+    ///
+    /// Nothing else may be queued when this is called.
+    async fn run_template(&mut self, buf: &mut dyn Buffer, template: &Template) -> Result<()> {
+        let _ = (buf, template);
+        Err(Error::Unsupported(
+            "backend does not support command templates".into(),
+        ))
+    }
+
+    /// Current TCK frequency, if the backend can change it.
+    fn frequency(&self) -> Option<Frequency> {
+        None
+    }
+
+    /// Change the TCK frequency, returning the one actually used (the
+    /// closest the cable can do without going over).
+    ///
+    /// Nothing may be queued when this is called.
+    async fn set_frequency(&mut self, hz: u32) -> Result<u32> {
+        let _ = hz;
+        Err(Error::Unsupported(
+            "backend does not support changing the frequency".into(),
+        ))
+    }
+
+    /// Check the cable itself works without involving the target, i.e. by
+    /// looping TDI back to TDO inside the cable.
+    ///
+    /// Nothing may be queued when this is called.
+    async fn self_test(&mut self) -> Result<()> {
+        Err(Error::Unsupported(
+            "backend does not support a self-test".into(),
+        ))
+    }
+}
+
+/// See [`Backend::frequency`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frequency {
+    pub hz: u32,
+    /// Asked for explicitly, i.e. with
+    /// [`cables::Options::clock_frequency`](crate::cables::Options::clock_frequency),
+    /// instead of the cable's default. The controller doesn't lower these.
+    pub forced: bool,
+}
+
+/// A batch of commands, already encoded for a specific backend. Sending it
+/// again skips building the command buffer, which dominates the time taken for
+/// short transfers.
+pub struct Template(Box<dyn Any + Send + Sync>);
+
+impl Template {
+    pub fn new<T: Any + Send + Sync>(data: T) -> Self {
+        Self(Box::new(data))
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
 }
 
 pub trait Buffer: Send {
@@ -68,6 +231,13 @@ pub trait Buffer: Send {
     /// Notify that `size` bytes were written. Used for progress bars.
     #[expect(unused)]
     fn notify_write(&mut self, size: usize) {}
+
+    /// Called by the [`Controller`](crate::Controller) after flushing part of
+    /// a chunked DR shift, once everything read so far is complete. Streaming
+    /// buffers hand the data off here.
+    fn flushed(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -97,9 +267,56 @@ impl<B: Backend + ?Sized + Send> Backend for Box<B> {
         B::bits(self, buf, before, data, len, after).await
     }
 
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        B::tdo_bits(self, buf, before, data, len, after).await
+    }
+
     async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
         B::flush(&mut *self, buf).await
     }
+
+    async fn idle_clocks(&mut self, buf: &mut dyn Buffer, count: usize) -> Result<()> {
+        B::idle_clocks(self, buf, count).await
+    }
+
+    async fn wait(&mut self, buf: &mut dyn Buffer, duration: Duration) -> Result<()> {
+        B::wait(self, buf, duration).await
+    }
+
+    fn supports_templates(&self) -> bool {
+        B::supports_templates(self)
+    }
+
+    fn flushes(&self) -> usize {
+        B::flushes(self)
+    }
+
+    fn take_template(&mut self) -> Result<Template> {
+        B::take_template(self)
+    }
+
+    async fn run_template(&mut self, buf: &mut dyn Buffer, template: &Template) -> Result<()> {
+        B::run_template(self, buf, template).await
+    }
+
+    fn frequency(&self) -> Option<Frequency> {
+        B::frequency(self)
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<u32> {
+        B::set_frequency(self, hz).await
+    }
+
+    async fn self_test(&mut self) -> Result<()> {
+        B::self_test(self).await
+    }
 }
 
 pub struct ScratchBuffer {
@@ -129,6 +346,16 @@ impl ScratchBuffer {
         self.data.clear();
         self.scratch = 0;
     }
+
+    /// Swap the data with `other`, which is cleared and becomes the new
+    /// (empty) buffer. Keeps the allocations of both around, unlike copying
+    /// the data out.
+    pub fn swap(&mut self, other: &mut Vec<u8>) {
+        let len = self.data.len() - self.scratch;
+        self.data.truncate(len);
+        std::mem::swap(&mut self.data, other);
+        self.clear();
+    }
 }
 
 impl Default for ScratchBuffer {
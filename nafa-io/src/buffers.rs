@@ -0,0 +1,157 @@
+//! [`Buffer`]s that don't keep the data in memory, for reads too large to
+//! hold at once (i.e. readback of the largest Virtex parts). Pass them to
+//! [`Controller::run_into_buffer`].
+//!
+//! [`Controller::run_into_buffer`]: crate::Controller::run_into_buffer
+
+use std::io::Write;
+
+use crate::{Buffer, Result, ScratchBuffer};
+
+/// Writes everything read to `W` after each flush, only holding about one
+/// [chunk](crate::Controller::set_chunk_size) in memory.
+pub struct WriteBuffer<W> {
+    buf: ScratchBuffer,
+    out: W,
+    written: usize,
+}
+
+impl<W: Write + Send> WriteBuffer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            buf: ScratchBuffer::new(),
+            out,
+            written: 0,
+        }
+    }
+
+    /// Bytes handed to `W` so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Write out anything left over and flush `W`.
+    pub fn finish(mut self) -> Result<W> {
+        self.flushed()?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write + Send> Buffer for WriteBuffer<W> {
+    fn extend(&mut self, size: usize, scratch: usize) -> &mut [u8] {
+        self.buf.extend(size, scratch)
+    }
+
+    fn flushed(&mut self) -> Result<()> {
+        self.out.write_all(self.buf.data())?;
+        self.written += self.buf.data().len();
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// Reads straight into a memory-mapped file, without copying through a
+/// buffer in between.
+///
+/// The file is created with the expected size up front, and grown if more
+/// than that is read. [`MmapBuffer::finish`] truncates it to what was read.
+#[cfg(feature = "mmap")]
+pub struct MmapBuffer {
+    file: std::fs::File,
+    map: memmap2::MmapMut,
+    /// Bytes of data in `map`, scratch space follows.
+    len: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapBuffer {
+    /// Create (or truncate) the file at `path`, sized for `expected` bytes.
+    pub fn create(path: &std::path::Path, expected: crate::units::Bytes<usize>) -> Result<Self> {
+        let file = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let map = Self::map(&file, expected.0)?;
+        Ok(Self { file, map, len: 0 })
+    }
+
+    fn map(file: &std::fs::File, len: usize) -> Result<memmap2::MmapMut> {
+        // a zero-length mapping fails on some platforms
+        file.set_len(len.max(1) as u64)?;
+        // SAFETY: the file was just created by us, and is only accessed
+        // through this mapping while it's alive. Other processes changing it
+        // at the same time is out of our control, as with any mmap.
+        Ok(unsafe { memmap2::MmapMut::map_mut(file)? })
+    }
+
+    /// Bytes read so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Everything read so far.
+    pub fn data(&self) -> &[u8] {
+        &self.map[..self.len]
+    }
+
+    /// Flush the mapping and cut the file down to what was read.
+    pub fn finish(self) -> Result<std::fs::File> {
+        self.map.flush()?;
+        drop(self.map);
+        self.file.set_len(self.len as u64)?;
+        Ok(self.file)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Buffer for MmapBuffer {
+    fn extend(&mut self, size: usize, scratch: usize) -> &mut [u8] {
+        let needed = self.len + size + scratch;
+        if needed > self.map.len() {
+            // `extend` can't fail, running out of disk or address space is as
+            // fatal as running out of memory for a `Vec`
+            self.map.flush().expect("failed to flush mmap");
+            let grown = needed.max(self.map.len() * 2);
+            self.map = Self::map(&self.file, grown).expect("failed to grow mmap");
+        }
+        let start = self.len;
+        self.len += size;
+        &mut self.map[start..needed]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_buffer() {
+        let mut buf = WriteBuffer::new(Vec::new());
+        buf.extend(2, 3).copy_from_slice(&[1, 2, 0xff, 0xff, 0xff]);
+        buf.flushed().unwrap();
+        buf.extend(1, 0).copy_from_slice(&[3]);
+        assert_eq!(buf.written(), 2);
+        assert_eq!(buf.finish().unwrap(), [1, 2, 3]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_buffer() {
+        let path = std::env::temp_dir().join(format!("nafa-mmap-{}", std::process::id()));
+        let mut buf = MmapBuffer::create(&path, crate::units::Bytes(2)).unwrap();
+        buf.extend(2, 1).copy_from_slice(&[1, 2, 0xff]);
+        // past the expected size, grows the file
+        buf.extend(3, 0).copy_from_slice(&[3, 4, 5]);
+        assert_eq!(buf.data(), [1, 2, 3, 4, 5]);
+        drop(buf.finish().unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), [1, 2, 3, 4, 5]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,26 +1,113 @@
-use std::pin::Pin;
+use std::{pin::Pin, time::Duration};
 
-use eyre::Result;
+use smol::future::FutureExt as _;
 
-use crate::{Backend, ftdi, usb_blaster, xpc};
+use crate::{Backend, Error, Result, ftdi, gentle, usb_blaster, xpc};
 
 type BoxedBackend = Box<dyn Backend>;
 type InitResult = Pin<Box<dyn Future<Output = Result<BoxedBackend>>>>;
-type InitFn = fn(nusb::Device) -> InitResult;
+type InitFn = fn(nusb::Device, &Options) -> InitResult;
 
-pub async fn init(device: nusb::DeviceInfo) -> Result<BoxedBackend, Vec<eyre::Report>> {
+/// Settings applied to any cable that supports them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// TCK edge TDI changes on.
+    pub tdi_edge: Edge,
+    /// TCK edge TDO is sampled on.
+    pub tdo_edge: Edge,
+    /// TCK frequency in Hz. `None` uses the default for the cable.
+    pub clock_frequency: Option<u32>,
+    /// Hold data valid on both edges of the clock. Each bit then takes three
+    /// phases instead of two, so the base clock runs 3/2 times faster to keep
+    /// TCK at the requested frequency. Needed when sharing the bus with I2C
+    /// devices.
+    pub three_phase: bool,
+    /// Slow, careful IO for unreliable connections, see [`crate::gentle`].
+    /// Also lowers the default clock frequency to [`gentle::FREQUENCY`].
+    pub gentle: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tdi_edge: Edge::Falling,
+            tdo_edge: Edge::Falling,
+            clock_frequency: None,
+            three_phase: false,
+            gentle: false,
+        }
+    }
+}
+
+impl Options {
+    /// Wrap `backend` as requested by [`Options::gentle`].
+    fn wrap(&self, backend: BoxedBackend) -> BoxedBackend {
+        match self.gentle {
+            true => Box::new(gentle::Gentle::new(backend)),
+            false => backend,
+        }
+    }
+
+    /// Clock frequency to use for a cable defaulting to `default`.
+    fn frequency(&self, default: u32) -> u32 {
+        match (self.clock_frequency, self.gentle) {
+            (Some(frequency), _) => frequency,
+            (None, true) => default.min(gentle::FREQUENCY),
+            (None, false) => default,
+        }
+    }
+
+    /// For cables where the clocking is fixed in firmware.
+    fn require_default(&self, cable: &str) -> Result<()> {
+        let clocking = Self {
+            gentle: false,
+            ..*self
+        };
+        if clocking != Self::default() {
+            return Err(Error::Unsupported(format!(
+                "{cable} does not support changing clock settings"
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl std::str::FromStr for Edge {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rising" | "pos" => Ok(Self::Rising),
+            "falling" | "neg" => Ok(Self::Falling),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown edge {s:?}, expected rising or falling"
+            ))),
+        }
+    }
+}
+
+pub async fn init(device: nusb::DeviceInfo, options: &Options) -> Result<BoxedBackend, Vec<Error>> {
     let mut errs = Vec::new();
 
     for cable in KNOWN {
         if cable.vid == device.vendor_id() && cable.pid == device.product_id() {
             tracing::info!(device = cable.name, "try init");
             let device = device.open().await.map_err(|x| vec![x.into()])?;
-            match (cable.init)(device).await {
+            match (cable.init)(device, options).await {
                 Ok(backend) => {
                     tracing::info!(device = cable.name, "init success");
-                    return Ok(backend);
+                    return Ok(options.wrap(backend));
                 }
-                Err(e) => errs.push(e.wrap_err(eyre::eyre!("while trying cable {}", cable.name))),
+                Err(e) => errs.push(Error::Context {
+                    context: format!("while trying cable {}", cable.name),
+                    source: Box::new(e),
+                }),
             }
         }
     }
@@ -28,6 +115,218 @@ pub async fn init(device: nusb::DeviceInfo) -> Result<BoxedBackend, Vec<eyre::Re
     Err(errs)
 }
 
+/// Every plugged-in device with the given VID:PID, i.e. to open several
+/// identical cables at once.
+pub async fn find_all(vid: u16, pid: u16) -> Result<Vec<nusb::DeviceInfo>> {
+    let devices = nusb::list_devices().await?;
+    Ok(devices
+        .filter(|d| d.vendor_id() == vid && d.product_id() == pid)
+        .collect())
+}
+
+/// Run `f` on each of `devices` concurrently, at most `jobs` at a time, i.e.
+/// to program several boards at once. Results are in the same order as
+/// `devices`.
+pub async fn for_each<T>(
+    devices: Vec<nusb::DeviceInfo>,
+    jobs: usize,
+    f: impl AsyncFn(nusb::DeviceInfo) -> T,
+) -> Vec<T> {
+    let jobs = smol::lock::Semaphore::new(jobs.max(1));
+    let ex = smol::LocalExecutor::new();
+    let tasks: Vec<_> = devices
+        .into_iter()
+        .map(|device| {
+            let (jobs, f) = (&jobs, &f);
+            ex.spawn(async move {
+                let _job = jobs.acquire().await;
+                f(device).await
+            })
+        })
+        .collect();
+    ex.run(async {
+        let mut results = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            results.push(task.await);
+        }
+        results
+    })
+    .await
+}
+
+/// [`init`] each of `devices` concurrently. Results are in the same order as
+/// `devices`.
+pub async fn init_all(
+    devices: Vec<nusb::DeviceInfo>,
+    options: &Options,
+) -> Vec<Result<BoxedBackend, Vec<Error>>> {
+    let jobs = devices.len();
+    for_each(devices, jobs, async |device| init(device, options).await).await
+}
+
+/// Recover a wedged cable: reset its USB port, wait for it to come back, then
+/// initialize it from scratch.
+///
+/// Nothing else may have the cable open, otherwise claiming its interface
+/// again fails.
+pub async fn recover(
+    device: &nusb::DeviceInfo,
+    timeout: Duration,
+    options: &Options,
+) -> Result<BoxedBackend, Vec<Error>> {
+    let device = reset_port(device, timeout).await.map_err(|e| vec![e])?;
+    init(device, options).await
+}
+
+/// Reset the USB port `device` is attached to, returning the device once it is
+/// visible again.
+///
+/// The returned info may differ from `device` if the cable re-enumerated.
+#[tracing::instrument(skip_all)]
+pub async fn reset_port(device: &nusb::DeviceInfo, timeout: Duration) -> Result<nusb::DeviceInfo> {
+    // a reset may or may not cause the device to re-enumerate, depending on the
+    // OS and cable. Don't rely on hotplug events, poll instead.
+    match device.open().await {
+        Ok(handle) => {
+            if let Err(e) = handle.reset().await {
+                tracing::warn!("failed to reset usb port: {e}");
+            }
+        }
+        Err(e) => tracing::warn!("failed to open device for reset: {e}"),
+    }
+
+    let wait = async {
+        loop {
+            smol::Timer::after(Duration::from_millis(100)).await;
+            let Ok(mut devices) = nusb::list_devices().await else {
+                continue;
+            };
+            if let Some(info) = devices.find(|info| is_same_cable(info, device)) {
+                tracing::info!("cable back after reset");
+                return Ok(info);
+            }
+        }
+    };
+    let timeout = async {
+        smol::Timer::after(timeout).await;
+        Err(crate::hotplug::ConnectionError::ReconnectTimeout(timeout).into())
+    };
+    wait.or(timeout).await
+}
+
+/// Same VID, PID, and serial number.
+pub(crate) fn is_same_cable(a: &nusb::DeviceInfo, b: &nusb::DeviceInfo) -> bool {
+    a.vendor_id() == b.vendor_id()
+        && a.product_id() == b.product_id()
+        && a.serial_number() == b.serial_number()
+}
+
+/// Initialize a single channel of a cable with more than one MPSSE engine. Each
+/// channel drives its own chain, and can be opened independently of the
+/// others.
+pub async fn init_channel(
+    device: nusb::DeviceInfo,
+    name: &str,
+    channel: ftdi::devices::Interface,
+    options: &Options,
+) -> Result<BoxedBackend> {
+    let Some(cable) = MULTI_CHANNEL.iter().find(|c| c.name == name) else {
+        let known: Vec<_> = MULTI_CHANNEL.iter().map(|c| c.name).collect();
+        return Err(Error::CableNotFound(format!(
+            "cable {name} does not have multiple channels, expected one of {known:?}"
+        )));
+    };
+    if !cable.channels.contains(&channel) {
+        return Err(Error::Unsupported(format!(
+            "channel {channel:?} of {name} cannot do jtag, expected one of {:?}",
+            cable.channels,
+        )));
+    }
+
+    tracing::info!(device = cable.name, ?channel, "try init");
+    let info = cable.info.with_interface(channel);
+    let device = device.open().await?;
+    let frequency = options.frequency(cable.clock_frequency);
+    let backend = ftdi::Device::new(device, &info, frequency, options).await?;
+    Ok(options.wrap(Box::new(backend)))
+}
+
+pub struct MultiChannel {
+    pub name: &'static str,
+    pub vid: u16,
+    pub pid: u16,
+    info: &'static ftdi::devices::Info,
+    clock_frequency: u32,
+    /// Channels with an MPSSE engine.
+    pub channels: &'static [ftdi::devices::Interface],
+}
+
+pub const MULTI_CHANNEL: &[MultiChannel] = &[MultiChannel {
+    name: "ft4232h",
+    vid: 0x0403,
+    pid: 0x6011,
+    info: &ftdi::devices::FT4232H,
+    clock_frequency: 1_500_000,
+    // C and D are UART / bitbang only
+    channels: &[ftdi::devices::Interface::A, ftdi::devices::Interface::B],
+}];
+
+/// Cables with an LED (or other visible GPIO) that [`identify`] can blink.
+pub struct Led {
+    pub name: &'static str,
+    pub vid: u16,
+    pub pid: u16,
+    info: &'static ftdi::devices::Info,
+    pub pin: ftdi::devices::Pin,
+}
+
+pub const LEDS: &[Led] = &[
+    Led {
+        name: "arm-usb-ocd-h",
+        vid: 0x15ba,
+        pid: 0x002b,
+        info: &ftdi::devices::ARM_USB_OCD_H,
+        pin: ftdi::devices::Pin::Cbus(3),
+    },
+    Led {
+        name: "olimex",
+        vid: 0x15b1,
+        pid: 0x0003,
+        info: &ftdi::devices::OLIMEX,
+        pin: ftdi::devices::Pin::Cbus(3),
+    },
+];
+
+/// Blink a cable's LED, so it can be found among identical cables.
+///
+/// `pin` overrides the pin from [`LEDS`], for cables not listed there. Those
+/// are driven with the plain FTDI pin setup on channel A.
+pub async fn identify(
+    device: &nusb::DeviceInfo,
+    pin: Option<ftdi::devices::Pin>,
+    count: usize,
+) -> Result<()> {
+    const PERIOD: Duration = Duration::from_millis(500);
+
+    let known = LEDS
+        .iter()
+        .find(|l| l.vid == device.vendor_id() && l.pid == device.product_id());
+    let (info, pin) = match (known, pin) {
+        (Some(led), pin) => (led.info, pin.unwrap_or(led.pin)),
+        (None, Some(pin)) => (&ftdi::devices::FT4232H, pin),
+        (None, None) => {
+            let known: Vec<_> = LEDS.iter().map(|l| l.name).collect();
+            return Err(Error::Unsupported(format!(
+                "no known led for cable {:04x}:{:04x}, expected one of {known:?} or a pin",
+                device.vendor_id(),
+                device.product_id(),
+            )));
+        }
+    };
+    tracing::info!(?pin, "blinking");
+    ftdi::blink(device.open().await?, info, pin, count, PERIOD).await
+}
+
 pub struct Cable {
     pub name: &'static str,
     pub vid: u16,
@@ -39,9 +338,13 @@ fn init_ftdi(
     device: nusb::Device,
     info: &'static ftdi::devices::Info,
     clock_frequency: u32,
+    options: &Options,
 ) -> InitResult {
+    let options = *options;
     Box::pin(async move {
-        Ok(Box::new(ftdi::Device::new(device, info, clock_frequency).await?) as BoxedBackend)
+        let clock_frequency = options.frequency(clock_frequency);
+        let device = ftdi::Device::new(device, info, clock_frequency, &options).await?;
+        Ok(Box::new(device) as BoxedBackend)
     })
 }
 
@@ -55,76 +358,84 @@ const fn c(vid: u16, pid: u16, name: &'static str, init: InitFn) -> Cable {
 }
 
 pub const KNOWN: &[Cable] = &[
-    c(0x0403, 0xcff8, "amontec", |device| {
-        init_ftdi(device, &ftdi::devices::AMONTEC, 1_500_000)
+    c(0x0403, 0xcff8, "amontec", |device, options| {
+        init_ftdi(device, &ftdi::devices::AMONTEC, 1_500_000, options)
     }),
-    c(0x15ba, 0x002b, "arm-usb-ocd-h", |device| {
-        init_ftdi(device, &ftdi::devices::ARM_USB_OCD_H, 1_500_000)
+    c(0x15ba, 0x002b, "arm-usb-ocd-h", |device, options| {
+        init_ftdi(device, &ftdi::devices::ARM_USB_OCD_H, 1_500_000, options)
     }),
-    c(0x0403, 0x6010, "bbv2", |device| {
-        init_ftdi(device, &ftdi::devices::BBV2, 1_500_000)
+    c(0x0403, 0x6010, "bbv2", |device, options| {
+        init_ftdi(device, &ftdi::devices::BBV2, 1_500_000, options)
     }),
-    c(0x0403, 0x6010, "bbv2_2", |device| {
-        init_ftdi(device, &ftdi::devices::BBV2_2, 1_500_000)
+    c(0x0403, 0x6010, "bbv2_2", |device, options| {
+        init_ftdi(device, &ftdi::devices::BBV2_2, 1_500_000, options)
     }),
-    c(0x0403, 0x8350, "cm1", |device| {
-        init_ftdi(device, &ftdi::devices::CM1, 1_500_000)
+    c(0x0403, 0x8350, "cm1", |device, options| {
+        init_ftdi(device, &ftdi::devices::CM1, 1_500_000, options)
     }),
-    c(0x0403, 0x6010, "dlp2232h", |device| {
-        init_ftdi(device, &ftdi::devices::DLP2232H, 1_500_000)
+    c(0x0403, 0x6010, "dlp2232h", |device, options| {
+        init_ftdi(device, &ftdi::devices::DLP2232H, 1_500_000, options)
     }),
-    c(0x0403, 0x6010, "ft2232test", |device| {
-        init_ftdi(device, &ftdi::devices::FT2232TEST, 8_000_000)
+    c(0x0403, 0x6010, "ft2232test", |device, options| {
+        init_ftdi(device, &ftdi::devices::FT2232TEST, 8_000_000, options)
     }),
-    c(0x0403, 0x6011, "ft4232h", |device| {
-        init_ftdi(device, &ftdi::devices::FT4232H, 1_500_000)
+    c(0x0403, 0x6011, "ft4232h", |device, options| {
+        init_ftdi(device, &ftdi::devices::FT4232H, 1_500_000, options)
     }),
-    c(0x0403, 0x6010, "ftdijtag", |device| {
-        init_ftdi(device, &ftdi::devices::FTDIJTAG, 1_500_000)
+    c(0x0403, 0x6010, "ftdijtag", |device, options| {
+        init_ftdi(device, &ftdi::devices::FTDIJTAG, 1_500_000, options)
     }),
-    c(0x0403, 0x6010, "ikda", |device| {
-        init_ftdi(device, &ftdi::devices::IKDA, 1_500_000)
+    c(0x0403, 0x6010, "ikda", |device, options| {
+        init_ftdi(device, &ftdi::devices::IKDA, 1_500_000, options)
     }),
-    c(0x0403, 0x6014, "jtaghs2", |device| {
-        init_ftdi(device, &ftdi::devices::JTAGHS2, 15_000_000)
+    c(0x0403, 0x6014, "jtaghs2", |device, options| {
+        init_ftdi(device, &ftdi::devices::JTAGHS2, 15_000_000, options)
     }),
-    c(0x0403, 0x6010, "l_motctl", |device| {
-        init_ftdi(device, &ftdi::devices::L_MOTCTL, 8_000_000)
+    c(0x0403, 0x6010, "l_motctl", |device, options| {
+        init_ftdi(device, &ftdi::devices::L_MOTCTL, 8_000_000, options)
     }),
-    c(0x0403, 0x6010, "llbbc", |device| {
-        init_ftdi(device, &ftdi::devices::LLBBC, 8_000_000)
+    c(0x0403, 0x6010, "llbbc", |device, options| {
+        init_ftdi(device, &ftdi::devices::LLBBC, 8_000_000, options)
     }),
-    c(0x0403, 0x6010, "llbus", |device| {
-        init_ftdi(device, &ftdi::devices::LLBUS, 1_500_000)
+    c(0x0403, 0x6010, "llbus", |device, options| {
+        init_ftdi(device, &ftdi::devices::LLBUS, 1_500_000, options)
     }),
-    c(0x0403, 0x6010, "llif", |device| {
-        init_ftdi(device, &ftdi::devices::LLIF, 8_000_000)
+    c(0x0403, 0x6010, "llif", |device, options| {
+        init_ftdi(device, &ftdi::devices::LLIF, 8_000_000, options)
     }),
-    c(0x2A19, 0x1009, "mimas_a7", |device| {
-        init_ftdi(device, &ftdi::devices::MIMAS_A7, 15_000_000)
+    c(0x2A19, 0x1009, "mimas_a7", |device, options| {
+        init_ftdi(device, &ftdi::devices::MIMAS_A7, 15_000_000, options)
     }),
-    c(0x0403, 0x6010, "nexys4", |device| {
-        init_ftdi(device, &ftdi::devices::NEXYS4, 30_000_000)
+    c(0x0403, 0x6010, "nexys4", |device, options| {
+        init_ftdi(device, &ftdi::devices::NEXYS4, 30_000_000, options)
     }),
-    c(0x15b1, 0x0003, "olimex", |device| {
-        init_ftdi(device, &ftdi::devices::OLIMEX, 1_500_000)
+    c(0x15b1, 0x0003, "olimex", |device, options| {
+        init_ftdi(device, &ftdi::devices::OLIMEX, 1_500_000, options)
     }),
-    c(0x9e88, 0x9e8f, "plugjtag", |device| {
-        init_ftdi(device, &ftdi::devices::PLUGJTAG, 1_500_000)
+    c(0x9e88, 0x9e8f, "plugjtag", |device, options| {
+        init_ftdi(device, &ftdi::devices::PLUGJTAG, 1_500_000, options)
     }),
-    c(0x0403, 0x8a98, "tumpa", |device| {
-        init_ftdi(device, &ftdi::devices::TUMPA, 1_500_000)
+    c(0x0403, 0x8a98, "tumpa", |device, options| {
+        init_ftdi(device, &ftdi::devices::TUMPA, 1_500_000, options)
     }),
-    c(0x0403, 0xbdc8, "turtelizer", |device| {
-        init_ftdi(device, &ftdi::devices::TURTELIZER, 1_500_000)
+    c(0x0403, 0xbdc8, "turtelizer", |device, options| {
+        init_ftdi(device, &ftdi::devices::TURTELIZER, 1_500_000, options)
     }),
-    c(0x03fd, 0x0008, "xpc", |device| {
-        Box::pin(async { Ok(Box::new(xpc::Device::new(device).await?) as BoxedBackend) })
+    c(0x03fd, 0x0008, "xpc", |device, options| {
+        let options = *options;
+        Box::pin(async move {
+            options.require_default("xpc")?;
+            Ok(Box::new(xpc::Device::new(device).await?) as BoxedBackend)
+        })
     }),
-    c(0x09fb, 0x6010, "usb-blaster II", |device| {
-        Box::pin(async { Ok(Box::new(usb_blaster::Device::new(device).await?) as BoxedBackend) })
+    c(0x09fb, 0x6010, "usb-blaster II", |device, options| {
+        let options = *options;
+        Box::pin(async move {
+            options.require_default("usb-blaster II")?;
+            Ok(Box::new(usb_blaster::Device::new(device).await?) as BoxedBackend)
+        })
     }),
-    c(0x1514, 0x2008, "flashpro5_ft4232hl", |device| {
-        init_ftdi(device, &ftdi::devices::FT4232HL, 4_000_000)
+    c(0x1514, 0x2008, "flashpro5_ft4232hl", |device, options| {
+        init_ftdi(device, &ftdi::devices::FT4232HL, 4_000_000, options)
     }),
 ];
@@ -0,0 +1,28 @@
+//! Stopping long transfers early, i.e. on Ctrl-C.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// Asks a [`Controller`](crate::Controller) to stop, see
+/// [`Controller::set_cancellation`](crate::Controller::set_cancellation).
+///
+/// Clones share the same flag, so one can be kept to cancel from elsewhere,
+/// i.e. a signal handler.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
@@ -1,46 +1,71 @@
-use std::{
-    collections::HashMap,
-    marker::PhantomData,
-    sync::atomic::{AtomicUsize, Ordering},
-};
+use std::{collections::HashMap, marker::PhantomData, time::Duration};
 
-use color_eyre::{Section as _, SectionExt as _};
-use eyre::{Result, eyre};
+mod batch;
+mod chain;
+mod scan;
 
+pub use self::{
+    batch::CommandBatch,
+    chain::{Chain, Tap, TapSelector},
+};
 use crate::{
-    Backend, Buffer, ScratchBuffer, ShortHex,
+    Backend, BitString, Buffer, CancellationToken, Error, Hex, ProgressSink, Result, ScratchBuffer,
+    ShortHex,
     backend::Data,
     devices::{DeviceInfo, GetSpecific},
+    hotplug::ConnectionError,
     jtag::{IdCode, PATHS, Path, State},
     units::{Bits, Bytes},
 };
 
-// `*const T` isn't `Send`, mostly as a lint. However, we want `Controller` to
-// be `Send`.
-//
-// The only pointer that's used in it (`.notify`) has no complex lifetime
-// requirements. It's only exposed by `.with_notifications()`, which takes in a
-// reference and only uses the pointer while inside the function.
-#[repr(transparent)]
-struct Ptr<T>(*const T);
-unsafe impl<T> Send for Ptr<T> where for<'a> &'a T: Send {}
-unsafe impl<T> Sync for Ptr<T> where for<'a> &'a T: Sync {}
-impl<T> Clone for Ptr<T> {
-    fn clone(&self) -> Self {
-        *self
-    }
-}
-impl<T> Copy for Ptr<T> {}
-
 pub struct Controller {
     backend: Box<dyn Backend>,
     before: Vec<(IdCode, DeviceInfo)>,
     active: (IdCode, DeviceInfo),
     after: Vec<(IdCode, DeviceInfo)>,
-    notify: Ptr<AtomicUsize>,
+    /// Other devices that IR shifts go to, see [`Controller::broadcast`].
+    broadcast: Vec<usize>,
+    progress: Option<Box<dyn ProgressSink>>,
+    /// See [`Controller::set_chunk_size`].
+    chunk: Option<Bytes<usize>>,
+    /// See [`Controller::set_cancellation`].
+    cancel: Option<CancellationToken>,
+    timeouts: Timeouts,
+    /// Where [`Controller::goto`] or a raw shift left the TAP. Everything else
+    /// starts and ends in [`State::RunTestIdle`].
+    state: State,
     buf: ScratchBuffer,
 }
 
+/// How long a [`Controller`] waits before giving up with [`Error::Timeout`],
+/// see [`Controller::set_timeouts`]. `None` waits forever.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// A whole [`Controller::run`], including queueing.
+    pub run: Option<Duration>,
+    /// Each flush, i.e. one chunk of a [chunked](Controller::set_chunk_size)
+    /// DR shift.
+    pub flush: Option<Duration>,
+}
+
+/// `fut`, failing with [`Error::Timeout`] if it takes longer than `after`.
+async fn with_timeout<T>(
+    what: &'static str,
+    after: Option<Duration>,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    use smol::future::FutureExt as _;
+
+    let Some(after) = after else {
+        return fut.await;
+    };
+    let timeout = async {
+        smol::Timer::after(after).await;
+        Err(Error::Timeout { what, after })
+    };
+    fut.or(timeout).await
+}
+
 pub struct TypedController<'a, T>(&'a mut Controller, PhantomData<T>);
 impl<'a, T> TypedController<'a, T>
 where
@@ -102,7 +127,7 @@ impl std::fmt::Display for IdCodeInfo<'_> {
             mfg = self.idcode.manufacturer(),
             mfg_str = self.idcode.manufacturer_name().unwrap_or("<unknown>"),
             part = self.idcode.part(),
-            part_str = self.info.map_or("<unknown>", |p| p.name),
+            part_str = self.info.map_or("<unknown>", |p| &p.name),
             version = self.idcode.version(),
             irlen = self.info.map_or(0, |i| i.irlen.0),
         )
@@ -118,48 +143,81 @@ fn get_info(
         .get(&idcode)
         .or_else(|| devices.get(&idcode.strip_version()));
     let Some(info) = info else {
-        let mut err = eyre!(
-            "idcode {code:08X} not found in device list\n{info}",
-            code = idcode.code(),
-            info = IdCodeInfo::new(4, idcode, None),
-        )
-        .wrap_err("cannot determine irlen");
+        use std::fmt::Write;
+        let mut details = IdCodeInfo::new(4, idcode, None).to_string();
 
         let shifted = IdCode::new(idcode.code() >> 1);
         if let info @ Some(_) = devices.get(&shifted) {
             let code = shifted.code();
             let info = IdCodeInfo::new(4, shifted, info);
-            err = err.note(format!(
-                "idcode {code:08X} was found in device list, possible device in bypass\n{info}"
-            ));
+            write!(
+                &mut details,
+                "\nnote: idcode {code:08X} was found in device list, possible device in \
+                 bypass\n{info}"
+            )
+            .expect("write!() to string cannot fail");
         }
 
-        err = err.section({
-            use std::fmt::Write;
-            let mut msg = String::new();
-            for (idx, (idcode, info)) in chain.iter().enumerate() {
-                let code = idcode.code();
-                let info = IdCodeInfo::new(7, *idcode, Some(info));
-                if idx != 0 {
-                    msg.push('\n');
-                }
-                write!(&mut msg, "{idx}: idcode {code:08X}\n{info}")
-                    .expect("write!() to string cannot fail");
-            }
-            msg.header("Current chain:")
-        });
+        if !chain.is_empty() {
+            details.push_str("\nCurrent chain:");
+        }
+        for (idx, (idcode, info)) in chain.iter().enumerate() {
+            let code = idcode.code();
+            let info = IdCodeInfo::new(7, *idcode, Some(info));
+            write!(&mut details, "\n{idx}: idcode {code:08X}\n{info}")
+                .expect("write!() to string cannot fail");
+        }
 
-        return Err(err);
+        return Err(Error::UnknownDevice {
+            idcode: idcode.code(),
+            details,
+        });
     };
     assert!(info.irlen <= Bits(32));
     Ok(info.clone())
 }
 
-#[tracing::instrument(skip_all)]
+/// Knobs for [`detect_chain_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetectOptions {
+    /// Attempts at enabling the Zynq US+ ARM DAP, each waiting longer for it
+    /// to come up. At least one is always made.
+    pub dap_attempts: u32,
+    pub unknown: UnknownDevices,
+}
+
+impl Default for DetectOptions {
+    fn default() -> Self {
+        Self {
+            dap_attempts: DAP_ATTEMPTS,
+            unknown: UnknownDevices::Reject,
+        }
+    }
+}
+
+/// What to do with an IDCODE that's not in the device list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnknownDevices {
+    /// Fail chain detection, since the irlen can't be known.
+    Reject,
+    /// Keep the device in the chain, always in BYPASS. Without an `irlen`,
+    /// it's guessed from the IR capture, which fails if that is ambiguous.
+    Bypass { irlen: Option<Bits<u8>> },
+}
+
 pub async fn detect_chain(
     backend: &mut dyn Backend,
     devices: &HashMap<IdCode, DeviceInfo>,
-) -> Result<Vec<(IdCode, DeviceInfo)>> {
+) -> Result<Chain> {
+    detect_chain_with(backend, devices, &DetectOptions::default()).await
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn detect_chain_with(
+    backend: &mut dyn Backend,
+    devices: &HashMap<IdCode, DeviceInfo>,
+    options: &DetectOptions,
+) -> Result<Chain> {
     let buf = &mut ScratchBuffer::new();
 
     let to_sir = Some(PATHS[State::TestLogicReset][State::ShiftIR]);
@@ -168,9 +226,22 @@ pub async fn detect_chain(
     backend.tms(buf, Path::RESET).await?;
     backend.bytes(buf, to_sir, capture, to_reset).await?;
     backend.flush(buf).await?;
-    tracing::info!(capture = %ShortHex(buf.data()), maybe_irlen = ?max_possible_combined_irlen(buf.data()));
+    let capture = BitString::new(buf.data(), Bits(buf.data().len() * 8));
+    tracing::info!(%capture, maybe_irlen = ?max_possible_combined_irlen(buf.data()));
+    let capture = buf.data().to_vec();
     buf.clear();
 
+    let devices = detect_devices(backend, buf, devices, options).await?;
+    Ok(Chain::new(devices, &capture))
+}
+
+/// IDCODEs through DR, falling back to [`scan::scan_chain`].
+async fn detect_devices(
+    backend: &mut dyn Backend,
+    buf: &mut ScratchBuffer,
+    devices: &HashMap<IdCode, DeviceInfo>,
+    options: &DetectOptions,
+) -> Result<Vec<(IdCode, DeviceInfo)>> {
     let reset_to_idle = PATHS[State::TestLogicReset][State::RunTestIdle];
     backend.tms(buf, Path::RESET).await?;
     backend.tms(buf, Path::RESET).await?;
@@ -187,10 +258,10 @@ pub async fn detect_chain(
         backend.flush(buf).await?;
 
         let (ids, []) = buf.data().as_chunks() else {
-            return Err(eyre!(
+            return Err(Error::Protocol(format!(
                 "failed to fill idcode, or returned extra data: {}",
                 ShortHex(buf.data()),
-            ));
+            )));
         };
         let id = ids[ret.len()];
         tracing::info!(id = %ShortHex(&id));
@@ -201,23 +272,27 @@ pub async fn detect_chain(
             }
 
             _idcode if must_be_last => {
-                return Err(eyre!("device after intel 1-bit-tap special case"));
+                return Err(Error::Chain(
+                    "device after intel 1-bit-tap special case".into(),
+                ));
             }
 
             // special case for Zynq US+: add ARM_DAP to the chain
             idcode if ret.is_empty() && idcode & 0xfff == (0x093 << 1) => {
                 buf.clear();
-                let idcode = zynq_us_init_arm_dap(backend, buf).await?;
+                let idcode = zynq_us_init_arm_dap(backend, buf, options.dap_attempts).await?;
                 let info = get_info(devices, &ret, idcode)?;
                 ret.push((idcode, info));
             }
 
-            // IDCODE guaranteed to start with a `1` bit, BYPASS as a single `0`. Nothing we can
-            // really do with a device in BYPASS. Could scan a pattern through IR until we see our
-            // input fed back, but that's annoying. All devices we care about start with IDCODE
-            // anyway.
+            // IDCODE guaranteed to start with a `1` bit, BYPASS as a single `0`. Everything after a
+            // device in BYPASS is misaligned, so start over with the slower IR scan.
             idcode if idcode & 1 != 1 => {
-                return Err(eyre!("device in BYPASS detected: {idcode:08X}"));
+                tracing::info!(
+                    at = ret.len(),
+                    "device in BYPASS detected, scanning IR instead"
+                );
+                return scan::scan_chain(backend, devices, options).await;
             }
 
             idcode => {
@@ -227,8 +302,19 @@ pub async fn detect_chain(
                     ret.push(info);
                     must_be_last = true;
                 } else {
-                    let info = get_info(devices, &ret, idcode)?;
-                    ret.push((idcode, info));
+                    match (get_info(devices, &ret, idcode), options.unknown) {
+                        (Ok(info), _) => ret.push((idcode, info)),
+                        (Err(e), UnknownDevices::Reject) => return Err(e),
+                        (Err(_), UnknownDevices::Bypass { irlen: Some(irlen) }) => {
+                            tracing::warn!(idcode = %Hex(idcode.code()), "unknown device, keeping it in BYPASS");
+                            ret.push((idcode, unknown_device("UNKNOWN", irlen)));
+                        }
+                        // only the IR scan can guess the irlen
+                        (Err(_), UnknownDevices::Bypass { irlen: None }) => {
+                            tracing::info!(idcode = %Hex(idcode.code()), "unknown device, scanning IR for its irlen");
+                            return scan::scan_chain(backend, devices, options).await;
+                        }
+                    }
                 }
             }
         }
@@ -238,11 +324,47 @@ pub async fn detect_chain(
     Ok(ret)
 }
 
+/// Chunk size for [`Controller::run_streaming`] when none was set.
+const STREAM_CHUNK: Bytes<usize> = Bytes(1 << 20);
+
+/// Each attempt at enabling the Zynq US+ ARM DAP waits longer after enabling,
+/// before reading the DAP IDCODE: not at all, then 1ms, 10ms, ...
+const DAP_ATTEMPTS: u32 = 4;
+
 #[tracing::instrument(skip_all)]
 async fn zynq_us_init_arm_dap(
     backend: &mut dyn Backend,
     buf: &mut ScratchBuffer,
+    attempts: u32,
 ) -> Result<IdCode> {
+    let attempts = attempts.max(1);
+    for attempt in 0..attempts {
+        let settle = match attempt {
+            0 => Duration::ZERO,
+            n => Duration::from_millis(10u64.pow(n - 1)),
+        };
+        buf.clear();
+        match zynq_us_try_arm_dap(backend, buf, settle).await? {
+            Some(idcode) => return Ok(idcode),
+            None => tracing::debug!(attempt, ?settle, "arm dap still in bypass"),
+        }
+    }
+
+    buf.clear();
+    let status = zynq_us_jtag_status(backend, buf).await?;
+    Err(Error::DapBypass {
+        attempts,
+        jtag_status: ZynqUsJtagStatus(status),
+    })
+}
+
+/// One try at enabling the DAP through the PS JTAG_CTRL register. `None`
+/// if it's still in BYPASS afterwards.
+async fn zynq_us_try_arm_dap(
+    backend: &mut dyn Backend,
+    buf: &mut ScratchBuffer,
+    settle: Duration,
+) -> Result<Option<IdCode>> {
     let reset_to_sir = Some(PATHS[State::TestLogicReset][State::ShiftIR]);
     let sir_to_idle = Some(PATHS[State::ShiftIR][State::RunTestIdle]);
     let idle_to_sdr = Some(PATHS[State::RunTestIdle][State::ShiftDR]);
@@ -262,17 +384,116 @@ async fn zynq_us_init_arm_dap(
     backend
         .bytes(buf, idle_to_sdr, enable_cmd, sdr_to_reset)
         .await?;
+    if !settle.is_zero() {
+        backend.wait(buf, settle).await?;
+    }
     backend.bytes(buf, reset_to_sdr, ones, sdr_to_reset).await?;
     backend.bytes(buf, reset_to_sdr, rx_4, sdr_to_idle).await?;
     backend.flush(buf).await?;
 
     let ([id], []) = buf.data().as_chunks() else {
-        return Err(eyre!("failed to get idcode after zynq us special case"));
+        return Err(Error::Protocol(
+            "failed to get idcode after zynq us special case".into(),
+        ));
     };
     match u32::from_le_bytes(*id) {
-        0xffff_ffff => Err(eyre!("end of chain after zynq us special case ???")),
-        idcode if idcode & 1 != 1 => Err(eyre!("still in bypass after zynq us special case")),
-        idcode => Ok(IdCode::new(idcode)),
+        0xffff_ffff => Err(Error::Chain(
+            "end of chain after zynq us special case ???".into(),
+        )),
+        idcode if idcode & 1 != 1 => Ok(None),
+        idcode => Ok(Some(IdCode::new(idcode))),
+    }
+}
+
+/// Read the PS JTAG_STATUS register, with the DAP still in BYPASS in front.
+async fn zynq_us_jtag_status(backend: &mut dyn Backend, buf: &mut ScratchBuffer) -> Result<u32> {
+    let reset_to_sir = Some(PATHS[State::TestLogicReset][State::ShiftIR]);
+    let sir_to_idle = Some(PATHS[State::ShiftIR][State::RunTestIdle]);
+    let idle_to_sdr = Some(PATHS[State::RunTestIdle][State::ShiftDR]);
+    let sdr_to_reset = Some(PATHS[State::ShiftDR][State::TestLogicReset]);
+
+    // from xczu9eg_ffvc900.bsd, after the 4 bit DAP BYPASS
+    let jtag_status = 0b011111 << (4 + 6) | 0b111111 << 4 | 0b1111;
+    let rx_5 = Data::Rx(Bytes(5));
+
+    backend.tms(buf, Path::RESET).await?;
+    backend
+        .bits(buf, reset_to_sir, jtag_status, Bits(16), sir_to_idle)
+        .await?;
+    backend.bytes(buf, idle_to_sdr, rx_5, sdr_to_reset).await?;
+    backend.flush(buf).await?;
+
+    let Ok(rx) = <[u8; 5]>::try_from(buf.data()) else {
+        return Err(Error::Protocol("failed to read zynq us JTAG_STATUS".into()));
+    };
+    let mut word = [0; 8];
+    word[..5].copy_from_slice(&rx);
+    // skip the DAP BYPASS bit
+    Ok((u64::from_le_bytes(word) >> 1) as u32)
+}
+
+/// The PS JTAG_STATUS register of a Zynq US+, see UG1085, "PS TAP
+/// Controller".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ZynqUsJtagStatus(pub u32);
+
+impl ZynqUsJtagStatus {
+    /// PL DONE.
+    pub fn done(self) -> bool {
+        self.0 & 1 << 3 != 0
+    }
+
+    /// PL INIT_B.
+    pub fn init(self) -> bool {
+        self.0 & 1 << 4 != 0
+    }
+
+    /// The PS boot mode pins, as sampled at power on.
+    pub fn boot_mode(self) -> u8 {
+        (self.0 >> 8 & 0xf) as u8
+    }
+
+    /// Name of [`Self::boot_mode`], UG1085 table 11-1.
+    pub fn boot_mode_name(self) -> &'static str {
+        match self.boot_mode() {
+            0x0 => "PS JTAG",
+            0x1 => "QSPI24",
+            0x2 => "QSPI32",
+            0x3 => "SD0",
+            0x4 => "NAND",
+            0x5 => "SD1",
+            0x6 => "eMMC",
+            0x7 => "USB0",
+            0x8 => "PJTAG MIO0",
+            0x9 => "PJTAG MIO1",
+            0xe => "SD1 LS",
+            _ => "reserved",
+        }
+    }
+}
+
+/// The raw value, then the decoded fields, i.e.
+/// `0x00000008 (boot mode 0x0 PS JTAG, INIT_B low, DONE high)`.
+impl std::fmt::Display for ZynqUsJtagStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = |high| if high { "high" } else { "low" };
+        write!(
+            f,
+            "{:#010x} (boot mode {:#x} {}, INIT_B {}, DONE {})",
+            self.0,
+            self.boot_mode(),
+            self.boot_mode_name(),
+            level(self.init()),
+            level(self.done()),
+        )
+    }
+}
+
+fn unknown_device(name: &'static str, irlen: Bits<u8>) -> DeviceInfo {
+    DeviceInfo {
+        irlen,
+        name: name.into(),
+        specific: crate::devices::Specific::Unknown,
     }
 }
 
@@ -286,7 +507,7 @@ fn intel_special_case(
     if let S::Intel = info.specific {
         let fake_tap = DeviceInfo {
             irlen: Bits(1),
-            name: "1_BIT_TAP",
+            name: "1_BIT_TAP".into(),
             specific: S::Unknown,
         };
         Some(((IdCode::new(0x00000001), fake_tap), (shifted, info.clone())))
@@ -304,11 +525,9 @@ impl Controller {
         after: Vec<(IdCode, DeviceInfo)>,
     ) -> Result<Self> {
         let mut buf = ScratchBuffer::new();
-        let reset_to_idle = PATHS[State::TestLogicReset][State::RunTestIdle];
-        backend.tms(&mut buf, Path::RESET).await?;
-        backend.tms(&mut buf, reset_to_idle).await?;
-        backend.flush(&mut buf).await?;
-        buf.clear();
+        let chain = before.iter().chain([&active]).chain(&after);
+        clamp_frequency(&mut backend, chain.map(|(_, info)| info)).await?;
+        reset_to_idle(&mut backend, &mut buf).await?;
 
         Ok(Self {
             backend,
@@ -316,10 +535,53 @@ impl Controller {
             before,
             active,
             after,
-            notify: Ptr(std::ptr::null()),
+            broadcast: Vec::new(),
+            progress: None,
+            chunk: None,
+            cancel: None,
+            timeouts: Timeouts::default(),
+            state: State::RunTestIdle,
         })
     }
 
+    /// Swap in a freshly-opened backend after the cable was reconnected.
+    ///
+    /// The chain is detected again, and must match the chain this controller
+    /// was created with. Otherwise, [`ConnectionError::ChainChanged`] is
+    /// returned and the controller is left unchanged.
+    #[tracing::instrument(skip_all)]
+    pub async fn reconnect(
+        &mut self,
+        mut backend: Box<dyn Backend>,
+        devices: &HashMap<IdCode, DeviceInfo>,
+    ) -> Result<()> {
+        let chain = detect_chain(&mut backend, devices).await?;
+
+        let expected: Vec<u32> = (self.before.iter())
+            .chain([&self.active])
+            .chain(&self.after)
+            .map(|(idcode, _)| idcode.code())
+            .collect();
+        let found: Vec<u32> = chain.iter().map(|tap| tap.idcode).collect();
+        if expected != found {
+            return Err(ConnectionError::ChainChanged { expected, found }.into());
+        }
+
+        self.buf.clear();
+        clamp_frequency(&mut backend, chain.iter().map(Tap::info)).await?;
+        reset_to_idle(&mut backend, &mut self.buf).await?;
+        self.backend = backend;
+        self.state = State::RunTestIdle;
+        Ok(())
+    }
+
+    /// Drop the backend, closing the cable. Until [`Controller::reconnect`]
+    /// succeeds, all IO fails with [`ConnectionError::Disconnected`].
+    pub fn detach(&mut self) {
+        self.backend = Box::new(crate::hotplug::Detached);
+        self.buf.clear();
+    }
+
     pub fn typed<T>(&mut self) -> Option<TypedController<'_, T>>
     where
         crate::devices::Specific: GetSpecific<T>,
@@ -331,10 +593,42 @@ impl Controller {
         }
     }
 
+    /// Direct access to the backend, with the TAP in whatever state the last
+    /// command left it. Prefer [`Controller::with_raw_backend`].
     pub fn backend(&mut self) -> (&mut ScratchBuffer, &mut dyn Backend) {
         (&mut self.buf, &mut self.backend)
     }
 
+    /// Lend out the backend for a custom sequence, i.e. to prototype a
+    /// protocol that [`Command`] can't express.
+    ///
+    /// `f` starts with an empty buffer and the TAP in [`State::RunTestIdle`],
+    /// and may leave it in any state. Nothing is lowered for the chain, so `f`
+    /// has to shift through every device, see [`Controller::chain`].
+    ///
+    /// Afterwards, even if `f` failed, the TAP is reset and put back in
+    /// Run-Test/Idle. That loads IDCODE or BYPASS in every device, and clears
+    /// the buffer, so anything read has to be copied into `T`.
+    ///
+    /// `f` may use its own error type, i.e. `eyre::Report`, as long as errors
+    /// from this crate convert into it.
+    pub async fn with_raw_backend<T, E: From<Error>>(
+        &mut self,
+        f: impl AsyncFnOnce(&mut dyn Backend, &mut ScratchBuffer) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.buf.clear();
+        self.backend.tms(&mut self.buf, Path::IDLE).await?;
+        self.backend.flush(&mut self.buf).await?;
+        self.buf.clear();
+
+        let ret = f(&mut *self.backend, &mut self.buf).await;
+        let resync = reset_to_idle(&mut self.backend, &mut self.buf).await;
+        self.state = State::RunTestIdle;
+        let ret = ret?;
+        resync?;
+        Ok(ret)
+    }
+
     pub fn info(&self) -> &DeviceInfo {
         &self.active.1
     }
@@ -351,20 +645,259 @@ impl Controller {
         &self.after
     }
 
-    pub async fn with_notifications<T>(
+    /// All devices on the chain, in the order they were detected.
+    pub fn chain(&self) -> impl Iterator<Item = &(IdCode, DeviceInfo)> {
+        self.before.iter().chain([&self.active]).chain(&self.after)
+    }
+
+    /// Send further commands to a different device on the chain. The chain is
+    /// not scanned again, and devices other than the selected one are put in
+    /// BYPASS by the next IR shift as usual.
+    pub fn select(&mut self, target: impl Into<Target>) -> Result<()> {
+        let idx = match target.into() {
+            Target::Index(idx) => idx,
+            Target::IdCode(idcode) => {
+                let mut matching = (self.chain().enumerate())
+                    .filter(|(_, (code, _))| *code == idcode)
+                    .map(|(idx, _)| idx);
+                match (matching.next(), matching.next()) {
+                    (Some(idx), None) => idx,
+                    (None, _) => {
+                        return Err(Error::InvalidInput(format!(
+                            "no device {:08X} on chain",
+                            idcode.code()
+                        )));
+                    }
+                    (Some(_), Some(_)) => {
+                        return Err(Error::InvalidInput(format!(
+                            "more than one device {:08X} on chain, select by index",
+                            idcode.code()
+                        )));
+                    }
+                }
+            }
+        };
+
+        let mut chain: Vec<_> = self.chain().cloned().collect();
+        if idx >= chain.len() {
+            return Err(Error::InvalidInput(format!(
+                "idx {idx} too large for chain of {}",
+                chain.len()
+            )));
+        }
+        self.after = chain.split_off(idx + 1);
+        self.active = chain.pop().expect("idx is in bounds");
+        self.before = chain;
+        self.broadcast.clear();
+        Ok(())
+    }
+
+    /// Select several identical devices at once: IR shifts load the same
+    /// instruction into all of them, and DR data shifted in passes through all
+    /// of them. Meant for streaming the same bitstream into each device in one
+    /// pass.
+    ///
+    /// The device closest to TDO becomes the active one, so DR reads and
+    /// [`Controller::capture_ir`] only see that device. The DR padding only
+    /// accounts for one bit per device, so data for registers longer than
+    /// that must be followed by enough filler to pass through the others.
+    pub fn broadcast(&mut self, targets: &[usize]) -> Result<()> {
+        let mut targets = targets.to_vec();
+        targets.sort_unstable();
+        targets.dedup();
+        let Some(&first) = targets.first() else {
+            return Err(Error::InvalidInput("no devices to broadcast to".into()));
+        };
+        let chain: Vec<_> = self.chain().map(|(idcode, _)| *idcode).collect();
+        if let Some(idx) = targets.iter().find(|idx| **idx >= chain.len()) {
+            return Err(Error::InvalidInput(format!(
+                "idx {idx} too large for chain of {}",
+                chain.len()
+            )));
+        }
+        if let Some(idx) = targets.iter().find(|idx| chain[**idx] != chain[first]) {
+            return Err(Error::InvalidInput(format!(
+                "cannot broadcast to different devices: {:08X} at {first}, {:08X} at {idx}",
+                chain[first].code(),
+                chain[*idx].code(),
+            )));
+        }
+
+        self.select(first)?;
+        self.broadcast = targets.split_off(1);
+        Ok(())
+    }
+
+    /// Report the bytes of commands created with a `*_with_notification`
+    /// constructor to `progress`, returning the previous sink. `None` stops
+    /// reporting.
+    pub fn set_progress(
+        &mut self,
+        progress: Option<Box<dyn ProgressSink>>,
+    ) -> Option<Box<dyn ProgressSink>> {
+        std::mem::replace(&mut self.progress, progress)
+    }
+
+    /// Tell the progress sink, if any, that a new step started.
+    pub fn progress_phase(&mut self, name: &str) {
+        if let Some(progress) = &mut self.progress {
+            progress.phase(name);
+        }
+    }
+
+    /// Stop commands early once `token` is cancelled: [`Controller::run`] and
+    /// friends check it before each command and between the chunks of a
+    /// [chunked](Self::set_chunk_size) DR shift. A cancelled command resets
+    /// the TAP to Test-Logic-Reset, rather than leaving it halfway through a
+    /// shift, and fails with [`Error::Cancelled`].
+    ///
+    /// Once cancelled, every command fails until the token is replaced.
+    pub fn set_cancellation(&mut self, token: Option<CancellationToken>) {
+        self.cancel = token;
+    }
+
+    /// Give up on commands that take too long, instead of waiting forever on
+    /// a wedged cable or target. After a timeout, the backend may be halfway
+    /// through a transfer: reconnect, or at least [reset](Self::reset), before
+    /// running anything else.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Reset the TAP if `ret` is [`Error::Cancelled`].
+    async fn abort_if_cancelled<T>(&mut self, ret: Result<T>) -> Result<T> {
+        if let Err(Error::Cancelled) = ret {
+            // the reset is best-effort, an error here would hide why the
+            // command stopped
+            self.buf.clear();
+            let _ = self.backend.tms(&mut self.buf, Path::RESET).await;
+            let _ = self.backend.flush(&mut self.buf).await;
+            self.buf.clear();
+            self.state = State::TestLogicReset;
+        }
+        ret
+    }
+
+    /// Move the TAP to `state` along the shortest path, i.e. to hold it in
+    /// [`State::PauseDR`] between raw shifts. Does nothing if it's already
+    /// there.
+    ///
+    /// The next [`Controller::run`] (or any other command) goes back to
+    /// [`State::RunTestIdle`] first.
+    ///
+    /// Shift-IR and Shift-DR can't be held without shifting, use
+    /// [`Controller::shift_ir`] and [`Controller::shift_dr`] instead.
+    pub async fn goto(&mut self, state: State) -> Result<()> {
+        check_raw_state(state)?;
+        let cancelled = Layout::new(self).check_cancelled();
+        self.abort_if_cancelled(cancelled).await?;
+        self.move_to(state).await
+    }
+
+    async fn move_to(&mut self, state: State) -> Result<()> {
+        if self.state == state {
+            return Ok(());
+        }
+
+        self.buf.clear();
+        let path = PATHS[self.state][state];
+        self.backend.tms(&mut self.buf, path).await?;
+        let flush = self.timeouts.flush;
+        with_timeout("flush", flush, self.backend.flush(&mut self.buf)).await?;
+        self.buf.clear();
+        self.state = state;
+        Ok(())
+    }
+
+    /// The state the TAP was left in by [`Controller::goto`] or a raw shift.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Load the first irlen bits of `tdi` (LSB of the first byte first) into
+    /// IR of the active device, starting from the current
+    /// [state](Controller::state) and ending in `end`. Other devices are put
+    /// in BYPASS as usual.
+    ///
+    /// Returns the IR shifted out of the active device, which starts with the
+    /// value captured in Capture-IR. With [`Controller::broadcast`], the IR of
+    /// every selected device is returned, each in its own bytes, TDO first.
+    ///
+    /// Unlike [`Command::ir`], this can end somewhere other than
+    /// [`State::RunTestIdle`], i.e. in [`State::PauseIR`] to go straight on to
+    /// a DR shift as [`Command::combined_ir_dr_tx_bits`] does.
+    pub async fn shift_ir(&mut self, tdi: &[u8], end: State) -> Result<&[u8]> {
+        let len = Bits(usize::from(self.info().irlen.0));
+        let ir = raw_payload(tdi, len)?;
+        self.raw_shift(State::ShiftIR, ir, end).await
+    }
+
+    /// Shift the first `len` bits of `tdi` (LSB of the first byte first)
+    /// through DR of the active device, starting from the current
+    /// [state](Controller::state) and ending in `end`. Other devices are in
+    /// BYPASS, as left by the last IR shift.
+    ///
+    /// Returns the `len` bits shifted out of the active device, in
+    /// `len.div_ceil(8)` bytes with the unused high bits of the last byte
+    /// zero.
+    ///
+    /// Long registers can be shifted in pieces by ending all but the last in
+    /// [`State::PauseDR`]: a shift starting there continues the register
+    /// rather than capturing it again. The bypass bits of other devices are
+    /// only shifted before the first piece and after the last one. Large
+    /// shifts are never [chunked](Self::set_chunk_size).
+    pub async fn shift_dr(&mut self, tdi: &[u8], len: Bits<usize>, end: State) -> Result<&[u8]> {
+        let dr = raw_payload(tdi, len)?;
+        self.raw_shift(State::ShiftDR, dr, end).await
+    }
+
+    async fn raw_shift(
         &mut self,
-        notify: &AtomicUsize,
-        f: impl AsyncFnOnce(&mut Self) -> T,
-    ) -> T {
-        // TODO: this _technically_ exposes some unsafety. If the user-provided closure
-        // panics, and the panic is later caught, we will be holding a
-        // potentially-dangling pointer.
-        //
-        // This _should_ use [PPYP](https://faultlore.com/blah/everyone-poops/).
-        let old_notify = std::mem::replace(&mut self.notify, Ptr(notify));
-        let r = f(self).await;
-        self.notify = old_notify;
-        r
+        register: State,
+        payload: Payload<'_>,
+        end: State,
+    ) -> Result<&[u8]> {
+        check_raw_state(end)?;
+        let chain = Layout::new(self);
+        let cancelled = chain.check_cancelled();
+        self.abort_if_cancelled(cancelled).await?;
+
+        // Exit1, Pause, and Exit2 are the states that a shift can be resumed
+        // from, without passing Capture or Update
+        let paused = |state| match register {
+            State::ShiftIR => matches!(state, State::Exit1IR | State::PauseIR | State::Exit2IR),
+            _ => matches!(state, State::Exit1DR | State::PauseDR | State::Exit2DR),
+        };
+
+        self.buf.clear();
+        let (backend, buf) = (&mut *self.backend, &mut self.buf);
+        if register == State::ShiftIR {
+            if paused(self.state) {
+                return Err(Error::InvalidInput(format!(
+                    "cannot shift IR in pieces, move on from {:?} first",
+                    self.state
+                )));
+            }
+            chain.ir(backend, buf, self.state, payload, end).await?;
+        } else {
+            let [before, payload, after] = chain.dr_segments(payload);
+            let before = (!paused(self.state)).then_some(before);
+            let after = (!paused(end)).then_some(after);
+            let segments = before.into_iter().chain([payload]).chain(after);
+            let enter = PATHS[self.state][State::ShiftDR];
+            let exit = PATHS[State::ShiftDR][end];
+            shift(backend, buf, enter, segments, exit).await?;
+        }
+        let flush = self.timeouts.flush;
+        with_timeout("flush", flush, self.backend.flush(&mut self.buf)).await?;
+        self.state = end;
+        Ok(self.buf.data())
+    }
+
+    /// Flush a move back to [`State::RunTestIdle`], if [`Controller::goto`] or
+    /// a raw shift left the TAP somewhere else.
+    async fn return_to_idle(&mut self) -> Result<()> {
+        self.move_to(State::RunTestIdle).await
     }
 
     /// Run a set of commands, returning the data read out of TDO.
@@ -372,114 +905,355 @@ impl Controller {
     /// Before the first command is run, the JTAG will be in
     /// [`State::RunTestIdle`].
     ///
-    /// When IO occurs, the number of bytes read is sent over `sender`.
+    /// Commands created with a `*_with_notification` constructor report their
+    /// progress to the sink set with [`Controller::set_progress`].
     #[tracing::instrument(skip_all)]
     pub async fn run<'d>(
         &mut self,
         commands: impl IntoIterator<Item = Command<'d>>,
     ) -> Result<&[u8]> {
+        self.buf.clear();
+        let ret = with_timeout("run", self.timeouts.run, self.run_inner(commands)).await;
+        self.abort_if_cancelled(ret).await?;
+        Ok(self.buf.data())
+    }
+
+    async fn run_inner<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+    ) -> Result<()> {
+        self.return_to_idle().await?;
+        let last_noisy = self.queue(commands, None).await?;
+        self.send(None, last_noisy).await
+    }
+
+    /// Flush what's queued, or run `template`, into the controller's buffer.
+    /// Reports progress if the last command wanted notifications.
+    async fn send(&mut self, template: Option<&crate::Template>, last_noisy: bool) -> Result<()> {
         let Self {
             ref mut backend,
             ref mut buf,
-            ref before,
-            active: (_, ref info),
-            ref after,
-            notify,
+            ref mut progress,
+            ..
         } = *self;
-        let notify = if notify.0.is_null() {
-            None
-        } else {
-            Some(unsafe { &*notify.0 })
+        let buf: &mut dyn Buffer = match (progress, last_noisy) {
+            (Some(progress), true) => &mut NoisyBuffer {
+                progress: &mut **progress,
+                buf,
+            },
+            _ => buf,
         };
-        buf.clear();
 
-        let irlen_before: Bits<u8> = Bits(before.iter().map(|i| i.1.irlen.0).sum());
-        let irlen_after: Bits<u8> = Bits(after.iter().map(|i| i.1.irlen.0).sum());
-        let irlen = ChainInfo {
-            before: irlen_before,
-            after: irlen_after,
+        let send = async {
+            match template {
+                Some(template) => backend.run_template(buf, template).await,
+                None => backend.flush(buf).await,
+            }
         };
+        with_timeout("flush", self.timeouts.flush, send).await
+    }
+
+    /// [`Controller::run`], with the data split up into what each command
+    /// read, instead of slicing the combined data by hand.
+    pub async fn run_reads<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+    ) -> Result<Reads<'_>> {
+        let commands: Vec<_> = commands.into_iter().collect();
+        let mut ranges = Vec::new();
+        let mut end = 0;
+        for len in commands.iter().filter_map(Command::read_len) {
+            ranges.push(end..end + len.0);
+            end += len.0;
+        }
 
-        let devices_before = before.len();
-        let devices_after = after.len();
-        assert!(devices_before <= 32);
-        assert!(devices_after <= 32);
-        let devices = ChainInfo {
-            before: devices_before as _,
-            after: devices_after as _,
+        let data = self.run(commands).await?;
+        if data.len() != end {
+            return Err(Error::Protocol(format!(
+                "read {} bytes, expected {end}",
+                data.len()
+            )));
+        }
+        Ok(Reads { data, ranges })
+    }
+
+    /// [`Controller::run`], returning the data by value. Useful when the
+    /// result feeds into the next commands, which can't borrow it from the
+    /// controller.
+    pub async fn run_owned<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+    ) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.run_into(commands, &mut out).await?;
+        Ok(out)
+    }
+
+    /// [`Controller::run`], putting the data in `out` instead. The previous
+    /// contents of `out` are dropped, but its allocation is reused. In a
+    /// polling loop, alternating between two buffers keeps the last result
+    /// around without allocating.
+    pub async fn run_into<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        self.run(commands).await?;
+        self.buf.swap(out);
+        Ok(())
+    }
+
+    /// Split DR shifts longer than `chunk` into pieces, flushed one at a time
+    /// with the TAP waiting in Pause-DR in between. The backend then never has
+    /// more than a chunk queued, and [progress](Self::set_progress)
+    /// advance during the shift instead of only at the end.
+    ///
+    /// Dropping a [`Controller::run`] future between chunks stops the
+    /// transfer, leaving the TAP in Pause-DR until the next
+    /// [`Controller::reset`]. [Cancelling](Self::set_cancellation) it resets
+    /// the TAP instead.
+    ///
+    /// `None`, the default, shifts each register in one go. Commands built by
+    /// [`Controller::prepare`] are never split.
+    pub fn set_chunk_size(&mut self, chunk: Option<Bytes<usize>>) {
+        assert!(chunk.is_none_or(|c| c.0 > 0), "chunk size must not be 0");
+        self.chunk = chunk;
+    }
+
+    /// [`Controller::run`], handing the data read to `sink` in pieces instead
+    /// of returning all of it at once, i.e. to write a multi-hundred-MB
+    /// readback straight to a file.
+    ///
+    /// Long DR shifts are split as with [`Controller::set_chunk_size`], in
+    /// pieces of 1MiB if no chunk size was set, so only about one chunk is
+    /// held in memory. `sink` gets the data in order, as `run` would return
+    /// it; an error from `sink` stops the transfer.
+    pub async fn run_streaming<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+        sink: impl FnMut(&[u8]) -> Result<()> + Send,
+    ) -> Result<()> {
+        let mut stream = StreamingBuffer {
+            buf: std::mem::take(&mut self.buf),
+            sink,
+        };
+        stream.buf.clear();
+        let ret = self.run_into_buffer(commands, &mut stream).await;
+        self.buf = stream.buf;
+        self.buf.clear();
+        ret
+    }
+
+    /// [`Controller::run_streaming`], reading into `buf` instead, i.e. a
+    /// [`WriteBuffer`](crate::buffers::WriteBuffer) or a memory-mapped file.
+    /// [`Buffer::flushed`] is called after each chunk and once at the end.
+    pub async fn run_into_buffer<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+        buf: &mut dyn Buffer,
+    ) -> Result<()> {
+        let chunk = self.chunk;
+        self.chunk = Some(chunk.unwrap_or(STREAM_CHUNK));
+        let run = self.timeouts.run;
+        let ret = with_timeout("run", run, self.run_streaming_inner(commands, buf)).await;
+        self.chunk = chunk;
+        self.abort_if_cancelled(ret).await
+    }
+
+    async fn run_streaming_inner<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+        stream: &mut dyn Buffer,
+    ) -> Result<()> {
+        self.return_to_idle().await?;
+        let last_noisy = self.queue(commands, Some(&mut *stream)).await?;
+        let buf: &mut dyn Buffer = match (&mut self.progress, last_noisy) {
+            (Some(progress), true) => &mut NoisyBuffer {
+                progress: &mut **progress,
+                buf: stream,
+            },
+            _ => stream,
+        };
+        let flush = self.timeouts.flush;
+        with_timeout("flush", flush, self.backend.flush(buf)).await?;
+        buf.flushed()
+    }
+
+    /// Build the commands for the backend once, to run them repeatedly with
+    /// [`Controller::run_prepared`]. Meant for tight polling loops, where
+    /// re-encoding the same few commands is most of the time spent.
+    ///
+    /// Falls back to running the commands normally if the backend does not
+    /// support [templates](crate::Template).
+    #[tracing::instrument(skip_all)]
+    pub async fn prepare<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+    ) -> Result<Prepared<'d>> {
+        if !self.backend.supports_templates() {
+            let commands = commands.into_iter().collect();
+            return Ok(Prepared(PreparedInner::Commands(commands)));
+        }
+
+        self.return_to_idle().await?;
+        self.buf.clear();
+        let flushes = self.backend.flushes();
+        let chunk = self.chunk.take();
+        let queued = self.queue(commands, None).await;
+        self.chunk = chunk;
+        let template = self.backend.take_template();
+        let noisy = queued?;
+        if self.backend.flushes() != flushes {
+            // the start of it already ran
+            return Err(Error::Unsupported(
+                "commands too large to prepare, were flushed early".into(),
+            ));
+        }
+        let template = template?;
+        Ok(Prepared(PreparedInner::Template { template, noisy }))
+    }
+
+    pub async fn run_prepared(&mut self, prepared: &Prepared<'_>) -> Result<&[u8]> {
+        match &prepared.0 {
+            PreparedInner::Template { template, noisy } => {
+                self.buf.clear();
+                let timeout = self.timeouts.run;
+                let run = self.run_template_inner(template, *noisy);
+                let ret = with_timeout("run", timeout, run).await;
+                self.abort_if_cancelled(ret).await?;
+                Ok(self.buf.data())
+            }
+            PreparedInner::Commands(commands) => self.run(commands.iter().copied()).await,
+        }
+    }
+
+    /// [`Controller::run_inner`], for a template.
+    async fn run_template_inner(&mut self, template: &crate::Template, noisy: bool) -> Result<()> {
+        self.return_to_idle().await?;
+        Layout::new(self).check_cancelled()?;
+        self.send(Some(template), noisy).await
+    }
+
+    /// Queue `commands` on the backend without flushing. Returns whether the
+    /// last command wants notifications.
+    ///
+    /// Data is read into `stream` if given, otherwise into the controller's
+    /// buffer.
+    async fn queue<'d>(
+        &mut self,
+        commands: impl IntoIterator<Item = Command<'d>>,
+        stream: Option<&mut dyn Buffer>,
+    ) -> Result<bool> {
+        let chain = Layout::new(self);
+        let Self {
+            ref mut backend,
+            ref mut buf,
+            ref mut progress,
+            active: (_, ref info),
+            ..
+        } = *self;
+        let base: &mut dyn Buffer = match stream {
+            Some(stream) => stream,
+            None => buf,
         };
 
         let mut last_noisy = false;
         for command in commands {
+            chain.check_cancelled()?;
             last_noisy = command.notify;
-            let buf: &mut dyn Buffer = match (notify, command.notify) {
-                (Some(notify), true) => &mut NoisyBuffer { notify, buf },
-                _ => buf,
+            let buf: &mut dyn Buffer = match (progress.as_deref_mut(), command.notify) {
+                (Some(progress), true) => &mut NoisyBuffer {
+                    progress,
+                    buf: &mut *base,
+                },
+                _ => &mut *base,
             };
             match command.inner {
                 CommandInner::IrTxBits { tdi } => {
-                    let data = BitTx {
+                    let ir = BitTx {
                         tdi,
                         len: info.irlen,
                     };
-                    io_bits_ir(backend, buf, irlen, data).await?
+                    let ir = Payload::Bits(ir);
+                    chain
+                        .ir(backend, buf, State::RunTestIdle, ir, State::RunTestIdle)
+                        .await?
                 }
                 CommandInner::DrTx { tdi } => {
-                    io_bytes(backend, buf, devices, Data::Tx(tdi)).await?
+                    chain
+                        .dr(backend, buf, State::RunTestIdle, Data::Tx(tdi))
+                        .await?
                 }
                 CommandInner::DrRx { len } => {
-                    io_bytes(backend, buf, devices, Data::Rx(len)).await?
+                    chain
+                        .dr(backend, buf, State::RunTestIdle, Data::Rx(len))
+                        .await?
                 }
                 CommandInner::DrTxRx { tdi } => {
-                    io_bytes(backend, buf, devices, Data::TxRx(tdi)).await?
+                    chain
+                        .dr(backend, buf, State::RunTestIdle, Data::TxRx(tdi))
+                        .await?
                 }
                 CommandInner::DrTxBits { tdi, len } => {
-                    io_bits_dr(backend, buf, devices, BitTx { tdi, len }).await?;
+                    let dr = Payload::Bits(BitTx { tdi, len });
+                    chain.dr(backend, buf, State::RunTestIdle, dr).await?
+                }
+                CommandInner::DrTxSliceBits { tdi, len } => {
+                    let dr = Payload::TxBits(tdi, len);
+                    chain.dr(backend, buf, State::RunTestIdle, dr).await?
+                }
+                CommandInner::DrRxBits { len } => {
+                    let dr = Payload::RxBits(len);
+                    chain.dr(backend, buf, State::RunTestIdle, dr).await?
                 }
                 CommandInner::CombinedIrDrTxBits { ir, dr, dr_len } => {
                     let ir = BitTx {
                         tdi: ir,
                         len: info.irlen,
                     };
-                    let dr = BitTx {
+                    let dr = Payload::Bits(BitTx {
                         tdi: dr,
                         len: dr_len,
-                    };
-                    io_bits_ir_dr(backend, buf, irlen, devices, ir, dr).await?
-                }
-                CommandInner::Idle { len } => {
-                    backend
-                        .bytes(buf, None, Data::ConstantTx(true, len), None)
-                        .await?
+                    });
+                    let ir = Payload::Bits(ir);
+                    chain
+                        .ir(backend, buf, State::RunTestIdle, ir, State::PauseIR)
+                        .await?;
+                    chain.dr(backend, buf, State::PauseIR, dr).await?
                 }
+                CommandInner::Idle { clocks } => backend.idle_clocks(buf, clocks).await?,
+                CommandInner::Wait { duration } => backend.wait(buf, duration).await?,
             }
         }
 
-        let buf: &mut dyn Buffer = match (notify, last_noisy) {
-            (Some(notify), true) => &mut NoisyBuffer { notify, buf },
-            _ => buf,
-        };
-
-        backend.flush(buf).await?;
-        Ok(self.buf.data())
+        Ok(last_noisy)
     }
 
     pub async fn reset(&mut self) -> Result<()> {
         self.buf.clear();
         self.backend.tms(&mut self.buf, Path::IDLE).await?;
+        self.state = State::RunTestIdle;
         Ok(())
     }
 
     pub async fn capture_ir(&mut self) -> Result<u32> {
-        let irlen_before: u8 = self.before.iter().map(|i| i.1.irlen.0).sum();
+        let cancelled = Layout::new(self).check_cancelled();
+        self.abort_if_cancelled(cancelled).await?;
+        self.return_to_idle().await?;
+
+        let irlen_before = Layout::new(self).ir_before();
         let irlen = self.info().irlen.0;
+        if irlen_before + usize::from(irlen) > 32 * 8 {
+            return Err(Error::Unsupported("chain too long to capture IR".into()));
+        }
 
         let p0 = Some(PATHS[State::RunTestIdle][State::ShiftIR]);
         let p1 = Some(PATHS[State::ShiftIR][State::RunTestIdle]);
         let data = Data::TxRx(&[0xff; 32]);
         self.buf.clear();
         self.backend.bytes(&mut self.buf, p0, data, p1).await?;
-        self.backend.flush(&mut self.buf).await?;
+        let flush = self.timeouts.flush;
+        with_timeout("flush", flush, self.backend.flush(&mut self.buf)).await?;
 
         // TODO: This works correctly for FTDI chips. Do other backends (XPC,
         // USB-Blaster) do the same?
@@ -490,184 +1264,459 @@ impl Controller {
         let mut reader = bitreader::BitReader::new(self.buf.data());
         let mut to_skip = irlen_before;
         while to_skip != 0 {
-            reader.read_u64(to_skip.min(64))?;
-            to_skip = to_skip.saturating_sub(64);
+            let chunk = to_skip.min(64);
+            reader.read_u64(chunk as u8)?;
+            to_skip -= chunk;
         }
         Ok(reader.read_u32(irlen)?)
     }
 }
 
-#[derive(Clone, Copy)]
-struct ChainInfo<T> {
-    before: T,
-    after: T,
+async fn reset_to_idle(backend: &mut dyn Backend, buf: &mut ScratchBuffer) -> Result<()> {
+    let reset_to_idle = PATHS[State::TestLogicReset][State::RunTestIdle];
+    backend.tms(buf, Path::RESET).await?;
+    backend.tms(buf, reset_to_idle).await?;
+    backend.flush(buf).await?;
+    buf.clear();
+    Ok(())
+}
+
+/// Lower TCK to the slowest [`DeviceInfo::max_tck`] on the chain. A forced
+/// frequency is left alone, with a warning if it's over.
+async fn clamp_frequency<'a>(
+    backend: &mut dyn Backend,
+    chain: impl IntoIterator<Item = &'a DeviceInfo>,
+) -> Result<()> {
+    let slowest = chain
+        .into_iter()
+        .filter_map(|info| Some((info.max_tck()?, &info.name)))
+        .min_by_key(|(max, _)| *max);
+    let (Some((max, name)), Some(current)) = (slowest, backend.frequency()) else {
+        return Ok(());
+    };
+    if current.hz <= max {
+        return Ok(());
+    }
+    if current.forced {
+        tracing::warn!(
+            frequency = current.hz,
+            max,
+            "forced TCK frequency is over the maximum for {name}"
+        );
+        return Ok(());
+    }
+    let hz = backend.set_frequency(max).await?;
+    tracing::info!(from = current.hz, to = hz, "lowered TCK for {name}");
+    Ok(())
+}
+
+/// Where the active device's registers sit in the chain.
+///
+/// Devices before the active one are closer to TDO, so their bits are shifted
+/// first. Other devices are held in BYPASS, which is also selected by shifting
+/// all ones into their IR.
+struct Layout {
+    /// IR of each device, TDO first. Adjacent bypassed devices are merged.
+    ir: Vec<IrSlot>,
+    /// One bypass bit per device.
+    dr: Padding,
+    /// Split byte DR shifts into chunks of at most this many bytes.
+    chunk: Option<usize>,
+    cancel: Option<CancellationToken>,
+    /// See [`Timeouts::flush`].
+    flush_timeout: Option<Duration>,
 }
 
 #[derive(Clone, Copy)]
-struct BitTx {
-    tdi: u32,
-    len: Bits<u8>,
+enum IrSlot {
+    /// This many bits of other devices.
+    Bypass(usize),
+    /// The active device, or one of the devices it is broadcast to.
+    Active,
 }
 
-async fn io_bits_ir(
-    backend: &mut dyn Backend,
-    buf: &mut dyn Buffer,
-    irlen: ChainInfo<Bits<u8>>,
-    ir: BitTx,
-) -> Result<()> {
-    let ir0 = Some(PATHS[State::RunTestIdle][State::ShiftIR]);
-    let ir1 = Some(PATHS[State::ShiftIR][State::RunTestIdle]);
+#[derive(Clone, Copy)]
+struct Padding {
+    before: usize,
+    after: usize,
+}
 
-    match (irlen.before, irlen.after) {
-        (Bits(0), Bits(0)) => {
-            backend.bits(buf, ir0, ir.tdi, ir.len, ir1).await?;
-        }
-        (pre, Bits(0)) => {
-            backend.bits(buf, ir0, u32::MAX, pre, None).await?;
-            backend.bits(buf, None, ir.tdi, ir.len, ir1).await?;
+impl Layout {
+    fn new(cont: &Controller) -> Self {
+        let active = cont.before.len();
+        let mut ir = Vec::new();
+        for (idx, (_, info)) in cont.chain().enumerate() {
+            let len = usize::from(info.irlen.0);
+            let selected = idx == active || cont.broadcast.contains(&idx);
+            match (selected, ir.last_mut()) {
+                (true, _) => ir.push(IrSlot::Active),
+                (false, Some(IrSlot::Bypass(bypass))) => *bypass += len,
+                (false, _) => ir.push(IrSlot::Bypass(len)),
+            }
         }
-        (Bits(0), post) => {
-            backend.bits(buf, ir0, ir.tdi, ir.len, None).await?;
-            backend.bits(buf, None, u32::MAX, post, ir1).await?;
+        Self {
+            ir,
+            dr: Padding {
+                before: cont.before.len(),
+                after: cont.after.len(),
+            },
+            chunk: cont.chunk.map(|c| c.0),
+            cancel: cont.cancel.clone(),
+            flush_timeout: cont.timeouts.flush,
         }
-        (pre, post) => {
-            backend.bits(buf, ir0, u32::MAX, pre, None).await?;
-            backend.bits(buf, None, ir.tdi, ir.len, None).await?;
-            backend.bits(buf, None, u32::MAX, post, ir1).await?;
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(Error::Cancelled),
+            _ => Ok(()),
         }
     }
-    Ok(())
-}
 
-async fn io_bits_dr(
-    backend: &mut dyn Backend,
-    buf: &mut dyn Buffer,
-    devices: ChainInfo<u8>,
-    dr: BitTx,
-) -> Result<()> {
-    let dr0 = Some(PATHS[State::RunTestIdle][State::ShiftDR]);
-    let dr1 = Some(PATHS[State::ShiftDR][State::RunTestIdle]);
+    /// IR bits shifted before the active device.
+    fn ir_before(&self) -> usize {
+        let bypass = self.ir.iter().map_while(|slot| match slot {
+            IrSlot::Bypass(len) => Some(len),
+            IrSlot::Active => None,
+        });
+        bypass.sum()
+    }
 
-    match (devices.before, devices.after) {
-        (0, 0) => {
-            backend.bits(buf, dr0, dr.tdi, dr.len, dr1).await?;
-        }
-        (pre, 0) => {
-            backend.bits(buf, dr0, u32::MAX, Bits(pre), None).await?;
-            backend.bits(buf, None, dr.tdi, dr.len, dr1).await?;
-        }
-        (0, post) => {
-            backend.bits(buf, dr0, dr.tdi, dr.len, None).await?;
-            backend.bits(buf, None, u32::MAX, Bits(post), dr1).await?;
+    /// Shift `ir` into the active device, starting from `enter` and ending in
+    /// `exit`.
+    async fn ir(
+        &self,
+        backend: &mut dyn Backend,
+        buf: &mut dyn Buffer,
+        enter: State,
+        ir: Payload<'_>,
+        exit: State,
+    ) -> Result<()> {
+        let enter = PATHS[enter][State::ShiftIR];
+        let exit = PATHS[State::ShiftIR][exit];
+        let segments = self.ir.iter().map(|slot| match *slot {
+            IrSlot::Bypass(len) => Segment::Pad(len),
+            IrSlot::Active => Segment::Payload(ir),
+        });
+        shift(backend, buf, enter, segments, exit).await
+    }
+
+    /// Shift `dr` through the active device, starting from `enter` and ending
+    /// in [`State::RunTestIdle`].
+    async fn dr<'d>(
+        &self,
+        backend: &mut dyn Backend,
+        buf: &mut dyn Buffer,
+        enter: State,
+        dr: impl Into<Payload<'d>>,
+    ) -> Result<()> {
+        let dr = dr.into();
+        let enter = PATHS[enter][State::ShiftDR];
+        let exit = PATHS[State::ShiftDR][State::RunTestIdle];
+        if let (Some(chunk), Payload::Bytes(data)) = (self.chunk, dr)
+            && data.len() > chunk
+        {
+            return self.dr_chunked(backend, buf, enter, data, chunk).await;
         }
-        (pre, post) => {
-            backend.bits(buf, dr0, u32::MAX, Bits(pre), None).await?;
-            backend.bits(buf, None, dr.tdi, dr.len, None).await?;
-            backend.bits(buf, None, u32::MAX, Bits(post), dr1).await?;
+        shift(backend, buf, enter, self.dr_segments(dr), exit).await
+    }
+
+    /// `dr` with the bypass bits of the other devices around it.
+    fn dr_segments<'d>(&self, dr: Payload<'d>) -> [Segment<'d>; 3] {
+        [Segment::Pad(self.dr.before), Segment::Payload(dr), Segment::Pad(self.dr.after)]
+    }
+
+    /// [`Layout::dr`], pausing in Pause-DR and flushing after every `chunk`
+    /// bytes.
+    async fn dr_chunked(
+        &self,
+        backend: &mut dyn Backend,
+        buf: &mut dyn Buffer,
+        enter: Path,
+        data: Data<'_>,
+        chunk: usize,
+    ) -> Result<()> {
+        let to_pause = PATHS[State::ShiftDR][State::PauseDR];
+        let from_pause = PATHS[State::PauseDR][State::ShiftDR];
+        let exit = PATHS[State::ShiftDR][State::RunTestIdle];
+
+        let (first, mut rest) = data.split_at(chunk);
+        let segments = [Segment::Pad(self.dr.before), Segment::Payload(Payload::Bytes(first))];
+        shift(backend, buf, enter, segments, to_pause).await?;
+        with_timeout("flush", self.flush_timeout, backend.flush(buf)).await?;
+        buf.flushed()?;
+        self.check_cancelled()?;
+
+        while rest.len() > chunk {
+            let (next, after) = rest.split_at(chunk);
+            rest = after;
+            let segments = [Segment::Payload(Payload::Bytes(next))];
+            shift(backend, buf, from_pause, segments, to_pause).await?;
+            with_timeout("flush", self.flush_timeout, backend.flush(buf)).await?;
+            buf.flushed()?;
+            self.check_cancelled()?;
         }
+
+        let segments = [Segment::Payload(Payload::Bytes(rest)), Segment::Pad(self.dr.after)];
+        shift(backend, buf, from_pause, segments, exit).await
     }
-    Ok(())
 }
 
-async fn io_bits_ir_dr(
+#[derive(Clone, Copy)]
+struct BitTx {
+    tdi: u32,
+    len: Bits<u8>,
+}
+
+#[derive(Clone, Copy)]
+enum Payload<'d> {
+    Bits(BitTx),
+    Bytes(Data<'d>),
+    /// The first `len` bits of the slice, LSB of the first byte first.
+    TxBits(&'d [u8], Bits<usize>),
+    RxBits(Bits<usize>),
+    /// Like [`Payload::TxBits`], also reading TDO.
+    TxRxBits(&'d [u8], Bits<usize>),
+}
+
+fn check_raw_state(state: State) -> Result<()> {
+    match state {
+        State::ShiftIR | State::ShiftDR => Err(Error::InvalidInput(format!(
+            "cannot stop in {state:?} outside of a shift"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The first `len` bits of `tdi`, read back, for a raw shift.
+fn raw_payload(tdi: &[u8], len: Bits<usize>) -> Result<Payload<'_>> {
+    if len.0 == 0 || len.0 > tdi.len() * 8 {
+        return Err(Error::InvalidInput(format!(
+            "cannot shift {} bits from {} bytes",
+            len.0,
+            tdi.len()
+        )));
+    }
+    Ok(Payload::TxRxBits(tdi, len))
+}
+
+impl<'d> From<Data<'d>> for Payload<'d> {
+    fn from(data: Data<'d>) -> Self {
+        Self::Bytes(data)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Segment<'d> {
+    /// Ones shifted through bypassed devices.
+    Pad(usize),
+    Payload(Payload<'d>),
+}
+
+/// Shift `segments` back to back, taking `enter` into the shift state first
+/// and `exit` out of it at the end.
+async fn shift<'d>(
     backend: &mut dyn Backend,
     buf: &mut dyn Buffer,
-    irlen: ChainInfo<Bits<u8>>,
-    devices: ChainInfo<u8>,
-    ir: BitTx,
-    dr: BitTx,
+    enter: Path,
+    segments: impl IntoIterator<Item = Segment<'d>>,
+    exit: Path,
 ) -> Result<()> {
-    let ir0 = Some(PATHS[State::RunTestIdle][State::ShiftIR]);
-    let ir1 = Some(PATHS[State::ShiftIR][State::PauseIR]);
-    let dr0 = Some(PATHS[State::PauseIR][State::ShiftDR]);
-    let dr1 = Some(PATHS[State::ShiftDR][State::RunTestIdle]);
-
-    match (irlen.before, irlen.after) {
-        (Bits(0), Bits(0)) => {
-            backend.bits(buf, ir0, ir.tdi, ir.len, ir1).await?;
-        }
-        (pre, Bits(0)) => {
-            backend.bits(buf, ir0, u32::MAX, pre, None).await?;
-            backend.bits(buf, None, ir.tdi, ir.len, ir1).await?;
-        }
-        (Bits(0), post) => {
-            backend.bits(buf, ir0, ir.tdi, ir.len, None).await?;
-            backend.bits(buf, None, u32::MAX, post, ir1).await?;
-        }
-        (pre, post) => {
-            backend.bits(buf, ir0, u32::MAX, pre, None).await?;
-            backend.bits(buf, None, ir.tdi, ir.len, None).await?;
-            backend.bits(buf, None, u32::MAX, post, ir1).await?;
-        }
-    }
-    match (devices.before, devices.after) {
-        (0, 0) => {
-            backend.bits(buf, dr0, dr.tdi, dr.len, dr1).await?;
-        }
-        (pre, 0) => {
-            backend.bits(buf, dr0, u32::MAX, Bits(pre), None).await?;
-            backend.bits(buf, None, dr.tdi, dr.len, dr1).await?;
-        }
-        (0, post) => {
-            backend.bits(buf, dr0, dr.tdi, dr.len, None).await?;
-            backend.bits(buf, None, u32::MAX, Bits(post), dr1).await?;
-        }
-        (pre, post) => {
-            backend.bits(buf, dr0, u32::MAX, Bits(pre), None).await?;
-            backend.bits(buf, None, dr.tdi, dr.len, None).await?;
-            backend.bits(buf, None, u32::MAX, Bits(post), dr1).await?;
+    let segments: Vec<_> = (segments.into_iter())
+        .filter(|s| !matches!(s, Segment::Pad(0)))
+        .collect();
+    let mut enter = Some(enter);
+    for (idx, segment) in segments.iter().enumerate() {
+        let exit = (idx == segments.len() - 1).then_some(exit);
+        match *segment {
+            Segment::Pad(len) => pad(backend, buf, enter.take(), len, exit).await?,
+            Segment::Payload(Payload::Bits(BitTx { tdi, len })) => {
+                backend.bits(buf, enter.take(), tdi, len, exit).await?
+            }
+            Segment::Payload(Payload::Bytes(data)) => {
+                backend.bytes(buf, enter.take(), data, exit).await?
+            }
+            Segment::Payload(Payload::TxBits(tdi, len)) => {
+                let (bytes, rest) = (len.0 / 8, len.0 % 8);
+                if bytes != 0 {
+                    let exit = if rest == 0 { exit } else { None };
+                    let data = Data::Tx(&tdi[..bytes]);
+                    backend.bytes(buf, enter.take(), data, exit).await?;
+                }
+                if rest != 0 {
+                    let tdi = u32::from(tdi[bytes]);
+                    let len = Bits(rest as u8);
+                    backend.bits(buf, enter.take(), tdi, len, exit).await?;
+                }
+            }
+            // whole bytes first, so the partial byte read by `tdo_bits` ends up
+            // right after them in `buf`
+            Segment::Payload(Payload::RxBits(len)) => {
+                let (bytes, rest) = (len.0 / 8, len.0 % 8);
+                if bytes != 0 {
+                    let exit = if rest == 0 { exit } else { None };
+                    let data = Data::Rx(Bytes(bytes));
+                    backend.bytes(buf, enter.take(), data, exit).await?;
+                }
+                if rest != 0 {
+                    let len = Bits(rest as u8);
+                    backend.tdo_bits(buf, enter.take(), 0, len, exit).await?;
+                }
+            }
+            Segment::Payload(Payload::TxRxBits(tdi, len)) => {
+                let (bytes, rest) = (len.0 / 8, len.0 % 8);
+                if bytes != 0 {
+                    let exit = if rest == 0 { exit } else { None };
+                    let data = Data::TxRx(&tdi[..bytes]);
+                    backend.bytes(buf, enter.take(), data, exit).await?;
+                }
+                if rest != 0 {
+                    let tdi = u32::from(tdi[bytes]);
+                    let len = Bits(rest as u8);
+                    backend.tdo_bits(buf, enter.take(), tdi, len, exit).await?;
+                }
+            }
         }
     }
     Ok(())
 }
 
-async fn io_bytes(
+/// Shift `len` ones, in chunks small enough for [`Backend::bits`].
+async fn pad(
     backend: &mut dyn Backend,
     buf: &mut dyn Buffer,
-    devices: ChainInfo<u8>,
-    data: Data<'_>,
+    mut enter: Option<Path>,
+    len: usize,
+    exit: Option<Path>,
 ) -> Result<()> {
-    let dr0 = Some(PATHS[State::RunTestIdle][State::ShiftDR]);
-    let dr1 = Some(PATHS[State::ShiftDR][State::RunTestIdle]);
-
-    match (devices.before, devices.after) {
-        (0, 0) => {
-            backend.bytes(buf, dr0, data, dr1).await?;
-        }
-        (pre, 0) => {
-            backend.bits(buf, dr0, u32::MAX, Bits(pre), None).await?;
-            backend.bytes(buf, None, data, dr1).await?;
-        }
-        (0, post) => {
-            backend.bytes(buf, dr0, data, None).await?;
-            backend.bits(buf, None, u32::MAX, Bits(post), dr1).await?;
-        }
-        (pre, post) => {
-            backend.bits(buf, dr0, u32::MAX, Bits(pre), None).await?;
-            backend.bytes(buf, None, data, None).await?;
-            backend.bits(buf, None, u32::MAX, Bits(post), dr1).await?;
-        }
+    let mut remaining = len;
+    while remaining != 0 {
+        let chunk = remaining.min(32);
+        remaining -= chunk;
+        let exit = if remaining == 0 { exit } else { None };
+        let len = Bits(chunk as u8);
+        backend.bits(buf, enter.take(), u32::MAX, len, exit).await?;
     }
-
     Ok(())
 }
 
 struct NoisyBuffer<'d> {
-    notify: &'d AtomicUsize,
-    buf: &'d mut ScratchBuffer,
+    progress: &'d mut dyn ProgressSink,
+    buf: &'d mut dyn Buffer,
 }
 
 impl Buffer for NoisyBuffer<'_> {
     fn extend(&mut self, size: usize, scratch: usize) -> &mut [u8] {
-        self.notify.fetch_add(size, Ordering::Relaxed);
-        Buffer::extend(self.buf, size, scratch)
+        self.progress.read(size);
+        self.buf.extend(size, scratch)
     }
 
     fn notify_write(&mut self, size: usize) {
-        self.notify.fetch_add(size, Ordering::Relaxed);
+        self.progress.written(size);
+    }
+
+    fn flushed(&mut self) -> Result<()> {
+        self.buf.flushed()
     }
 }
 
+/// Hands everything read to `sink` after each flush, see
+/// [`Controller::run_streaming`].
+struct StreamingBuffer<F> {
+    buf: ScratchBuffer,
+    sink: F,
+}
+
+impl<F: FnMut(&[u8]) -> Result<()> + Send> Buffer for StreamingBuffer<F> {
+    fn extend(&mut self, size: usize, scratch: usize) -> &mut [u8] {
+        self.buf.extend(size, scratch)
+    }
+
+    fn flushed(&mut self) -> Result<()> {
+        if !self.buf.data().is_empty() {
+            (self.sink)(self.buf.data())?;
+        }
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// A device on the chain, for [`Controller::select`].
+#[derive(Clone, Copy)]
+pub enum Target {
+    /// Position on the chain, as for [`Controller::chain`].
+    Index(usize),
+    /// The only device with this IDCODE.
+    IdCode(IdCode),
+}
+
+impl From<usize> for Target {
+    fn from(idx: usize) -> Self {
+        Self::Index(idx)
+    }
+}
+
+impl From<IdCode> for Target {
+    fn from(idcode: IdCode) -> Self {
+        Self::IdCode(idcode)
+    }
+}
+
+/// Data returned by [`Controller::run_reads`], one slice per command that
+/// reads, in order.
+#[derive(Clone, Debug)]
+pub struct Reads<'a> {
+    data: &'a [u8],
+    ranges: Vec<std::ops::Range<usize>>,
+}
+
+impl<'a> Reads<'a> {
+    /// Number of commands that read.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// What the `idx`th reading command read.
+    pub fn get(&self, idx: usize) -> Option<&'a [u8]> {
+        let range = self.ranges.get(idx)?.clone();
+        Some(&self.data[range])
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &'a [u8]> + '_ {
+        let data = self.data;
+        self.ranges.iter().map(move |range| &data[range.clone()])
+    }
+
+    /// Everything read, as [`Controller::run`] returns it.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl std::ops::Index<usize> for Reads<'_> {
+    type Output = [u8];
+
+    fn index(&self, idx: usize) -> &[u8] {
+        &self.data[self.ranges[idx].clone()]
+    }
+}
+
+/// Commands built once by [`Controller::prepare`].
+pub struct Prepared<'d>(PreparedInner<'d>);
+
+enum PreparedInner<'d> {
+    /// With whether the last command wanted notifications.
+    Template {
+        template: crate::Template,
+        noisy: bool,
+    },
+    Commands(Vec<Command<'d>>),
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Command<'d> {
     notify: bool,
@@ -682,13 +1731,32 @@ enum CommandInner<'d> {
     DrRx { len: Bytes<usize> },
     DrTxRx { tdi: &'d [u8] },
     DrTxBits { tdi: u32, len: Bits<u8> },
+    DrTxSliceBits { tdi: &'d [u8], len: Bits<usize> },
+    DrRxBits { len: Bits<usize> },
 
     CombinedIrDrTxBits { ir: u32, dr: u32, dr_len: Bits<u8> },
 
-    Idle { len: Bytes<usize> },
+    Idle { clocks: usize },
+    Wait { duration: Duration },
 }
 
 impl<'d> Command<'d> {
+    /// How many bytes this command reads, if any.
+    pub fn read_len(&self) -> Option<Bytes<usize>> {
+        match self.inner {
+            CommandInner::DrRx { len } => Some(len),
+            CommandInner::DrTxRx { tdi } => Some(Bytes(tdi.len())),
+            CommandInner::DrRxBits { len } => Some(len.bytes_ceil()),
+            CommandInner::IrTxBits { .. }
+            | CommandInner::DrTx { .. }
+            | CommandInner::DrTxBits { .. }
+            | CommandInner::DrTxSliceBits { .. }
+            | CommandInner::CombinedIrDrTxBits { .. }
+            | CommandInner::Idle { .. }
+            | CommandInner::Wait { .. } => None,
+        }
+    }
+
     pub fn ir(tdi: u32) -> Self {
         let inner = CommandInner::IrTxBits { tdi };
         let notify = false;
@@ -737,15 +1805,67 @@ impl<'d> Command<'d> {
         Self { notify, inner }
     }
 
+    /// Like [`Command::dr_tx_bits`], for registers longer than 32 bits. Shifts
+    /// the first `len` bits of `tdi`, LSB of the first byte first.
+    pub fn dr_tx_slice_bits(tdi: &'d [u8], len: Bits<usize>) -> Self {
+        assert!(len.0 <= tdi.len() * 8, "dr_tx_slice_bits longer than tdi");
+        let inner = CommandInner::DrTxSliceBits { tdi, len };
+        let notify = false;
+        Self { notify, inner }
+    }
+
+    /// Read exactly `len` bits of DR. Returns `len.div_ceil(8)` bytes,
+    /// LSB-first, with the unused high bits of the last byte zero.
+    pub fn dr_rx_bits(len: Bits<usize>) -> Self {
+        let inner = CommandInner::DrRxBits { len };
+        let notify = false;
+        Self { notify, inner }
+    }
+
     pub fn combined_ir_dr_tx_bits(ir: u32, dr: u32, dr_len: Bits<u8>) -> Self {
         let inner = CommandInner::CombinedIrDrTxBits { ir, dr, dr_len };
         let notify = false;
         Self { notify, inner }
     }
 
+    /// Stay in [`State::RunTestIdle`] for `8 * len` TCK cycles.
     pub fn idle(len: Bytes<usize>) -> Self {
-        let inner = CommandInner::Idle { len };
+        Self::idle_clocks(len.0 * 8)
+    }
+
+    /// Stay in [`State::RunTestIdle`] for `count` TCK cycles.
+    pub fn idle_clocks(count: usize) -> Self {
+        let inner = CommandInner::Idle { clocks: count };
         let notify = false;
         Self { notify, inner }
     }
+
+    /// Stay in [`State::RunTestIdle`] for at least `duration`, regardless of
+    /// TCK frequency. Flushes all commands before it.
+    pub fn wait(duration: Duration) -> Self {
+        let inner = CommandInner::Wait { duration };
+        let notify = false;
+        Self { notify, inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zynq_us_jtag_status() {
+        let status = ZynqUsJtagStatus(0x0000_0218);
+        assert!(status.done());
+        assert!(status.init());
+        assert_eq!(status.boot_mode(), 0x2);
+        assert_eq!(
+            status.to_string(),
+            "0x00000218 (boot mode 0x2 QSPI32, INIT_B high, DONE high)"
+        );
+        assert_eq!(
+            ZynqUsJtagStatus(0x0000_0e00).to_string(),
+            "0x00000e00 (boot mode 0xe SD1 LS, INIT_B low, DONE low)"
+        );
+    }
 }
@@ -0,0 +1,184 @@
+//! A builder for long command sequences, instead of writing out
+//! `[Command; N]` by hand.
+
+use std::time::Duration;
+
+use super::Command;
+use crate::units::{Bits, Bytes};
+
+/// Commands collected by chaining calls, to pass to
+/// [`Controller::run`](crate::Controller::run) by reference:
+///
+/// ```ignore
+/// let batch = CommandBatch::new()
+///     .ir(CFG_IN)
+///     .dr_tx(&header)
+///     .idle_clocks(100)
+///     .ir(CFG_OUT)
+///     .dr_rx(Bytes(4));
+/// let status = cont.run(&batch).await?;
+/// ```
+///
+/// The `*_owned` methods keep the data in the batch, for data built on the
+/// spot that would otherwise need a `let` binding to outlive the commands.
+#[derive(Clone, Debug, Default)]
+pub struct CommandBatch<'d> {
+    entries: Vec<Entry<'d>>,
+}
+
+#[derive(Clone, Debug)]
+enum Entry<'d> {
+    Command(Command<'d>),
+    /// A command made from data owned by the batch, when iterating.
+    Owned(Vec<u8>, for<'a> fn(&'a [u8]) -> Command<'a>),
+}
+
+impl<'d> CommandBatch<'d> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add any command, i.e. one without a method here.
+    pub fn command(mut self, command: Command<'d>) -> Self {
+        self.entries.push(Entry::Command(command));
+        self
+    }
+
+    fn owned(mut self, tdi: Vec<u8>, make: for<'a> fn(&'a [u8]) -> Command<'a>) -> Self {
+        self.entries.push(Entry::Owned(tdi, make));
+        self
+    }
+
+    pub fn ir(self, tdi: u32) -> Self {
+        self.command(Command::ir(tdi))
+    }
+
+    pub fn dr_tx(self, tdi: &'d [u8]) -> Self {
+        self.command(Command::dr_tx(tdi))
+    }
+
+    pub fn dr_tx_owned(self, tdi: Vec<u8>) -> Self {
+        self.owned(tdi, |tdi| Command::dr_tx(tdi))
+    }
+
+    pub fn dr_tx_with_notification(self, tdi: &'d [u8]) -> Self {
+        self.command(Command::dr_tx_with_notification(tdi))
+    }
+
+    pub fn dr_tx_owned_with_notification(self, tdi: Vec<u8>) -> Self {
+        self.owned(tdi, |tdi| Command::dr_tx_with_notification(tdi))
+    }
+
+    pub fn dr_rx(self, len: Bytes<usize>) -> Self {
+        self.command(Command::dr_rx(len))
+    }
+
+    pub fn dr_rx_with_notification(self, len: Bytes<usize>) -> Self {
+        self.command(Command::dr_rx_with_notification(len))
+    }
+
+    pub fn dr_txrx(self, tdi: &'d [u8]) -> Self {
+        self.command(Command::dr_txrx(tdi))
+    }
+
+    pub fn dr_txrx_owned(self, tdi: Vec<u8>) -> Self {
+        self.owned(tdi, |tdi| Command::dr_txrx(tdi))
+    }
+
+    pub fn dr_tx_bits(self, tdi: u32, len: Bits<u8>) -> Self {
+        self.command(Command::dr_tx_bits(tdi, len))
+    }
+
+    /// See [`Command::dr_tx_slice_bits`].
+    pub fn dr_tx_slice_bits(self, tdi: &'d [u8], len: Bits<usize>) -> Self {
+        self.command(Command::dr_tx_slice_bits(tdi, len))
+    }
+
+    /// See [`Command::dr_rx_bits`].
+    pub fn dr_rx_bits(self, len: Bits<usize>) -> Self {
+        self.command(Command::dr_rx_bits(len))
+    }
+
+    pub fn combined_ir_dr_tx_bits(self, ir: u32, dr: u32, dr_len: Bits<u8>) -> Self {
+        self.command(Command::combined_ir_dr_tx_bits(ir, dr, dr_len))
+    }
+
+    /// See [`Command::idle`].
+    pub fn idle(self, len: Bytes<usize>) -> Self {
+        self.command(Command::idle(len))
+    }
+
+    /// See [`Command::idle_clocks`].
+    pub fn idle_clocks(self, count: usize) -> Self {
+        self.command(Command::idle_clocks(count))
+    }
+
+    /// See [`Command::wait`].
+    pub fn wait(self, duration: Duration) -> Self {
+        self.command(Command::wait(duration))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The commands, borrowing owned data from the batch.
+    pub fn iter(&self) -> impl Iterator<Item = Command<'_>> {
+        self.entries.iter().map(|entry| match entry {
+            Entry::Command(command) => *command,
+            Entry::Owned(tdi, make) => make(tdi),
+        })
+    }
+}
+
+impl<'a, 'd: 'a> IntoIterator for &'a CommandBatch<'d> {
+    type Item = Command<'a>;
+    type IntoIter = Box<dyn Iterator<Item = Command<'a>> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl<'d> Extend<Command<'d>> for CommandBatch<'d> {
+    fn extend<I: IntoIterator<Item = Command<'d>>>(&mut self, iter: I) {
+        self.entries.extend(iter.into_iter().map(Entry::Command));
+    }
+}
+
+impl<'d> FromIterator<Command<'d>> for CommandBatch<'d> {
+    fn from_iter<I: IntoIterator<Item = Command<'d>>>(iter: I) -> Self {
+        let mut batch = Self::new();
+        batch.extend(iter);
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{self, FakeDevice};
+
+    #[test]
+    fn test_batch() {
+        smol::block_on(async {
+            let device = FakeDevice::new(0x0362_d093, Bits(6), 0b001001);
+            let device = device.register(0b000010, Bits(32), &[0; 4]);
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+
+            let batch = CommandBatch::new()
+                .ir(0b000010)
+                .dr_tx_owned(0x1234_5678_u32.to_le_bytes().to_vec())
+                .idle_clocks(10)
+                .ir(0b000010)
+                .dr_rx(Bytes(4));
+            assert_eq!(batch.len(), 5);
+            let read = cont.run(&batch).await.unwrap();
+            assert_eq!(read, 0x1234_5678_u32.to_le_bytes());
+        });
+    }
+}
@@ -0,0 +1,232 @@
+//! The devices found by [`detect_chain`](super::detect_chain).
+
+use std::fmt;
+
+use facet::Facet;
+
+use super::IdCodeInfo;
+use crate::{
+    devices::{DeviceInfo, Specific},
+    jtag::IdCode,
+};
+
+/// Every TAP on the chain, nearest TDO first.
+///
+/// [`Display`](fmt::Display) lists them as `detect-chain` prints them, and
+/// the [`Facet`] derive serializes the same fields for scripts.
+#[derive(Clone, Debug, Facet)]
+pub struct Chain {
+    pub taps: Vec<Tap>,
+}
+
+#[derive(Clone, Debug, Facet)]
+pub struct Tap {
+    /// Index in the chain, as passed to `--jtag-idx`.
+    pub position: usize,
+    pub idcode: u32,
+    pub name: String,
+    pub irlen: u8,
+    /// IR right after reset, if the whole chain fit in the capture. The low
+    /// two bits are `0b01` on any compliant TAP.
+    pub ir_capture: Option<u32>,
+    #[facet(opaque, skip_serializing)]
+    info: DeviceInfo,
+}
+
+impl Chain {
+    /// `capture` is what came out of the IR of the whole chain after reset,
+    /// TDO first.
+    pub(super) fn new(devices: Vec<(IdCode, DeviceInfo)>, capture: &[u8]) -> Self {
+        let available = capture.len() * 8;
+        let mut offset = 0;
+        let taps = devices
+            .into_iter()
+            .enumerate()
+            .map(|(position, (idcode, info))| {
+                let irlen = usize::from(info.irlen.0);
+                let ir_capture = (offset + irlen <= available).then(|| {
+                    (0..irlen).fold(0, |acc, idx| {
+                        let bit = offset + idx;
+                        acc | u32::from(capture[bit / 8] >> (bit % 8) & 1) << idx
+                    })
+                });
+                offset += irlen;
+                Tap {
+                    position,
+                    idcode: idcode.code(),
+                    name: info.name.to_string(),
+                    irlen: info.irlen.0,
+                    ir_capture,
+                    info,
+                }
+            });
+        Self {
+            taps: taps.collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.taps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.taps.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Tap> {
+        self.taps.iter()
+    }
+
+    /// The device to operate on when none was chosen: the only one in the
+    /// chain with known specifics. This skips ARM DAPs, so the PL of a
+    /// Zynq-7000 (DAP with irlen 4, PL with irlen 6) is picked without asking,
+    /// and also devices kept in BYPASS by
+    /// [`UnknownDevices::Bypass`](super::UnknownDevices::Bypass).
+    pub fn default_target(&self) -> Option<usize> {
+        let mut known = (self.taps.iter())
+            .filter(|tap| !matches!(tap.info.specific, Specific::Unknown))
+            .map(|tap| tap.position);
+        match (known.next(), known.next()) {
+            (Some(idx), None) => Some(idx),
+            _ => None,
+        }
+    }
+
+    /// Positions of the devices matching `target`.
+    pub fn select(&self, target: &TapSelector) -> Vec<usize> {
+        (self.taps.iter())
+            .filter(|tap| target.matches(tap))
+            .map(|tap| tap.position)
+            .collect()
+    }
+
+    /// Split into the devices before `idx`, the one at `idx`, and the ones
+    /// after, as taken by [`Controller::new`](crate::Controller::new).
+    #[allow(clippy::type_complexity)]
+    pub fn split(
+        &self,
+        idx: usize,
+    ) -> Option<(
+        Vec<(IdCode, DeviceInfo)>,
+        (IdCode, DeviceInfo),
+        Vec<(IdCode, DeviceInfo)>,
+    )> {
+        let active = self.taps.get(idx)?.device();
+        let before = self.taps[..idx].iter().map(Tap::device).collect();
+        let after = self.taps[idx + 1..].iter().map(Tap::device).collect();
+        Some((before, active, after))
+    }
+}
+
+impl Tap {
+    pub fn idcode(&self) -> IdCode {
+        IdCode::new(self.idcode)
+    }
+
+    pub fn info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    pub fn device(&self) -> (IdCode, DeviceInfo) {
+        (self.idcode(), self.info.clone())
+    }
+}
+
+impl<'a> IntoIterator for &'a Chain {
+    type Item = &'a Tap;
+    type IntoIter = std::slice::Iter<'a, Tap>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.taps.iter()
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let default = self.default_target();
+        for (idx, tap) in self.taps.iter().enumerate() {
+            if idx != 0 {
+                writeln!(f)?;
+            }
+            let default = if default == Some(idx) {
+                " (default)"
+            } else {
+                ""
+            };
+            write!(f, "{}: {:08X}{default}\n{tap}", tap.position, tap.idcode)?;
+        }
+        Ok(())
+    }
+}
+
+/// The details of one TAP, indented to go under its index.
+impl fmt::Display for Tap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", IdCodeInfo::new(4, self.idcode(), Some(&self.info)))?;
+        if let Some(capture) = self.ir_capture {
+            let width = usize::from(self.irlen);
+            write!(f, "\n    ir capture   0b{capture:0width$b}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A device to operate on, chosen without knowing its position.
+#[derive(Clone, PartialEq, Eq)]
+pub enum TapSelector {
+    /// An IDCODE. The version is only compared if it isn't 0.
+    IdCode(IdCode),
+    /// A device name, i.e. `xc7a35t`, ignoring case.
+    Name(String),
+}
+
+impl TapSelector {
+    pub fn matches(&self, tap: &Tap) -> bool {
+        match self {
+            Self::IdCode(idcode) if idcode.strip_version() == *idcode => {
+                IdCode::new(tap.idcode).strip_version() == *idcode
+            }
+            Self::IdCode(idcode) => tap.idcode == idcode.code(),
+            Self::Name(name) => tap.name.eq_ignore_ascii_case(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Bits;
+
+    #[test]
+    fn test_ir_capture() {
+        let device = |irlen| DeviceInfo {
+            irlen: Bits(irlen),
+            name: "dev".into(),
+            specific: Specific::Unknown,
+        };
+        let devices = vec![
+            (IdCode::new(1), device(6)),
+            (IdCode::new(3), device(4)),
+            (IdCode::new(5), device(8)),
+        ];
+        // 0b110001 then 0b0001, TDO first, then only part of the last one
+        let chain = Chain::new(devices, &[0b0111_0001, 0b0000_0100]);
+        let captures: Vec<_> = chain.iter().map(|tap| tap.ir_capture).collect();
+        assert_eq!(captures, [Some(0b110001), Some(0b0001), None]);
+        assert_eq!(chain.taps[1].position, 1);
+        assert_eq!(chain.default_target(), None);
+
+        assert_eq!(chain.select(&TapSelector::IdCode(IdCode::new(5))), [2]);
+        assert_eq!(
+            chain.select(&TapSelector::IdCode(IdCode::new(0x1000_0005))),
+            []
+        );
+        assert_eq!(chain.select(&TapSelector::Name("DEV".into())), [0, 1, 2]);
+
+        let json = facet_json::to_string(&chain.taps[1]).unwrap();
+        assert_eq!(
+            json,
+            r#"{"position":1,"idcode":3,"name":"dev","irlen":4,"ir_capture":1}"#
+        );
+    }
+}
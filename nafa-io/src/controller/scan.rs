@@ -0,0 +1,300 @@
+//! Chain discovery for chains where some devices come out of reset with
+//! BYPASS selected instead of IDCODE.
+//!
+//! Without an IDCODE for every device, the DR scan in [`super::detect_chain`]
+//! can't tell where one device ends and the next begins. Instead:
+//! 1. Flood IR with ones, then shift a single zero. The number of bits until it
+//!    comes out is the combined IR length, and the bits shifted out before the
+//!    flood are the captured IR values.
+//! 2. With every device in BYPASS (all ones), do the same through DR, giving
+//!    the number of devices.
+//! 3. After a reset, each device has either a 32-bit IDCODE (starting with a
+//!    `1`), or a single `0` BYPASS bit in DR.
+//! 4. Every captured IR value starts with `1, 0` (IEEE 1149.1-2013, 7.1.1).
+//!    Devices with a known IDCODE have a known IR length, the rest of the
+//!    capture has to be split between the others where a `1, 0` starts. If
+//!    there's more than one way to do that, give up.
+//!
+//! Unknown IDCODEs allowed by [`UnknownDevices::Bypass`] without an irlen get
+//! theirs the same way.
+
+use std::collections::HashMap;
+
+use super::{DetectOptions, UnknownDevices, get_info, unknown_device};
+use crate::{
+    Backend, BitString, Error, Result, ScratchBuffer,
+    backend::Data,
+    devices::DeviceInfo,
+    jtag::{IdCode, PATHS, Path, State},
+    units::Bits,
+};
+
+/// Longest combined IR, and most devices, this can measure.
+const MAX_BITS: usize = 256;
+
+pub(super) async fn scan_chain(
+    backend: &mut dyn Backend,
+    devices: &HashMap<IdCode, DeviceInfo>,
+    options: &DetectOptions,
+) -> Result<Vec<(IdCode, DeviceInfo)>> {
+    let buf = &mut ScratchBuffer::new();
+
+    let reset_to_sir = Some(PATHS[State::TestLogicReset][State::ShiftIR]);
+    let sir_to_sdr = Some(PATHS[State::ShiftIR][State::ShiftDR]);
+    let reset_to_sdr = Some(PATHS[State::TestLogicReset][State::ShiftDR]);
+    let sdr_to_reset = Some(PATHS[State::ShiftDR][State::TestLogicReset]);
+
+    // leaves all ones in IR, so DR is BYPASS for every device afterwards
+    let flood = flood_pattern();
+    backend.tms(buf, Path::RESET).await?;
+    backend
+        .bytes(buf, reset_to_sir, Data::TxRx(&flood), None)
+        .await?;
+    backend.flush(buf).await?;
+    let irlen = measure(buf.data())
+        .ok_or_else(|| Error::Chain(format!("combined irlen over {MAX_BITS}")))?;
+    let capture: Vec<_> = (0..irlen).map(|idx| bit(buf.data(), idx)).collect();
+    tracing::info!(irlen, capture = %BitString::new(buf.data(), Bits(irlen)));
+    buf.clear();
+
+    backend
+        .bytes(buf, sir_to_sdr, Data::TxRx(&flood), sdr_to_reset)
+        .await?;
+    backend.flush(buf).await?;
+    let count =
+        measure(buf.data()).ok_or_else(|| Error::Chain(format!("more than {MAX_BITS} devices")))?;
+    tracing::info!(count);
+    buf.clear();
+
+    let dr_ones = vec![0xff; count * 4 + 1];
+    backend
+        .bytes(buf, reset_to_sdr, Data::TxRx(&dr_ones), sdr_to_reset)
+        .await?;
+    backend.flush(buf).await?;
+    let ids = parse_dr(buf.data(), count)?;
+    buf.clear();
+
+    let mut known = Vec::with_capacity(count);
+    for id in &ids {
+        let info = match (id, options.unknown) {
+            (None, _) => None,
+            (Some(idcode), UnknownDevices::Reject) => Some(get_info(devices, &[], *idcode)?),
+            (Some(idcode), UnknownDevices::Bypass { irlen }) => {
+                match get_info(devices, &[], *idcode) {
+                    Ok(info) => Some(info),
+                    Err(_) => irlen.map(|irlen| unknown_device("UNKNOWN", irlen)),
+                }
+            }
+        };
+        known.push(info);
+    }
+    let lens: Vec<_> = known
+        .iter()
+        .map(|info| info.as_ref().map(|i| i.irlen.0 as usize))
+        .collect();
+    let lens = split_ir(&capture, &lens)?;
+
+    let chain = ids.into_iter().zip(known).zip(lens);
+    let chain = chain.map(|((id, info), len)| {
+        if let Some(info) = info {
+            let idcode = id.expect("only devices with an idcode have info");
+            return Ok((idcode, info));
+        }
+        let irlen = u8::try_from(len)
+            .ok()
+            .filter(|len| *len <= 32)
+            .ok_or_else(|| {
+                Error::Chain(format!(
+                    "device without known irlen has irlen {len}, over 32"
+                ))
+            })?;
+        match id {
+            Some(idcode) => Ok((idcode, unknown_device("UNKNOWN", Bits(irlen)))),
+            None => Ok((IdCode::new(0), unknown_device("BYPASS", Bits(irlen)))),
+        }
+    });
+    chain.collect()
+}
+
+/// `MAX_BITS` ones, a single zero, and enough ones after to push it out
+/// again.
+fn flood_pattern() -> Vec<u8> {
+    let ones = MAX_BITS / 8;
+    let mut flood = vec![0xff; 2 * ones + 2];
+    flood[ones] = 0xfe;
+    flood
+}
+
+/// How long after the flood the zero came out.
+fn measure(rx: &[u8]) -> Option<usize> {
+    let total = rx.len() * 8;
+    (MAX_BITS..total)
+        .find(|idx| !bit(rx, *idx))
+        .map(|idx| idx - MAX_BITS)
+}
+
+/// Reads `count` devices from a DR scan right after reset.
+fn parse_dr(rx: &[u8], count: usize) -> Result<Vec<Option<IdCode>>> {
+    let total = rx.len() * 8;
+    let mut offset = 0;
+    let mut ids = Vec::with_capacity(count);
+    for _ in 0..count {
+        if offset + 32 <= total && bit(rx, offset) {
+            let code = (0..32).fold(0, |acc, idx| acc | u32::from(bit(rx, offset + idx)) << idx);
+            ids.push(Some(IdCode::new(code)));
+            offset += 32;
+        } else if offset < total && !bit(rx, offset) {
+            ids.push(None);
+            offset += 1;
+        } else {
+            return Err(Error::Chain(format!(
+                "dr scan doesn't match {count} devices"
+            )));
+        }
+    }
+    if (offset..total).any(|idx| !bit(rx, idx)) {
+        return Err(Error::Chain(format!(
+            "dr scan doesn't match {count} devices"
+        )));
+    }
+    Ok(ids)
+}
+
+/// Find the only way to split `capture` into IRs of the given lengths,
+/// picking lengths for the unknown (`None`) ones.
+fn split_ir(capture: &[bool], known: &[Option<usize>]) -> Result<Vec<usize>> {
+    fn search(
+        capture: &[bool],
+        known: &[Option<usize>],
+        current: &mut Vec<usize>,
+        found: &mut Vec<Vec<usize>>,
+    ) {
+        if found.len() > 1 {
+            return;
+        }
+        let Some((first, rest)) = known.split_first() else {
+            if capture.is_empty() {
+                found.push(current.clone());
+            }
+            return;
+        };
+        let lens = match first {
+            Some(len) => *len..=*len,
+            None => 2..=capture.len(),
+        };
+        for len in lens {
+            let fits = len <= capture.len() && capture[0] && (len < 2 || !capture[1]);
+            if fits {
+                current.push(len);
+                search(&capture[len..], rest, current, found);
+                current.pop();
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    search(capture, known, &mut Vec::new(), &mut found);
+    match found.len() {
+        0 => Err(Error::Chain(
+            "ir capture doesn't fit the detected devices".into(),
+        )),
+        1 => Ok(found.remove(0)),
+        _ => Err(Error::Chain(format!(
+            "ir lengths are ambiguous, could be {:?} or {:?}",
+            found[0], found[1]
+        ))),
+    }
+}
+
+fn bit(data: &[u8], idx: usize) -> bool {
+    data[idx / 8] >> (idx % 8) & 1 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DetectOptions, detect_chain, detect_chain_with,
+        devices::{self, Specific},
+        fake::{FakeBackend, FakeDevice},
+    };
+
+    const XC7A35T: u32 = 0x0362_d093;
+    const ARM_DAP: u32 = 0x4ba0_0477;
+
+    #[test]
+    fn test_scan_chain() {
+        smol::block_on(async {
+            // everything after the bypassed device is misaligned in the DR
+            // scan of `detect_chain`, so this goes through `scan_chain`
+            let mut backend = FakeBackend::new(vec![
+                FakeDevice::new(ARM_DAP, Bits(4), 0b1110),
+                FakeDevice::bypass(Bits(5)),
+                FakeDevice::new(XC7A35T, Bits(6), 0b001001),
+            ]);
+            let devices = devices::all().collect();
+            let chain = detect_chain(&mut backend, &devices).await.unwrap();
+            let taps: Vec<_> = (chain.taps.iter())
+                .map(|tap| (tap.idcode, tap.irlen))
+                .collect();
+            assert_eq!(taps, [(ARM_DAP, 4), (0, 5), (XC7A35T, 6)]);
+            assert!(matches!(chain.taps[1].info().specific, Specific::Unknown));
+        });
+    }
+
+    #[test]
+    fn test_unknown_irlen() {
+        smol::block_on(async {
+            const UNKNOWN: u32 = 0x1234_5679;
+            let backend = || {
+                FakeBackend::new(vec![
+                    FakeDevice::new(UNKNOWN, Bits(5), 0b00010),
+                    FakeDevice::new(XC7A35T, Bits(6), 0b001001),
+                ])
+            };
+            let devices = devices::all().collect();
+            let options = |irlen| DetectOptions {
+                unknown: UnknownDevices::Bypass { irlen },
+                ..DetectOptions::default()
+            };
+
+            assert!(detect_chain(&mut backend(), &devices).await.is_err());
+            // the irlen comes from the IR capture
+            let chain = detect_chain_with(&mut backend(), &devices, &options(None))
+                .await
+                .unwrap();
+            let taps: Vec<_> = (chain.taps.iter())
+                .map(|tap| (tap.idcode, tap.irlen))
+                .collect();
+            assert_eq!(taps, [(UNKNOWN, 5), (XC7A35T, 6)]);
+            let chain = detect_chain_with(&mut backend(), &devices, &options(Some(Bits(5))))
+                .await
+                .unwrap();
+            assert_eq!(chain.taps[0].irlen, 5);
+        });
+    }
+
+    #[test]
+    fn test_split_ir() {
+        let bits = |s: &str| s.chars().map(|c| c == '1').collect::<Vec<_>>();
+
+        // two unknown 4-bit, then a known 6-bit
+        let capture = bits("10001011100101");
+        assert_eq!(
+            split_ir(&capture, &[None, None, Some(6)]).unwrap(),
+            [4, 4, 6]
+        );
+        // `1, 0` inside an IR makes this ambiguous without a length
+        let capture = bits("10101000");
+        assert!(split_ir(&capture, &[None, None]).is_err());
+        assert_eq!(split_ir(&capture, &[Some(4), None]).unwrap(), [4, 4]);
+
+        // bypass, idcode 0x0000_0003, bypass, then ones
+        let rx = [0b0000_0110, 0, 0, 0, 0b1111_1100, 0xff];
+        let ids = parse_dr(&rx, 3).unwrap();
+        assert_eq!(ids.len(), 3);
+        assert!(ids[0].is_none() && ids[2].is_none());
+        assert_eq!(ids[1].unwrap().code(), 3);
+        assert!(parse_dr(&rx, 2).is_err());
+    }
+}
@@ -1,14 +1,22 @@
+use std::{
+    borrow::Cow,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
 use facet::Facet;
 
 use crate::{
+    Error, Result,
+    error::Context as _,
     jtag::IdCode,
-    units::{Bits, Words32},
+    units::{Bits, Bytes, Words32},
 };
 
 #[derive(Clone, Debug)]
 pub struct DeviceInfo {
     pub irlen: Bits<u8>,
-    pub name: &'static str,
+    pub name: Cow<'static, str>,
     pub specific: Specific,
 }
 
@@ -16,12 +24,44 @@ pub struct DeviceInfo {
 pub enum Specific {
     Unknown,
     Xilinx32(Xilinx32Info),
+    Xilinx16(Xilinx16Info),
+    XilinxVirtex(XilinxVirtexInfo),
+    XilinxCpld(XilinxCpldInfo),
+    XilinxProm(XilinxPromInfo),
     XilinxZynq(XilinxZynqInfo),
     XilinxVersal(XilinxVersalInfo),
     Intel,
     Microchip,
 }
 
+impl DeviceInfo {
+    /// Fastest TCK this device is specified for, in Hz, if known.
+    pub fn max_tck(&self) -> Option<u32> {
+        self.specific.max_tck()
+    }
+}
+
+impl Specific {
+    /// See [`DeviceInfo::max_tck`].
+    pub fn max_tck(&self) -> Option<u32> {
+        match self {
+            Specific::Xilinx32(info) => Some(info.family.max_tck()),
+            // Spartan-6, DS162 TCK frequency
+            Specific::Xilinx16(_) => Some(33_000_000),
+            Specific::XilinxVirtex(info) => Some(info.family.max_tck()),
+            // XC9500XL, DS054 TCK frequency
+            Specific::XilinxCpld(_) => Some(10_000_000),
+            // XCFxxS, DS123 TCK frequency; XC18V00, DS026 TCK frequency
+            Specific::XilinxProm(_) => Some(10_000_000),
+            // MAX 10 / Cyclone 10 LP, tJCP of 40ns
+            Specific::Intel => Some(25_000_000),
+            // PolarFire
+            Specific::Microchip => Some(25_000_000),
+            Specific::Unknown | Specific::XilinxZynq(_) | Specific::XilinxVersal(_) => None,
+        }
+    }
+}
+
 pub trait GetSpecific<T> {
     fn get(&self) -> Option<&T>;
 }
@@ -33,6 +73,38 @@ impl GetSpecific<Xilinx32Info> for Specific {
         }
     }
 }
+impl GetSpecific<Xilinx16Info> for Specific {
+    fn get(&self) -> Option<&Xilinx16Info> {
+        match self {
+            Specific::Xilinx16(info) => Some(info),
+            _ => None,
+        }
+    }
+}
+impl GetSpecific<XilinxVirtexInfo> for Specific {
+    fn get(&self) -> Option<&XilinxVirtexInfo> {
+        match self {
+            Specific::XilinxVirtex(info) => Some(info),
+            _ => None,
+        }
+    }
+}
+impl GetSpecific<XilinxCpldInfo> for Specific {
+    fn get(&self) -> Option<&XilinxCpldInfo> {
+        match self {
+            Specific::XilinxCpld(info) => Some(info),
+            _ => None,
+        }
+    }
+}
+impl GetSpecific<XilinxPromInfo> for Specific {
+    fn get(&self) -> Option<&XilinxPromInfo> {
+        match self {
+            Specific::XilinxProm(info) => Some(info),
+            _ => None,
+        }
+    }
+}
 impl GetSpecific<XilinxZynqInfo> for Specific {
     fn get(&self) -> Option<&XilinxZynqInfo> {
         match self {
@@ -56,10 +128,47 @@ pub struct Xilinx32Info {
     pub family: Xilinx32Family,
     pub slr: u8,
     pub readback: Option<Words32<usize>>,
+    /// Application cores of the processing system, on a Zynq. `None` on
+    /// FPGAs without one.
+    pub ps_cores: Option<u8>,
+    /// Where the pad frames are in a readback. `None` if not known, then
+    /// readbacks keep them.
+    pub layout: Option<FrameLayout>,
+}
+
+/// The configuration rows of each SLR of a part, as many frames as the frame
+/// address counts through in each.
+///
+/// Readback (and the frame data of a bitstream) goes through every row of
+/// logic and I/O (block type 0), the top half then the bottom half, then the
+/// same rows again for the block RAM contents (block type 1). Each row is
+/// followed by [`FrameLayout::ROW_PAD`] pad frames, which aren't counted
+/// here.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameLayout {
+    /// Frames of each row, in readback order, for each SLR. SLR 0 is the
+    /// master.
+    pub slrs: Cow<'static, [Cow<'static, [u16]>]>,
+}
+
+impl FrameLayout {
+    /// Pad frames after each row.
+    pub const ROW_PAD: usize = 2;
+
+    /// Frames of one readback of `slr`: the pad frame it starts with, then
+    /// every row with its pad frames.
+    pub fn readback_frames(&self, slr: u8) -> Option<usize> {
+        let rows = self.slrs.get(usize::from(slr))?;
+        let frames: usize = rows
+            .iter()
+            .map(|&row| usize::from(row) + Self::ROW_PAD)
+            .sum();
+        Some(1 + frames)
+    }
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, Facet)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Facet)]
 pub enum Xilinx32Family {
     /// 7-series
     S7,
@@ -69,8 +178,89 @@ pub enum Xilinx32Family {
     UP,
 }
 
+impl Xilinx32Family {
+    /// Fastest TCK in the datasheet's JTAG switching characteristics, in Hz.
+    pub const fn max_tck(self) -> u32 {
+        match self {
+            Self::S7 => 66_000_000,
+            Self::US | Self::UP => 50_000_000,
+        }
+    }
+
+    /// Length of one configuration frame.
+    pub const fn frame_words(self) -> Words32<usize> {
+        match self {
+            Self::S7 => Words32(101),
+            Self::US => Words32(123),
+            Self::UP => Words32(93),
+        }
+    }
+}
+
+/// Spartan-6, with its 16-bit configuration logic.
+#[derive(Clone, Debug)]
+pub struct Xilinx16Info {
+    /// Readback length, in 16-bit configuration words.
+    pub readback: Option<usize>,
+}
+
+/// Virtex-4/5/6: 32-bit configuration packets like [`Xilinx32Info`], but
+/// with a 10- or 14-bit IR (the 6-bit configuration instruction padded with
+/// ones, i.e. IDCODE is `0x3c9`), and Virtex-4 has its own register map.
+#[derive(Clone, Debug)]
+pub struct XilinxVirtexInfo {
+    pub family: VirtexFamily,
+    pub readback: Option<Words32<usize>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Facet)]
+#[repr(u8)]
+pub enum VirtexFamily {
+    V4,
+    V5,
+    V6,
+}
+
+impl VirtexFamily {
+    /// Fastest TCK in the datasheet's JTAG switching characteristics, in Hz.
+    pub const fn max_tck(self) -> u32 {
+        match self {
+            Self::V4 | Self::V5 => 33_000_000,
+            Self::V6 => 66_000_000,
+        }
+    }
+}
+
+/// XC9500XL CPLD, programmed through its 8-bit ISC instructions from a
+/// JEDEC file rather than configured with a bitstream.
 #[derive(Clone, Debug)]
-pub struct XilinxZynqInfo {}
+pub struct XilinxCpldInfo {
+    /// Number of function blocks, each adding 8 bits to the ISC data
+    /// register (and 11664 fuses to the JEDEC file).
+    pub function_blocks: u8,
+}
+
+/// XCFxxS platform flash or XC18V00 ISP PROM, the serial configuration PROM
+/// next to an FPGA on older boards.
+#[derive(Clone, Debug)]
+pub struct XilinxPromInfo {
+    pub size: Bytes<usize>,
+    pub family: XilinxPromFamily,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XilinxPromFamily {
+    /// XCF01S, XCF02S, XCF04S.
+    XcfS,
+    /// XC18V512, XC18V01, XC18V02, XC18V04.
+    Xc18v,
+}
+
+#[derive(Clone, Debug)]
+pub struct XilinxZynqInfo {
+    /// Application cores of the processing system.
+    pub ps_cores: u8,
+}
 
 #[derive(Clone, Debug)]
 pub struct XilinxVersalInfo {}
@@ -80,12 +270,229 @@ pub struct XilinxVersalInfo {}
 pub fn builtin() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
     [].into_iter()
         .chain(xilinx())
+        .chain(xilinx16())
+        .chain(xilinx_virtex())
+        .chain(xilinx_cpld())
+        .chain(xilinx_prom())
         .chain(xilinx_zynq())
         .chain(xilinx_versal())
         .chain(intel())
         .chain(microchip())
 }
 
+/// Parse a device list, in TOML or JSON (if it starts with `{`). Intended to
+/// be chained after [`builtin`] before collecting, so entries here replace the
+/// builtin ones with the same idcode.
+///
+/// ```toml
+/// [[device]]
+/// idcode = "0x0362d093"  # hex with 0x, or decimal
+/// irlen = 6
+/// name = "xc7a35t"
+/// # one of s7, us, up (Xilinx 32-bit), s6 (Spartan-6), v4, v5, v6 (Virtex),
+/// # zynq, versal, intel, microchip. Leave out for devices that should only
+/// # ever be in BYPASS.
+/// family = "s7"
+/// # Xilinx 32-bit, Spartan-6, and Virtex only, configuration readback length
+/// # in configuration words (32-bit, or 16-bit for Spartan-6)
+/// readback = 547521
+/// # Xilinx 32-bit only, frames of each configuration row, a list per SLR
+/// # (see `FrameLayout`). Lets readbacks drop the pad frames after each row,
+/// # and be split by SLR.
+/// # rows = [[...], ...]
+/// ```
+///
+/// The JSON form is the same, i.e. `{"device": [{"idcode": "0x0362d093",
+/// ...}]}`.
+pub fn from_str(data: &str) -> Result<Vec<(IdCode, DeviceInfo)>> {
+    let raw: RawDatabase = if data.trim_start().starts_with('{') {
+        facet_json::from_str(data)
+            .map_err(|e| Error::InvalidInput(format!("invalid json device list: {e}")))?
+    } else {
+        facet_toml::from_str(data)
+            .map_err(|e| Error::InvalidInput(format!("invalid toml device list: {e}")))?
+    };
+    raw.device
+        .into_iter()
+        .map(|device| {
+            let name = device.name.clone();
+            device
+                .parse()
+                .with_context(|| format!("invalid device {name:?}"))
+        })
+        .collect()
+}
+
+/// [`from_str`], reading the file at `path`.
+pub fn from_path(path: &Path) -> Result<Vec<(IdCode, DeviceInfo)>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    from_str(&data).with_context(|| format!("in {}", path.display()))
+}
+
+#[derive(Facet)]
+struct RawDatabase {
+    #[facet(default)]
+    device: Vec<RawDevice>,
+}
+
+#[derive(Facet)]
+struct RawDevice {
+    idcode: String,
+    irlen: u8,
+    name: String,
+    #[facet(default)]
+    family: Option<String>,
+    #[facet(default)]
+    readback: Option<usize>,
+    #[facet(default)]
+    ps_cores: Option<u8>,
+    #[facet(default)]
+    rows: Option<Vec<Vec<u16>>>,
+}
+
+impl RawDevice {
+    fn parse(self) -> Result<(IdCode, DeviceInfo)> {
+        let idcode = match self.idcode.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => self.idcode.parse(),
+        };
+        let idcode = idcode.with_context(|| format!("invalid idcode {:?}", self.idcode))?;
+        if !(1..=32).contains(&self.irlen) {
+            return Err(Error::InvalidInput(format!(
+                "irlen {} outside of 1..=32",
+                self.irlen
+            )));
+        }
+
+        let xilinx32 = |family| {
+            if !self.irlen.is_multiple_of(6) {
+                return Err(Error::InvalidInput(
+                    "xilinx irlen must be a multiple of 6".into(),
+                ));
+            }
+            Ok(Specific::Xilinx32(Xilinx32Info {
+                family,
+                slr: self.irlen / 6,
+                readback: self.readback.map(Words32),
+                ps_cores: self.ps_cores,
+                layout: match &self.rows {
+                    Some(rows) if rows.len() != usize::from(self.irlen / 6) => {
+                        return Err(Error::InvalidInput(format!(
+                            "rows given for {} SLRs, expected {}",
+                            rows.len(),
+                            self.irlen / 6
+                        )));
+                    }
+                    Some(rows) => Some(FrameLayout {
+                        slrs: rows.iter().map(|rows| Cow::Owned(rows.clone())).collect(),
+                    }),
+                    None => None,
+                },
+            }))
+        };
+        let virtex = |family| {
+            if self.irlen < 6 {
+                return Err(Error::InvalidInput(
+                    "virtex irlen must be at least 6".into(),
+                ));
+            }
+            Ok(Specific::XilinxVirtex(XilinxVirtexInfo {
+                family,
+                readback: self.readback.map(Words32),
+            }))
+        };
+        let specific = match self.family.as_deref() {
+            None => Specific::Unknown,
+            Some("s7") => xilinx32(Xilinx32Family::S7)?,
+            Some("us") => xilinx32(Xilinx32Family::US)?,
+            Some("up") => xilinx32(Xilinx32Family::UP)?,
+            Some("s6") => Specific::Xilinx16(Xilinx16Info {
+                readback: self.readback,
+            }),
+            Some("v4") => virtex(VirtexFamily::V4)?,
+            Some("v5") => virtex(VirtexFamily::V5)?,
+            Some("v6") => virtex(VirtexFamily::V6)?,
+            Some("zynq") => Specific::XilinxZynq(XilinxZynqInfo {
+                ps_cores: self.ps_cores.unwrap_or(4),
+            }),
+            Some("versal") => Specific::XilinxVersal(XilinxVersalInfo {}),
+            Some("intel") => Specific::Intel,
+            Some("microchip") => Specific::Microchip,
+            Some(other) => return Err(Error::InvalidInput(format!("unknown family {other:?}"))),
+        };
+        if self.readback.is_some()
+            && !matches!(
+                specific,
+                Specific::Xilinx32(_) | Specific::Xilinx16(_) | Specific::XilinxVirtex(_)
+            )
+        {
+            return Err(Error::InvalidInput(
+                "readback is only used for xilinx 32-bit families, spartan-6, and virtex".into(),
+            ));
+        }
+        if self.rows.is_some() && !matches!(specific, Specific::Xilinx32(_)) {
+            return Err(Error::InvalidInput(
+                "rows is only used for xilinx 32-bit families".into(),
+            ));
+        }
+        if self.ps_cores.is_some()
+            && !matches!(specific, Specific::Xilinx32(_) | Specific::XilinxZynq(_))
+        {
+            return Err(Error::InvalidInput(
+                "ps_cores is only used for xilinx 32-bit families and zynq".into(),
+            ));
+        }
+
+        let info = DeviceInfo {
+            irlen: Bits(self.irlen),
+            name: Cow::Owned(self.name),
+            specific,
+        };
+        Ok((id(idcode), info))
+    }
+}
+
+static REGISTERED: Mutex<Vec<(IdCode, DeviceInfo)>> = Mutex::new(Vec::new());
+
+/// Add devices to the ones returned by [`all`], i.e. from a crate supporting
+/// another vendor. When collected, later registrations replace earlier ones,
+/// and builtin ones, with the same idcode.
+pub fn register(devices: impl IntoIterator<Item = (IdCode, DeviceInfo)>) {
+    let mut registered = REGISTERED.lock().unwrap_or_else(PoisonError::into_inner);
+    registered.extend(devices);
+}
+
+/// [`builtin`] devices, followed by every [registered](register) one.
+pub fn all() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
+    let registered = REGISTERED.lock().unwrap_or_else(PoisonError::into_inner);
+    builtin().chain(registered.clone())
+}
+
+/// Idcodes of the devices `part` is for, with `part` as in the header of a
+/// Vivado `.bit`, i.e. `7a35tcpg236` or `xcku040-ffva1156-2-e`.
+///
+/// That's every device with the longest name `part` starts with, ignoring the
+/// `xc` prefix and the `i` or `_CIV` suffix some names have. Empty if no
+/// device matches.
+pub fn for_part(part: &str) -> Vec<IdCode> {
+    fn base(name: &str) -> &str {
+        let name = name.strip_prefix("xc").unwrap_or(name);
+        let name = name.strip_suffix("_CIV").unwrap_or(name);
+        name.strip_suffix('i').unwrap_or(name)
+    }
+    let part = part.to_ascii_lowercase();
+    let part = part.strip_prefix("xc").unwrap_or(&part);
+    let devices: Vec<_> = all()
+        .filter(|(_, info)| !base(&info.name).is_empty() && part.starts_with(base(&info.name)))
+        .collect();
+    let longest = devices.iter().map(|(_, info)| base(&info.name).len()).max();
+    (devices.into_iter())
+        .filter(|(_, info)| Some(base(&info.name).len()) == longest)
+        .map(|(idcode, _)| idcode)
+        .collect()
+}
+
 const fn id(code: u32) -> IdCode {
     IdCode::new(code)
 }
@@ -94,7 +501,7 @@ fn intel() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
     const fn info(idcode: u32, irlen: u8, name: &'static str) -> (IdCode, DeviceInfo) {
         let info = DeviceInfo {
             irlen: Bits(irlen),
-            name,
+            name: Cow::Borrowed(name),
             specific: Specific::Intel,
         };
         (id(idcode), info)
@@ -116,12 +523,55 @@ fn microchip() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
     #[rustfmt::skip]
     static DEVICES: &[(IdCode, DeviceInfo)] = &[
         // polarfire (8 IR_Len)
-        (id(0x5F8131CF), DeviceInfo { irlen: B(8), name: "MPF300T", specific: S::Microchip }),
+        (id(0x5F8131CF), DeviceInfo { irlen: B(8), name: Cow::Borrowed("MPF300T"), specific: S::Microchip }),
     ];
 
     DEVICES.iter().cloned()
 }
 
+const fn starts_with(name: &str, prefix: &str) -> bool {
+    let (name, prefix) = (name.as_bytes(), prefix.as_bytes());
+    if name.len() < prefix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < prefix.len() {
+        if name[i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn ends_with(name: &str, suffix: &str) -> bool {
+    let (name, suffix) = (name.as_bytes(), suffix.as_bytes());
+    if name.len() < suffix.len() {
+        return false;
+    }
+    let offset = name.len() - suffix.len();
+    let mut i = 0;
+    while i < suffix.len() {
+        if name[offset + i] != suffix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Single-core Zynq-7000 parts end in `s` (DS190), dual-core Zynq
+/// UltraScale+ parts in `cg` (DS891).
+const fn ps_cores(name: &str) -> Option<u8> {
+    if starts_with(name, "xc7z") {
+        Some(if ends_with(name, "s") { 1 } else { 2 })
+    } else if starts_with(name, "xczu") {
+        Some(if ends_with(name, "cg") { 2 } else { 4 })
+    } else {
+        None
+    }
+}
+
 fn xilinx() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
     use Xilinx32Family as F;
 
@@ -147,10 +597,12 @@ fn xilinx() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
                 Some(r) => Some(Words32(r)),
                 None => None,
             },
+            ps_cores: ps_cores(name),
+            layout: None,
         });
         let info = DeviceInfo {
             irlen: Bits(irlen),
-            name,
+            name: Cow::Borrowed(name),
             specific,
         };
         assert!(irlen.is_multiple_of(6));
@@ -307,12 +759,157 @@ fn xilinx() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
     DEVICES.iter().cloned()
 }
 
+fn xilinx16() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
+    /// `bits` is the bitstream length from UG380 table 5-5, which covers
+    /// every frame.
+    const fn info(idcode: u32, name: &'static str, bits: usize) -> (IdCode, DeviceInfo) {
+        let specific = Specific::Xilinx16(Xilinx16Info {
+            readback: Some(bits / 16),
+        });
+        let info = DeviceInfo {
+            irlen: Bits(6),
+            name: Cow::Borrowed(name),
+            specific,
+        };
+        (id(idcode), info)
+    }
+
+    static DEVICES: &[(IdCode, DeviceInfo)] = &[
+        info(0x4000093, "xc6slx4", 2724832),
+        info(0x4001093, "xc6slx9", 2724832),
+        info(0x4002093, "xc6slx16", 3713568),
+        info(0x4004093, "xc6slx25", 6440432),
+        info(0x4008093, "xc6slx45", 11939296),
+        info(0x400e093, "xc6slx75", 19719712),
+        info(0x4011093, "xc6slx100", 26691232),
+        info(0x401d093, "xc6slx150", 33909664),
+        info(0x4024093, "xc6slx25t", 6440432),
+        info(0x4028093, "xc6slx45t", 11939296),
+        info(0x402e093, "xc6slx75t", 19719712),
+        info(0x4031093, "xc6slx100t", 26691232),
+        info(0x403d093, "xc6slx150t", 33909664),
+    ];
+
+    DEVICES.iter().cloned()
+}
+
+fn xilinx_virtex() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
+    use VirtexFamily as F;
+
+    /// `bits` is the bitstream length from the configuration user guide
+    /// (UG071, UG191, UG360), where known.
+    const fn info(
+        idcode: u32,
+        irlen: u8,
+        name: &'static str,
+        family: F,
+        bits: Option<usize>,
+    ) -> (IdCode, DeviceInfo) {
+        let specific = Specific::XilinxVirtex(XilinxVirtexInfo {
+            family,
+            readback: match bits {
+                Some(bits) => Some(Words32(bits / 32)),
+                None => None,
+            },
+        });
+        let info = DeviceInfo {
+            irlen: Bits(irlen),
+            name: Cow::Borrowed(name),
+            specific,
+        };
+        (id(idcode), info)
+    }
+
+    static DEVICES: &[(IdCode, DeviceInfo)] = &[
+        info(0x1658093, 10, "xc4vlx15", F::V4, None),
+        info(0x167c093, 10, "xc4vlx25", F::V4, None),
+        info(0x16a4093, 10, "xc4vlx40", F::V4, None),
+        info(0x16b4093, 10, "xc4vlx60", F::V4, None),
+        info(0x16d8093, 10, "xc4vlx80", F::V4, None),
+        info(0x1700093, 10, "xc4vlx100", F::V4, None),
+        info(0x1718093, 10, "xc4vlx160", F::V4, None),
+        info(0x1e58093, 10, "xc4vfx12", F::V4, None),
+        info(0x1e64093, 14, "xc4vfx20", F::V4, None),
+        info(0x2088093, 10, "xc4vsx35", F::V4, None),
+        info(0x286e093, 10, "xc5vlx30", F::V5, None),
+        info(0x2896093, 10, "xc5vlx50", F::V5, None),
+        info(0x28d6093, 10, "xc5vlx110", F::V5, None),
+        info(0x2a96093, 10, "xc5vlx50t", F::V5, Some(14052352)),
+        info(0x2ad6093, 10, "xc5vlx110t", F::V5, Some(31118848)),
+        info(0x4244093, 10, "xc6vlx75t", F::V6, None),
+        info(0x424a093, 10, "xc6vlx130t", F::V6, None),
+        info(0x4250093, 10, "xc6vlx240t", F::V6, Some(73523008)),
+        info(0x4252093, 10, "xc6vlx365t", F::V6, None),
+        info(0x4256093, 10, "xc6vlx550t", F::V6, None),
+    ];
+
+    DEVICES.iter().cloned()
+}
+
+fn xilinx_cpld() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
+    const fn info(idcode: u32, name: &'static str, function_blocks: u8) -> (IdCode, DeviceInfo) {
+        let specific = Specific::XilinxCpld(XilinxCpldInfo { function_blocks });
+        let info = DeviceInfo {
+            irlen: Bits(8),
+            name: Cow::Borrowed(name),
+            specific,
+        };
+        (id(idcode), info)
+    }
+
+    static DEVICES: &[(IdCode, DeviceInfo)] = &[
+        info(0x9602093, "xc9536xl", 2),
+        info(0x9604093, "xc9572xl", 4),
+        info(0x9608093, "xc95144xl", 8),
+        info(0x9616093, "xc95288xl", 16),
+    ];
+
+    DEVICES.iter().cloned()
+}
+
+fn xilinx_prom() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
+    const fn info(
+        idcode: u32,
+        name: &'static str,
+        family: XilinxPromFamily,
+        kbit: usize,
+    ) -> (IdCode, DeviceInfo) {
+        let specific = Specific::XilinxProm(XilinxPromInfo {
+            size: Bytes(kbit << 7),
+            family,
+        });
+        let info = DeviceInfo {
+            irlen: Bits(8),
+            name: Cow::Borrowed(name),
+            specific,
+        };
+        (id(idcode), info)
+    }
+
+    static DEVICES: &[(IdCode, DeviceInfo)] = &[
+        info(0x5044093, "xcf01s", XilinxPromFamily::XcfS, 1024),
+        info(0x5045093, "xcf02s", XilinxPromFamily::XcfS, 2048),
+        info(0x5046093, "xcf04s", XilinxPromFamily::XcfS, 4096),
+        info(0x5023093, "xc18v512", XilinxPromFamily::Xc18v, 512),
+        info(0x5024093, "xc18v01", XilinxPromFamily::Xc18v, 1024),
+        info(0x5025093, "xc18v02", XilinxPromFamily::Xc18v, 2048),
+        info(0x5026093, "xc18v04", XilinxPromFamily::Xc18v, 4096),
+    ];
+
+    DEVICES.iter().cloned()
+}
+
 fn xilinx_zynq() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
     const fn info(idcode: u32, irlen: u8, name: &'static str) -> (IdCode, DeviceInfo) {
-        let specific = Specific::XilinxZynq(XilinxZynqInfo {});
+        let specific = Specific::XilinxZynq(XilinxZynqInfo {
+            ps_cores: match ps_cores(name) {
+                Some(cores) => cores,
+                None => 4,
+            },
+        });
         let info = DeviceInfo {
             irlen: Bits(irlen),
-            name,
+            name: Cow::Borrowed(name),
             specific,
         };
         assert!(irlen.is_multiple_of(6));
@@ -321,7 +918,7 @@ fn xilinx_zynq() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
     const fn unknown(idcode: u32, irlen: u8, name: &'static str) -> (IdCode, DeviceInfo) {
         let info = DeviceInfo {
             irlen: Bits(irlen),
-            name,
+            name: Cow::Borrowed(name),
             specific: Specific::Unknown,
         };
         (id(idcode), info)
@@ -368,7 +965,7 @@ fn xilinx_versal() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
         let specific = Specific::XilinxVersal(XilinxVersalInfo {});
         let info = DeviceInfo {
             irlen: Bits(irlen),
-            name,
+            name: Cow::Borrowed(name),
             specific,
         };
         assert!(irlen.is_multiple_of(6));
@@ -427,3 +1024,94 @@ fn xilinx_versal() -> impl Iterator<Item = (IdCode, DeviceInfo)> {
 
     DEVICES.iter().cloned()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        let toml = r#"
+            [[device]]
+            idcode = "0x0362d093"
+            irlen = 6
+            name = "custom"
+            family = "s7"
+            readback = 1234
+
+            [[device]]
+            idcode = "1"
+            irlen = 4
+            name = "other"
+        "#;
+        let json = r#"{"device": [
+            {"idcode": "0x0362d093", "irlen": 6, "name": "custom", "family": "s7", "readback": 1234},
+            {"idcode": "1", "irlen": 4, "name": "other"}
+        ]}"#;
+        for data in [toml, json] {
+            let devices = from_str(data).unwrap();
+            let [(a, a_info), (b, b_info)] = &devices[..] else {
+                panic!("expected two devices");
+            };
+            assert_eq!((a.code(), &*a_info.name), (0x0362d093, "custom"));
+            let Specific::Xilinx32(x) = &a_info.specific else {
+                panic!("expected xilinx32");
+            };
+            assert_eq!(x.readback, Some(Words32(1234)));
+            assert_eq!((b.code(), b_info.irlen), (1, Bits(4)));
+        }
+
+        assert!(
+            from_str("[[device]]\nidcode = \"1\"\nirlen = 5\nname = \"x\"\nfamily = \"us\"")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_frame_layout() {
+        let device = |rows| {
+            let toml = format!(
+                "[[device]]\nidcode = \"1\"\nirlen = 18\nname = \"x\"\nfamily = \"up\"\nrows = \
+                 {rows}"
+            );
+            from_str(&toml).map(|devices| devices[0].1.specific.clone())
+        };
+        let Ok(Specific::Xilinx32(info)) = device("[[10, 4], [10, 4], [6]]") else {
+            panic!("expected xilinx32");
+        };
+        let layout = info.layout.unwrap();
+        assert_eq!(layout.slrs[1][..], [10, 4]);
+        assert_eq!(layout.readback_frames(0), Some(1 + 12 + 6));
+        assert_eq!(layout.readback_frames(2), Some(1 + 8));
+        assert_eq!(layout.readback_frames(3), None);
+        assert!(device("[[10, 4]]").is_err());
+    }
+
+    #[test]
+    fn test_for_part() {
+        let codes = |part| for_part(part).iter().map(|c| c.code()).collect::<Vec<_>>();
+        assert_eq!(codes("7a35tcpg236"), [0x362d093]);
+        assert_eq!(codes("7a100tcsg324"), [0x3631093]);
+        assert_eq!(codes("7vx415tffg1157").len(), 2);
+        assert_eq!(codes("xc7z020clg400"), [0x3727093]);
+        assert!(codes("notapart").is_empty());
+    }
+
+    #[test]
+    fn test_ps_cores() {
+        let cores = |name: &str| {
+            let (_, info) = builtin().find(|(_, info)| info.name == name).unwrap();
+            let Specific::Xilinx32(x) = info.specific else {
+                panic!("expected xilinx32");
+            };
+            x.ps_cores
+        };
+        assert_eq!(cores("xc7a35ti"), None);
+        assert_eq!(cores("xc7z007s"), Some(1));
+        assert_eq!(cores("xc7z020i"), Some(2));
+        assert_eq!(cores("xczu3cg"), Some(2));
+        assert_eq!(cores("xczu3tcg"), Some(2));
+        assert_eq!(cores("xczu17eg"), Some(4));
+        assert_eq!(cores("xczu28dr"), Some(4));
+    }
+}
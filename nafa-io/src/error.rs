@@ -0,0 +1,132 @@
+//! The error type for everything in this crate.
+
+use std::fmt::Display;
+
+use crate::hotplug::ConnectionError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// No plugged-in device matches, or the cable is not one we know.
+    #[error("{0}")]
+    CableNotFound(String),
+    /// The cable or backend can't do what was asked, i.e. changing the clock
+    /// on a cable with fixed clocking.
+    #[error("{0}")]
+    Unsupported(String),
+    /// Opening or claiming the USB device failed.
+    #[error(transparent)]
+    Usb(#[from] nusb::Error),
+    #[error(transparent)]
+    Transfer(#[from] nusb::transfer::TransferError),
+    /// Includes USB timeouts, see [`Error::is_timeout`].
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The cable returned less data than was clocked.
+    #[error("failed to fill buffer: read {read} bytes, expected {expected}")]
+    ShortRead { read: usize, expected: usize },
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+    /// Nothing answered on the JTAG chain.
+    #[error("no devices on jtag chain")]
+    NoDevices,
+    /// An IDCODE that's not in the device list, so the chain can't be laid
+    /// out. `details` has the decoded IDCODE and the chain so far.
+    #[error("idcode {idcode:08X} not found in device list, cannot determine irlen\n{details}")]
+    UnknownDevice { idcode: u32, details: String },
+    /// The ARM DAP of a Zynq US+ stayed in BYPASS after being enabled. It
+    /// stays disabled while the PS boots with JTAG security enabled, or is held
+    /// in reset.
+    #[error(
+        "arm dap still in bypass after zynq us special case, tried {attempts} times (PS \
+         JTAG_STATUS {jtag_status}), check the PS boot mode and eFUSE JTAG disable bits"
+    )]
+    DapBypass {
+        attempts: u32,
+        jtag_status: crate::controller::ZynqUsJtagStatus,
+    },
+    /// The chain could be read, but doesn't make sense, i.e. an ambiguous IR
+    /// capture.
+    #[error("{0}")]
+    Chain(String),
+    /// The cable or a device answered with something unexpected.
+    #[error("{0}")]
+    Protocol(String),
+    /// A bad argument, or input that failed to parse.
+    #[error("{0}")]
+    InvalidInput(String),
+    /// A [`Controller`](crate::Controller) operation ran into one of its
+    /// [`Timeouts`](crate::Timeouts), i.e. because the cable
+    /// stopped answering or the target is wedged. The TAP and the backend are
+    /// in an unknown state afterwards.
+    #[error("{what} did not finish within {after:?}, the cable or target may be stuck")]
+    Timeout {
+        what: &'static str,
+        after: std::time::Duration,
+    },
+    /// The [`CancellationToken`](crate::CancellationToken) of the controller
+    /// was cancelled. The TAP was reset to Test-Logic-Reset.
+    #[error("cancelled")]
+    Cancelled,
+    /// `source`, with what was being done at the time.
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Whether this is a USB transfer or [`Error::Timeout`] that timed out,
+    /// possibly wrapped in [`Error::Context`].
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            Self::Io(e) => e.kind() == std::io::ErrorKind::TimedOut,
+            Self::Timeout { .. } => true,
+            Self::Context { source, .. } => source.is_timeout(),
+            _ => false,
+        }
+    }
+
+    /// The innermost error, under any [`Error::Context`].
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            e => e,
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(e: std::num::ParseIntError) -> Self {
+        Self::InvalidInput(e.to_string())
+    }
+}
+
+impl From<bitreader::BitReaderError> for Error {
+    fn from(e: bitreader::BitReaderError) -> Self {
+        Self::Protocol(e.to_string())
+    }
+}
+
+/// Like `eyre::WrapErr`, for this crate's [`Error`].
+pub(crate) trait Context<T> {
+    fn context(self, context: impl Display) -> Result<T>;
+    fn with_context<D: Display>(self, f: impl FnOnce() -> D) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<D: Display>(self, f: impl FnOnce() -> D) -> Result<T> {
+        self.map_err(|e| Error::Context {
+            context: f().to_string(),
+            source: Box::new(e.into()),
+        })
+    }
+}
@@ -0,0 +1,648 @@
+//! A backend without a cable, emulating a chain of TAPs clock by clock. For
+//! testing code that drives a target, i.e. [`Controller::run`] sequencing and
+//! register reads.
+//!
+//! Each [`FakeDevice`] has an IR, selects IDCODE (or BYPASS, without an
+//! IDCODE) on reset, and serves a DR per instruction. Instructions without a
+//! register select BYPASS.
+//!
+//! [`Controller::run`]: crate::Controller::run
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use crate::{
+    Backend, Buffer, Error, Result,
+    backend::{Data, Frequency},
+    jtag::{self, GRAPH, State},
+    units::{Bits, Bytes},
+};
+
+/// A DR selected by an instruction.
+#[derive(Clone, Debug)]
+struct Register {
+    /// LSB (first shifted out) first.
+    value: Vec<bool>,
+    /// Whether Update-DR stores what was shifted in.
+    writable: bool,
+}
+
+impl Register {
+    fn new(value: &[u8], len: Bits<usize>, writable: bool) -> Self {
+        Self {
+            value: (0..len.0).map(|idx| bit(value, idx)).collect(),
+            writable,
+        }
+    }
+}
+
+static BYPASS: Register = Register {
+    value: Vec::new(),
+    writable: false,
+};
+
+/// One TAP on the chain of a [`FakeBackend`].
+#[derive(Clone, Debug)]
+pub struct FakeDevice {
+    irlen: Bits<u8>,
+    /// Instruction selected on reset.
+    reset_ir: u32,
+    /// Value loaded into IR on Capture-IR.
+    ir_capture: u32,
+    registers: HashMap<u32, Register>,
+
+    ir: u32,
+    ir_shift: VecDeque<bool>,
+    dr_shift: VecDeque<bool>,
+}
+
+impl FakeDevice {
+    /// A device with `idcode`, read with instruction `idcode_ir`.
+    pub fn new(idcode: u32, irlen: Bits<u8>, idcode_ir: u32) -> Self {
+        let mut slf = Self::bypass(irlen);
+        let idcode = Register::new(&idcode.to_le_bytes(), Bits(32), false);
+        slf.registers.insert(idcode_ir, idcode);
+        slf.reset_ir = idcode_ir;
+        slf.ir = idcode_ir;
+        slf
+    }
+
+    /// A device without an IDCODE, selecting BYPASS on reset.
+    pub fn bypass(irlen: Bits<u8>) -> Self {
+        assert!((1..=32).contains(&irlen.0), "irlen must be 1 to 32 bits");
+        let ones = u32::MAX >> (32 - irlen.0);
+        Self {
+            irlen,
+            reset_ir: ones,
+            ir_capture: 0b01,
+            registers: HashMap::new(),
+            ir: ones,
+            ir_shift: VecDeque::new(),
+            dr_shift: VecDeque::new(),
+        }
+    }
+
+    /// Load `value` into IR on Capture-IR, instead of `0b01`.
+    pub fn ir_capture(mut self, value: u32) -> Self {
+        self.ir_capture = value;
+        self
+    }
+
+    /// Add a writable register of `len` bits for instruction `ir`, starting
+    /// out as `value` (LSB of the first byte shifted out first).
+    pub fn register(mut self, ir: u32, len: Bits<usize>, value: &[u8]) -> Self {
+        self.registers.insert(ir, Register::new(value, len, true));
+        self
+    }
+
+    /// Add a register for instruction `ir` that always reads `value`, whatever
+    /// is shifted through it, i.e. a status register.
+    pub fn constant(mut self, ir: u32, len: Bits<usize>, value: &[u8]) -> Self {
+        self.registers.insert(ir, Register::new(value, len, false));
+        self
+    }
+
+    /// Contents of the register for instruction `ir`, in the same format as
+    /// given to [`FakeDevice::register`].
+    pub fn register_value(&self, ir: u32) -> Option<Vec<u8>> {
+        let register = self.registers.get(&ir)?;
+        let mut ret = vec![0; register.value.len().div_ceil(8)];
+        for (idx, bit) in register.value.iter().enumerate() {
+            ret[idx / 8] |= u8::from(*bit) << (idx % 8);
+        }
+        Some(ret)
+    }
+
+    fn selected(&self) -> &Register {
+        self.registers.get(&self.ir).unwrap_or(&BYPASS)
+    }
+
+    /// Clock once in `state`, returning TDO while shifting.
+    fn clock(&mut self, state: State, tdi: bool) -> Option<bool> {
+        match state {
+            State::TestLogicReset => self.ir = self.reset_ir,
+            State::CaptureIR => {
+                let len = self.irlen.0.into();
+                let capture = self.ir_capture.to_le_bytes();
+                self.ir_shift = (0..len).map(|idx| bit(&capture, idx)).collect();
+            }
+            State::UpdateIR => {
+                let ir = self.ir_shift.iter().rev();
+                self.ir = ir.fold(0, |acc, bit| acc << 1 | u32::from(*bit));
+            }
+            State::CaptureDR => {
+                self.dr_shift = match self.selected().value[..] {
+                    [] => VecDeque::from([false]),
+                    ref value => value.iter().copied().collect(),
+                };
+            }
+            State::UpdateDR => {
+                let shifted: Vec<_> = self.dr_shift.iter().copied().collect();
+                if let Some(register) = self.registers.get_mut(&self.ir)
+                    && register.writable
+                {
+                    register.value = shifted;
+                }
+            }
+            State::ShiftIR => return Some(shift(&mut self.ir_shift, tdi)),
+            State::ShiftDR => return Some(shift(&mut self.dr_shift, tdi)),
+            _ => (),
+        }
+        None
+    }
+}
+
+fn shift(register: &mut VecDeque<bool>, tdi: bool) -> bool {
+    register.push_back(tdi);
+    register.pop_front().unwrap_or(tdi)
+}
+
+fn bit(data: &[u8], idx: usize) -> bool {
+    data.get(idx / 8).is_some_and(|b| b >> (idx % 8) & 1 == 1)
+}
+
+/// Emulates a chain of [`FakeDevice`]s, the first one closest to TDO (the
+/// order [`detect_chain`](crate::detect_chain) returns them in).
+///
+/// Starts out in Test-Logic-Reset. TDO is high outside of Shift-IR and
+/// Shift-DR.
+pub struct FakeBackend {
+    devices: Vec<FakeDevice>,
+    state: State,
+    /// TDO of reads since the last flush.
+    read: Vec<u8>,
+    frequency: Option<Frequency>,
+}
+
+impl FakeBackend {
+    pub fn new(devices: Vec<FakeDevice>) -> Self {
+        Self {
+            devices,
+            state: State::TestLogicReset,
+            read: Vec::new(),
+            frequency: None,
+        }
+    }
+
+    /// Pretend TCK can be changed, starting at `frequency`.
+    pub fn with_frequency(mut self, frequency: Frequency) -> Self {
+        self.frequency = Some(frequency);
+        self
+    }
+
+    pub fn devices(&self) -> &[FakeDevice] {
+        &self.devices
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    fn clock(&mut self, tms: bool, tdi: bool) -> bool {
+        let mut carry = tdi;
+        let mut shifting = false;
+        for device in self.devices.iter_mut().rev() {
+            if let Some(tdo) = device.clock(self.state, carry) {
+                carry = tdo;
+                shifting = true;
+            }
+        }
+        self.state = GRAPH[self.state][tms];
+        !shifting || carry
+    }
+
+    fn path(&mut self, path: jtag::Path) {
+        for tms in path {
+            self.clock(tms, true);
+        }
+    }
+
+    /// Shift `len` bits of `tdi`, reading TDO into [`Self::read`] if `read`.
+    fn shift(
+        &mut self,
+        before: Option<jtag::Path>,
+        tdi: impl Fn(usize) -> bool,
+        len: usize,
+        after: Option<jtag::Path>,
+        read: bool,
+    ) {
+        if let Some(path) = before {
+            self.path(path);
+        }
+        let mut after = after.map(|p| p.into_iter());
+        let mut tdo = vec![0; len.div_ceil(8)];
+        for idx in 0..len {
+            // the last bit is shifted on the first clock of the path out
+            let tms = match &mut after {
+                Some(path) if idx + 1 == len => path.next().unwrap_or(false),
+                _ => false,
+            };
+            tdo[idx / 8] |= u8::from(self.clock(tms, tdi(idx))) << (idx % 8);
+        }
+        for tms in after.into_iter().flatten() {
+            self.clock(tms, true);
+        }
+        if read {
+            self.read.extend(tdo);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for FakeBackend {
+    async fn tms(&mut self, _buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
+        self.path(path);
+        Ok(())
+    }
+
+    async fn bytes(
+        &mut self,
+        _buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: Data<'_>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let len = Bits::from(Bytes(data.len())).0;
+        match data {
+            Data::Tx(tdi) => self.shift(before, |idx| bit(tdi, idx), len, after, false),
+            Data::TxRx(tdi) => self.shift(before, |idx| bit(tdi, idx), len, after, true),
+            Data::Rx(_) => self.shift(before, |_| true, len, after, true),
+            Data::ConstantTx(tdi, _) => self.shift(before, |_| tdi, len, after, false),
+        }
+        Ok(())
+    }
+
+    async fn bits(
+        &mut self,
+        _buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let tdi = |idx| data >> idx & 1 == 1;
+        self.shift(before, tdi, len.0.into(), after, false);
+        Ok(())
+    }
+
+    async fn tdo_bits(
+        &mut self,
+        _buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let tdi = |idx| data >> idx & 1 == 1;
+        self.shift(before, tdi, len.0.into(), after, true);
+        Ok(())
+    }
+
+    async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
+        buf.extend(self.read.len(), 0).copy_from_slice(&self.read);
+        self.read.clear();
+        Ok(())
+    }
+
+    async fn wait(&mut self, buf: &mut dyn Buffer, _duration: Duration) -> Result<()> {
+        self.flush(buf).await
+    }
+
+    fn frequency(&self) -> Option<Frequency> {
+        self.frequency
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<u32> {
+        let Some(frequency) = &mut self.frequency else {
+            return Err(Error::Unsupported("no frequency set".into()));
+        };
+        frequency.hz = hz;
+        Ok(hz)
+    }
+}
+
+/// A [`Controller`](crate::Controller) on a chain of `devices`, with the last
+/// one active, for tests.
+pub async fn controller(devices: Vec<FakeDevice>) -> Result<crate::Controller> {
+    controller_with(FakeBackend::new(devices)).await
+}
+
+/// [`controller`], on a [`FakeBackend`] that was set up further, or wrapped.
+pub async fn controller_with(backend: impl Backend + 'static) -> Result<crate::Controller> {
+    let mut backend: Box<dyn Backend> = Box::new(backend);
+    let devices = crate::devices::all().collect();
+    let chain = crate::detect_chain(&mut backend, &devices).await?;
+    let (before, active, after) = (chain.len().checked_sub(1))
+        .and_then(|last| chain.split(last))
+        .ok_or(Error::NoDevices)?;
+    crate::Controller::new(backend, before, active, after).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::{CancellationToken, Command, Error, Timeouts, devices};
+
+    const XC7A35T: u32 = 0x0362_d093;
+    const ARM_DAP: u32 = 0x4ba0_0477;
+
+    fn chain() -> FakeBackend {
+        FakeBackend::new(vec![
+            FakeDevice::new(ARM_DAP, Bits(4), 0b1110),
+            FakeDevice::new(XC7A35T, Bits(6), 0b001001).register(0b000010, Bits(32), &[0; 4]),
+        ])
+    }
+
+    #[test]
+    fn test_detect_chain() {
+        smol::block_on(async {
+            let mut backend = chain();
+            let devices = devices::all().collect();
+            let chain = crate::detect_chain(&mut backend, &devices).await.unwrap();
+            let idcodes: Vec<_> = chain.iter().map(|tap| tap.idcode).collect();
+            assert_eq!(idcodes, [ARM_DAP, XC7A35T]);
+            let captures: Vec<_> = chain.iter().map(|tap| tap.ir_capture).collect();
+            assert_eq!(captures, [Some(0b01), Some(0b01)]);
+            assert_eq!(chain.default_target(), Some(1));
+        });
+    }
+
+    #[test]
+    fn test_controller() {
+        smol::block_on(async {
+            let mut cont = controller_with(chain()).await.unwrap();
+
+            let idcode = cont
+                .run([Command::ir(0b001001), Command::dr_rx(Bytes(4))])
+                .await
+                .unwrap();
+            assert_eq!(idcode, XC7A35T.to_le_bytes());
+
+            let value = 0x1234_5678_u32.to_le_bytes();
+            cont.run([Command::ir(0b000010), Command::dr_tx(&value)])
+                .await
+                .unwrap();
+            let read = cont
+                .run([Command::ir(0b000010), Command::dr_rx(Bytes(4))])
+                .await
+                .unwrap();
+            assert_eq!(read, value);
+        });
+    }
+
+    #[test]
+    fn test_clamp_frequency() {
+        smol::block_on(async {
+            for (forced, expected) in [(false, 66_000_000), (true, 100_000_000)] {
+                let backend = chain().with_frequency(Frequency {
+                    hz: 100_000_000,
+                    forced,
+                });
+                let mut cont = controller_with(backend).await.unwrap();
+                let frequency = (cont
+                    .with_raw_backend(async |backend, _| Ok::<_, Error>(backend.frequency())))
+                .await
+                .unwrap();
+                assert_eq!(frequency.map(|f| f.hz), Some(expected));
+            }
+        });
+    }
+
+    #[test]
+    fn test_run_reads() {
+        smol::block_on(async {
+            let mut cont = controller_with(chain()).await.unwrap();
+
+            let value = [0x12, 0x34, 0x56, 0x78];
+            let reads = cont
+                .run_reads([
+                    Command::ir(0b001001),
+                    Command::dr_rx(Bytes(4)),
+                    Command::ir(0b000010),
+                    Command::dr_txrx(&value),
+                    Command::dr_rx_bits(Bits(4)),
+                ])
+                .await
+                .unwrap();
+            assert_eq!(reads.len(), 3);
+            assert_eq!(&reads[0], XC7A35T.to_le_bytes());
+            assert_eq!(&reads[1], [0; 4]);
+            assert_eq!(&reads[2], [0x2]);
+        });
+    }
+
+    #[test]
+    fn test_cancel() {
+        smol::block_on(async {
+            let mut cont = controller_with(chain()).await.unwrap();
+
+            let token = CancellationToken::new();
+            cont.set_cancellation(Some(token.clone()));
+            token.cancel();
+            let err = cont.run([Command::ir(0b001001)]).await.unwrap_err();
+            assert!(matches!(err, Error::Cancelled), "{err}");
+
+            cont.set_cancellation(None);
+            cont.reset().await.unwrap();
+            let idcode = cont
+                .run([Command::ir(0b001001), Command::dr_rx(Bytes(4))])
+                .await
+                .unwrap();
+            assert_eq!(idcode, XC7A35T.to_le_bytes());
+        });
+    }
+
+    #[test]
+    fn test_raw_shift() {
+        smol::block_on(async {
+            let mut cont = controller_with(chain()).await.unwrap();
+
+            let captured = cont.shift_ir(&[0b000010], State::PauseIR).await.unwrap();
+            assert_eq!(captured, [0b01]);
+            assert_eq!(cont.state(), State::PauseIR);
+
+            // one register in two pieces, holding in Pause-DR in between
+            let value = 0x1234_5678_u32.to_le_bytes();
+            let old = (cont.shift_dr(&value[..2], Bits(16), State::PauseDR))
+                .await
+                .unwrap();
+            assert_eq!(old, [0, 0]);
+            (cont.shift_dr(&value[2..], Bits(16), State::RunTestIdle))
+                .await
+                .unwrap();
+
+            cont.goto(State::PauseDR).await.unwrap();
+            let read = cont
+                .run([Command::ir(0b000010), Command::dr_rx(Bytes(4))])
+                .await
+                .unwrap();
+            assert_eq!(read, value);
+            assert_eq!(cont.state(), State::RunTestIdle);
+        });
+    }
+
+    /// Once set, never finishes a flush, like a cable waiting on a stuck
+    /// target.
+    struct Stuck(FakeBackend, Arc<AtomicBool>);
+
+    #[async_trait::async_trait]
+    impl Backend for Stuck {
+        async fn tms(&mut self, buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
+            self.0.tms(buf, path).await
+        }
+
+        async fn bytes(
+            &mut self,
+            buf: &mut dyn Buffer,
+            before: Option<jtag::Path>,
+            data: Data<'_>,
+            after: Option<jtag::Path>,
+        ) -> Result<()> {
+            self.0.bytes(buf, before, data, after).await
+        }
+
+        async fn bits(
+            &mut self,
+            buf: &mut dyn Buffer,
+            before: Option<jtag::Path>,
+            data: u32,
+            len: Bits<u8>,
+            after: Option<jtag::Path>,
+        ) -> Result<()> {
+            self.0.bits(buf, before, data, len, after).await
+        }
+
+        async fn tdo_bits(
+            &mut self,
+            buf: &mut dyn Buffer,
+            before: Option<jtag::Path>,
+            data: u32,
+            len: Bits<u8>,
+            after: Option<jtag::Path>,
+        ) -> Result<()> {
+            self.0.tdo_bits(buf, before, data, len, after).await
+        }
+
+        async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
+            if self.1.load(Ordering::Relaxed) {
+                smol::future::pending().await
+            }
+            self.0.flush(buf).await
+        }
+    }
+
+    /// Flushes on its own while queueing any long enough shift, like a cable
+    /// with a small command buffer. Templates are empty.
+    struct EarlyFlush(FakeBackend, usize);
+
+    #[async_trait::async_trait]
+    impl Backend for EarlyFlush {
+        async fn tms(&mut self, buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
+            self.0.tms(buf, path).await
+        }
+
+        async fn bytes(
+            &mut self,
+            buf: &mut dyn Buffer,
+            before: Option<jtag::Path>,
+            data: Data<'_>,
+            after: Option<jtag::Path>,
+        ) -> Result<()> {
+            let long = data.len() > 4;
+            self.0.bytes(buf, before, data, after).await?;
+            if long {
+                self.flush(buf).await?;
+            }
+            Ok(())
+        }
+
+        async fn bits(
+            &mut self,
+            buf: &mut dyn Buffer,
+            before: Option<jtag::Path>,
+            data: u32,
+            len: Bits<u8>,
+            after: Option<jtag::Path>,
+        ) -> Result<()> {
+            self.0.bits(buf, before, data, len, after).await
+        }
+
+        async fn tdo_bits(
+            &mut self,
+            buf: &mut dyn Buffer,
+            before: Option<jtag::Path>,
+            data: u32,
+            len: Bits<u8>,
+            after: Option<jtag::Path>,
+        ) -> Result<()> {
+            self.0.tdo_bits(buf, before, data, len, after).await
+        }
+
+        async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
+            self.1 += 1;
+            self.0.flush(buf).await
+        }
+
+        fn supports_templates(&self) -> bool {
+            true
+        }
+
+        fn flushes(&self) -> usize {
+            self.1
+        }
+
+        fn take_template(&mut self) -> Result<crate::Template> {
+            Ok(crate::Template::new(()))
+        }
+    }
+
+    #[test]
+    fn test_prepare_flushed() {
+        smol::block_on(async {
+            let mut cont = controller_with(EarlyFlush(chain(), 0)).await.unwrap();
+
+            // a write only, so nothing was read that could give it away
+            let err = (cont.prepare([Command::ir(0b000010), Command::dr_tx(&[0; 8])]))
+                .await
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("flushed early"), "{err}");
+            (cont.prepare([Command::ir(0b000010), Command::dr_tx(&[0; 4])]))
+                .await
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn test_timeout() {
+        smol::block_on(async {
+            let stuck = Arc::new(AtomicBool::new(false));
+            let mut cont = controller_with(Stuck(chain(), stuck.clone()))
+                .await
+                .unwrap();
+
+            let flush = Duration::from_millis(10);
+            cont.set_timeouts(Timeouts {
+                run: None,
+                flush: Some(flush),
+            });
+            stuck.store(true, Ordering::Relaxed);
+            let err = cont
+                .run([Command::ir(0b001001), Command::dr_rx(Bytes(4))])
+                .await
+                .unwrap_err();
+            assert!(err.is_timeout(), "{err}");
+            assert!(
+                matches!(err, Error::Timeout { what: "flush", after } if after == flush),
+                "{err}"
+            );
+        });
+    }
+}
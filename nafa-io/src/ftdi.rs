@@ -1,44 +1,144 @@
-use eyre::Result;
+use std::{
+    ops::RangeInclusive,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
 use tracing::{debug, instrument};
 
 use crate::{
-    Backend, Buffer, ScratchBuffer,
-    backend::Data,
+    Backend, Buffer, Error, Result, ScratchBuffer, Template,
+    backend::{Data, Frequency},
+    cables::{self, Edge},
+    error::Context as _,
     jtag,
     units::{Bits, Bytes},
 };
 
 pub mod devices;
+pub mod eeprom;
 mod io;
 
+pub use io::Retry;
+
 pub struct Device {
     dev: io::Device,
+    retry: Retry,
+    /// [`WRITE_NEG`] / [`READ_NEG`], depending on [`cables::Options`].
+    write_neg: u8,
+    read_neg: u8,
     cmd_buf: Vec<u8>,
     reads: Vec<Read>,
+    frequency: Frequency,
+    three_phase: bool,
+    /// See [`Backend::flushes`].
+    flushes: usize,
+}
+
+/// A fully built command buffer, with [`MpsseCommand::SendImmediate`] already
+/// appended.
+struct FtdiTemplate {
+    cmd_buf: Vec<u8>,
+    reads: Vec<Read>,
+    read_len: usize,
+    scratch: usize,
 }
 
 #[derive(Clone, Copy, Debug)]
 enum Read {
     Bytes(usize),
+    /// A bit-mode read. The chip shifts bits in from the top, so the `n` valid
+    /// bits end up in the high bits of the byte.
+    Bits(u8),
+    /// The bit read while moving out of a shift state, belonging to the
+    /// previous [`Read::Bits`].
     ExtraBit,
 }
 
+/// Send `txdata` while reading back `rxdata`. `sent` is set once all of
+/// `txdata` was written.
+///
+/// Reading runs concurrently with writing, so TDO data is pulled out of the
+/// chip while later commands are still being sent. Otherwise, the chip's
+/// (small) output buffer fills up and it stops accepting commands until the
+/// write is done.
 #[instrument(skip_all)]
-async fn xfer(dev: &mut io::Device, txdata: &[u8], rxdata: &mut [u8]) -> Result<()> {
-    dev.send(txdata).await?;
-    dev.recv(rxdata).await?;
+async fn xfer(
+    dev: &mut io::Device,
+    txdata: &[u8],
+    rxdata: &mut [u8],
+    sent: &AtomicBool,
+) -> Result<()> {
+    dev.flush_rx().await?;
+    let send = async {
+        dev.send(txdata).await?;
+        sent.store(true, Ordering::Relaxed);
+        Ok(())
+    };
+    if rxdata.is_empty() {
+        return send.await;
+    }
+    futures_lite::future::try_zip(send, dev.recv(rxdata, sent)).await?;
     Ok(())
 }
 
+/// Like [`xfer`], but on a transient error while writing, recover the device
+/// and resend the whole command buffer.
+///
+/// Once the write finished, the chip has clocked out the commands, and
+/// sending them again would shift the TAP a second time. Errors after that,
+/// i.e. a short or timed out read, are returned right away.
+#[instrument(skip_all)]
+async fn xfer_retry(
+    dev: &mut io::Device,
+    retry: Retry,
+    txdata: &[u8],
+    rxdata: &mut [u8],
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let sent = AtomicBool::new(false);
+        let err = match xfer(dev, txdata, rxdata, &sent).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        if attempt >= retry.attempts || sent.load(Ordering::Relaxed) || !io::is_transient(&err) {
+            return Err(err);
+        }
+        attempt += 1;
+        tracing::warn!(
+            attempt,
+            max = retry.attempts,
+            "transfer failed, retrying: {err}"
+        );
+        dev.recover()
+            .await
+            .with_context(|| format!("failed to recover after transfer error: {err}"))?;
+        smol::Timer::after(retry.delay).await;
+    }
+}
+
 impl Device {
     pub async fn new(
         handle: nusb::Device,
         info: &devices::Info,
         clock_frequency: u32,
+        options: &cables::Options,
     ) -> Result<Self> {
-        let mut dev = io::Device::new(handle, info.interface).await?;
+        // the cable's default may be too fast for three-phase clocking, only
+        // one asked for is an error
+        let clock_frequency = match options.clock_frequency {
+            Some(hz) => hz,
+            None => clock_frequency.min(*tck_range(options.three_phase).end()),
+        };
+        let base_frequency = base_frequency(clock_frequency, options.three_phase)?;
 
-        let (clkdiv, divisor) = get_mpsse_clock(clock_frequency);
+        let dev = io::Device::new(handle, info.interface).await?;
+
+        let three_phase = match options.three_phase {
+            true => MpsseCommand::Enable3PhaseClocking,
+            false => MpsseCommand::Disable3PhaseClocking,
+        };
+        let (clkdiv, divisor) = get_mpsse_clock(base_frequency);
         let init_cmd = [
             MpsseCommand::SetDataBitsLowbyte as u8,
             info.dbus_data,
@@ -46,6 +146,7 @@ impl Device {
             MpsseCommand::SetDataBitsHighbyte as u8,
             info.cbus_data,
             info.cbus_en,
+            three_phase as u8,
             clkdiv,
             MpsseCommand::SetClockFrequency as u8,
             (divisor & 0xff) as u8,
@@ -55,8 +156,23 @@ impl Device {
 
         let mut me = Self {
             dev,
+            retry: Retry::default(),
+            write_neg: match options.tdi_edge {
+                Edge::Rising => 0,
+                Edge::Falling => WRITE_NEG,
+            },
+            read_neg: match options.tdo_edge {
+                Edge::Rising => 0,
+                Edge::Falling => READ_NEG,
+            },
             cmd_buf: Vec::new(),
             reads: Vec::new(),
+            frequency: Frequency {
+                hz: mpsse_frequency(clkdiv, divisor, options.three_phase),
+                forced: options.clock_frequency.is_some(),
+            },
+            three_phase: options.three_phase,
+            flushes: 0,
         };
         let buf = &mut ScratchBuffer::new();
         me.tms(buf, jtag::Path::RESET).await?;
@@ -66,19 +182,151 @@ impl Device {
         me.flush(buf).await?;
 
         let ([idcode], []) = buf.data().as_chunks() else {
-            return Err(eyre::eyre!("failed to fill buffer"));
+            return Err(Error::ShortRead {
+                read: buf.data().len(),
+                expected: 4,
+            });
         };
         if u32::from_le_bytes(*idcode) == 0xffffffff {
-            return Err(eyre::eyre!("no devices on chain"));
+            // tell a broken cable apart from a missing / unpowered target
+            return match loopback_test(&mut me.dev, me.retry).await {
+                Ok(()) => {
+                    tracing::info!("cable self-test passed");
+                    Err(Error::NoDevices)
+                }
+                Err(e) => Err(e).context("no devices on chain, and cable self-test failed"),
+            };
         }
 
         Ok(me)
     }
+
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+/// Toggle `pin` `count` times, i.e. to blink an LED on it. The other pins are
+/// driven as for JTAG, and `pin` is left as it was at the end.
+///
+/// Unlike [`Device::new`], this does not need anything attached to the cable.
+#[instrument(skip(handle, info))]
+pub async fn blink(
+    handle: nusb::Device,
+    info: &devices::Info,
+    pin: devices::Pin,
+    count: usize,
+    period: std::time::Duration,
+) -> Result<()> {
+    let dev = io::Device::new(handle, info.interface).await?;
+    let (cmd, data, en, mask) = match pin {
+        devices::Pin::Dbus(bit) => (
+            MpsseCommand::SetDataBitsLowbyte as u8,
+            info.dbus_data,
+            info.dbus_en,
+            1 << bit,
+        ),
+        devices::Pin::Cbus(bit) => (
+            MpsseCommand::SetDataBitsHighbyte as u8,
+            info.cbus_data,
+            info.cbus_en,
+            1 << bit,
+        ),
+    };
+    for idx in 0..count * 2 {
+        let data = match idx % 2 {
+            0 => data ^ mask,
+            _ => data,
+        };
+        dev.send(&[cmd, data, en | mask]).await?;
+        smol::Timer::after(period / 2).await;
+    }
+    // restore the direction as well
+    dev.send(&[cmd, data, en]).await
+}
+
+/// Shift a known pattern with the MPSSE engine's internal loopback enabled,
+/// which connects TDI to TDO inside the chip.
+///
+/// TMS is not touched, so the target only sees TCK pulses in whatever stable
+/// state it was left in.
+#[instrument(skip_all)]
+async fn loopback_test(dev: &mut io::Device, retry: Retry) -> Result<()> {
+    let pattern: Vec<u8> = (0..=u8::MAX).collect();
+    let len = (pattern.len() - 1) as u16;
+
+    // fixed edges: in loopback, sampling on the edge TDI changes on would
+    // read the previous bit
+    let mut cmd_buf =
+        vec![MpsseCommand::EnableLoopback as u8, DO_READ | DO_WRITE | LSB | WRITE_NEG];
+    cmd_buf.extend(len.to_le_bytes());
+    cmd_buf.extend(&pattern);
+    cmd_buf.push(MpsseCommand::DisableLoopback as u8);
+    cmd_buf.push(MpsseCommand::SendImmediate as u8);
+
+    let mut read = vec![0; pattern.len()];
+    xfer_retry(dev, retry, &cmd_buf, &mut read).await?;
+    if read != pattern {
+        let bad = read.iter().zip(&pattern).filter(|(a, b)| a != b).count();
+        return Err(Error::Protocol(format!(
+            "loopback mismatch in {bad}/{} bytes, read back {}",
+            pattern.len(),
+            crate::ShortHex(&read),
+        )));
+    }
+    Ok(())
+}
+
+const MAX_FREQUENCY: u32 = 30_000_000;
+const MIN_FREQUENCY: u32 = 92;
+
+/// Supported TCK frequencies. Three-phase clocking stretches each bit to 3
+/// phases of a 2-phase clock, so the base clock runs faster than TCK, and TCK
+/// tops out lower.
+const fn tck_range(three_phase: bool) -> RangeInclusive<u32> {
+    match three_phase {
+        true => MIN_FREQUENCY.div_ceil(3) * 2..=MAX_FREQUENCY / 3 * 2,
+        false => MIN_FREQUENCY..=MAX_FREQUENCY,
+    }
+}
+
+/// The MPSSE clock giving a TCK of `hz`, if it's in range.
+fn base_frequency(hz: u32, three_phase: bool) -> Result<u32> {
+    let range = tck_range(three_phase);
+    if !range.contains(&hz) {
+        let clocking = if three_phase {
+            " with three-phase clocking"
+        } else {
+            ""
+        };
+        return Err(Error::InvalidInput(format!(
+            "clock frequency {hz} outside of supported range {}..={}{clocking}",
+            range.start(),
+            range.end(),
+        )));
+    }
+    Ok(match three_phase {
+        true => hz / 2 * 3,
+        false => hz,
+    })
+}
+
+/// TCK frequency from the result of [`get_mpsse_clock`].
+fn mpsse_frequency(clkdiv: u8, divisor: u16, three_phase: bool) -> u32 {
+    let base = match clkdiv == MpsseCommand::EnableClockDivide as u8 {
+        true => 6_000_000,
+        false => 30_000_000,
+    } / (u32::from(divisor) + 1);
+    match three_phase {
+        true => base / 3 * 2,
+        false => base,
+    }
 }
 
 fn get_mpsse_clock(freq: u32) -> (u8, u16) {
-    const MAX: u32 = 30_000_000;
-    const MIN: u32 = 92;
+    const MAX: u32 = MAX_FREQUENCY;
+    const MIN: u32 = MIN_FREQUENCY;
 
     assert!(
         freq >= MIN,
@@ -135,6 +383,8 @@ enum MpsseCommand {
     EnableClockDivide = 0x8B,
     Enable3PhaseClocking = 0x8C,
     Disable3PhaseClocking = 0x8D,
+    ClockBits = 0x8E,
+    ClockBytes = 0x8F,
     EnableAdaptiveClocking = 0x96,
     DisableAdaptiveClocking = 0x97,
 }
@@ -153,7 +403,7 @@ impl Device {
     fn read_buf_required(&self) -> usize {
         let f = |&x| match x {
             Read::Bytes(n) => n,
-            Read::ExtraBit => 1,
+            Read::Bits(_) | Read::ExtraBit => 1,
         };
         self.reads.iter().map(f).sum()
     }
@@ -161,6 +411,7 @@ impl Device {
     fn read_len(&self) -> usize {
         let f = |&x| match x {
             Read::Bytes(n) => n,
+            Read::Bits(_) => 1,
             Read::ExtraBit => 0,
         };
         self.reads.iter().map(f).sum()
@@ -171,16 +422,16 @@ impl Device {
         buf: &mut dyn Buffer,
         path: jtag::Path,
         tdi: bool,
-        read_first_bit: bool,
+        read_first_bit: Option<Read>,
     ) -> Result<()> {
         debug!(%path, tdi);
 
         let tdi = if tdi { 0x80 } else { 0x00 };
-        let flags = WRITE_TMS | LSB | BITMODE | WRITE_NEG;
+        let flags = WRITE_TMS | LSB | BITMODE | self.write_neg;
 
-        if read_first_bit {
-            self.reads.push(Read::ExtraBit);
-            self.cmd_buf.push(flags | DO_READ | READ_NEG);
+        if let Some(read) = read_first_bit {
+            self.reads.push(read);
+            self.cmd_buf.push(flags | DO_READ | self.read_neg);
             self.cmd_buf.push(0);
             self.cmd_buf.push(tdi | path.as_clocked());
 
@@ -211,7 +462,7 @@ static ZEROES: &[u8; MAX_READ_WRITE_LEN] = &[0x00; MAX_READ_WRITE_LEN];
 impl Backend for Device {
     #[instrument(skip_all)]
     async fn tms(&mut self, buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
-        self.tms_internal(buf, path, true, false).await
+        self.tms_internal(buf, path, true, None).await
     }
 
     #[instrument(skip_all)]
@@ -223,17 +474,17 @@ impl Backend for Device {
         after: Option<jtag::Path>,
     ) -> Result<()> {
         if let Some(path) = before {
-            self.tms_internal(buf, path, true, false).await?;
+            self.tms_internal(buf, path, true, None).await?;
         }
 
         let mut last_bit = true;
-        let mut read_last_bit = false;
+        let mut read_last_bit = None;
 
         match data {
             Data::Tx(tdi) | Data::TxRx(tdi) => {
                 let read = matches!(data, Data::TxRx(_));
-                let read_cmd = if read { DO_READ | READ_NEG } else { 0 };
-                let cmd = read_cmd | DO_WRITE | WRITE_NEG | LSB;
+                let read_cmd = if read { DO_READ | self.read_neg } else { 0 };
+                let cmd = read_cmd | DO_WRITE | self.write_neg | LSB;
 
                 let (tdi, last) = match (after, tdi.split_last()) {
                     (Some(_), Some((l, data))) => (data, Some(*l)),
@@ -256,8 +507,8 @@ impl Backend for Device {
 
                 if let Some(last) = last {
                     if read {
-                        self.reads.push(Read::Bytes(1));
-                        read_last_bit = true;
+                        self.reads.push(Read::Bits(7));
+                        read_last_bit = Some(Read::ExtraBit);
                     }
                     self.cmd_buf.push(cmd | BITMODE);
                     // 7 bits, tx last bit as part of tms
@@ -276,7 +527,7 @@ impl Backend for Device {
                         to_add -= 1;
                     }
 
-                    let cmd = DO_READ | LSB | READ_NEG;
+                    let cmd = DO_READ | LSB | self.read_neg;
                     if to_add != 0 {
                         self.reads.push(Read::Bytes(to_add));
                         let read_len = assert_data_len(to_add);
@@ -287,11 +538,11 @@ impl Backend for Device {
                     }
 
                     if last {
-                        self.reads.push(Read::Bytes(1));
+                        self.reads.push(Read::Bits(7));
                         self.cmd_buf.push(cmd | BITMODE);
                         // 7 bits, rx last bit as part of tms
                         self.cmd_buf.push(6);
-                        read_last_bit = true;
+                        read_last_bit = Some(Read::ExtraBit);
                     }
 
                     self.maybe_flush(buf).await?;
@@ -307,7 +558,7 @@ impl Backend for Device {
                     };
                     let write_len = assert_data_len(to_add);
 
-                    self.cmd_buf.push(DO_WRITE | LSB | WRITE_NEG);
+                    self.cmd_buf.push(DO_WRITE | LSB | self.write_neg);
                     self.cmd_buf.push(write_len as u8);
                     self.cmd_buf.push((write_len >> 8) as u8);
                     self.cmd_buf.extend_from_slice(tdi);
@@ -337,7 +588,7 @@ impl Backend for Device {
         after: Option<jtag::Path>,
     ) -> Result<()> {
         if let Some(path) = before {
-            self.tms_internal(buf, path, true, false).await?;
+            self.tms_internal(buf, path, true, None).await?;
         }
 
         let mut len = match after {
@@ -345,7 +596,7 @@ impl Backend for Device {
             None => len.0,
         };
 
-        let cmd = DO_WRITE | LSB | WRITE_NEG | BITMODE;
+        let cmd = DO_WRITE | LSB | self.write_neg | BITMODE;
         while len != 0 {
             let added = if len > 8 { 8 } else { len };
             self.cmd_buf.push(cmd);
@@ -356,7 +607,72 @@ impl Backend for Device {
         }
 
         if let Some(path) = after {
-            self.tms_internal(buf, path, data & 1 == 1, false).await?;
+            self.tms_internal(buf, path, data & 1 == 1, None).await?;
+        }
+
+        self.maybe_flush(buf).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn idle_clocks(&mut self, buf: &mut dyn Buffer, count: usize) -> Result<()> {
+        let (mut bytes, bits) = (count / 8, count % 8);
+        while bytes != 0 {
+            let to_add = bytes.min(MAX_READ_WRITE_LEN);
+            let len = assert_data_len(to_add);
+            self.cmd_buf.push(MpsseCommand::ClockBytes as u8);
+            self.cmd_buf.push(len as u8);
+            self.cmd_buf.push((len >> 8) as u8);
+            bytes -= to_add;
+            self.maybe_flush(buf).await?;
+        }
+        if bits != 0 {
+            self.cmd_buf.push(MpsseCommand::ClockBits as u8);
+            self.cmd_buf.push(bits as u8 - 1);
+        }
+        self.maybe_flush(buf).await
+    }
+
+    #[instrument(skip_all)]
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        mut data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        if let Some(path) = before {
+            self.tms_internal(buf, path, true, None).await?;
+        }
+
+        let mut len = match after {
+            Some(_) => len.0 - 1,
+            None => len.0,
+        };
+        let mut last_read = None;
+
+        let cmd = DO_WRITE | DO_READ | LSB | self.write_neg | self.read_neg | BITMODE;
+        while len != 0 {
+            let added = if len > 8 { 8 } else { len };
+            self.reads.push(Read::Bits(added));
+            self.cmd_buf.push(cmd);
+            self.cmd_buf.push(added - 1);
+            self.cmd_buf.push(data as u8);
+            data >>= added;
+            len -= added;
+            last_read = Some(added);
+        }
+
+        if let Some(path) = after {
+            // the bit read as part of the tms transition either joins the
+            // previous partial byte, or starts a new one
+            let read = match last_read {
+                Some(n) if n < 8 => Read::ExtraBit,
+                _ => Read::Bits(1),
+            };
+            self.tms_internal(buf, path, data & 1 == 1, Some(read))
+                .await?;
         }
 
         self.maybe_flush(buf).await?;
@@ -365,7 +681,12 @@ impl Backend for Device {
 
     #[instrument(skip_all)]
     async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
-        self.cmd_buf.push(MpsseCommand::SendImmediate as u8);
+        self.flushes += 1;
+        // only needed to get the chip to return data before the latency timer
+        // expires
+        if !self.reads.is_empty() {
+            self.cmd_buf.push(MpsseCommand::SendImmediate as u8);
+        }
         let read_len = self.read_len();
         debug!(
             write_len = self.cmd_buf.len(),
@@ -376,7 +697,7 @@ impl Backend for Device {
         let scratch = self.read_buf_required() - read_len;
 
         let buf = buf.extend(read_len, scratch);
-        if let Err(e) = xfer(&mut self.dev, &self.cmd_buf, buf).await {
+        if let Err(e) = xfer_retry(&mut self.dev, self.retry, &self.cmd_buf, buf).await {
             self.cmd_buf.clear();
             self.reads.clear();
             return Err(e);
@@ -387,23 +708,115 @@ impl Backend for Device {
         self.reads.clear();
         Ok(())
     }
+
+    fn supports_templates(&self) -> bool {
+        true
+    }
+
+    fn flushes(&self) -> usize {
+        self.flushes
+    }
+
+    async fn self_test(&mut self) -> Result<()> {
+        if !self.cmd_buf.is_empty() {
+            return Err(Error::InvalidInput(
+                "self-test with commands still queued".into(),
+            ));
+        }
+        loopback_test(&mut self.dev, self.retry).await
+    }
+
+    fn frequency(&self) -> Option<Frequency> {
+        Some(self.frequency)
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<u32> {
+        if !self.cmd_buf.is_empty() {
+            return Err(Error::InvalidInput(
+                "frequency change with commands still queued".into(),
+            ));
+        }
+        let base = base_frequency(hz, self.three_phase)?;
+        let (clkdiv, mut divisor) = get_mpsse_clock(base);
+        // the divisor rounds down, which can end up faster than asked for
+        if mpsse_frequency(clkdiv, divisor, self.three_phase) > hz {
+            divisor += 1;
+        }
+        let cmd = [
+            clkdiv,
+            MpsseCommand::SetClockFrequency as u8,
+            (divisor & 0xff) as u8,
+            ((divisor >> 8) & 0xff) as u8,
+        ];
+        self.dev.send(&cmd).await?;
+        self.frequency.hz = mpsse_frequency(clkdiv, divisor, self.three_phase);
+        Ok(self.frequency.hz)
+    }
+
+    fn take_template(&mut self) -> Result<Template> {
+        let read_len = self.read_len();
+        let scratch = self.read_buf_required() - read_len;
+        let mut cmd_buf = std::mem::take(&mut self.cmd_buf);
+        cmd_buf.push(MpsseCommand::SendImmediate as u8);
+        Ok(Template::new(FtdiTemplate {
+            cmd_buf,
+            reads: std::mem::take(&mut self.reads),
+            read_len,
+            scratch,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn run_template(&mut self, buf: &mut dyn Buffer, template: &Template) -> Result<()> {
+        let Some(template) = template.downcast_ref::<FtdiTemplate>() else {
+            return Err(Error::InvalidInput(
+                "template was not created by an ftdi backend".into(),
+            ));
+        };
+        if !self.cmd_buf.is_empty() {
+            return Err(Error::InvalidInput(
+                "cannot run template with commands still queued".into(),
+            ));
+        }
+
+        let buf = buf.extend(template.read_len, template.scratch);
+        xfer_retry(&mut self.dev, self.retry, &template.cmd_buf, buf).await?;
+        shift_reads(buf, &template.reads);
+        Ok(())
+    }
 }
 
 fn shift_reads(mut buf: &mut [u8], reads: &[Read]) {
+    fn align(byte: &mut u8, valid: u8) {
+        *byte >>= 8 - valid;
+    }
+
     let mut last_byte = &mut 0;
+    let mut valid = 8;
     for r in reads {
         match r {
             Read::Bytes(n) => {
+                align(last_byte, valid);
                 let (chunk, rest) = buf.split_at_mut(*n);
                 last_byte = &mut chunk[chunk.len() - 1];
+                valid = 8;
+                buf = rest;
+            }
+            Read::Bits(n) => {
+                align(last_byte, valid);
+                let (byte, rest) = buf.split_first_mut().unwrap();
+                last_byte = byte;
+                valid = *n;
                 buf = rest;
             }
             Read::ExtraBit => {
                 *last_byte = *last_byte >> 1 | buf[0] & 0x80;
+                valid += 1;
                 buf = &mut buf[1..];
             }
         }
     }
+    align(last_byte, valid);
 }
 
 fn assert_data_len(len: usize) -> u16 {
@@ -414,3 +827,29 @@ fn assert_data_len(len: usize) -> u16 {
     );
     (len - 1) as u16
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_frequency() {
+        assert_eq!(base_frequency(30_000_000, false).unwrap(), 30_000_000);
+        assert_eq!(base_frequency(20_000_000, true).unwrap(), 30_000_000);
+        assert!(base_frequency(30_000_000, true).is_err());
+        assert!(base_frequency(91, false).is_err());
+        assert!(base_frequency(62, true).is_ok_and(|base| base >= MIN_FREQUENCY));
+    }
+
+    #[test]
+    fn test_shift_reads_bits() {
+        // 8 bits, then 4 bits + 1 bit from the tms transition
+        let mut buf = [0xab, 0x50, 0x80];
+        shift_reads(&mut buf, &[Read::Bits(8), Read::Bits(4), Read::ExtraBit]);
+        assert_eq!(buf[..2], [0xab, 0b1_0101]);
+
+        let mut buf = [0x12, 0x34, 0x80, 0x00];
+        shift_reads(&mut buf, &[Read::Bytes(1), Read::Bits(7), Read::ExtraBit]);
+        assert_eq!(buf[..2], [0x12, 0x9a]);
+    }
+}
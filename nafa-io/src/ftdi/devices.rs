@@ -1,3 +1,5 @@
+use crate::Error;
+
 #[rustfmt::skip]
 mod consts {
     use super::Info;
@@ -32,16 +34,63 @@ mod consts {
 pub use consts::*;
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug)]
-pub(crate) enum Interface {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interface {
     A = 0,
     B = 1,
     C = 2,
-    #[expect(unused)]
     D = 3,
 }
 
-#[derive(Debug)]
+impl std::str::FromStr for Interface {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "A" | "a" | "0" => Ok(Self::A),
+            "B" | "b" | "1" => Ok(Self::B),
+            "C" | "c" | "2" => Ok(Self::C),
+            "D" | "d" | "3" => Ok(Self::D),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown ftdi channel {s:?}, expected one of A, B, C, D or 0-3"
+            ))),
+        }
+    }
+}
+
+/// A GPIO of the MPSSE engine, by bit index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pin {
+    /// `xDBUSn`, set with the low byte command.
+    Dbus(u8),
+    /// `xCBUSn`, set with the high byte command.
+    Cbus(u8),
+}
+
+impl std::str::FromStr for Pin {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let (ctor, bit): (fn(u8) -> Self, _) = if let Some(bit) = lower.strip_prefix("dbus") {
+            (Self::Dbus, bit)
+        } else if let Some(bit) = lower.strip_prefix("cbus") {
+            (Self::Cbus, bit)
+        } else {
+            return Err(Error::InvalidInput(format!(
+                "unknown pin {s:?}, expected DBUSn or CBUSn"
+            )));
+        };
+        match bit.parse() {
+            Ok(bit @ 0..8) => Ok(ctor(bit)),
+            _ => Err(Error::InvalidInput(format!(
+                "invalid pin index in {s:?}, expected 0-7"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub struct Info {
     pub(super) interface: Interface,
     pub(super) dbus_data: u8,
@@ -66,4 +115,9 @@ impl Info {
             cbus_en,
         }
     }
+
+    /// The same pin setup, on a different channel of the chip.
+    pub const fn with_interface(self, interface: Interface) -> Self {
+        Self { interface, ..self }
+    }
 }
@@ -0,0 +1,399 @@
+//! Configuration EEPROM of FT2232H / FT4232H chips: USB strings and channel
+//! modes.
+//!
+//! Layout follows libftdi. Only the fields needed to label and provision cables
+//! are exposed; everything else is kept as read.
+
+use std::time::Duration;
+
+use nusb::transfer::{ControlIn, ControlOut, ControlType, Recipient};
+
+use crate::{Error, Result, ftdi::devices::Interface};
+
+const READ_EEPROM: u8 = 0x90;
+const WRITE_EEPROM: u8 = 0x91;
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Largest EEPROM supported by the chip (93C66).
+const MAX_SIZE: usize = 256;
+/// Strings are placed after the fixed fields.
+const STRINGS_START: usize = 0x9a;
+
+const MANUFACTURER: usize = 0x0e;
+const PRODUCT: usize = 0x10;
+const SERIAL: usize = 0x12;
+
+const DRIVER_VCP: u8 = 0x08;
+const CHANNEL_MODE_MASK: u8 = 0x07;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip {
+    Ft2232H,
+    Ft4232H,
+}
+
+impl Chip {
+    /// From `bcdDevice` of the device descriptor.
+    pub fn from_device_version(version: u16) -> Option<Self> {
+        match version {
+            0x0700 => Some(Self::Ft2232H),
+            0x0800 => Some(Self::Ft4232H),
+            _ => None,
+        }
+    }
+
+    /// Byte and bit mask of the driver flag for a channel.
+    ///
+    /// The FT4232H has no per-channel mode, so it packs the driver flags of
+    /// C and D in the upper nibble instead.
+    fn driver_bit(self, interface: Interface) -> Option<(usize, u8)> {
+        match (self, interface) {
+            (_, Interface::A) => Some((0, DRIVER_VCP)),
+            (_, Interface::B) => Some((1, DRIVER_VCP)),
+            (Self::Ft4232H, Interface::C) => Some((0, DRIVER_VCP << 4)),
+            (Self::Ft4232H, Interface::D) => Some((1, DRIVER_VCP << 4)),
+            (Self::Ft2232H, Interface::C | Interface::D) => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelMode {
+    Uart,
+    Fifo245,
+    CpuFifo,
+    FastSerial,
+}
+
+impl ChannelMode {
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits & CHANNEL_MODE_MASK {
+            0 => Some(Self::Uart),
+            1 => Some(Self::Fifo245),
+            2 => Some(Self::CpuFifo),
+            4 => Some(Self::FastSerial),
+            _ => None,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Self::Uart => 0,
+            Self::Fifo245 => 1,
+            Self::CpuFifo => 2,
+            Self::FastSerial => 4,
+        }
+    }
+}
+
+impl std::str::FromStr for ChannelMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uart" => Ok(Self::Uart),
+            "fifo" | "245" => Ok(Self::Fifo245),
+            "cpu-fifo" => Ok(Self::CpuFifo),
+            "fast-serial" => Ok(Self::FastSerial),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown channel mode {s:?}, expected one of uart, fifo, cpu-fifo, fast-serial"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Driver {
+    D2xx,
+    Vcp,
+}
+
+impl std::str::FromStr for Driver {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "d2xx" => Ok(Self::D2xx),
+            "vcp" => Ok(Self::Vcp),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown driver {s:?}, expected d2xx or vcp"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Channel {
+    /// `None` on the FT4232H, where the mode is only set at runtime.
+    pub mode: Option<ChannelMode>,
+    /// Driver the OS should bind. Only relevant on Windows.
+    pub driver: Driver,
+}
+
+pub struct Eeprom {
+    chip: Chip,
+    data: Vec<u8>,
+}
+
+impl Eeprom {
+    #[tracing::instrument(skip_all)]
+    pub async fn read(device: &nusb::Device) -> Result<Self> {
+        let version = device.device_descriptor().device_version();
+        let Some(chip) = Chip::from_device_version(version) else {
+            return Err(Error::Unsupported(format!(
+                "unsupported ftdi chip (bcdDevice {version:04X}), expected FT2232H or FT4232H"
+            )));
+        };
+
+        let mut data = Vec::with_capacity(MAX_SIZE);
+        for addr in 0..(MAX_SIZE / 2) as u16 {
+            let request = ControlIn {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Device,
+                request: READ_EEPROM,
+                value: 0,
+                index: addr,
+                length: 2,
+            };
+            let word = device.control_in(request, TIMEOUT).await?;
+            data.extend_from_slice(&word);
+        }
+
+        // A 93C46 wraps around after 128 bytes
+        let (low, high) = data.split_at(MAX_SIZE / 2);
+        if low == high {
+            data.truncate(MAX_SIZE / 2);
+        }
+
+        Self::from_bytes(chip, data)
+    }
+
+    /// Write the whole EEPROM back, with an updated checksum.
+    #[tracing::instrument(skip_all)]
+    pub async fn write(&mut self, device: &nusb::Device) -> Result<()> {
+        self.update_checksum();
+        for (addr, word) in self.data.chunks(2).enumerate() {
+            let request = ControlOut {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Device,
+                request: WRITE_EEPROM,
+                value: u16::from_le_bytes([word[0], word[1]]),
+                index: addr as u16,
+                data: &[],
+            };
+            device.control_out(request, TIMEOUT).await?;
+        }
+        Ok(())
+    }
+
+    pub fn from_bytes(chip: Chip, data: Vec<u8>) -> Result<Self> {
+        if data.len() != MAX_SIZE && data.len() != MAX_SIZE / 2 {
+            return Err(Error::Protocol(format!(
+                "unexpected eeprom size {}",
+                data.len()
+            )));
+        }
+        if data.iter().all(|b| *b == 0xff) {
+            return Err(Error::Protocol("eeprom is blank".into()));
+        }
+        let slf = Self { chip, data };
+        let stored = slf.word(slf.data.len() / 2 - 1);
+        let expected = slf.checksum();
+        if stored != expected {
+            return Err(Error::Protocol(format!(
+                "eeprom checksum mismatch: stored {stored:04X}, expected {expected:04X}"
+            )));
+        }
+        Ok(slf)
+    }
+
+    pub fn chip(&self) -> Chip {
+        self.chip
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn vendor_id(&self) -> u16 {
+        self.word(1)
+    }
+
+    pub fn product_id(&self) -> u16 {
+        self.word(2)
+    }
+
+    pub fn manufacturer(&self) -> Result<String> {
+        self.string(MANUFACTURER)
+    }
+
+    pub fn product(&self) -> Result<String> {
+        self.string(PRODUCT)
+    }
+
+    pub fn serial(&self) -> Result<String> {
+        self.string(SERIAL)
+    }
+
+    /// Replace all USB strings. They share one area of the EEPROM, so they
+    /// are always laid out together.
+    pub fn set_strings(&mut self, manufacturer: &str, product: &str, serial: &str) -> Result<()> {
+        let mask = self.data.len() - 1;
+        // up to the checksum, which is the last word
+        let available = self.data.len() - 2 - (STRINGS_START & mask);
+        let mut addr = STRINGS_START;
+        for (field, s) in [(MANUFACTURER, manufacturer), (PRODUCT, product), (SERIAL, serial)] {
+            let utf16: Vec<u16> = s.encode_utf16().collect();
+            let len = 2 + utf16.len() * 2;
+            if addr - STRINGS_START + len > available {
+                return Err(Error::InvalidInput("strings do not fit in eeprom".into()));
+            }
+
+            self.data[field] = addr as u8;
+            self.data[field + 1] = len as u8;
+            let mut bytes = vec![len as u8, 0x03];
+            bytes.extend(utf16.iter().flat_map(|c| c.to_le_bytes()));
+            for b in bytes {
+                self.data[addr & mask] = b;
+                addr += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// `None` if the chip does not have this channel.
+    pub fn channel(&self, interface: Interface) -> Option<Channel> {
+        let (offset, driver_bit) = self.chip.driver_bit(interface)?;
+        let byte = self.data[offset];
+        let mode = match self.chip {
+            Chip::Ft2232H => Some(ChannelMode::from_bits(byte)?),
+            Chip::Ft4232H => None,
+        };
+        let driver = match byte & driver_bit {
+            0 => Driver::D2xx,
+            _ => Driver::Vcp,
+        };
+        Some(Channel { mode, driver })
+    }
+
+    pub fn set_channel(&mut self, interface: Interface, channel: Channel) -> Result<()> {
+        let Some((offset, driver_bit)) = self.chip.driver_bit(interface) else {
+            return Err(Error::InvalidInput(format!(
+                "{:?} has no channel {interface:?}",
+                self.chip
+            )));
+        };
+        let byte = &mut self.data[offset];
+        match (self.chip, channel.mode) {
+            (Chip::Ft2232H, Some(mode)) => {
+                *byte &= !CHANNEL_MODE_MASK;
+                *byte |= mode.bits();
+            }
+            (Chip::Ft4232H, Some(_)) => {
+                return Err(Error::InvalidInput(
+                    "FT4232H channels have no configurable mode".into(),
+                ));
+            }
+            (_, None) => (),
+        }
+        match channel.driver {
+            Driver::D2xx => *byte &= !driver_bit,
+            Driver::Vcp => *byte |= driver_bit,
+        }
+        Ok(())
+    }
+
+    fn word(&self, idx: usize) -> u16 {
+        u16::from_le_bytes([self.data[idx * 2], self.data[idx * 2 + 1]])
+    }
+
+    fn string(&self, field: usize) -> Result<String> {
+        let mask = self.data.len() - 1;
+        let addr = usize::from(self.data[field]);
+        let len = usize::from(self.data[field + 1]);
+        if len < 2 {
+            return Ok(String::new());
+        }
+        let bytes: Vec<u8> = (addr..addr + len).map(|a| self.data[a & mask]).collect();
+        if bytes[0] as usize != len || bytes[1] != 0x03 {
+            return Err(Error::Protocol(format!(
+                "invalid string descriptor at {addr:#04X}"
+            )));
+        }
+        let utf16 = bytes[2..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]));
+        Ok(char::decode_utf16(utf16)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect())
+    }
+
+    fn checksum(&self) -> u16 {
+        let words = self.data.len() / 2 - 1;
+        (0..words).fold(0xaaaa, |checksum: u16, idx| {
+            (self.word(idx) ^ checksum).rotate_left(1)
+        })
+    }
+
+    fn update_checksum(&mut self) {
+        let checksum = self.checksum();
+        let len = self.data.len();
+        self.data[len - 2..].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strings_roundtrip() {
+        let mut data = vec![0; MAX_SIZE];
+        data[2..6].copy_from_slice(&[0x03, 0x04, 0x10, 0x60]);
+        let mut eeprom = Eeprom {
+            chip: Chip::Ft2232H,
+            data,
+        };
+        eeprom.set_strings("nafa", "JTAG cable", "NF0001").unwrap();
+        eeprom
+            .set_channel(
+                Interface::B,
+                Channel {
+                    mode: Some(ChannelMode::Fifo245),
+                    driver: Driver::Vcp,
+                },
+            )
+            .unwrap();
+        eeprom.update_checksum();
+
+        let eeprom = Eeprom::from_bytes(Chip::Ft2232H, eeprom.data).unwrap();
+        assert_eq!(eeprom.vendor_id(), 0x0403);
+        assert_eq!(eeprom.product_id(), 0x6010);
+        assert_eq!(eeprom.manufacturer().unwrap(), "nafa");
+        assert_eq!(eeprom.product().unwrap(), "JTAG cable");
+        assert_eq!(eeprom.serial().unwrap(), "NF0001");
+        assert_eq!(
+            eeprom.channel(Interface::B),
+            Some(Channel {
+                mode: Some(ChannelMode::Fifo245),
+                driver: Driver::Vcp,
+            })
+        );
+    }
+
+    #[test]
+    fn test_ft4232h_drivers() {
+        let mut eeprom = Eeprom {
+            chip: Chip::Ft4232H,
+            data: vec![0; MAX_SIZE],
+        };
+        let vcp = Channel {
+            mode: None,
+            driver: Driver::Vcp,
+        };
+        eeprom.set_channel(Interface::D, vcp).unwrap();
+        assert_eq!(eeprom.data[..2], [0x00, 0x80]);
+        assert_eq!(eeprom.channel(Interface::D), Some(vcp));
+        assert_eq!(eeprom.channel(Interface::B).unwrap().driver, Driver::D2xx);
+    }
+}
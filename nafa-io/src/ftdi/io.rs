@@ -1,9 +1,11 @@
-use std::time::Duration;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
 
-use eyre::Result;
 use nusb::transfer::{self, ControlOut, ControlType, Recipient};
 
-use crate::ftdi::devices::Interface;
+use crate::{Error, Result, ftdi::devices::Interface};
 
 pub struct Device {
     iface: nusb::Interface,
@@ -12,6 +14,9 @@ pub struct Device {
 }
 
 const CHUNK_SIZE: usize = 1024;
+/// Transfers kept in flight per direction, so the next chunk is already queued
+/// when the previous one completes.
+const NUM_TRANSFERS: usize = 4;
 
 struct Endpoints {
     in_: u8,
@@ -28,6 +33,44 @@ mod requests {
 }
 
 const TIMEOUT: Duration = Duration::from_millis(5000);
+/// How long to keep reading status-only packets once all commands were
+/// written. The chip has everything it needs by then, so data missing for
+/// longer is not coming.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How many times a failed transfer is attempted again before giving up.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    pub attempts: u32,
+    /// Wait between recovering the endpoints and resending.
+    pub delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            delay: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Whether `err` may go away by recovering the endpoints and trying again.
+///
+/// Stalls and timeouts are retried. A disconnected cable, an invalid request,
+/// or an error of unknown kind is not.
+pub fn is_transient(err: &Error) -> bool {
+    use std::io::ErrorKind;
+
+    match err.root() {
+        Error::Transfer(transfer::TransferError::Stall) => true,
+        Error::Io(e) => matches!(
+            e.kind(),
+            ErrorKind::TimedOut | ErrorKind::ConnectionReset | ErrorKind::Interrupted
+        ),
+        _ => false,
+    }
+}
 
 #[tracing::instrument(skip_all)]
 fn determine_max_packet_size(iface: &nusb::Interface) -> usize {
@@ -92,13 +135,13 @@ impl Device {
     }
 
     #[tracing::instrument(skip_all)]
-    pub async fn send(&mut self, data: &[u8]) -> Result<()> {
+    pub async fn send(&self, data: &[u8]) -> Result<()> {
         use futures_lite::AsyncWriteExt;
-        self.flush_rx().await?;
         let mut writer = self
             .iface
             .endpoint::<transfer::Bulk, transfer::Out>(self.endpoints.in_)?
             .writer(CHUNK_SIZE)
+            .with_num_transfers(NUM_TRANSFERS)
             .with_write_timeout(TIMEOUT);
         tracing::debug!(len = %data.len(), buf = %crate::ShortHex(data));
         writer.write_all(data).await?;
@@ -107,7 +150,7 @@ impl Device {
     }
 
     #[tracing::instrument(skip_all)]
-    pub async fn recv(&mut self, mut buf: &mut [u8]) -> Result<usize> {
+    pub async fn recv(&self, mut buf: &mut [u8], sent: &AtomicBool) -> Result<usize> {
         use futures_lite::AsyncReadExt;
 
         let original_len = buf.len();
@@ -116,6 +159,7 @@ impl Device {
             .iface
             .endpoint::<transfer::Bulk, transfer::In>(self.endpoints.out)?
             .reader(CHUNK_SIZE)
+            .with_num_transfers(NUM_TRANSFERS)
             .with_read_timeout(TIMEOUT);
 
         let mut read_buffer = [0; CHUNK_SIZE];
@@ -127,21 +171,40 @@ impl Device {
         let read_buffer = &mut read_buffer[..max_read_len];
 
         let mut actual_bytes_read = 0;
+        let mut last_data = Instant::now();
+        let mut idle_since = None;
         while !buf.is_empty() {
             let bytes_read = reader.read(read_buffer).await?;
             tracing::debug!(len = %read_buffer.len(), bytes_read, read = %crate::ShortHex(&read_buffer[..bytes_read]));
-            if bytes_read <= 2 {
+            if bytes_read == 0 {
                 break;
             }
+            // reading may start before the commands producing the data are sent,
+            // in which case the chip answers with status-only packets every
+            // latency timer tick. Once everything is sent, those mean the chip
+            // has nothing pending, so only wait a little longer.
+            if bytes_read <= 2 {
+                let deadline = if sent.load(Ordering::Relaxed) {
+                    idle_since.get_or_insert_with(Instant::now).elapsed() > IDLE_TIMEOUT
+                } else {
+                    last_data.elapsed() > TIMEOUT
+                };
+                if deadline {
+                    break;
+                }
+                continue;
+            }
+            last_data = Instant::now();
+            idle_since = None;
             for packet in read_buffer[..bytes_read].chunks(self.packet_size) {
                 let data = &packet[2..];
                 let buf_len = buf.len();
                 let Some((first, rest)) = buf.split_at_mut_checked(data.len()) else {
-                    return Err(eyre::eyre!(
+                    return Err(Error::Protocol(format!(
                         "too much data for buffer: read {} bytes, expected {}",
                         data.len(),
                         buf_len,
-                    ));
+                    )));
                 };
                 first.copy_from_slice(data);
                 actual_bytes_read += data.len();
@@ -150,14 +213,33 @@ impl Device {
         }
 
         if actual_bytes_read != original_len {
-            return Err(eyre::eyre!(
-                "failed to fill buffer: read {actual_bytes_read} bytes, expected {original_len}"
-            ));
+            return Err(Error::ShortRead {
+                read: actual_bytes_read,
+                expected: original_len,
+            });
         }
 
         Ok(actual_bytes_read)
     }
 
+    /// Bring the device back to a usable state after a failed transfer: clear
+    /// any halt condition on both bulk endpoints, then drop whatever is left
+    /// in the chip's buffers.
+    #[tracing::instrument(skip_all)]
+    pub async fn recover(&mut self) -> Result<()> {
+        self.iface
+            .endpoint::<transfer::Bulk, transfer::Out>(self.endpoints.in_)?
+            .clear_halt()
+            .await?;
+        self.iface
+            .endpoint::<transfer::Bulk, transfer::In>(self.endpoints.out)?
+            .clear_halt()
+            .await?;
+        self.flush_tx().await?;
+        self.flush_rx().await?;
+        Ok(())
+    }
+
     /// Flush the read buffer on the chip
     #[tracing::instrument(skip_all)]
     pub async fn flush_rx(&self) -> Result<()> {
@@ -213,3 +295,28 @@ impl Interface {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_is_transient() {
+        let io = |kind| Error::Io(std::io::Error::from(kind));
+        assert!(is_transient(&io(ErrorKind::TimedOut)));
+        assert!(is_transient(&Error::Transfer(
+            transfer::TransferError::Stall
+        )));
+        assert!(is_transient(&Error::Io(
+            transfer::TransferError::Stall.into()
+        )));
+        assert!(!is_transient(&io(ErrorKind::Other)));
+        assert!(!is_transient(&io(ErrorKind::ConnectionAborted)));
+        assert!(!is_transient(&Error::ShortRead {
+            read: 1,
+            expected: 2
+        }));
+    }
+}
@@ -0,0 +1,126 @@
+//! A slow, careful IO profile, for targets behind long ribbon cables,
+//! isolators, or flaky connectors.
+
+use std::time::Duration;
+
+use crate::{
+    Backend, Buffer, Result,
+    backend::{Data, Frequency},
+    jtag,
+    units::{Bits, Bytes},
+};
+
+/// TCK frequency used when no frequency was asked for explicitly.
+pub const FREQUENCY: u32 = 100_000;
+/// How long to wait after moving between TAP states.
+const SETTLE: Duration = Duration::from_millis(1);
+/// Largest shift sent to the cable at once.
+const MAX_BURST: usize = 512;
+
+/// Wraps a backend so every state transition is flushed and followed by a
+/// short delay, and long shifts are split into small transfers.
+pub struct Gentle<B> {
+    inner: B,
+}
+
+impl<B: Backend> Gentle<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+
+    async fn settle(&mut self, buf: &mut dyn Buffer, moved: bool) -> Result<()> {
+        if moved {
+            self.inner.wait(buf, SETTLE).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: Backend> Backend for Gentle<B> {
+    async fn tms(&mut self, buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
+        self.inner.tms(buf, path).await?;
+        self.settle(buf, true).await
+    }
+
+    async fn bytes(
+        &mut self,
+        buf: &mut dyn Buffer,
+        mut before: Option<jtag::Path>,
+        data: Data<'_>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let len = match data {
+            Data::Tx(tdi) | Data::TxRx(tdi) => tdi.len(),
+            Data::Rx(Bytes(len)) | Data::ConstantTx(_, Bytes(len)) => len,
+        };
+        // always at least one chunk, so the paths are taken for empty data too
+        let mut offset = 0;
+        loop {
+            let end = len.min(offset + MAX_BURST);
+            let chunk = match data {
+                Data::Tx(tdi) => Data::Tx(&tdi[offset..end]),
+                Data::TxRx(tdi) => Data::TxRx(&tdi[offset..end]),
+                Data::Rx(_) => Data::Rx(Bytes(end - offset)),
+                Data::ConstantTx(tdi, _) => Data::ConstantTx(tdi, Bytes(end - offset)),
+            };
+            let (entered, exit) = (before.take(), if end == len { after } else { None });
+            self.inner.bytes(buf, entered, chunk, exit).await?;
+            self.inner.flush(buf).await?;
+            self.settle(buf, entered.is_some() || exit.is_some())
+                .await?;
+            offset = end;
+            if offset >= len {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.inner.bits(buf, before, data, len, after).await?;
+        self.settle(buf, before.is_some() || after.is_some()).await
+    }
+
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.inner.tdo_bits(buf, before, data, len, after).await?;
+        self.settle(buf, before.is_some() || after.is_some()).await
+    }
+
+    async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
+        self.inner.flush(buf).await
+    }
+
+    async fn idle_clocks(&mut self, buf: &mut dyn Buffer, count: usize) -> Result<()> {
+        self.inner.idle_clocks(buf, count).await
+    }
+
+    async fn wait(&mut self, buf: &mut dyn Buffer, duration: Duration) -> Result<()> {
+        self.inner.wait(buf, duration).await
+    }
+
+    fn frequency(&self) -> Option<Frequency> {
+        self.inner.frequency()
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<u32> {
+        self.inner.set_frequency(hz).await
+    }
+
+    async fn self_test(&mut self) -> Result<()> {
+        self.inner.self_test().await
+    }
+}
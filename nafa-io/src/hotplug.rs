@@ -0,0 +1,163 @@
+//! Tracking of a single cable across unplug / replug events.
+
+use std::time::Duration;
+
+use futures_lite::StreamExt as _;
+use nusb::hotplug::{HotplugEvent, HotplugWatch};
+use smol::future::FutureExt as _;
+
+use crate::{Backend, Buffer, Result, backend::Data, cables, jtag, units::Bits};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectionError {
+    #[error("cable disconnected")]
+    Disconnected,
+    #[error("cable did not reconnect within {0:?}")]
+    ReconnectTimeout(Duration),
+    #[error("jtag chain changed after reconnect: expected {expected:08X?}, found {found:08X?}")]
+    ChainChanged { expected: Vec<u32>, found: Vec<u32> },
+}
+
+pub struct Watch {
+    events: HotplugWatch,
+    device: nusb::DeviceInfo,
+    connected: bool,
+}
+
+impl Watch {
+    /// Start watching `device`. Must be created before any IO is done, so that
+    /// no events are missed.
+    pub fn new(device: nusb::DeviceInfo) -> Result<Self> {
+        Ok(Self {
+            events: nusb::watch_devices()?,
+            device,
+            connected: true,
+        })
+    }
+
+    pub fn device(&self) -> &nusb::DeviceInfo {
+        &self.device
+    }
+
+    /// Resolves once the cable has been unplugged. Never resolves if the cable
+    /// stays connected.
+    pub async fn disconnected(&mut self) -> ConnectionError {
+        while self.connected {
+            match self.events.next().await {
+                Some(HotplugEvent::Disconnected(id)) if id == self.device.id() => {
+                    tracing::warn!("cable disconnected");
+                    self.connected = false;
+                }
+                Some(_) => (),
+                None => std::future::pending().await,
+            }
+        }
+        ConnectionError::Disconnected
+    }
+
+    /// Check if the cable was unplugged, giving the OS up to `grace` to report
+    /// it.
+    ///
+    /// Used after an IO error, which often arrives before the hotplug event.
+    pub async fn was_disconnected(&mut self, grace: Duration) -> bool {
+        let disconnected = async {
+            self.disconnected().await;
+            true
+        };
+        let timeout = async {
+            smol::Timer::after(grace).await;
+            false
+        };
+        disconnected.or(timeout).await
+    }
+
+    /// Wait for the same cable (same VID, PID, and serial number) to be plugged
+    /// back in.
+    pub async fn reconnected(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<nusb::DeviceInfo, ConnectionError> {
+        let wait = async {
+            while !self.connected {
+                match self.events.next().await {
+                    Some(HotplugEvent::Connected(info)) if self.is_same_cable(&info) => {
+                        tracing::info!("cable reconnected");
+                        self.device = info;
+                        self.connected = true;
+                    }
+                    Some(_) => (),
+                    None => std::future::pending().await,
+                }
+            }
+            Ok(self.device.clone())
+        };
+        let timeout = async {
+            smol::Timer::after(timeout).await;
+            Err(ConnectionError::ReconnectTimeout(timeout))
+        };
+        wait.or(timeout).await
+    }
+
+    /// Reset the USB port the cable is attached to, then wait for it to show up
+    /// again. See [`cables::reset_port`].
+    ///
+    /// Any open handle to the cable (i.e. a backend) should be dropped first,
+    /// otherwise re-opening the cable may fail with the interface still
+    /// claimed.
+    pub async fn reset_port(&mut self, timeout: Duration) -> Result<nusb::DeviceInfo> {
+        self.connected = false;
+        self.device = cables::reset_port(&self.device, timeout).await?;
+        self.connected = true;
+        Ok(self.device.clone())
+    }
+
+    fn is_same_cable(&self, info: &nusb::DeviceInfo) -> bool {
+        cables::is_same_cable(info, &self.device)
+    }
+}
+
+/// Stand-in for a backend that has been released. All IO fails.
+pub(crate) struct Detached;
+
+#[async_trait::async_trait]
+impl Backend for Detached {
+    async fn tms(&mut self, _: &mut dyn Buffer, _: jtag::Path) -> Result<()> {
+        Err(ConnectionError::Disconnected.into())
+    }
+
+    async fn bytes(
+        &mut self,
+        _: &mut dyn Buffer,
+        _: Option<jtag::Path>,
+        _: Data<'_>,
+        _: Option<jtag::Path>,
+    ) -> Result<()> {
+        Err(ConnectionError::Disconnected.into())
+    }
+
+    async fn bits(
+        &mut self,
+        _: &mut dyn Buffer,
+        _: Option<jtag::Path>,
+        _: u32,
+        _: Bits<u8>,
+        _: Option<jtag::Path>,
+    ) -> Result<()> {
+        Err(ConnectionError::Disconnected.into())
+    }
+
+    async fn tdo_bits(
+        &mut self,
+        _: &mut dyn Buffer,
+        _: Option<jtag::Path>,
+        _: u32,
+        _: Bits<u8>,
+        _: Option<jtag::Path>,
+    ) -> Result<()> {
+        Err(ConnectionError::Disconnected.into())
+    }
+
+    async fn flush(&mut self, _: &mut dyn Buffer) -> Result<()> {
+        Err(ConnectionError::Disconnected.into())
+    }
+}
@@ -1,18 +1,34 @@
 mod backend;
+pub mod buffers;
 pub mod cables;
+mod cancel;
 pub mod controller;
 pub mod devices;
+mod error;
+pub mod fake;
 pub mod ftdi;
+pub mod gentle;
+pub mod hotplug;
 pub mod jtag;
-pub mod units;
+mod progress;
+pub mod trace;
 pub mod usb_blaster;
-mod utils;
+pub mod vcd;
+pub mod words;
 pub mod xpc;
 
+pub use nafa_util::{BitString, Hex, HexDump, ShortHex, SpaceHex, units};
+
 pub use crate::{
-    backend::{Backend, Buffer, Data, ScratchBuffer},
-    controller::{Command, Controller, detect_chain},
-    utils::{Hex, ShortHex, SpaceHex},
+    backend::{Backend, Buffer, Data, Frequency, ScratchBuffer, Template},
+    cancel::CancellationToken,
+    controller::{
+        Chain, Command, CommandBatch, Controller, DetectOptions, Prepared, Reads, Tap, TapSelector,
+        Timeouts, UnknownDevices, detect_chain, detect_chain_with,
+    },
+    error::{Error, Result},
+    progress::ProgressSink,
+    words::{WordOrder, WordsExt},
 };
 
 pub fn timeout<T>(duration: std::time::Duration, val: T) -> impl Future<Output = T> {
@@ -0,0 +1,40 @@
+//! Progress of long transfers, i.e. programming or reading back a bitstream.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+/// Receives progress from a [`Controller`](crate::Controller), see
+/// [`Controller::set_progress`](crate::Controller::set_progress).
+///
+/// Only commands created with a `*_with_notification` constructor report
+/// bytes, so a single progress bar can follow the transfer that matters.
+pub trait ProgressSink: Send {
+    /// `bytes` more were sent to the cable.
+    fn written(&mut self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// `bytes` more were read back.
+    fn read(&mut self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// A new step of a longer operation started, i.e. `"program"` after
+    /// `"shutdown"`.
+    fn phase(&mut self, name: &str) {
+        let _ = name;
+    }
+}
+
+/// Counts bytes written and read together, to poll from another task.
+impl ProgressSink for Arc<AtomicUsize> {
+    fn written(&mut self, bytes: usize) {
+        self.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn read(&mut self, bytes: usize) {
+        self.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
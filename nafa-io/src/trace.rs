@@ -0,0 +1,539 @@
+//! A transcript of everything sent to a backend, for debugging protocol quirks
+//! (i.e. the IR replication of a Zynq) after the fact.
+//!
+//! The transcript is JSON lines, one [`Event`] per [`Backend`] call. Events are
+//! written on each flush, once the TDO they read is known. A [`Replay`] plays a
+//! transcript back, so code driving a real target can be tested without it.
+
+use std::{collections::VecDeque, io::Write, path::Path, time::Duration};
+
+use facet::Facet;
+
+use crate::{
+    Backend, Buffer, Error, Result, ScratchBuffer, ShortHex,
+    backend::{Data, Frequency},
+    error::Context as _,
+    jtag,
+    units::{Bits, Bytes},
+};
+
+/// One call to a [`Backend`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Facet)]
+pub struct Event {
+    /// Name of the [`Backend`] method, i.e. `bytes`.
+    pub op: String,
+    /// TMS path clocked before the data, one `0` or `1` per clock. For `tms`,
+    /// the path itself.
+    #[facet(default)]
+    pub before: Option<String>,
+    /// TMS path clocked after the data.
+    #[facet(default)]
+    pub after: Option<String>,
+    /// TDI in hex, in the order it is shifted (LSB of the first byte first).
+    #[facet(default)]
+    pub tdi: Option<String>,
+    /// Constant TDI level, for [`Data::ConstantTx`].
+    #[facet(default)]
+    pub fill: Option<bool>,
+    /// Bytes for `bytes`, bits for `bits` and `tdo_bits`, clocks for
+    /// `idle_clocks`, and microseconds for `wait`.
+    #[facet(default)]
+    pub len: Option<usize>,
+    /// TDO in hex, for calls that read.
+    #[facet(default)]
+    pub tdo: Option<String>,
+    /// Why a `flush` failed. TDO of the calls before it is missing.
+    #[facet(default)]
+    pub error: Option<String>,
+}
+
+// The constructors for calls also return how many bytes the call reads.
+impl Event {
+    fn new(op: &str) -> Self {
+        Self {
+            op: op.into(),
+            ..Default::default()
+        }
+    }
+
+    fn paths(mut self, before: Option<jtag::Path>, after: Option<jtag::Path>) -> Self {
+        self.before = before.map(|p| p.to_string());
+        self.after = after.map(|p| p.to_string());
+        self
+    }
+
+    fn tms(path: jtag::Path) -> (Self, usize) {
+        (Self::new("tms").paths(Some(path), None), 0)
+    }
+
+    fn bytes(before: Option<jtag::Path>, data: Data, after: Option<jtag::Path>) -> (Self, usize) {
+        let mut event = Self::new("bytes").paths(before, after);
+        event.len = Some(data.len());
+        let read = match data {
+            Data::Tx(tdi) => {
+                event.tdi = Some(hex(tdi));
+                0
+            }
+            Data::TxRx(tdi) => {
+                event.tdi = Some(hex(tdi));
+                tdi.len()
+            }
+            Data::Rx(Bytes(len)) => len,
+            Data::ConstantTx(fill, _) => {
+                event.fill = Some(fill);
+                0
+            }
+        };
+        (event, read)
+    }
+
+    fn bits(
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+        tdo: bool,
+    ) -> (Self, usize) {
+        let op = if tdo { "tdo_bits" } else { "bits" };
+        let mut event = Self::new(op).paths(before, after);
+        event.tdi = Some(bits_hex(data, len));
+        event.len = Some(len.0.into());
+        let read = if tdo {
+            usize::from(len.0).div_ceil(8)
+        } else {
+            0
+        };
+        (event, read)
+    }
+
+    fn idle_clocks(count: usize) -> (Self, usize) {
+        let mut event = Self::new("idle_clocks");
+        event.len = Some(count);
+        (event, 0)
+    }
+
+    fn wait(duration: Duration) -> Self {
+        let mut event = Self::new("wait");
+        event.len = Some(duration.as_micros().try_into().unwrap_or(usize::MAX));
+        event
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    format!("{:#}", ShortHex(data))
+}
+
+fn unhex(s: &str) -> Result<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return Err(Error::InvalidInput(format!("odd length hex string {s:?}")));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&s[i..i + 2], 16)?))
+        .collect()
+}
+
+/// The lines of a hex dump of TDI around where `expected` and `actual` first
+/// differ, empty if they don't.
+fn tdi_diff(expected: &str, actual: &str) -> String {
+    const WIDTH: usize = 16;
+    let (Ok(expected), Ok(actual)) = (unhex(expected), unhex(actual)) else {
+        return String::new();
+    };
+    let differs = expected.iter().zip(&actual).position(|(e, a)| e != a);
+    let Some(first) = differs
+        .or_else(|| (expected.len() != actual.len()).then(|| expected.len().min(actual.len())))
+    else {
+        return String::new();
+    };
+    let start = first / WIDTH * WIDTH;
+    let dump = |data: &[u8]| {
+        let line = data.get(start..).unwrap_or_default();
+        let line = &line[..line.len().min(WIDTH)];
+        crate::HexDump::new(line)
+            .width(WIDTH)
+            .offset(start)
+            .ascii(true)
+            .to_string()
+    };
+    format!(
+        "\nfirst TDI difference at byte {first}\nexpected: {}\nactual:   {}",
+        dump(&expected),
+        dump(&actual),
+    )
+}
+
+/// `data`, truncated to `len` bits, as little-endian bytes.
+fn bits_hex(data: u32, len: Bits<u8>) -> String {
+    let bytes = usize::from(len.0).div_ceil(8);
+    let masked = data & u32::MAX.checked_shr(32 - u32::from(len.0)).unwrap_or(0);
+    hex(&masked.to_le_bytes()[..bytes])
+}
+
+/// Wraps a backend, writing every call to a transcript.
+///
+/// Command templates are not recorded, so this backend does not support them.
+pub struct Recorder<B> {
+    inner: B,
+    out: Box<dyn Write + Send>,
+    /// Read data of the inner backend, until it's split among the events.
+    scratch: ScratchBuffer,
+    /// Events since the last flush, with how many bytes each one reads.
+    pending: Vec<(Event, usize)>,
+}
+
+impl<B: Backend> Recorder<B> {
+    pub fn new(inner: B, out: Box<dyn Write + Send>) -> Self {
+        Self {
+            inner,
+            out,
+            scratch: ScratchBuffer::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn write(out: &mut dyn Write, event: &Event) -> Result<()> {
+        let line = facet_json::to_string(event)
+            .map_err(|e| Error::Protocol(format!("failed to serialize event: {e}")))?;
+        writeln!(out, "{line}")?;
+        Ok(())
+    }
+}
+
+/// Collects read data for a wrapping backend, while progress still goes to the
+/// real buffer.
+pub(crate) struct Capture<'a> {
+    scratch: &'a mut ScratchBuffer,
+    outer: &'a mut dyn Buffer,
+}
+
+impl<'a> Capture<'a> {
+    pub(crate) fn new(scratch: &'a mut ScratchBuffer, outer: &'a mut dyn Buffer) -> Self {
+        Self { scratch, outer }
+    }
+}
+
+impl Buffer for Capture<'_> {
+    fn extend(&mut self, size: usize, scratch: usize) -> &mut [u8] {
+        self.scratch.extend(size, scratch)
+    }
+
+    fn notify_write(&mut self, size: usize) {
+        self.outer.notify_write(size);
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: Backend> Backend for Recorder<B> {
+    async fn tms(&mut self, buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
+        self.pending.push(Event::tms(path));
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.tms(buf, path).await
+    }
+
+    async fn bytes(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: Data<'_>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.pending.push(Event::bytes(before, data, after));
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.bytes(buf, before, data, after).await
+    }
+
+    async fn bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.pending
+            .push(Event::bits(before, data, len, after, false));
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.bits(buf, before, data, len, after).await
+    }
+
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.pending
+            .push(Event::bits(before, data, len, after, true));
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.tdo_bits(buf, before, data, len, after).await
+    }
+
+    async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
+        let result = (self.inner)
+            .flush(&mut Capture::new(&mut self.scratch, buf))
+            .await;
+
+        let data = self.scratch.data();
+        let mut offset = 0;
+        for (mut event, read) in self.pending.drain(..) {
+            if read != 0 && result.is_ok() {
+                let tdo = data.get(offset..offset + read).unwrap_or_default();
+                event.tdo = Some(hex(tdo));
+                offset += read;
+            }
+            Self::write(&mut self.out, &event)?;
+        }
+        let mut event = Event::new("flush");
+        event.error = result.as_ref().err().map(|e| e.to_string());
+        Self::write(&mut self.out, &event)?;
+        self.out.flush()?;
+
+        buf.extend(data.len(), 0).copy_from_slice(data);
+        self.scratch.clear();
+        result
+    }
+
+    async fn idle_clocks(&mut self, buf: &mut dyn Buffer, count: usize) -> Result<()> {
+        self.pending.push(Event::idle_clocks(count));
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.idle_clocks(buf, count).await
+    }
+
+    async fn wait(&mut self, buf: &mut dyn Buffer, duration: Duration) -> Result<()> {
+        // the inner backend flushes on its own, so resolve everything queued first
+        self.flush(buf).await?;
+        Self::write(&mut self.out, &Event::wait(duration))?;
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.wait(buf, duration).await
+    }
+
+    fn frequency(&self) -> Option<Frequency> {
+        self.inner.frequency()
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<u32> {
+        self.inner.set_frequency(hz).await
+    }
+
+    async fn self_test(&mut self) -> Result<()> {
+        self.inner.self_test().await
+    }
+}
+
+/// Plays back a transcript written by a [`Recorder`]: every call has to match
+/// the next event, and reads return the recorded TDO. Waits return
+/// immediately.
+pub struct Replay {
+    events: VecDeque<Event>,
+    /// Index of the next event, for error messages.
+    index: usize,
+    /// TDO of the calls since the last flush.
+    read: Vec<u8>,
+}
+
+impl Replay {
+    pub fn new(events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+            index: 0,
+            read: Vec::new(),
+        }
+    }
+
+    /// Parse a transcript. Blank lines are skipped.
+    pub fn parse(data: &str) -> Result<Self> {
+        let events = data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                facet_json::from_str(line).map_err(|e| {
+                    Error::InvalidInput(format!("invalid event on line {}: {e}", idx + 1))
+                })
+            })
+            .collect::<Result<Vec<Event>>>()?;
+        Ok(Self::new(events))
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&data).with_context(|| format!("in {}", path.display()))
+    }
+
+    /// Events not played back yet. Tests should check this is zero at the end.
+    pub fn remaining(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Check `actual` is the next event, ignoring what was read, and queue
+    /// `read` bytes of its TDO.
+    fn expect(&mut self, (actual, read): (Event, usize)) -> Result<()> {
+        let index = self.index;
+        let Some(expected) = self.events.pop_front() else {
+            return Err(Error::Protocol(format!(
+                "replay: event {index} not in transcript: {actual:?}"
+            )));
+        };
+        self.index += 1;
+        let recorded = Event {
+            tdo: None,
+            error: None,
+            ..expected.clone()
+        };
+        if recorded != actual {
+            let mut msg = format!(
+                "replay: event {index} differs\nexpected: {expected:?}\nactual:   {actual:?}"
+            );
+            if let (Some(expected), Some(actual)) = (&recorded.tdi, &actual.tdi) {
+                msg += &tdi_diff(expected, actual);
+            }
+            return Err(Error::Protocol(msg));
+        }
+        if let Some(error) = expected.error {
+            return Err(Error::Protocol(format!(
+                "replay: recorded error at event {index}: {error}"
+            )));
+        }
+        if read != 0 {
+            let Some(tdo) = &expected.tdo else {
+                return Err(Error::Protocol(format!(
+                    "replay: event {index} has no recorded TDO"
+                )));
+            };
+            let tdo = unhex(tdo)?;
+            if tdo.len() != read {
+                return Err(Error::Protocol(format!(
+                    "replay: event {index} recorded {} bytes of TDO, expected {read}",
+                    tdo.len()
+                )));
+            }
+            self.read.extend(tdo);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Replay {
+    async fn tms(&mut self, _buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
+        self.expect(Event::tms(path))
+    }
+
+    async fn bytes(
+        &mut self,
+        _buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: Data<'_>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.expect(Event::bytes(before, data, after))
+    }
+
+    async fn bits(
+        &mut self,
+        _buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.expect(Event::bits(before, data, len, after, false))
+    }
+
+    async fn tdo_bits(
+        &mut self,
+        _buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        self.expect(Event::bits(before, data, len, after, true))
+    }
+
+    async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
+        self.expect((Event::new("flush"), 0))?;
+        buf.extend(self.read.len(), 0).copy_from_slice(&self.read);
+        self.read.clear();
+        Ok(())
+    }
+
+    async fn idle_clocks(&mut self, _buf: &mut dyn Buffer, count: usize) -> Result<()> {
+        self.expect(Event::idle_clocks(count))
+    }
+
+    async fn wait(&mut self, buf: &mut dyn Buffer, duration: Duration) -> Result<()> {
+        self.flush(buf).await?;
+        self.expect((Event::wait(duration), 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jtag::{PATHS, State};
+
+    use super::*;
+
+    const TRANSCRIPT: &str = r#"
+{"op":"tms","before":"11111"}
+{"op":"bytes","before":"100","after":"10","tdi":"A55A","len":2}
+{"op":"tdo_bits","before":"1100","after":"1","tdi":"3F","len":6,"tdo":"11"}
+{"op":"bytes","len":4,"tdo":"93D06303"}
+{"op":"flush"}
+"#;
+
+    #[test]
+    fn test_replay() {
+        smol::block_on(async {
+            let mut replay = Replay::parse(TRANSCRIPT).unwrap();
+            let buf = &mut ScratchBuffer::new();
+            let dr = PATHS[State::RunTestIdle][State::ShiftDR];
+            let dr_exit = PATHS[State::Exit1DR][State::RunTestIdle];
+            let ir = PATHS[State::RunTestIdle][State::ShiftIR];
+            let ir_exit = PATHS[State::Exit1IR][State::UpdateIR];
+
+            replay.tms(buf, jtag::Path::RESET).await.unwrap();
+            let tdi = Data::Tx(&[0xa5, 0x5a]);
+            (replay.bytes(buf, Some(dr), tdi, Some(dr_exit)).await).unwrap();
+            // bits past `len` are not part of the shift
+            (replay
+                .tdo_bits(buf, Some(ir), 0xff, Bits(6), Some(ir_exit))
+                .await)
+                .unwrap();
+            (replay.bytes(buf, None, Data::Rx(Bytes(4)), None).await).unwrap();
+            assert!(buf.data().is_empty());
+            replay.flush(buf).await.unwrap();
+            assert_eq!(buf.data(), [0x11, 0x93, 0xd0, 0x63, 0x03]);
+            assert_eq!(replay.remaining(), 0);
+        });
+    }
+
+    #[test]
+    fn test_replay_mismatch() {
+        smol::block_on(async {
+            let mut replay = Replay::parse(TRANSCRIPT).unwrap();
+            let buf = &mut ScratchBuffer::new();
+            let dr = PATHS[State::RunTestIdle][State::ShiftDR];
+
+            replay.tms(buf, jtag::Path::RESET).await.unwrap();
+            let tdi = Data::Tx(&[0xa5, 0x5b]);
+            let err = replay.bytes(buf, Some(dr), tdi, None).await.unwrap_err();
+            assert!(err.to_string().contains("event 1 differs"), "{err}");
+            let diff = err.to_string();
+            let diff = diff
+                .split_once("first TDI difference at byte 1\n")
+                .unwrap()
+                .1;
+            let lines: Vec<_> = diff.lines().map(str::trim_end).collect();
+            let pad = " ".repeat(3 * 14 + 2);
+            assert_eq!(
+                lines,
+                [format!("expected: 0000: A5 5A{pad}.Z"), format!("actual:   0000: A5 5B{pad}.["),]
+            );
+        });
+    }
+}
@@ -1,10 +1,9 @@
 use std::time::Duration;
 
-use eyre::Result;
 use nusb::transfer::{ControlIn, ControlType, Recipient};
 
 use crate::{
-    Backend, Buffer, Data, jtag,
+    Backend, Buffer, Data, Result, jtag,
     units::{Bits, Bytes},
 };
 
@@ -29,7 +28,8 @@ mod bitbang {
 #[derive(Clone, Copy)]
 enum Read {
     Bytes(u8),
-    Bits,
+    /// Up to 8 bits read one at a time, packed into a single byte.
+    Bits(u8),
 }
 
 const MAX_READ_WRITE_LEN: usize = 0b111111;
@@ -148,6 +148,24 @@ impl Backend for Device {
             .await
     }
 
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let mut remaining = len.0;
+        while remaining != 0 {
+            let n = remaining.min(8);
+            self.read_buf.push(Read::Bits(n));
+            remaining -= n;
+        }
+        self.bits_internal(buf, before, data, len, after, true)
+            .await
+    }
+
     async fn bytes(
         &mut self,
         buf: &mut dyn Buffer,
@@ -188,7 +206,7 @@ impl Backend for Device {
                 if let Some(last) = last {
                     buf.notify_write(1);
                     if read {
-                        self.read_buf.push(Read::Bits);
+                        self.read_buf.push(Read::Bits(8));
                     }
                     self.bits_internal(buf, None, last.into(), Bits(8), after, read)
                         .await?;
@@ -233,7 +251,7 @@ impl Backend for Device {
     async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
         let it = self.read_buf.iter().copied().map(|r| match r {
             Read::Bytes(len) => len.into(),
-            Read::Bits => 1,
+            Read::Bits(_) => 1,
         });
         let total_read_len: usize = it.sum();
 
@@ -1,12 +1,13 @@
 use std::time::Duration;
 
-use eyre::Result;
 use futures_lite::{AsyncReadExt, AsyncWriteExt};
 use nusb::{
     io::EndpointRead,
     transfer::{self, Bulk, In, Out},
 };
 
+use crate::Result;
+
 pub(super) struct Device {
     pub(super) iface: nusb::Interface,
 }
@@ -37,9 +38,9 @@ impl Device {
                     reader.read_exact(into).await?;
                     buf = rest;
                 }
-                super::Read::Bits => {
+                super::Read::Bits(len) => {
                     let (into, rest) = buf.split_first_mut().unwrap();
-                    *into = read_bits(&mut reader).await?;
+                    *into = read_bits(&mut reader, len).await?;
                     buf = rest;
                 }
             }
@@ -60,11 +61,12 @@ impl Device {
     }
 }
 
-async fn read_bits(reader: &mut EndpointRead<Bulk>) -> Result<u8> {
+async fn read_bits(reader: &mut EndpointRead<Bulk>, len: u8) -> Result<u8> {
     let mut read_buffer = [0; 8];
-    reader.read_exact(&mut read_buffer).await?;
+    let read_buffer = &mut read_buffer[..len.into()];
+    reader.read_exact(read_buffer).await?;
     let mut ret = 0;
-    for (idx, byte) in read_buffer.into_iter().enumerate() {
+    for (idx, byte) in read_buffer.iter().enumerate() {
         if byte & 1 == 1 {
             ret |= 1 << idx;
         }
@@ -0,0 +1,327 @@
+//! Writes the JTAG signals of everything sent to a backend as a VCD file, to
+//! look at in a waveform viewer (i.e. GTKWave).
+//!
+//! The waveform is what the calls mean, not what the cable does: TCK runs at a
+//! constant rate of one clock per two time units, waits are not shown, and TDI
+//! is `x` wherever the value does not matter (during TMS paths and reads).
+
+use std::{io::Write, time::Duration};
+
+use crate::{
+    Backend, Buffer, Result, ScratchBuffer,
+    backend::{Data, Frequency},
+    jtag,
+    trace::Capture,
+    units::{Bits, Bytes},
+};
+
+const TCK: char = '!';
+const TMS: char = '"';
+const TDI: char = '#';
+const TDO: char = '$';
+
+/// TDI of a shift.
+enum Tdi {
+    Data(Vec<u8>),
+    Constant(bool),
+    Unknown,
+}
+
+/// One call, until its TDO is known.
+enum Op {
+    Tms(jtag::Path),
+    Shift {
+        before: Option<jtag::Path>,
+        tdi: Tdi,
+        len: Bits<usize>,
+        after: Option<jtag::Path>,
+        /// Offset into the read data of the TDO, if this shift reads.
+        read: Option<usize>,
+    },
+}
+
+/// Wraps a backend, writing the waveform of every call to a VCD file.
+///
+/// Command templates are not recorded, so this backend does not support them.
+pub struct Vcd<B> {
+    inner: B,
+    out: Box<dyn Write + Send>,
+    /// Read data of the inner backend, until it's matched up with the clocks.
+    scratch: ScratchBuffer,
+    /// Calls since the last flush.
+    pending: Vec<Op>,
+    /// Bytes read by `pending`.
+    read_len: usize,
+    time: u64,
+    /// Last value written for each of TMS, TDI, and TDO.
+    last: [char; 3],
+}
+
+impl<B: Backend> Vcd<B> {
+    /// Wrap `inner`, writing the VCD header to `out` immediately.
+    pub fn new(inner: B, mut out: Box<dyn Write + Send>) -> Result<Self> {
+        writeln!(out, "$version nafa $end")?;
+        writeln!(out, "$timescale 1 ns $end")?;
+        writeln!(out, "$scope module jtag $end")?;
+        for (id, name) in [(TCK, "tck"), (TMS, "tms"), (TDI, "tdi"), (TDO, "tdo")] {
+            writeln!(out, "$var wire 1 {id} {name} $end")?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        writeln!(out, "#0\n$dumpvars\n0{TCK}\nx{TMS}\nx{TDI}\nx{TDO}\n$end")?;
+        Ok(Self {
+            inner,
+            out,
+            scratch: ScratchBuffer::new(),
+            pending: Vec::new(),
+            read_len: 0,
+            time: 0,
+            last: ['x'; 3],
+        })
+    }
+
+    fn shift(
+        &mut self,
+        before: Option<jtag::Path>,
+        tdi: Tdi,
+        len: Bits<usize>,
+        after: Option<jtag::Path>,
+        read: bool,
+    ) {
+        let read = read.then(|| {
+            let offset = self.read_len;
+            self.read_len += len.0.div_ceil(8);
+            offset
+        });
+        self.pending.push(Op::Shift {
+            before,
+            tdi,
+            len,
+            after,
+            read,
+        });
+    }
+
+    /// One TCK period, changing the signals on the falling edge.
+    fn clock(&mut self, tms: bool, tdi: Option<bool>, tdo: Option<bool>) -> Result<()> {
+        let bit = |b: Option<bool>| match b {
+            Some(true) => '1',
+            Some(false) => '0',
+            None => 'x',
+        };
+        writeln!(self.out, "#{}\n0{TCK}", self.time)?;
+        let values = [bit(Some(tms)), bit(tdi), bit(tdo)];
+        for ((id, value), last) in [TMS, TDI, TDO].into_iter().zip(values).zip(&mut self.last) {
+            if *last != value {
+                writeln!(self.out, "{value}{id}")?;
+                *last = value;
+            }
+        }
+        writeln!(self.out, "#{}\n1{TCK}", self.time + 1)?;
+        self.time += 2;
+        Ok(())
+    }
+
+    fn path(&mut self, path: jtag::Path) -> Result<()> {
+        for tms in path {
+            self.clock(tms, None, None)?;
+        }
+        Ok(())
+    }
+
+    /// Write the clocks of `op`, with TDO from `read`.
+    fn write(&mut self, op: &Op, read: &[u8]) -> Result<()> {
+        let bit = |data: &[u8], idx: usize| data.get(idx / 8).map(|b| b >> (idx % 8) & 1 == 1);
+        let (before, tdi, len, after, offset) = match op {
+            Op::Tms(path) => return self.path(*path),
+            Op::Shift {
+                before,
+                tdi,
+                len,
+                after,
+                read,
+            } => (before, tdi, len, after, read),
+        };
+
+        if let Some(path) = before {
+            self.path(*path)?;
+        }
+        let mut after = after.map(|p| p.into_iter());
+        for idx in 0..len.0 {
+            let tdi = match tdi {
+                Tdi::Data(data) => bit(data, idx),
+                Tdi::Constant(value) => Some(*value),
+                Tdi::Unknown => None,
+            };
+            let tdo = offset.and_then(|offset| bit(read.get(offset..)?, idx));
+            // the last bit is shifted on the first clock of the path out
+            let tms = match &mut after {
+                Some(path) if idx + 1 == len.0 => path.next().unwrap_or(false),
+                _ => false,
+            };
+            self.clock(tms, tdi, tdo)?;
+        }
+        for tms in after.into_iter().flatten() {
+            self.clock(tms, None, None)?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: Backend> Backend for Vcd<B> {
+    async fn tms(&mut self, buf: &mut dyn Buffer, path: jtag::Path) -> Result<()> {
+        self.pending.push(Op::Tms(path));
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.tms(buf, path).await
+    }
+
+    async fn bytes(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: Data<'_>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let (tdi, read) = match data {
+            Data::Tx(tdi) => (Tdi::Data(tdi.to_vec()), false),
+            Data::TxRx(tdi) => (Tdi::Data(tdi.to_vec()), true),
+            Data::Rx(_) => (Tdi::Unknown, true),
+            Data::ConstantTx(value, _) => (Tdi::Constant(value), false),
+        };
+        let len = Bytes(data.len()).into();
+        self.shift(before, tdi, len, after, read);
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.bytes(buf, before, data, after).await
+    }
+
+    async fn bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let tdi = Tdi::Data(data.to_le_bytes().to_vec());
+        self.shift(before, tdi, len.into_(), after, false);
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.bits(buf, before, data, len, after).await
+    }
+
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        let tdi = Tdi::Data(data.to_le_bytes().to_vec());
+        self.shift(before, tdi, len.into_(), after, true);
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.tdo_bits(buf, before, data, len, after).await
+    }
+
+    async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
+        let result = (self.inner)
+            .flush(&mut Capture::new(&mut self.scratch, buf))
+            .await;
+
+        // TDO is unknown if the flush failed
+        let mut read = std::mem::take(&mut self.scratch);
+        if result.is_err() {
+            read.clear();
+        }
+        for op in std::mem::take(&mut self.pending) {
+            self.write(&op, read.data())?;
+        }
+        self.out.flush()?;
+
+        buf.extend(read.data().len(), 0)
+            .copy_from_slice(read.data());
+        read.clear();
+        self.scratch = read;
+        self.read_len = 0;
+        result
+    }
+
+    async fn idle_clocks(&mut self, buf: &mut dyn Buffer, count: usize) -> Result<()> {
+        self.shift(None, Tdi::Constant(true), Bits(count), None, false);
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.idle_clocks(buf, count).await
+    }
+
+    async fn wait(&mut self, buf: &mut dyn Buffer, duration: Duration) -> Result<()> {
+        // the inner backend flushes on its own, so resolve everything queued first
+        self.flush(buf).await?;
+        let buf = &mut Capture::new(&mut self.scratch, buf);
+        self.inner.wait(buf, duration).await
+    }
+
+    fn frequency(&self) -> Option<Frequency> {
+        self.inner.frequency()
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<u32> {
+        self.inner.set_frequency(hz).await
+    }
+
+    async fn self_test(&mut self) -> Result<()> {
+        self.inner.self_test().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::trace::Replay;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_shift() {
+        let transcript = r#"
+{"op":"tdo_bits","after":"10","tdi":"05","len":3,"tdo":"06"}
+{"op":"flush"}
+"#;
+        smol::block_on(async {
+            let out = Shared::default();
+            let replay = Replay::parse(transcript).unwrap();
+            let mut vcd = Vcd::new(replay, Box::new(out.clone())).unwrap();
+            let buf = &mut ScratchBuffer::new();
+            let exit = jtag::PATHS[jtag::State::Exit1DR][jtag::State::RunTestIdle];
+            vcd.tdo_bits(buf, None, 0b101, Bits(3), Some(exit))
+                .await
+                .unwrap();
+            vcd.flush(buf).await.unwrap();
+            assert_eq!(buf.data(), [0b110]);
+
+            let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+            let changes = out.split("$enddefinitions $end\n").nth(1).unwrap();
+            let expected = [
+                "#0\n$dumpvars\n0!\nx\"\nx#\nx$\n$end",
+                // tms 0, tdi 1, tdo 0
+                "#0\n0!\n0\"\n1#\n0$\n#1\n1!",
+                "#2\n0!\n0#\n1$\n#3\n1!",
+                // last bit, leaving shift-dr
+                "#4\n0!\n1\"\n1#\n#5\n1!",
+                "#6\n0!\n0\"\nx#\nx$\n#7\n1!\n",
+            ];
+            assert_eq!(changes, expected.join("\n"));
+        });
+    }
+}
@@ -0,0 +1,90 @@
+//! Words out of data returned by [`Controller::run`](crate::Controller::run).
+//!
+//! Returned data is bytes in the order they were shifted, bit 0 of byte 0
+//! first. Devices disagree on how that maps onto their registers, so the
+//! mapping is spelled out with a [`WordOrder`] instead of byte swapping and
+//! bit reversing at each use.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WordOrder {
+    pub endian: Endian,
+    /// Reverse the bits of the whole word, after putting the bytes together.
+    pub reverse_bits: bool,
+}
+
+impl WordOrder {
+    /// Bit 0 of the word is shifted first. Most JTAG data registers, like
+    /// IDCODE.
+    pub const LSB_FIRST: Self = Self {
+        endian: Endian::Little,
+        reverse_bits: false,
+    };
+    /// The top bit of the word is shifted first, like the Xilinx configuration
+    /// interface.
+    pub const MSB_FIRST: Self = Self {
+        endian: Endian::Little,
+        reverse_bits: true,
+    };
+}
+
+pub trait Word: Copy {
+    const BYTES: usize;
+    fn from_bytes(bytes: &[u8], order: WordOrder) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {$(
+        impl Word for $t {
+            const BYTES: usize = size_of::<$t>();
+            fn from_bytes(bytes: &[u8], order: WordOrder) -> Self {
+                let bytes = bytes.try_into().expect("called with exactly BYTES bytes");
+                let word = match order.endian {
+                    Endian::Little => <$t>::from_le_bytes(bytes),
+                    Endian::Big => <$t>::from_be_bytes(bytes),
+                };
+                if order.reverse_bits { word.reverse_bits() } else { word }
+            }
+        }
+    )*};
+}
+impl_word!(u16, u32, u64);
+
+pub trait WordsExt {
+    /// Every whole word in the data. Bytes left over at the end, short of a
+    /// word, are skipped.
+    fn words<W: Word>(&self, order: WordOrder) -> impl ExactSizeIterator<Item = W> + '_;
+}
+
+impl WordsExt for [u8] {
+    fn words<W: Word>(&self, order: WordOrder) -> impl ExactSizeIterator<Item = W> + '_ {
+        self.chunks_exact(W::BYTES)
+            .map(move |chunk| W::from_bytes(chunk, order))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words() {
+        let data = [0x01, 0x80, 0x00, 0x00, 0xff];
+        let lsb: Vec<u32> = data.words(WordOrder::LSB_FIRST).collect();
+        assert_eq!(lsb, [0x0000_8001]);
+        let msb: Vec<u32> = data.words(WordOrder::MSB_FIRST).collect();
+        assert_eq!(msb, [0x8001_0000]);
+        let be: Vec<u16> = data
+            .words(WordOrder {
+                endian: Endian::Big,
+                reverse_bits: false,
+            })
+            .collect();
+        assert_eq!(be, [0x0180, 0x0000]);
+    }
+}
@@ -1,10 +1,9 @@
 use std::time::Duration;
 
-use eyre::Result;
 use nusb::transfer::{self, ControlIn, ControlOut, ControlType, Recipient};
 use tracing::{info, instrument};
 
-use crate::{Backend, Buffer, Hex, backend::Data, jtag, units::Bits};
+use crate::{Backend, Buffer, Hex, Result, backend::Data, jtag, units::Bits};
 
 pub mod firmware;
 
@@ -38,7 +37,8 @@ pub struct Device {
     /// └─────────┘└─────────────────────────────────────┘
     /// ```
     cmd_buf: Vec<u8>,
-    cmd_read_len: usize,
+    /// Number of TDO bits sampled by `cmd_buf`. Returned packed LSB-first.
+    cmd_read_bits: usize,
     num_bits: u8,
 }
 
@@ -114,7 +114,7 @@ impl Device {
         Ok(Self {
             iface,
             cmd_buf: Vec::new(),
-            cmd_read_len: 0,
+            cmd_read_bits: 0,
             num_bits: 0,
         })
     }
@@ -135,6 +135,9 @@ impl Device {
         };
 
         *b0 |= (tms as u8) << (self.num_bits + 4) | (tdi as u8) << self.num_bits;
+        if tdo {
+            self.cmd_read_bits += 1;
+        }
         *b1 |= (tdo as u8) << (self.num_bits + 4) | (tck as u8) << self.num_bits;
 
         self.num_bits = (self.num_bits + 1) & 3;
@@ -238,17 +241,24 @@ async fn shift(
         .writer(128)
         .with_num_transfers(8)
         .with_write_timeout(S);
-    writer.write_all(in_buf).await?;
-    writer.flush().await?;
-
-    if let Some(out) = out_buf {
-        iface
-            .endpoint::<transfer::Bulk, transfer::In>(0x86)?
-            .reader(128)
-            .with_num_transfers(8)
-            .with_read_timeout(S)
-            .read_exact(out)
-            .await?;
+    let write = async {
+        writer.write_all(in_buf).await?;
+        writer.flush().await
+    };
+
+    // The cable starts returning TDO as soon as it has shifted enough bits, so
+    // read while still writing instead of waiting for the whole write to
+    // finish.
+    match out_buf {
+        Some(out) => {
+            let mut reader = iface
+                .endpoint::<transfer::Bulk, transfer::In>(0x86)?
+                .reader(128)
+                .with_num_transfers(8)
+                .with_read_timeout(S);
+            futures_lite::future::try_zip(write, reader.read_exact(out)).await?;
+        }
+        None => write.await?,
     }
 
     Ok(())
@@ -306,9 +316,6 @@ impl Backend for Device {
                         self.add_bit(tms, byte >> 7 & 1 != 0, tdo);
                     }
 
-                    if tdo {
-                        self.cmd_read_len += 1;
-                    }
                     buf.notify_write(1);
                     self.maybe_flush(buf).await?;
                 }
@@ -333,9 +340,6 @@ impl Backend for Device {
                         self.add_bit(tms, tdi, tdo);
                     }
 
-                    if tdo {
-                        self.cmd_read_len += 1;
-                    }
                     self.maybe_flush(buf).await?;
                 }
             }
@@ -395,28 +399,87 @@ impl Backend for Device {
         Ok(())
     }
 
+    #[instrument(skip_all)]
+    async fn tdo_bits(
+        &mut self,
+        buf: &mut dyn Buffer,
+        before: Option<jtag::Path>,
+        mut data: u32,
+        len: Bits<u8>,
+        after: Option<jtag::Path>,
+    ) -> Result<()> {
+        if let Some(path) = before {
+            for tms in path {
+                self.add_bit(tms, true, false);
+            }
+        }
+
+        let len = match after {
+            Some(_) => len.0 - 1,
+            None => len.0,
+        };
+
+        let tms = false;
+        for _ in 0..len {
+            self.add_bit(tms, data & 1 == 1, true);
+            data >>= 1;
+        }
+
+        if let Some(path) = after {
+            let mut it = path.into_iter();
+            if let Some(tms) = it.next() {
+                self.add_bit(tms, data & 1 == 1, true);
+            }
+            for tms in it {
+                self.add_bit(tms, true, false);
+            }
+        }
+
+        // TDO is packed without gaps, so a partial byte has to be the last
+        // thing read in a transfer
+        if !self.cmd_read_bits.is_multiple_of(8) {
+            self.flush(buf).await?;
+        }
+        self.maybe_flush(buf).await?;
+        Ok(())
+    }
+
     #[instrument(skip_all)]
     async fn flush(&mut self, buf: &mut dyn Buffer) -> Result<()> {
         if self.num_bits == 0 {
             self.add_bit_internal(false, false, false, false);
         }
 
-        let buf = match self.cmd_read_len {
+        let read_len = self.cmd_read_bits.div_ceil(8);
+        let mut buf = match read_len {
             0 => None,
-            _ => Some(buf.extend(self.cmd_read_len, 0)),
+            _ => Some(buf.extend(read_len, 0)),
         };
         let in_bits = (self.cmd_buf.len() - 2) / 2 * 4 + usize::from(self.num_bits);
         let in_bits = in_bits.try_into().unwrap();
         tracing::debug!(
             in_bits,
             in_len = self.cmd_buf.len(),
-            expect_read = self.cmd_read_len,
+            expect_read = read_len,
             data = %crate::ShortHex(&self.cmd_buf),
         );
-        shift(&self.iface, 0xa6, in_bits, &self.cmd_buf, buf).await?;
+        shift(
+            &self.iface,
+            0xa6,
+            in_bits,
+            &self.cmd_buf,
+            buf.as_deref_mut(),
+        )
+        .await?;
+        let partial = self.cmd_read_bits % 8;
+        if let Some([.., last]) = buf
+            && partial != 0
+        {
+            *last &= (1 << partial) - 1;
+        }
 
         self.cmd_buf.clear();
-        self.cmd_read_len = 0;
+        self.cmd_read_bits = 0;
         self.num_bits = 0;
 
         Ok(())
@@ -1,6 +1,6 @@
 use eyre::Result;
 use facet::Facet;
-use nafa_io::Controller;
+use nafa_io::{Backend, Controller, ScratchBuffer};
 use zerocopy::FromBytes;
 
 #[derive(FromBytes, Facet)]
@@ -72,7 +72,10 @@ fn get_slice<const N: usize, T>(data: &[T], offset: usize) -> Option<&[T; N]> {
 }
 
 async fn read_pf_jtag_device(cont: &mut Controller) -> Result<PF> {
-    let (buf, b) = cont.backend();
+    cont.with_raw_backend(read_pf_jtag_device_raw).await
+}
+
+async fn read_pf_jtag_device_raw(b: &mut dyn Backend, buf: &mut ScratchBuffer) -> Result<PF> {
     let read_design_info = *get_slice(&read_design_info::<48>(b, buf).await?, 0).unwrap();
     let e1command = device_integrity_and_dsn::<32, 16>(b, buf).await?;
     Ok(PF {
@@ -0,0 +1,210 @@
+//! `Display` helpers for register values and shifted data.
+//!
+//! [`ShortHex`], [`SpaceHex`], and [`BitString`] truncate long data by default,
+//! so they stay readable in logs. The precision sets a different limit (in
+//! bytes, or bits for [`BitString`]), and the alternate flag prints everything:
+//! `{:.16}` shows at most 16 bytes, `{:#}` shows all of them.
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::units::Bits;
+
+pub struct Hex<T>(pub T);
+impl Display for Hex<u8> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:02X}", self.0)
+    }
+}
+impl Display for Hex<u16> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:04X}", self.0)
+    }
+}
+impl Display for Hex<u32> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:08X}", self.0)
+    }
+}
+
+const MAX_DISPLAY: usize = 64;
+
+/// How many items to show, given the formatter flags and the default limit.
+fn limit(f: &Formatter, default: usize) -> usize {
+    match f.precision() {
+        _ if f.alternate() => usize::MAX,
+        Some(max) => max,
+        None => default,
+    }
+}
+
+pub struct ShortHex<'a>(pub &'a [u8]);
+impl Display for ShortHex<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let max = limit(f, MAX_DISPLAY);
+        for e in self.0.iter().take(max) {
+            write!(f, "{:02X}", e)?;
+        }
+        if self.0.len() > max {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct SpaceHex<'a>(pub &'a [u8]);
+impl Display for SpaceHex<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let max = limit(f, MAX_DISPLAY);
+        for e in self.0.iter().take(max) {
+            write!(f, "{:02X} ", e)?;
+        }
+        if self.0.len() > max {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Multi-line dump, each line starting with the offset of its first byte:
+///
+/// ```text
+/// 0000: 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F 10
+/// 0010: 11 12
+/// ```
+///
+/// Never truncated. There's no newline after the last line.
+///
+/// With [`HexDump::ascii`], each line ends with the printable bytes as text,
+/// `.` for the rest:
+///
+/// ```text
+/// 0000: 58 49 4C 49 4E 58 00 01  XILINX..
+/// ```
+pub struct HexDump<'a> {
+    data: &'a [u8],
+    width: usize,
+    offset: usize,
+    ascii: bool,
+}
+
+impl<'a> HexDump<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            width: 16,
+            offset: 0,
+            ascii: false,
+        }
+    }
+
+    /// Bytes per line, 16 by default.
+    pub fn width(self, width: usize) -> Self {
+        assert!(width > 0, "hex dump width must be at least 1");
+        Self { width, ..self }
+    }
+
+    /// Address of the first byte, for dumps of a part of something larger.
+    pub fn offset(self, offset: usize) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// Add a column with the bytes as ASCII, off by default.
+    pub fn ascii(self, ascii: bool) -> Self {
+        Self { ascii, ..self }
+    }
+}
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let last = self.offset + self.data.len().saturating_sub(1);
+        let digits = (last.max(1).ilog(16) as usize + 1).max(4);
+        for (idx, line) in self.data.chunks(self.width).enumerate() {
+            if idx != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:0digits$X}:", self.offset + idx * self.width)?;
+            for e in line {
+                write!(f, " {:02X}", e)?;
+            }
+            if self.ascii {
+                // line up with full lines
+                let pad = 3 * (self.width - line.len());
+                write!(f, "{:pad$}  ", "")?;
+                for e in line {
+                    let c = if e.is_ascii_graphic() || *e == b' ' {
+                        *e as char
+                    } else {
+                        '.'
+                    };
+                    write!(f, "{c}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The first `len` bits of `data`, as shifted (LSB of the first byte first),
+/// printed like a number: the last bit shifted is on the left.
+///
+/// Truncation keeps the bits shifted first, the rest is replaced by `...`.
+pub struct BitString<'a> {
+    data: &'a [u8],
+    len: usize,
+}
+
+impl<'a> BitString<'a> {
+    pub fn new(data: &'a [u8], len: Bits<usize>) -> Self {
+        assert!(len.0 <= data.len() * 8, "bit string longer than its data");
+        Self { data, len: len.0 }
+    }
+}
+
+impl Display for BitString<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let shown = self.len.min(limit(f, MAX_DISPLAY * 8));
+        if shown < self.len {
+            write!(f, "...")?;
+        }
+        for idx in (0..shown).rev() {
+            let bit = self.data[idx / 8] >> (idx % 8) & 1;
+            write!(f, "{bit}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        let data: Vec<u8> = (1..=18).collect();
+        assert_eq!(format!("{:.2}", ShortHex(&data)), "0102...");
+        assert_eq!(format!("{:#}", ShortHex(&data)).len(), 36);
+        assert_eq!(format!("{:.1}", SpaceHex(&data)), "01 ...");
+        assert_eq!(
+            HexDump::new(&data[..10])
+                .width(4)
+                .offset(0xfff8)
+                .to_string(),
+            "0FFF8: 01 02 03 04\n0FFFC: 05 06 07 08\n10000: 09 0A",
+        );
+        assert_eq!(
+            HexDump::new(b"nafa\x00\xff")
+                .width(4)
+                .ascii(true)
+                .to_string(),
+            "0000: 6E 61 66 61  nafa\n0004: 00 FF        ..",
+        );
+        assert_eq!(
+            BitString::new(&[0b1001_0110, 1], Bits(9)).to_string(),
+            "110010110"
+        );
+        assert_eq!(
+            format!("{:.3}", BitString::new(&[0b110], Bits(8))),
+            "...110"
+        );
+    }
+}
@@ -0,0 +1,6 @@
+//! Small helpers shared by the nafa crates, without any dependencies.
+
+pub mod hex;
+pub mod units;
+
+pub use crate::hex::{BitString, Hex, HexDump, ShortHex, SpaceHex};
@@ -0,0 +1,277 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::{Add, AddAssign, Div, Mul, Rem, Sub, SubAssign},
+    str::FromStr,
+};
+
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bits<T>(pub T);
+
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bytes<T>(pub T);
+
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Words32<T>(pub T);
+
+impl<T> From<Bytes<T>> for Bits<T>
+where
+    T: Mul<usize, Output = T>,
+{
+    fn from(value: Bytes<T>) -> Self {
+        Self(value.0 * 8)
+    }
+}
+
+impl<T> From<Words32<T>> for Bytes<T>
+where
+    T: Mul<usize, Output = T>,
+{
+    fn from(value: Words32<T>) -> Self {
+        Self(value.0 * 4)
+    }
+}
+
+impl<T> Bits<T>
+where
+    T: Copy,
+    T: Div<usize, Output = T>,
+    T: Rem<usize, Output = T>,
+{
+    pub fn as_pair(self) -> (Bytes<T>, Bits<T>) {
+        (Bytes(self.0 / 8), Bits(self.0 % 8))
+    }
+}
+
+// note: `.into()` can't use the trait because there's a
+// ```
+// impl From<T> for T { ... }
+// ```
+// in the standard library
+
+impl<T> Bits<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Bits<U> {
+        Bits(f(self.0))
+    }
+    pub fn into_<U: From<T>>(self) -> Bits<U> {
+        Bits(self.0.into())
+    }
+}
+impl<T> Bytes<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Bytes<U> {
+        Bytes(f(self.0))
+    }
+    pub fn into_<U: From<T>>(self) -> Bytes<U> {
+        Bytes(self.0.into())
+    }
+}
+impl<T> Words32<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Words32<U> {
+        Words32(f(self.0))
+    }
+    pub fn into_<U: From<T>>(self) -> Words32<U> {
+        Words32(self.0.into())
+    }
+}
+
+macro_rules! impl_arith {
+    ($($unit:ident),*) => {$(
+        impl<T: Add<Output = T>> Add for $unit<T> {
+            type Output = Self;
+            fn add(self, rhs: Self) -> Self {
+                Self(self.0 + rhs.0)
+            }
+        }
+
+        impl<T: AddAssign> AddAssign for $unit<T> {
+            fn add_assign(&mut self, rhs: Self) {
+                self.0 += rhs.0;
+            }
+        }
+
+        impl<T: Sub<Output = T>> Sub for $unit<T> {
+            type Output = Self;
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0 - rhs.0)
+            }
+        }
+
+        impl<T: SubAssign> SubAssign for $unit<T> {
+            fn sub_assign(&mut self, rhs: Self) {
+                self.0 -= rhs.0;
+            }
+        }
+
+        /// Scale by a plain number, i.e. `Bytes(4) * count`.
+        impl<T: Mul<Output = T>> Mul<T> for $unit<T> {
+            type Output = Self;
+            fn mul(self, rhs: T) -> Self {
+                Self(self.0 * rhs)
+            }
+        }
+    )*};
+}
+impl_arith!(Bits, Bytes, Words32);
+
+impl<T: Display> Display for Bits<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bits", self.0)
+    }
+}
+
+impl<T: Display> Display for Bytes<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes", self.0)
+    }
+}
+
+impl<T: Display> Display for Words32<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} words", self.0)
+    }
+}
+
+/// A [`Bytes`] that isn't a whole number of [`Words32`], see
+/// `Words32::try_from`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotWholeWords {
+    pub bytes: usize,
+}
+
+impl Display for NotWholeWords {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes is not a whole number of 32-bit words",
+            self.bytes
+        )
+    }
+}
+
+impl std::error::Error for NotWholeWords {}
+
+/// Failed to parse a unit, see the [`FromStr`] impls.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseUnitError(String);
+
+impl Display for ParseUnitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseUnitError {}
+
+/// Parse `s` as a number, decimal or with a `0x` prefix, optionally followed
+/// by `unit` as [`Display`] writes it (or its singular).
+fn parse_count<T>(
+    s: &str,
+    unit: &str,
+    from_str_radix: fn(&str, u32) -> Result<T, std::num::ParseIntError>,
+) -> Result<T, ParseUnitError> {
+    let trimmed = s.trim();
+    let number = (trimmed.strip_suffix(unit))
+        .or_else(|| trimmed.strip_suffix(&unit[..unit.len() - 1]))
+        .unwrap_or(trimmed)
+        .trim_end()
+        .replace('_', "");
+    let parsed = match number.strip_prefix("0x") {
+        Some(hex) => from_str_radix(hex, 16),
+        None => from_str_radix(&number, 10),
+    };
+    parsed.map_err(|e| ParseUnitError(format!("invalid number of {unit} '{s}': {e}")))
+}
+
+macro_rules! impl_int {
+    ($($t:ty),*) => {$(
+        impl Bits<$t> {
+            /// Whole bytes needed to hold this many bits.
+            pub const fn bytes_ceil(self) -> Bytes<$t> {
+                Bytes(self.0.div_ceil(8))
+            }
+        }
+
+        impl Bytes<$t> {
+            /// Whole 32-bit words needed to hold this many bytes.
+            pub const fn words32_ceil(self) -> Words32<$t> {
+                Words32(self.0.div_ceil(4))
+            }
+
+            /// This many bytes as bits, or `None` on overflow.
+            pub const fn checked_bits(self) -> Option<Bits<$t>> {
+                match self.0.checked_mul(8) {
+                    Some(bits) => Some(Bits(bits)),
+                    None => None,
+                }
+            }
+        }
+
+        impl TryFrom<Bytes<$t>> for Words32<$t> {
+            type Error = NotWholeWords;
+
+            fn try_from(value: Bytes<$t>) -> Result<Self, Self::Error> {
+                if value.0 % 4 == 0 {
+                    Ok(Words32(value.0 / 4))
+                } else {
+                    Err(NotWholeWords {
+                        bytes: value.0 as usize,
+                    })
+                }
+            }
+        }
+
+        impl FromStr for Bits<$t> {
+            type Err = ParseUnitError;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                parse_count(s, "bits", <$t>::from_str_radix).map(Self)
+            }
+        }
+
+        impl FromStr for Bytes<$t> {
+            type Err = ParseUnitError;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                parse_count(s, "bytes", <$t>::from_str_radix).map(Self)
+            }
+        }
+
+        impl FromStr for Words32<$t> {
+            type Err = ParseUnitError;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                parse_count(s, "words", <$t>::from_str_radix).map(Self)
+            }
+        }
+    )*};
+}
+impl_int!(u8, u16, u32, u64, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(Bits(13_usize).bytes_ceil(), Bytes(2));
+        assert_eq!(Bytes(5_usize).words32_ceil(), Words32(2));
+        assert_eq!(Words32::try_from(Bytes(8_usize)), Ok(Words32(2)));
+        assert_eq!(
+            Words32::try_from(Bytes(9_usize)),
+            Err(NotWholeWords { bytes: 9 })
+        );
+        assert_eq!(Bytes(u32::MAX).checked_bits(), None);
+        assert_eq!(Bytes(3_usize) + Bytes(1) - Bytes(2), Bytes(2));
+        assert_eq!(Words32(3_usize) * 4, Words32(12));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("4096".parse(), Ok(Bytes(4096_usize)));
+        assert_eq!("0x1000 bytes".parse(), Ok(Bytes(4096_usize)));
+        assert_eq!("1 bit".parse(), Ok(Bits(1_u8)));
+        assert_eq!("1_000words".parse(), Ok(Words32(1000_u32)));
+        assert_eq!(Bytes(12_usize).to_string().parse(), Ok(Bytes(12_usize)));
+        assert!("12 bits".parse::<Bytes<usize>>().is_err());
+        assert!("256".parse::<Bits<u8>>().is_err());
+    }
+}
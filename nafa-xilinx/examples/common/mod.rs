@@ -0,0 +1,50 @@
+//! Setup shared by the examples (and the HIL runner): open the first known
+//! cable and select the only device with known specifics, like the CLI does
+//! without `--usb` and `--jtag-idx`.
+
+use std::collections::HashMap;
+
+use eyre::{OptionExt as _, Result};
+use nafa_io::{
+    Controller,
+    cables::{self, KNOWN},
+    devices,
+};
+
+pub async fn open() -> Result<Controller> {
+    let is_known = |d: &nusb::DeviceInfo| {
+        (KNOWN.iter()).any(|c| c.vid == d.vendor_id() && c.pid == d.product_id())
+    };
+    let device = (nusb::list_devices().await?)
+        .find(is_known)
+        .ok_or_eyre("no known cable plugged in")?;
+    let mut backend = match cables::init(device, &cables::Options::default()).await {
+        Ok(backend) => backend,
+        Err(errs) => {
+            let errs: Vec<_> = errs.into_iter().map(eyre::Report::from).collect();
+            return Err(eyre::eyre!("failed to init cable: {errs:?}"));
+        }
+    };
+
+    let devices: HashMap<_, _> = devices::all().collect();
+    let chain = nafa_io::detect_chain(&mut *backend, &devices).await?;
+    let idx = (chain.default_target()).ok_or_eyre("no single known device on the chain")?;
+    let (before, active, after) = chain.split(idx).expect("idx is in bounds");
+    Ok(Controller::new(backend, before, active, after).await?)
+}
+
+/// One DRP register as a physical value, with the first transfer function if
+/// the register has several.
+#[allow(dead_code)]
+pub fn convert(
+    family: nafa_io::devices::Xilinx32Family,
+    addr: nafa_xilinx::_32bit::drp::Addr,
+    raw: u16,
+) -> Option<f32> {
+    use nafa_xilinx::_32bit::drp::Transfer;
+    match addr.transfer(family) {
+        Transfer::None => None,
+        Transfer::Exactly(f) => Some(f(raw)),
+        Transfer::OneOf(many) => many.first().map(|f| f(raw)),
+    }
+}
@@ -0,0 +1,34 @@
+//! Program a `.bin` bitstream into the FPGA of an Arty A7 (or any other
+//! board with a single 7-series device on the chain).
+//!
+//! ```text
+//! cargo run -p nafa-xilinx --example program_arty -- top.bin
+//! ```
+
+mod common;
+
+use eyre::{OptionExt as _, Result};
+use nafa_io::devices::Xilinx32Info;
+use nafa_xilinx::_32bit::actions;
+
+fn main() -> Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_eyre("usage: program_arty <bitstream.bin>")?;
+    // `.bin` files are MSB first, the cable shifts LSB first
+    let data: Vec<u8> = (std::fs::read(&path)?.iter())
+        .map(|b| b.reverse_bits())
+        .collect();
+
+    smol::block_on(async {
+        let mut cont = common::open().await?;
+        println!("programming {} ({} bytes)", cont.info().name, data.len());
+        let cont = (cont.typed::<Xilinx32Info>()).ok_or_eyre("selected device is not xilinx")?;
+
+        let stats = actions::program::run(cont, &data).await?;
+        println!("shutdown: {:?}", stats.time_shutdown);
+        println!(" program: {:?}", stats.time_program);
+        println!(" startup: {:?}", stats.time_startup);
+        Ok(())
+    })
+}
@@ -0,0 +1,50 @@
+//! Print the die temperature and supply voltages once a second.
+//!
+//! ```text
+//! cargo run -p nafa-xilinx --example xadc_monitor -- [count]
+//! ```
+
+mod common;
+
+use std::time::Duration;
+
+use eyre::{OptionExt as _, Result};
+use nafa_io::devices::Xilinx32Info;
+use nafa_xilinx::_32bit::{
+    actions,
+    drp::{Addr, Cmd, Command},
+};
+
+const SENSORS: [(&str, Addr, &str); 3] = [
+    ("temp", Addr::Temperature, "C"),
+    ("vccint", Addr::VccInt, "V"),
+    ("vccaux", Addr::VccAux, "V"),
+];
+
+fn main() -> Result<()> {
+    let count: Option<usize> = std::env::args().nth(1).map(|c| c.parse()).transpose()?;
+
+    smol::block_on(async {
+        let mut cont = common::open().await?;
+        for _ in 0..count.unwrap_or(usize::MAX) {
+            let mut cont =
+                (cont.typed::<Xilinx32Info>()).ok_or_eyre("selected device is not xilinx")?;
+            let family = cont.info().family;
+            let regs = SENSORS.map(|(_, addr, _)| Command {
+                cmd: Cmd::Read,
+                addr,
+                data: 0,
+            });
+            let values = actions::xadc::run(cont.reborrow(), regs).await?;
+
+            let mut line = String::new();
+            for ((name, addr, unit), raw) in SENSORS.iter().zip(values) {
+                let value = common::convert(family, *addr, raw).unwrap_or(f32::NAN);
+                line += &format!("{name}: {value:.3}{unit}  ");
+            }
+            println!("{}", line.trim_end());
+            smol::Timer::after(Duration::from_secs(1)).await;
+        }
+        Ok(())
+    })
+}
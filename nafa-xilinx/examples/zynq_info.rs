@@ -0,0 +1,23 @@
+//! Dump the JTAG and configuration registers of the PL of a Zynq as JSON. The
+//! ARM DAP next to it is skipped when picking the device.
+//!
+//! ```text
+//! cargo run -p nafa-xilinx --example zynq_info
+//! ```
+
+mod common;
+
+use eyre::{OptionExt as _, Result};
+use nafa_io::devices::Xilinx32Info;
+use nafa_xilinx::_32bit::actions;
+
+fn main() -> Result<()> {
+    smol::block_on(async {
+        let mut cont = common::open().await?;
+        let cont = (cont.typed::<Xilinx32Info>()).ok_or_eyre("selected device is not xilinx")?;
+        let info = actions::info::run(cont).await?;
+        facet_json::to_writer_std(std::io::stdout(), &info)?;
+        println!();
+        Ok(())
+    })
+}
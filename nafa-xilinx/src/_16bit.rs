@@ -0,0 +1,20 @@
+//! Spartan-6, whose configuration logic takes 16-bit words and packets
+//! (UG380) instead of the 32-bit ones of later families. The JTAG side is
+//! the same 6-bit TAP, without SLRs.
+
+use nafa_io::{controller::TypedController, devices::Xilinx16Info};
+
+pub mod actions;
+pub(crate) mod commands;
+mod io_utils;
+pub mod registers;
+
+pub type Controller<'a> = TypedController<'a, Xilinx16Info>;
+
+pub const fn to_wire_order(x: u16) -> [u8; 2] {
+    x.reverse_bits().to_le_bytes()
+}
+
+pub(crate) fn packets_to_wire_order(packets: impl IntoIterator<Item = u16>) -> Vec<u8> {
+    packets.into_iter().flat_map(to_wire_order).collect()
+}
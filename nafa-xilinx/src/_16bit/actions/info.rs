@@ -0,0 +1,124 @@
+use eyre::{Result, bail};
+use facet::Facet;
+use nafa_io::{WordOrder, WordsExt as _};
+
+use crate::{
+    _16bit::{
+        Controller, commands,
+        io_utils::{
+            read_device_register, read_device_register_word as device_register,
+            read_jtag_register as jtag_register,
+        },
+        registers::Addr,
+    },
+    dna,
+};
+
+#[derive(Facet)]
+pub struct S6 {
+    pub jtag: S6Jtag,
+    pub registers: S6Registers,
+}
+
+#[derive(Facet)]
+pub struct S6Jtag {
+    pub idcode: [u8; 4],
+    pub usercode: [u8; 4],
+    /// 57 bits
+    pub dna: [u8; 8],
+    pub user1: [u8; 4],
+    pub user2: [u8; 4],
+    pub user3: [u8; 4],
+    pub user4: [u8; 4],
+}
+
+#[derive(Facet)]
+pub struct S6Registers {
+    pub stat: u16,
+    pub ctl: u16,
+    pub cor1: u16,
+    pub cor2: u16,
+    pub idcode: u32,
+    pub bootsts: u16,
+    pub mode_reg: u16,
+    pub general: [u16; 5],
+}
+
+impl S6 {
+    pub async fn read(mut cont: Controller<'_>) -> Result<Self> {
+        let jtag = S6Jtag {
+            idcode: *jtag_register(cont.reborrow(), commands::IDCODE).await?,
+            usercode: *jtag_register(cont.reborrow(), commands::USERCODE).await?,
+            dna: dna::read_16bit(cont.borrow()).await?,
+            user1: *jtag_register(cont.reborrow(), commands::USER1).await?,
+            user2: *jtag_register(cont.reborrow(), commands::USER2).await?,
+            user3: *jtag_register(cont.reborrow(), commands::USER3).await?,
+            user4: *jtag_register(cont.reborrow(), commands::USER4).await?,
+        };
+        let mut general = [0; 5];
+        let general_addrs =
+            [Addr::General1, Addr::General2, Addr::General3, Addr::General4, Addr::General5];
+        for (reg, addr) in general.iter_mut().zip(general_addrs) {
+            *reg = device_register(cont.reborrow(), addr).await?;
+        }
+        let registers = S6Registers {
+            stat: device_register(cont.reborrow(), Addr::Stat).await?,
+            ctl: device_register(cont.reborrow(), Addr::Ctl).await?,
+            cor1: device_register(cont.reborrow(), Addr::Cor1).await?,
+            cor2: device_register(cont.reborrow(), Addr::Cor2).await?,
+            idcode: read_idcode(cont.reborrow()).await?,
+            bootsts: device_register(cont.reborrow(), Addr::Bootsts).await?,
+            mode_reg: device_register(cont.reborrow(), Addr::ModeReg).await?,
+            general,
+        };
+        Ok(Self { jtag, registers })
+    }
+}
+
+/// IDCODE is the only register two words long, high word first.
+async fn read_idcode(cont: Controller<'_>) -> Result<u32> {
+    let data = read_device_register(cont, Addr::Idcode, 2).await?;
+    let &[high, low] = &data.words::<u16>(WordOrder::MSB_FIRST).collect::<Vec<_>>()[..] else {
+        bail!("expected 2 IDCODE words, got {}", data.len() / 2);
+    };
+    Ok(u32::from(high) << 16 | u32::from(low))
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::{
+        devices::Xilinx16Info,
+        fake::{self, FakeDevice},
+        units::Bits,
+    };
+
+    use super::*;
+    use crate::_16bit::{commands::ir, to_wire_order};
+
+    #[test]
+    fn test_read() {
+        smol::block_on(async {
+            const XC6SLX9: u32 = 0x0400_1093;
+            // every configuration register reads as these two words
+            let cfg_out: Vec<u8> = [0x3018, 0x0400]
+                .into_iter()
+                .flat_map(to_wire_order)
+                .collect();
+            let dna = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0x01];
+            let device = FakeDevice::new(XC6SLX9, Bits(6), ir(commands::IDCODE))
+                .register(ir(commands::USERCODE), Bits(32), &[0xef, 0xbe, 0xad, 0xde])
+                .register(ir(commands::ISC_DNA), Bits(57), &dna)
+                .constant(ir(commands::CFG_OUT), Bits(32), &cfg_out);
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+            let cont = cont.typed::<Xilinx16Info>().unwrap();
+
+            let info = S6::read(cont).await.unwrap();
+            assert_eq!(u32::from_le_bytes(info.jtag.idcode), XC6SLX9);
+            assert_eq!(u32::from_le_bytes(info.jtag.usercode), 0xdead_beef);
+            assert_eq!(info.jtag.dna[..7], dna[..7]);
+            assert_eq!(info.registers.stat, 0x3018);
+            assert_eq!(info.registers.idcode, 0x3018_0400);
+            assert_eq!(info.registers.general, [0x3018; 5]);
+        });
+    }
+}
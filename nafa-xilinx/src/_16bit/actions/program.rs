@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use eyre::{Result, bail};
+use nafa_io::{Command, units::Bytes};
+
+use crate::{
+    _16bit::{
+        Controller,
+        commands::{self, ir},
+        io_utils::read_device_register_word,
+        registers::{Addr, Stat},
+    },
+    _32bit::IRCapture,
+};
+
+pub struct ProgramStats {
+    pub time_shutdown: Duration,
+    pub time_program: Duration,
+    pub time_startup: Duration,
+    /// STAT once the device started.
+    pub stat: Stat,
+}
+
+/// TCKs in Run-Test/Idle after JSTART, for the startup sequence to finish.
+const STARTUP_CLOCKS: Bytes<usize> = Bytes(2000 / 8);
+/// How long housecleaning after JPROGRAM may take.
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Startup is clocked by TCK above, this only covers STAT reads failing
+/// right after it.
+const STARTUP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Clear the configuration, shift in `data` (in wire order, like for the
+/// 32-bit families), and run the startup sequence. Fails with STAT if the
+/// device didn't start.
+pub async fn run(mut cont: Controller<'_>, data: &[u8]) -> Result<ProgramStats> {
+    let start = Instant::now();
+    cont.borrow().progress_phase("shutdown");
+    cont.borrow()
+        .run([Command::ir(ir(commands::JPROGRAM))])
+        .await?;
+    loop {
+        let capture = IRCapture::from_bits_retain(cont.borrow().capture_ir().await? as _);
+        if capture.contains(IRCapture::INIT) {
+            break;
+        }
+        if start.elapsed() > INIT_TIMEOUT {
+            bail!("INIT did not go high after JPROGRAM");
+        }
+    }
+    let end_shutdown = Instant::now();
+
+    cont.borrow().progress_phase("program");
+    cont.borrow()
+        .run([Command::ir(ir(commands::CFG_IN)), Command::dr_tx_with_notification(data)])
+        .await?;
+    let end_program = Instant::now();
+
+    cont.borrow().progress_phase("startup");
+    cont.borrow()
+        .run([Command::ir(ir(commands::JSTART)), Command::idle(STARTUP_CLOCKS)])
+        .await?;
+    let started = Instant::now();
+    let mut last = None;
+    let stat = loop {
+        if let Ok(stat) = read_device_register_word(cont.reborrow(), Addr::Stat).await {
+            let stat = Stat::from_bits_retain(stat);
+            if stat.started() {
+                break stat;
+            }
+            last = Some(stat);
+        }
+        if started.elapsed() > STARTUP_TIMEOUT {
+            match last {
+                Some(stat) => bail!("device did not start, STAT {stat}"),
+                None => bail!("device did not start, and STAT could not be read"),
+            }
+        }
+    };
+
+    Ok(ProgramStats {
+        time_shutdown: end_shutdown - start,
+        time_program: end_program - end_shutdown,
+        time_startup: end_program.elapsed(),
+        stat,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::{
+        devices::Xilinx16Info,
+        fake::{self, FakeDevice},
+        units::Bits,
+    };
+
+    use super::*;
+    use crate::_16bit::to_wire_order;
+
+    const XC6SLX9: u32 = 0x0400_1093;
+
+    /// A Spartan-6 whose STAT reads as `stat`, with INIT high.
+    fn device(stat: Stat) -> FakeDevice {
+        let stat = to_wire_order(stat.bits());
+        FakeDevice::new(XC6SLX9, Bits(6), ir(commands::IDCODE))
+            .ir_capture(0b110011)
+            .constant(ir(commands::CFG_OUT), Bits(16), &stat)
+    }
+
+    #[test]
+    fn test_run() {
+        smol::block_on(async {
+            let mut cont = fake::controller(vec![device(Stat::STARTED)]).await.unwrap();
+            let cont = cont.typed::<Xilinx16Info>().unwrap();
+            let stats = run(cont, &[0xff; 16]).await.unwrap();
+            assert_eq!(stats.stat, Stat::STARTED);
+
+            let mut cont = fake::controller(vec![device(Stat::INIT_B)]).await.unwrap();
+            let cont = cont.typed::<Xilinx16Info>().unwrap();
+            let err = run(cont, &[0xff; 16]).await.err().unwrap();
+            assert!(err.to_string().contains("DONE is low"), "{err}");
+        });
+    }
+}
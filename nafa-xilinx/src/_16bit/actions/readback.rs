@@ -0,0 +1,67 @@
+use eyre::Result;
+use nafa_io::{Command, units::Bytes};
+
+use crate::_16bit::{
+    Controller,
+    commands::{self, ir},
+    packets_to_wire_order,
+    registers::{Addr, CmdCode, NOOP, OpCode, SYNC, type1, type2},
+};
+
+/// Read back `words` 16-bit words of configuration memory, starting at the
+/// first frame, in wire order.
+pub async fn run(cont: Controller<'_>, words: usize) -> Result<&[u8]> {
+    let count = u32::try_from(words)?;
+    let packets = SYNC.into_iter().chain([NOOP]).chain([
+        type1(OpCode::Write, Addr::Cmd, 1),
+        CmdCode::Rcrc as u16,
+        NOOP,
+        type1(OpCode::Write, Addr::FarMaj, 2),
+        0x0000,
+        0x0000,
+        type1(OpCode::Write, Addr::Cmd, 1),
+        CmdCode::Rcfg as u16,
+        type1(OpCode::Read, Addr::Fdro, 0),
+    ]);
+    let packets = packets
+        .chain(type2(OpCode::Read, Addr::Fdro, count))
+        .chain([NOOP; 4]);
+    let readback = packets_to_wire_order(packets);
+
+    Ok(cont
+        .consume()
+        .run([
+            Command::ir(ir(commands::CFG_IN)),
+            Command::dr_tx(&readback),
+            Command::ir(ir(commands::CFG_OUT)),
+            Command::dr_rx_with_notification(Bytes(2 * words)),
+        ])
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::{
+        devices::Xilinx16Info,
+        fake::{self, FakeDevice},
+        units::Bits,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        smol::block_on(async {
+            const XC6SLX9: u32 = 0x0400_1093;
+            let frames: Vec<u8> = (0..64).collect();
+            let device = FakeDevice::new(XC6SLX9, Bits(6), ir(commands::IDCODE)).constant(
+                ir(commands::CFG_OUT),
+                Bits(8 * frames.len()),
+                &frames,
+            );
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+            let cont = cont.typed::<Xilinx16Info>().unwrap();
+            assert_eq!(run(cont, frames.len() / 2).await.unwrap(), frames);
+        });
+    }
+}
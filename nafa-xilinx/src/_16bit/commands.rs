@@ -0,0 +1,34 @@
+// from xc6slx9_tqg144.bsd
+#[repr(u8)]
+#[expect(non_camel_case_types)]
+#[allow(unused, clippy::upper_case_acronyms)]
+#[derive(Clone, Copy)]
+#[rustfmt::skip]
+pub enum Inst {
+    BYPASS      = 0b111111,
+    IDCODE      = 0b001001,
+    USERCODE    = 0b001000,
+    SAMPLE      = 0b000001,
+    EXTEST      = 0b001111,
+    HIGHZ       = 0b001010,
+    CFG_OUT     = 0b000100,
+    CFG_IN      = 0b000101,
+    USER1       = 0b000010,
+    USER2       = 0b000011,
+    USER3       = 0b011010,
+    USER4       = 0b011011,
+    JPROGRAM    = 0b001011,
+    JSTART      = 0b001100,
+    JSHUTDOWN   = 0b001101,
+    ISC_ENABLE  = 0b010000,
+    ISC_PROGRAM = 0b010001,
+    ISC_NOOP    = 0b010100,
+    ISC_DISABLE = 0b010110,
+    ISC_DNA     = 0b110000,
+}
+
+pub use self::Inst::*;
+
+pub const fn ir(inst: Inst) -> u32 {
+    inst as u8 as u32
+}
@@ -0,0 +1,44 @@
+use eyre::{OptionExt as _, Result};
+use nafa_io::{Command, WordOrder, WordsExt as _, units::Bytes};
+
+use super::{
+    Controller,
+    commands::{self, Inst, ir},
+    packets_to_wire_order,
+    registers::{Addr, NOOP, OpCode, SYNC, type1},
+};
+
+/// Read `count` words of `addr` through CFG_IN/CFG_OUT, in wire order.
+pub async fn read_device_register(cont: Controller<'_>, addr: Addr, count: u16) -> Result<&[u8]> {
+    let packets = SYNC
+        .into_iter()
+        .chain([NOOP, type1(OpCode::Read, addr, count), NOOP, NOOP]);
+    let tiny_bitstream = packets_to_wire_order(packets);
+
+    let data = cont
+        .consume()
+        .run([
+            Command::ir(ir(commands::CFG_IN)),
+            Command::dr_tx(&tiny_bitstream),
+            Command::ir(ir(commands::CFG_OUT)),
+            Command::dr_rx(Bytes(2 * usize::from(count))),
+        ])
+        .await?;
+    Ok(data)
+}
+
+pub async fn read_device_register_word(cont: Controller<'_>, addr: Addr) -> Result<u16> {
+    let data = read_device_register(cont, addr, 1).await?;
+    (data.words(WordOrder::MSB_FIRST).next()).ok_or_eyre("no register word read")
+}
+
+pub async fn read_jtag_register<const N: usize>(
+    cont: Controller<'_>,
+    inst: Inst,
+) -> Result<&[u8; N]> {
+    let slice = cont
+        .consume()
+        .run([Command::ir(ir(inst)), Command::dr_rx(Bytes(N))])
+        .await?;
+    Ok(slice.try_into()?)
+}
@@ -0,0 +1,191 @@
+//! Spartan-6 configuration packets and registers (UG380 chapter 5).
+
+use std::fmt;
+
+use bitflags::bitflags;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(unused)]
+pub enum OpCode {
+    Noop = 0,
+    Read = 1,
+    Write = 2,
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(unused)]
+pub enum Addr {
+    Crc = 0x00,
+    FarMaj = 0x01,
+    FarMin = 0x02,
+    Fdri = 0x03,
+    Fdro = 0x04,
+    Cmd = 0x05,
+    Ctl = 0x06,
+    Mask = 0x07,
+    Stat = 0x08,
+    Lout = 0x09,
+    Cor1 = 0x0a,
+    Cor2 = 0x0b,
+    PwrdnReg = 0x0c,
+    Flr = 0x0d,
+    Idcode = 0x0e,
+    Cwdt = 0x0f,
+    HcOptReg = 0x10,
+    Csbo = 0x12,
+    General1 = 0x13,
+    General2 = 0x14,
+    General3 = 0x15,
+    General4 = 0x16,
+    General5 = 0x17,
+    ModeReg = 0x18,
+    PuGwe = 0x19,
+    PuGts = 0x1a,
+    Mfwr = 0x1b,
+    CclkFreq = 0x1c,
+    SeuOpt = 0x1d,
+    ExpSign = 0x1e,
+    RdbkSign = 0x1f,
+    Bootsts = 0x20,
+    EyeMask = 0x21,
+    CbcReg = 0x22,
+}
+
+/// Values written to [`Addr::Cmd`].
+#[repr(u16)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(unused)]
+pub enum CmdCode {
+    Null = 0,
+    Wcfg = 1,
+    Mfw = 2,
+    Lfrm = 3,
+    Rcfg = 4,
+    Start = 5,
+    Rcrc = 7,
+    Aghigh = 8,
+    Grestore = 10,
+    Shutdown = 11,
+    Desync = 13,
+    Iprog = 14,
+}
+
+pub const SYNC: [u16; 2] = [0xaa99, 0x5566];
+pub const NOOP: u16 = 0x2000;
+
+/// ```text
+/// [15:13] header type
+/// [12:11] opcode
+/// [10: 5] address
+/// [ 4: 0] word count
+/// ```
+pub const fn type1(op: OpCode, addr: Addr, word_count: u16) -> u16 {
+    let header = 1 << 13;
+    let opcode = ((op as u16) & 0x3) << 11;
+    let address = ((addr as u16) & 0x3f) << 5;
+    header | opcode | address | (word_count & 0x1f)
+}
+
+/// Header and 32-bit word count of a type 2 packet, to `addr` of the type 1
+/// packet before it.
+pub const fn type2(op: OpCode, addr: Addr, word_count: u32) -> [u16; 3] {
+    let header = 2 << 13 | ((op as u16) & 0x3) << 11 | ((addr as u16) & 0x3f) << 5;
+    [header, (word_count >> 16) as u16, word_count as u16]
+}
+
+bitflags! {
+    /// STAT (UG380 table 5-35).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Stat: u16 {
+        const CRC_ERROR    = 1 << 0;
+        const ID_ERROR     = 1 << 1;
+        const DCM_LOCK     = 1 << 2;
+        const GTS_CFG_B    = 1 << 3;
+        const GWE          = 1 << 4;
+        const GHIGH_B      = 1 << 5;
+        const DEC_ERROR    = 1 << 6;
+        const PART_SECURED = 1 << 7;
+        const INIT_B       = 1 << 12;
+        const DONE         = 1 << 13;
+        const IN_PWRDN     = 1 << 14;
+        const SWWD         = 1 << 15;
+
+        const _ = !0;
+    }
+}
+
+impl Stat {
+    /// Bits set once the startup sequence has finished.
+    pub const STARTED: Self = Self::DONE
+        .union(Self::GWE)
+        .union(Self::GTS_CFG_B)
+        .union(Self::INIT_B);
+
+    pub fn started(self) -> bool {
+        self.contains(Self::STARTED)
+    }
+
+    /// Everything in this status that would keep the device from starting,
+    /// most likely cause first.
+    pub fn problems(self) -> Vec<&'static str> {
+        let errors = [
+            (
+                Self::ID_ERROR,
+                "IDCODE in the bitstream doesn't match the device",
+            ),
+            (Self::CRC_ERROR, "CRC error in the bitstream"),
+            (Self::DEC_ERROR, "decryption failed, wrong key?"),
+        ];
+        let missing = [
+            (Self::INIT_B, "INIT_B is low"),
+            (Self::DONE, "DONE is low"),
+            (Self::GWE, "global write enable not released"),
+            (Self::GTS_CFG_B, "I/Os still tristated"),
+        ];
+        let errors = errors.into_iter().filter(|(flag, _)| self.contains(*flag));
+        let missing = missing
+            .into_iter()
+            .filter(|(flag, _)| !self.contains(*flag));
+        errors.chain(missing).map(|(_, msg)| msg).collect()
+    }
+}
+
+/// The raw value, then what's wrong with it, i.e. `0x1000 (DONE is low, ...)`.
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problems()[..] {
+            [] => write!(f, "{:#06x} (started)", self.bits()),
+            problems => write!(f, "{:#06x} ({})", self.bits(), problems.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packets() {
+        // from UG380 examples
+        assert_eq!(type1(OpCode::Read, Addr::Stat, 1), 0x2901);
+        assert_eq!(type1(OpCode::Write, Addr::Cmd, 1), 0x30a1);
+        assert_eq!(type1(OpCode::Read, Addr::Fdro, 0), 0x2880);
+        assert_eq!(
+            type2(OpCode::Read, Addr::Fdro, 0x1_2345),
+            [0x4880, 0x0001, 0x2345]
+        );
+    }
+
+    #[test]
+    fn test_stat() {
+        assert_eq!(Stat::STARTED.to_string(), "0x3018 (started)");
+        assert_eq!(
+            (Stat::STARTED | Stat::CRC_ERROR)
+                .difference(Stat::DONE)
+                .to_string(),
+            "0x1019 (CRC error in the bitstream, DONE is low)"
+        );
+    }
+}
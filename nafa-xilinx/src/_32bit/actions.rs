@@ -1,5 +1,12 @@
 pub mod bbram;
+pub mod config;
+pub mod efuse;
+pub mod frames;
 pub mod info;
 pub mod program;
 pub mod readback;
+pub mod reset;
+pub mod seu;
+pub mod verify;
+pub mod vio;
 pub mod xadc;
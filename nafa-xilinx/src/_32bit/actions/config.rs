@@ -0,0 +1,50 @@
+//! Read-modify-write of the [option registers](crate::_32bit::config).
+
+use eyre::{Result, bail};
+
+use crate::_32bit::{
+    Controller,
+    config::{self, ConfigRegister},
+    io_utils::{read_device_register_word, write_device_registers},
+    registers::Addr,
+};
+
+pub async fn read<R: ConfigRegister>(cont: Controller<'_>, slr: u8) -> Result<R> {
+    let bits = read_device_register_word(cont, slr, config::addr::<R>()).await?;
+    Ok(R::from_bits_retain(bits))
+}
+
+/// Read the register, change it with `f`, and write it back. Only the bits
+/// changed by `f` are written, through the MASK register for CTL0.
+///
+/// Returns the value read back afterwards. Fails if the changed bits didn't
+/// stick, i.e. because the security level forbids it.
+pub async fn modify<R: ConfigRegister>(
+    mut cont: Controller<'_>,
+    slr: u8,
+    f: impl FnOnce(R) -> R,
+) -> Result<R> {
+    let addr = config::addr::<R>();
+    let old = read::<R>(cont.reborrow(), slr).await?;
+    let new = f(old);
+    let changed = old.bits() ^ new.bits();
+    if changed == 0 {
+        return Ok(old);
+    }
+
+    write_device_registers(
+        cont.reborrow(),
+        slr,
+        &[(Addr::Mask, changed), (addr, new.bits())],
+    )
+    .await?;
+    let actual = read::<R>(cont, slr).await?;
+    if (actual.bits() ^ new.bits()) & changed != 0 {
+        bail!(
+            "{addr:?} is {:#010x} after writing {:#010x}",
+            actual.bits(),
+            new.bits()
+        );
+    }
+    Ok(actual)
+}
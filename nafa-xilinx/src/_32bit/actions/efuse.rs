@@ -0,0 +1,89 @@
+//! Reading the eFUSE registers over JTAG.
+//!
+//! Which registers exist, and how long they are, depends on the family
+//! (UG470 "eFUSE", UG570 "eFUSE"). 7-series has a 64-bit FUSE_DNA and no
+//! RSA hash, security or 128-bit user registers. FUSE_USER_128 is only on
+//! UltraScale+.
+
+use eyre::Result;
+use nafa_io::{devices::Xilinx32Family as Family, units::Bytes};
+
+use crate::_32bit::{
+    Controller,
+    commands::{self, master_on, shifted},
+    io_utils::read_jtag_register_into,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fuse {
+    Cntl,
+    Dna,
+    Key,
+    User,
+    User128,
+    Rsa,
+    Sec,
+}
+
+impl Fuse {
+    /// Length of the register on `family`, or `None` if it doesn't have it.
+    pub const fn len(self, family: Family) -> Option<Bytes<usize>> {
+        let s7 = matches!(family, Family::S7);
+        let len = match self {
+            Self::Cntl if s7 => 2,
+            Self::Cntl => 4,
+            Self::Dna if s7 => 8,
+            Self::Dna => 12,
+            Self::Key => 32,
+            Self::User => 4,
+            Self::User128 if matches!(family, Family::UP) => 16,
+            Self::Rsa | Self::Sec if s7 => return None,
+            Self::Rsa => 48,
+            Self::Sec => 2,
+            Self::User128 => return None,
+        };
+        Some(Bytes(len))
+    }
+
+    /// The instruction selecting the register in `active_slr`.
+    const fn ir(self, num_slr: u8, active_slr: u8) -> u32 {
+        let inst = match self {
+            Self::Cntl => commands::FUSE_CNTL,
+            Self::Dna => commands::FUSE_DNA,
+            Self::Key => commands::FUSE_KEY,
+            Self::Rsa => commands::FUSE_RSA,
+            Self::Sec => commands::FUSE_SEC,
+            Self::User => return master_on(commands::FUSE_USER, num_slr, active_slr),
+            Self::User128 => return master_on(commands::FUSE_USER_128, num_slr, active_slr),
+        };
+        shifted(inst, num_slr, active_slr)
+    }
+}
+
+/// Read `fuse` from `active_slr`, or `None` if the family doesn't have it.
+pub async fn read(cont: Controller<'_>, active_slr: u8, fuse: Fuse) -> Result<Option<Vec<u8>>> {
+    let info = cont.info();
+    let Some(len) = fuse.len(info.family) else {
+        return Ok(None);
+    };
+    let ir = fuse.ir(info.slr, active_slr);
+    let mut ret = vec![0; len.0];
+    read_jtag_register_into(cont, active_slr, ir, &mut ret).await?;
+    Ok(Some(ret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_len() {
+        assert_eq!(Fuse::Dna.len(Family::S7), Some(Bytes(8)));
+        assert_eq!(Fuse::Dna.len(Family::US), Some(Bytes(12)));
+        assert_eq!(Fuse::User128.len(Family::S7), None);
+        assert_eq!(Fuse::User128.len(Family::US), None);
+        assert_eq!(Fuse::User128.len(Family::UP), Some(Bytes(16)));
+        assert_eq!(Fuse::Rsa.len(Family::S7), None);
+        assert_eq!(Fuse::Sec.len(Family::UP), Some(Bytes(2)));
+    }
+}
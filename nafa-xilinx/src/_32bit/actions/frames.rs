@@ -0,0 +1,98 @@
+//! Writing configuration frames at a frame address, without a bitstream. The
+//! complement of [`super::readback::read_frame`], i.e. for patching block RAM
+//! contents or flipping bits for fault injection.
+//!
+//! The design keeps running while frames are written. Frames holding LUT RAM
+//! or SRL contents, or block RAM in use, can be overwritten by the design at
+//! the same time.
+
+use eyre::{Result, bail};
+use nafa_io::units::Words32;
+
+use crate::_32bit::{
+    Controller,
+    crc::Crc,
+    io_utils::{read_device_register_word, send_device_packets, write_one},
+    registers::{Addr, CmdCode, OpCode, Type1, type2},
+    status::Stat,
+};
+
+/// Write `frames`, any number of whole frames, starting at frame address
+/// `far`. The write is protected by a CRC, which is checked afterwards.
+pub async fn write(mut cont: Controller<'_>, far: u32, frames: &[u32]) -> Result<()> {
+    let frame = cont.info().family.frame_words();
+    if frames.is_empty() || !frames.len().is_multiple_of(frame.0) {
+        bail!(
+            "expected a multiple of {frame} to write, got {}",
+            Words32(frames.len())
+        );
+    }
+    let idcode = read_device_register_word(cont.reborrow(), 0, Addr::Idcode).await?;
+    let packets = write_sequence(idcode, far, frames, frame);
+    send_device_packets(cont.reborrow(), 0, packets).await?;
+
+    let stat = Stat::from_bits_retain(read_device_register_word(cont, 0, Addr::Stat).await?);
+    if stat.contains(Stat::CRC_ERROR) {
+        bail!("CRC error after writing frames, STAT: {stat}");
+    }
+    Ok(())
+}
+
+/// Configuration packets writing `frames` from `far` on, followed by a pad
+/// frame to flush the frame buffer, then desyncing.
+fn write_sequence(idcode: u32, far: u32, frames: &[u32], frame: Words32<usize>) -> Vec<u32> {
+    let mut crc = Crc::new(0);
+    let mut packets = vec![write_one(Addr::Cmd), CmdCode::Rcrc as u32, Type1::NOOP, Type1::NOOP];
+    let mut write = |packets: &mut Vec<u32>, addr: Addr, value: u32| {
+        packets.extend([write_one(addr), value]);
+        crc.update(addr as u8, value);
+    };
+    write(&mut packets, Addr::Idcode, idcode);
+    write(&mut packets, Addr::Cmd, CmdCode::Wcfg as u32);
+    write(&mut packets, Addr::Far, far);
+    packets.push(Type1::NOOP);
+
+    let pad = std::iter::repeat_n(0, frame.0);
+    let data = frames.iter().copied().chain(pad);
+    let len = frames.len() + frame.0;
+    packets.push(Type1::new(OpCode::Write, Addr::Fdri, Words32(0)).to_raw());
+    packets.push(type2(OpCode::Write, len as u32));
+    for word in data {
+        packets.push(word);
+        crc.update(Addr::Fdri as u8, word);
+    }
+
+    packets.extend([write_one(Addr::Crc), crc.value()]);
+    packets.extend([write_one(Addr::Cmd), CmdCode::Desync as u32]);
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_sequence() {
+        let frame = Words32(101);
+        let frames = vec![0x1234_5678; 2 * frame.0];
+        let packets = write_sequence(0x0362_d093, 0x0000_0100, &frames, frame);
+
+        let fdri = (packets.iter())
+            .position(|&p| p == Type1::new(OpCode::Write, Addr::Fdri, Words32(0)).to_raw())
+            .unwrap();
+        assert_eq!(packets[fdri + 1], type2(OpCode::Write, 3 * 101));
+        let data = &packets[fdri + 2..][..3 * 101];
+        assert_eq!(&data[..2 * 101], frames);
+        assert!(data[2 * 101..].iter().all(|&w| w == 0));
+
+        let tail = &packets[fdri + 2 + 3 * 101..];
+        assert_eq!(tail[0], write_one(Addr::Crc));
+        assert_eq!(&tail[2..], [write_one(Addr::Cmd), CmdCode::Desync as u32]);
+
+        // the CRC covers the data, so changing a single bit changes it
+        let mut flipped = frames.clone();
+        flipped[7] ^= 1 << 3;
+        let other = write_sequence(0x0362_d093, 0x0000_0100, &flipped, frame);
+        assert_ne!(other[fdri + 2 + 3 * 101 + 1], tail[1]);
+    }
+}
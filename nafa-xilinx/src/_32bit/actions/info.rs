@@ -1,13 +1,16 @@
-use eyre::Result;
+use eyre::{Result, eyre};
 use facet::Facet;
-use nafa_io::devices::Xilinx32Family;
+use nafa_io::{devices::Xilinx32Family, jtag::IdCode};
 
 use crate::_32bit::{
-    Controller, commands,
+    Controller,
+    actions::efuse::{self, Fuse},
+    commands,
     io_utils::{
         read_device_register_word as device_register,
+        read_device_register_words as device_registers,
         read_jtag_register_duplicated as jtag_duplicated, read_jtag_register_master as jtag_master,
-        read_jtag_register_shifted as jtag_shifted,
+        read_jtag_register_master_on as jtag_master_on,
     },
     registers::Addr,
 };
@@ -57,7 +60,7 @@ pub struct S7Jtag {
 pub struct S7JtagPerDevice {
     pub idcode: [u8; 4],
     pub usercode: [u8; 4],
-    pub fuse_user: [u8; 4],
+    pub fuse_user: Vec<u8>,
     pub user1: [u8; 4],
     pub user2: [u8; 4],
     pub user3: [u8; 4],
@@ -66,9 +69,11 @@ pub struct S7JtagPerDevice {
 
 #[derive(Facet)]
 pub struct S7JtagPerSlr {
-    pub cntl: [u8; 2],
-    pub fuse_dna: [u8; 8],
-    pub fuse_key: [u8; 32],
+    /// USERCODE of this SLR. The device USERCODE is the master's.
+    pub usercode: [u8; 4],
+    pub cntl: Vec<u8>,
+    pub fuse_dna: Vec<u8>,
+    pub fuse_key: Vec<u8>,
 }
 
 // shared with US/UP
@@ -82,8 +87,9 @@ pub struct USJtag {
 pub struct USJtagPerDevice {
     pub idcode: [u8; 4],
     pub usercode: [u8; 4],
-    pub fuse_user: [u8; 4],
-    pub fuse_user_128: [u8; 4],
+    pub fuse_user: Vec<u8>,
+    /// Only on UltraScale+.
+    pub fuse_user_128: Option<Vec<u8>>,
     pub user1: [u8; 4],
     pub user2: [u8; 4],
     pub user3: [u8; 4],
@@ -92,11 +98,13 @@ pub struct USJtagPerDevice {
 
 #[derive(Facet)]
 pub struct USJtagPerSlr {
-    pub cntl: [u8; 4],
-    pub fuse_dna: [u8; 12],
-    pub fuse_key: [u8; 32],
-    pub fuse_rsa: [u8; 48],
-    pub fuse_sec: [u8; 2],
+    /// USERCODE of this SLR. The device USERCODE is the master's.
+    pub usercode: [u8; 4],
+    pub cntl: Vec<u8>,
+    pub fuse_dna: Vec<u8>,
+    pub fuse_key: Vec<u8>,
+    pub fuse_rsa: Vec<u8>,
+    pub fuse_sec: Vec<u8>,
 }
 
 #[derive(Facet)]
@@ -119,23 +127,44 @@ pub struct RegistersPerSlr {
     pub bspi: u32,
 }
 
+impl RegistersPerSlr {
+    fn from_words(words: &[u32]) -> Self {
+        let [ctl0, stat, cor0, idcode, axss, cor1, wbstar, timer, bootsts, ctl1, bspi] =
+            words.try_into().expect("one word per register");
+        Self {
+            ctl0,
+            stat,
+            cor0,
+            idcode,
+            axss,
+            cor1,
+            wbstar,
+            timer,
+            bootsts,
+            ctl1,
+            bspi,
+        }
+    }
+}
+
 impl S7 {
     async fn read(mut cont: Controller<'_>) -> Result<Self> {
         let jtag = S7Jtag {
             device: S7JtagPerDevice {
                 idcode: *jtag_duplicated(cont.reborrow(), commands::IDCODE).await?,
-                usercode: *jtag_master(cont.reborrow(), commands::USERCODE).await?,
-                fuse_user: *jtag_master(cont.reborrow(), commands::FUSE_USER).await?,
-                user1: *jtag_master(cont.reborrow(), commands::USER1).await?,
-                user2: *jtag_master(cont.reborrow(), commands::USER2).await?,
-                user3: *jtag_master(cont.reborrow(), commands::USER3).await?,
-                user4: *jtag_master(cont.reborrow(), commands::USER4).await?,
+                usercode: jtag_master(cont.reborrow(), commands::USERCODE).await?,
+                fuse_user: fuse(cont.reborrow(), 0, Fuse::User).await?,
+                user1: jtag_master(cont.reborrow(), commands::USER1).await?,
+                user2: jtag_master(cont.reborrow(), commands::USER2).await?,
+                user3: jtag_master(cont.reborrow(), commands::USER3).await?,
+                user4: jtag_master(cont.reborrow(), commands::USER4).await?,
             },
             slrs: read_slrs(cont.info().slr, async |slr| {
                 Ok(S7JtagPerSlr {
-                    cntl: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_CNTL).await?,
-                    fuse_dna: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_DNA).await?,
-                    fuse_key: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_KEY).await?,
+                    usercode: jtag_master_on(cont.reborrow(), slr, commands::USERCODE).await?,
+                    cntl: fuse(cont.reborrow(), slr, Fuse::Cntl).await?,
+                    fuse_dna: fuse(cont.reborrow(), slr, Fuse::Dna).await?,
+                    fuse_key: fuse(cont.reborrow(), slr, Fuse::Key).await?,
                 })
             })
             .await?,
@@ -167,6 +196,13 @@ impl UP {
     }
 }
 
+/// An eFUSE register `cont`'s family always has.
+async fn fuse(cont: Controller<'_>, slr: u8, fuse: Fuse) -> Result<Vec<u8>> {
+    let family = cont.info().family;
+    let data = efuse::read(cont, slr, fuse).await?;
+    data.ok_or_else(|| eyre!("no {fuse:?} eFUSE register on {family:?}"))
+}
+
 async fn read_slrs<T>(num_slr: u8, mut f: impl AsyncFnMut(u8) -> Result<T>) -> Result<Vec<T>> {
     let mut ret = Vec::with_capacity(num_slr.into());
     for slr in 0..num_slr {
@@ -178,65 +214,72 @@ async fn read_slrs<T>(num_slr: u8, mut f: impl AsyncFnMut(u8) -> Result<T>) -> R
 async fn read_us_jtag_device(mut cont: Controller<'_>) -> Result<USJtagPerDevice> {
     Ok(USJtagPerDevice {
         idcode: *jtag_duplicated(cont.reborrow(), commands::IDCODE).await?,
-        usercode: *jtag_master(cont.reborrow(), commands::USERCODE).await?,
-        fuse_user: *jtag_master(cont.reborrow(), commands::FUSE_USER).await?,
-        fuse_user_128: *jtag_master(cont.reborrow(), commands::FUSE_USER).await?,
-        user1: *jtag_master(cont.reborrow(), commands::USER1).await?,
-        user2: *jtag_master(cont.reborrow(), commands::USER2).await?,
-        user3: *jtag_master(cont.reborrow(), commands::USER3).await?,
-        user4: *jtag_master(cont.reborrow(), commands::USER4).await?,
+        usercode: jtag_master(cont.reborrow(), commands::USERCODE).await?,
+        fuse_user: fuse(cont.reborrow(), 0, Fuse::User).await?,
+        fuse_user_128: efuse::read(cont.reborrow(), 0, Fuse::User128).await?,
+        user1: jtag_master(cont.reborrow(), commands::USER1).await?,
+        user2: jtag_master(cont.reborrow(), commands::USER2).await?,
+        user3: jtag_master(cont.reborrow(), commands::USER3).await?,
+        user4: jtag_master(cont.reborrow(), commands::USER4).await?,
     })
 }
 
 async fn read_us_jtag_per_slr(mut cont: Controller<'_>) -> Result<Vec<USJtagPerSlr>, eyre::Error> {
     read_slrs(cont.info().slr, async |slr| {
         Ok(USJtagPerSlr {
-            cntl: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_CNTL).await?,
-            fuse_dna: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_DNA).await?,
-            fuse_key: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_KEY).await?,
-            fuse_rsa: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_RSA).await?,
-            fuse_sec: *jtag_shifted(cont.reborrow(), slr, commands::FUSE_SEC).await?,
+            usercode: jtag_master_on(cont.reborrow(), slr, commands::USERCODE).await?,
+            cntl: fuse(cont.reborrow(), slr, Fuse::Cntl).await?,
+            fuse_dna: fuse(cont.reborrow(), slr, Fuse::Dna).await?,
+            fuse_key: fuse(cont.reborrow(), slr, Fuse::Key).await?,
+            fuse_rsa: fuse(cont.reborrow(), slr, Fuse::Rsa).await?,
+            fuse_sec: fuse(cont.reborrow(), slr, Fuse::Sec).await?,
         })
     })
     .await
 }
 
-// This ends up doing something like:
-//
-// send: sync noop ctl0 noop
-// read: ctl0
-// flush
-// send: sync noop stat noop
-// read: stat
-// flush
-// ...
-//
-// Which is annoying. I would _like_ to do:
-//
-// send: sync noop ctl0 stat cor0 ...
-// read: ctl0 stat cor0 ...
-// flush
-//
-// However, attempting that (with or without noops between) seems to result in
-// incorrect output.
-// Specifically, it read `ctl0` twice, then zeroes for the rest (when attempted
-// on an XC7A35T)
+/// The registers read, in the order of [`RegistersPerSlr`].
+const REGISTERS: [Addr; 11] = [
+    Addr::Ctl0,
+    Addr::Stat,
+    Addr::Cor0,
+    Addr::Idcode,
+    Addr::Axss,
+    Addr::Cor1,
+    Addr::Wbstar,
+    Addr::Timer,
+    Addr::Bootsts,
+    Addr::Ctl1,
+    Addr::Bspi,
+];
+
+// All registers of an SLR are read in one flush (see
+// `read_device_register_words`). If that fails, or the IDCODE register
+// doesn't match the device, fall back to one flush per register, which is
+// slower but known to work everywhere.
 async fn read_registers(mut cont: Controller<'_>) -> Result<Registers> {
     let num_slr = cont.info().slr;
+    let idcode = cont.borrow().idcode().strip_version();
     let slrs = read_slrs(num_slr, async |slr| {
-        Ok(RegistersPerSlr {
-            ctl0: device_register(cont.reborrow(), slr, Addr::Ctl0).await?,
-            stat: device_register(cont.reborrow(), slr, Addr::Stat).await?,
-            cor0: device_register(cont.reborrow(), slr, Addr::Cor0).await?,
-            idcode: device_register(cont.reborrow(), slr, Addr::Idcode).await?,
-            axss: device_register(cont.reborrow(), slr, Addr::Axss).await?,
-            cor1: device_register(cont.reborrow(), slr, Addr::Cor1).await?,
-            wbstar: device_register(cont.reborrow(), slr, Addr::Wbstar).await?,
-            timer: device_register(cont.reborrow(), slr, Addr::Timer).await?,
-            bootsts: device_register(cont.reborrow(), slr, Addr::Bootsts).await?,
-            ctl1: device_register(cont.reborrow(), slr, Addr::Ctl1).await?,
-            bspi: device_register(cont.reborrow(), slr, Addr::Bspi).await?,
-        })
+        match device_registers(cont.reborrow(), slr, &REGISTERS).await {
+            Ok(words) => {
+                let regs = RegistersPerSlr::from_words(&words);
+                if IdCode::new(regs.idcode).strip_version() == idcode {
+                    return Ok(regs);
+                }
+                tracing::debug!(
+                    slr,
+                    "batched register read returned bad IDCODE {:#010x}",
+                    regs.idcode
+                );
+            }
+            Err(e) => tracing::debug!(slr, "batched register read failed: {e}"),
+        }
+        let mut words = [0; REGISTERS.len()];
+        for (word, addr) in words.iter_mut().zip(REGISTERS) {
+            *word = device_register(cont.reborrow(), slr, addr).await?;
+        }
+        Ok(RegistersPerSlr::from_words(&words))
     })
     .await?;
     Ok(Registers { slrs })
@@ -1,25 +1,58 @@
 use std::time::{Duration, Instant};
 
-use eyre::Result;
-use nafa_io::{Command, units::Bytes};
-use smol::future::FutureExt as _;
+use eyre::{Result, bail};
+use nafa_io::{
+    Command,
+    devices::{Specific, Xilinx32Info},
+    units::Bytes,
+};
 
 use crate::_32bit::{
     Controller, IRCapture,
     commands::{self, duplicated, shifted},
+    io_utils::read_device_register_word,
+    registers::Addr,
+    status::Stat,
+    to_wire_order,
 };
 
 pub struct ProgramStats {
     pub time_shutdown: Duration,
     pub time_program: Duration,
-    pub time_verify: Duration,
-    pub success: bool,
+    pub time_startup: Duration,
+    /// STAT once the device started.
+    pub stat: Stat,
 }
 
+/// TCKs in Run-Test/Idle after JSTART, for the startup sequence to finish
+/// (UG470 table 6-2).
+const STARTUP_CLOCKS: Bytes<usize> = Bytes(2000 / 8);
+/// TCKs in Run-Test/Idle after JSHUTDOWN, for the shutdown sequence.
+const SHUTDOWN_CLOCKS: Bytes<usize> = Bytes(2000 / 8);
+/// Startup is clocked by TCK above, this only covers STAT reads failing
+/// right after it.
+const STARTUP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Program `data`, then run the startup sequence. Fails with the decoded
+/// STAT if the device didn't start.
 pub async fn run(mut cont: Controller<'_>, data: &[u8]) -> Result<ProgramStats> {
+    let (time_shutdown, time_program) = load(cont.reborrow(), data).await?;
+    let start = Instant::now();
+    let stat = startup(cont).await?;
+    Ok(ProgramStats {
+        time_shutdown,
+        time_program,
+        time_startup: start.elapsed(),
+        stat,
+    })
+}
+
+/// Clear the configuration and shift in `data`, without starting the device.
+async fn load(mut cont: Controller<'_>, data: &[u8]) -> Result<(Duration, Duration)> {
     let num_slr = cont.info().slr;
 
     let start = Instant::now();
+    cont.borrow().progress_phase("shutdown");
     cont.borrow()
         .run([Command::ir(duplicated(commands::JPROGRAM))])
         .await?;
@@ -30,40 +63,157 @@ pub async fn run(mut cont: Controller<'_>, data: &[u8]) -> Result<ProgramStats>
     } {}
     let end_shutdown = Instant::now();
 
+    cont.borrow().progress_phase("program");
     cont.borrow()
         .run([
             Command::ir(duplicated(commands::JSHUTDOWN)),
             Command::ir(shifted(commands::CFG_IN, num_slr, 0)),
             Command::dr_tx_with_notification(data),
-            Command::ir(duplicated(commands::JSTART)),
-            Command::idle(Bytes(250)),
         ])
         .await?;
     let end_program = Instant::now();
+    Ok((end_shutdown - start, end_program - end_shutdown))
+}
+
+/// Issue JSTART with enough clocks for the startup sequence, then wait for
+/// STAT to show DONE, GWE, and the I/Os released. A CRC error is reported as
+/// such, it means the bitstream didn't arrive intact.
+pub async fn startup(mut cont: Controller<'_>) -> Result<Stat> {
+    cont.borrow().progress_phase("startup");
+    cont.borrow()
+        .run([Command::ir(duplicated(commands::JSTART)), Command::idle(STARTUP_CLOCKS)])
+        .await?;
+    check_started(wait_started(cont).await)
+}
+
+/// Read STAT again after programming, and check the device is still
+/// running, i.e. for `program --verify`. Errors like [`startup`].
+pub async fn verify_started(cont: Controller<'_>) -> Result<Stat> {
+    let stat = read_device_register_word(cont, 0, Addr::Stat).await;
+    check_started(stat.ok().map(Stat::from_bits_retain))
+}
+
+fn check_started(stat: Option<Stat>) -> Result<Stat> {
+    match stat {
+        Some(stat) if stat.started() => Ok(stat),
+        Some(stat) if stat.contains(Stat::CRC_ERROR) => bail!(
+            "device did not start: the CRC it computed doesn't match the bitstream, so the data \
+             was corrupted on the way in (check the cable and TCK frequency), STAT {stat}"
+        ),
+        Some(stat) => bail!("device did not start, STAT {stat}"),
+        None => bail!("device did not start, and STAT could not be read"),
+    }
+}
+
+/// Run the shutdown sequence with JSHUTDOWN: assert GTS to tristate the
+/// I/Os and deassert GWE, keeping the configuration. The device keeps its
+/// state until started again with [`startup`], or is ready for a partial or
+/// full reconfiguration without glitching the I/Os.
+pub async fn shutdown(mut cont: Controller<'_>) -> Result<Stat> {
+    cont.borrow().progress_phase("shutdown");
+    cont.borrow()
+        .run([Command::ir(duplicated(commands::JSHUTDOWN)), Command::idle(SHUTDOWN_CLOCKS)])
+        .await?;
+    let stopped = |stat: Stat| !stat.intersects(Stat::GWE | Stat::GTS_CFG_B);
+    match wait_stat(cont, stopped).await {
+        Some(stat) if stopped(stat) => Ok(stat),
+        Some(stat) => bail!("device did not shut down, STAT {stat}"),
+        None => bail!("device did not shut down, and STAT could not be read"),
+    }
+}
+
+/// The last STAT read, once started or after [`STARTUP_TIMEOUT`].
+async fn wait_started(cont: Controller<'_>) -> Option<Stat> {
+    wait_stat(cont, Stat::started).await
+}
 
-    let status = async {
-        loop {
-            // Sometimes, immediately after programming, the FPGA won't respond
-            // for a few ms. This surfaces as a "failed to fill buffer", which
-            // we ignore in favor of just trying again.
-            //
-            // Infinite loop is negated by timeout above.
-            let Ok(ir) = cont.borrow().capture_ir().await else {
-                continue;
-            };
-            let ir_capture = IRCapture::from_bits_retain(ir as _);
-            break ir_capture.intersects(IRCapture::DONE);
+async fn wait_stat(mut cont: Controller<'_>, done: impl Fn(Stat) -> bool) -> Option<Stat> {
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    let mut last = None;
+    loop {
+        // Sometimes, immediately after programming, the FPGA won't respond
+        // for a few ms. This surfaces as a "failed to fill buffer", which
+        // we ignore in favor of just trying again.
+        if let Ok(stat) = read_device_register_word(cont.reborrow(), 0, Addr::Stat).await {
+            let stat = Stat::from_bits_retain(stat);
+            last = Some(stat);
+            if done(stat) {
+                return last;
+            }
         }
-    };
-    let success = status
-        .or(nafa_io::timeout(Duration::from_millis(100), false))
-        .await;
-    let end_status = Instant::now();
+        if Instant::now() > deadline {
+            return last;
+        }
+    }
+}
 
-    Ok(ProgramStats {
-        time_shutdown: end_shutdown - start,
-        time_program: end_program - end_shutdown,
-        time_verify: end_status - end_program,
-        success,
-    })
+/// Filler after the bitstream for each additional broadcast target, so the
+/// end of the bitstream makes it through the configuration registers of the
+/// devices in front of the last one.
+const BROADCAST_FILLER_WORDS: usize = 32;
+const NOOP: u32 = 0x2000_0000;
+
+/// Program the devices at `targets` (chain indices) with the same bitstream,
+/// shifting it once through all of them.
+///
+/// Unlike [`run`], a target that didn't start isn't an error: the returned
+/// list has the STAT of each target, in the order given, `None` where it
+/// couldn't be read. `stats.stat` is that of the first target. The
+/// controller is left with the first target selected.
+pub async fn broadcast(
+    mut cont: Controller<'_>,
+    targets: &[usize],
+    data: &[u8],
+) -> Result<(ProgramStats, Vec<Option<Stat>>)> {
+    // the rest goes by the info of the active device, so every target has to
+    // match it. Targets past the end of the chain are left to `broadcast`
+    let info = cont.info().clone();
+    let chain: Vec<_> = cont.borrow().chain().map(|(_, d)| d.clone()).collect();
+    for &idx in targets {
+        let Some(device) = chain.get(idx) else {
+            continue;
+        };
+        let same = |t: &Xilinx32Info| t.family == info.family && t.slr == info.slr;
+        if !matches!(&device.specific, Specific::Xilinx32(t) if same(t)) {
+            bail!(
+                "cannot broadcast to {} at {idx}, it isn't a {:?} device with {} SLRs",
+                device.name,
+                info.family,
+                info.slr,
+            );
+        }
+    }
+    cont.borrow().broadcast(targets)?;
+
+    let filler = (targets.len() - 1) * BROADCAST_FILLER_WORDS;
+    let mut padded = data.to_vec();
+    padded.extend(std::iter::repeat_n(to_wire_order(NOOP), filler).flatten());
+    let times = async {
+        let times = load(cont.reborrow(), &padded).await?;
+        cont.borrow().progress_phase("startup");
+        cont.borrow()
+            .run([Command::ir(duplicated(commands::JSTART)), Command::idle(STARTUP_CLOCKS)])
+            .await?;
+        eyre::Ok(times)
+    }
+    .await;
+
+    // select each target on its own again, even if programming failed
+    let start = Instant::now();
+    let mut stats = Vec::with_capacity(targets.len());
+    for &idx in targets {
+        cont.borrow().select(idx)?;
+        stats.push(wait_started(cont.reborrow()).await);
+    }
+    cont.borrow().select(targets[0])?;
+
+    let (time_shutdown, time_program) = times?;
+    let stat = stats.first().copied().flatten();
+    let stats_first = ProgramStats {
+        time_shutdown,
+        time_program,
+        time_startup: start.elapsed(),
+        stat: stat.unwrap_or(Stat::empty()),
+    };
+    Ok((stats_first, stats))
 }
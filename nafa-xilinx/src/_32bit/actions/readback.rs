@@ -1,23 +1,153 @@
-use eyre::Result;
+use eyre::{OptionExt as _, Result};
 use nafa_io::{
-    Command,
+    Buffer, Command, WordOrder, WordsExt as _,
     units::{Bytes, Words32},
 };
 
 use crate::_32bit::{
-    Controller, bitstream_to_wire_order,
+    Controller,
     commands::{self, shifted},
-    registers::{Addr, OpCode, Type1, type2},
+    io_utils::write_one,
+    layout::Readback,
+    registers::{Addr, CmdCode, OpCode, Type1, type2},
+    to_wire_order,
 };
 
 pub async fn run(cont: Controller<'_>, len: Bytes<usize>) -> Result<&[u8]> {
     let num_slr = cont.info().slr;
+    let readback = readback_sequence(false);
+    let commands = commands(&readback, num_slr, 0, len);
+    Ok(cont.consume().run(commands).await?)
+}
 
-    let readback = [
+/// [`run`], of `slr` instead of the master SLR. With `capture`, like
+/// [`capture`].
+pub async fn run_slr(
+    cont: Controller<'_>,
+    slr: u8,
+    len: Bytes<usize>,
+    capture: bool,
+) -> Result<&[u8]> {
+    let num_slr = cont.info().slr;
+    let readback = readback_sequence(capture);
+    let commands = commands(&readback, num_slr, slr, len);
+    Ok(cont.consume().run(commands).await?)
+}
+
+/// Read back every SLR in turn, and keep only the frames holding
+/// configuration, see [`Readback::from_slrs`]. Needs the [`FrameLayout`] of
+/// the part, which also gives how much to read.
+///
+/// With `capture`, each SLR captures its state right before it is read, so
+/// the SLRs are not captured at the same time.
+///
+/// [`FrameLayout`]: nafa_io::devices::FrameLayout
+pub async fn frames(mut cont: Controller<'_>, capture: bool) -> Result<Readback> {
+    let layout = (cont.info().layout.clone()).ok_or_eyre("frame layout of this part not known")?;
+    let frame = cont.info().family.frame_words();
+    let mut readbacks = Vec::new();
+    for slr in 0..cont.info().slr {
+        let frames = layout.readback_frames(slr).ok_or_eyre("no rows for SLR")?;
+        let len = Bytes::from(frame * frames);
+        readbacks.push(run_slr(cont.reborrow(), slr, len, capture).await?.to_vec());
+    }
+    Readback::from_slrs(readbacks.iter().map(Vec::as_slice), frame.0, &layout)
+}
+
+/// [`run`], after copying the current state of the flip-flops and block RAM
+/// into configuration memory with GCAPTURE. The captured values replace the
+/// initial values in the frames read back, giving a snapshot of the running
+/// design.
+pub async fn capture(cont: Controller<'_>, len: Bytes<usize>) -> Result<&[u8]> {
+    let num_slr = cont.info().slr;
+    let readback = readback_sequence(true);
+    let commands = commands(&readback, num_slr, 0, len);
+    Ok(cont.consume().run(commands).await?)
+}
+
+/// [`run`], reading into `buf` as the data arrives instead of returning all of
+/// it at once. See [`nafa_io::Controller::run_into_buffer`].
+pub async fn run_into(cont: Controller<'_>, len: Bytes<usize>, buf: &mut dyn Buffer) -> Result<()> {
+    let num_slr = cont.info().slr;
+    let readback = readback_sequence(false);
+    let commands = commands(&readback, num_slr, 0, len);
+    Ok(cont.consume().run_into_buffer(commands, buf).await?)
+}
+
+/// [`capture`], reading into `buf` like [`run_into`].
+pub async fn capture_into(
+    cont: Controller<'_>,
+    len: Bytes<usize>,
+    buf: &mut dyn Buffer,
+) -> Result<()> {
+    let num_slr = cont.info().slr;
+    let readback = readback_sequence(true);
+    let commands = commands(&readback, num_slr, 0, len);
+    Ok(cont.consume().run_into_buffer(commands, buf).await?)
+}
+
+/// Read the single frame at frame address `far`, as words.
+pub async fn read_frame(cont: Controller<'_>, far: u32) -> Result<Vec<u32>> {
+    read_frames(cont, far, 1).await
+}
+
+/// Read `count` frames from frame address `far` on, as words, following the
+/// order the frame address auto-increments in.
+///
+/// FDRO starts with a pad frame before the addressed one, so one more frame
+/// is read and the first is dropped.
+pub async fn read_frames(cont: Controller<'_>, far: u32, count: usize) -> Result<Vec<u32>> {
+    let num_slr = cont.info().slr;
+    let frame = cont.info().family.frame_words();
+    let words = frame * (count + 1);
+    let readback = frame_sequence(far, words.map(|w| w as u32));
+    let desync = desync_sequence();
+    let commands = [
+        Command::ir(shifted(commands::CFG_IN, num_slr, 0)),
+        Command::dr_tx(&readback),
+        Command::ir(shifted(commands::CFG_OUT, num_slr, 0)),
+        Command::dr_rx(Bytes::from(words)),
+        Command::ir(shifted(commands::CFG_IN, num_slr, 0)),
+        Command::dr_tx(&desync),
+    ];
+    let data = cont.consume().run(commands).await?;
+    let words = (data.words(WordOrder::MSB_FIRST)).skip(frame.0).collect();
+    Ok(words)
+}
+
+/// Configuration packets reading `count` words of frames from `far` on.
+fn frame_sequence(far: u32, count: Words32<u32>) -> Vec<u8> {
+    let write_cmd = Type1::new(OpCode::Write, Addr::Cmd, Words32(1)).to_raw();
+    let sequence = [
         Type1::SYNC,
         Type1::NOOP,
-        Type1::new(OpCode::Write, Addr::Cmd, Words32(1)).to_raw(),
-        0x0000_0004, // rcfg
+        write_cmd,
+        CmdCode::Rcfg as u32,
+        Type1::new(OpCode::Write, Addr::Far, Words32(1)).to_raw(),
+        far,
+        Type1::new(OpCode::Read, Addr::Fdro, Words32(0)).to_raw(),
+        type2(OpCode::Read, count.0),
+        Type1::NOOP,
+        Type1::NOOP,
+    ];
+    sequence.into_iter().flat_map(to_wire_order).collect()
+}
+
+/// Configuration packets ending a [`frame_sequence`] read, so the
+/// configuration logic is left as it was found.
+fn desync_sequence() -> Vec<u8> {
+    let sequence = [write_one(Addr::Cmd), CmdCode::Desync as u32, Type1::NOOP, Type1::NOOP];
+    sequence.into_iter().flat_map(to_wire_order).collect()
+}
+
+/// Configuration packets starting a readback of all frames, in wire order,
+/// optionally capturing the design state first.
+fn readback_sequence(capture: bool) -> Vec<u8> {
+    let write_cmd = Type1::new(OpCode::Write, Addr::Cmd, Words32(1)).to_raw();
+    let gcapture = [write_cmd, CmdCode::Gcapture as u32, Type1::NOOP];
+    let readback = [
+        write_cmd,
+        CmdCode::Rcfg as u32,
         Type1::new(OpCode::Write, Addr::Far, Words32(1)).to_raw(),
         0x0000_0000,
         Type1::new(OpCode::Read, Addr::Fdro, Words32(0)).to_raw(),
@@ -25,8 +155,10 @@ pub async fn run(cont: Controller<'_>, len: Bytes<usize>) -> Result<&[u8]> {
         Type1::NOOP,
         Type1::NOOP,
     ];
-    let readback = bitstream_to_wire_order(readback);
-    let readback = readback.as_flattened();
+    let sequence = [Type1::SYNC, Type1::NOOP]
+        .into_iter()
+        .chain(capture.then_some(gcapture).into_iter().flatten())
+        .chain(readback);
     // so: the fdro read len
     // You would _think_ that this should be `args.len`, or maybe `args.len * 4` or
     // `* 32` because it's words or bytes or bits or something.
@@ -41,13 +173,101 @@ pub async fn run(cont: Controller<'_>, len: Bytes<usize>) -> Result<&[u8]> {
     //
     // Notably, this does _not_ mess up subsequent `cont.run()`. If I were to guess,
     // going out of the `DR` side of JTAG makes the fpga just drop all further data.
+    sequence.flat_map(to_wire_order).collect()
+}
 
-    let commands = [
-        Command::ir(shifted(commands::CFG_IN, num_slr, 0)),
+fn commands(readback: &[u8], num_slr: u8, slr: u8, len: Bytes<usize>) -> [Command<'_>; 4] {
+    [
+        Command::ir(shifted(commands::CFG_IN, num_slr, slr)),
         Command::dr_tx(readback),
-        Command::ir(shifted(commands::CFG_OUT, num_slr, 0)),
+        Command::ir(shifted(commands::CFG_OUT, num_slr, slr)),
         Command::dr_rx_with_notification(len),
-    ];
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::{
+        devices::{self, FrameLayout, Specific},
+        fake::{FakeBackend, FakeDevice},
+        units::Bits,
+    };
+
+    use super::*;
+    use crate::ir::IrEncoder;
+
+    fn words(data: &[u8]) -> Vec<u32> {
+        data.words(WordOrder::MSB_FIRST).collect()
+    }
+
+    #[test]
+    fn test_frame_sequence() {
+        let readback = words(&frame_sequence(0x0040_0100, Words32(202)));
+        assert_eq!(readback[..2], [Type1::SYNC, Type1::NOOP]);
+        assert_eq!(readback[4..6], [write_one(Addr::Far), 0x0040_0100]);
+        assert_eq!(readback[7], type2(OpCode::Read, 202));
+        assert_eq!(readback[8..], [Type1::NOOP, Type1::NOOP]);
+
+        let desync = words(&desync_sequence());
+        assert_eq!(
+            desync,
+            [write_one(Addr::Cmd), CmdCode::Desync as u32, Type1::NOOP, Type1::NOOP,]
+        );
+    }
+
+    #[test]
+    fn test_frames_slr() {
+        smol::block_on(async {
+            const XCVU9P: u32 = 0x04b3_1093;
+            let rows: [&[u16]; 3] = [&[2, 1], &[3], &[1, 1]];
+            let frame = 93;
+
+            // every frame numbered by SLR and position, pad frames marked
+            let mut device = FakeDevice::new(
+                XCVU9P,
+                Bits(18),
+                IrEncoder::Slr { num_slr: 3 }.duplicated(commands::IDCODE),
+            );
+            for (slr, rows) in rows.iter().enumerate() {
+                let mut frames = vec![0xdead_0000];
+                for &row in *rows {
+                    frames.extend((0..row).map(|idx| (slr as u32) << 16 | u32::from(idx)));
+                    frames.extend([0xdead_0000; FrameLayout::ROW_PAD]);
+                }
+                let data: Vec<u8> = (frames.iter())
+                    .flat_map(|&f| (0..frame).flat_map(move |_| to_wire_order(f)))
+                    .collect();
+                let cfg_out = shifted(commands::CFG_OUT, 3, slr as u8) & 0x3_ffff;
+                device = device.constant(cfg_out, Bits(8 * data.len()), &data);
+            }
+
+            let (idcode, mut info) = devices::all()
+                .find(|(idcode, _)| idcode.code() == XCVU9P)
+                .unwrap();
+            let Specific::Xilinx32(x) = &mut info.specific else {
+                panic!("expected xilinx32");
+            };
+            x.layout = Some(FrameLayout {
+                slrs: rows.iter().map(|rows| rows.to_vec().into()).collect(),
+            });
+            let backend = Box::new(FakeBackend::new(vec![device]));
+            let mut cont = nafa_io::Controller::new(backend, vec![], (idcode, info), vec![])
+                .await
+                .unwrap();
 
-    cont.consume().run(commands).await
+            let readback = frames(cont.typed().unwrap(), false).await.unwrap();
+            let firsts: Vec<Vec<u32>> = (readback.slrs.iter())
+                .map(|slr| slr.iter().map(|frame| frame[0]).collect())
+                .collect();
+            assert_eq!(
+                firsts,
+                [vec![0, 1, 0], vec![1 << 16, 1 << 16 | 1, 1 << 16 | 2], vec![2 << 16, 2 << 16]]
+            );
+            assert!(
+                readback.slrs[1]
+                    .iter()
+                    .all(|f| f.iter().all(|&w| w == f[0]))
+            );
+        });
+    }
 }
@@ -0,0 +1,120 @@
+//! Clear the configuration with JPROGRAM, as pulsing PROG_B would, or reboot
+//! into another image with IPROG.
+
+use std::time::{Duration, Instant};
+
+use eyre::{Result, bail};
+use nafa_io::Command;
+use smol::future::FutureExt as _;
+
+use crate::_32bit::{
+    Controller, IRCapture,
+    commands::{self, duplicated},
+    io_utils::{read_device_register_word, send_device_packets, write_one},
+    registers::{Addr, CmdCode},
+    status::BootSts,
+};
+
+/// Housecleaning takes a few ms even on the largest parts.
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct ResetStats {
+    /// Until INIT went high, i.e. the configuration memory was cleared.
+    pub time_init: Duration,
+    /// Until DONE went high, if waited for.
+    pub time_done: Option<Duration>,
+}
+
+/// Issue JPROGRAM and wait for INIT.
+///
+/// Afterwards the device configures itself again from whatever its mode pins
+/// select, i.e. an attached flash. With `wait_done`, wait up to that long for
+/// DONE, and fail if it didn't go high. Without, the device is left as it
+/// is, unconfigured in JTAG mode.
+pub async fn run(mut cont: Controller<'_>, wait_done: Option<Duration>) -> Result<ResetStats> {
+    let start = Instant::now();
+    cont.borrow()
+        .run([Command::ir(duplicated(commands::JPROGRAM))])
+        .await?;
+    restarted(cont, start, "JPROGRAM", wait_done).await
+}
+
+/// Highest address in the START_ADDR field of WBSTAR.
+const WBSTAR_MAX: u32 = 0x1fff_ffff;
+
+/// Reboot into the image at `addr` in flash (MultiBoot): set WBSTAR and
+/// issue IPROG. If that image fails to load, the device falls back to the
+/// one at address 0, see [`BootSts`].
+///
+/// `wait_done` is as for [`run`].
+pub async fn multiboot(
+    mut cont: Controller<'_>,
+    addr: u32,
+    wait_done: Option<Duration>,
+) -> Result<ResetStats> {
+    if addr > WBSTAR_MAX {
+        bail!("address {addr:#x} does not fit in WBSTAR (max {WBSTAR_MAX:#x})");
+    }
+    let start = Instant::now();
+    let packets = [write_one(Addr::Wbstar), addr, write_one(Addr::Cmd), CmdCode::Iprog as u32];
+    send_device_packets(cont.reborrow(), 0, packets).await?;
+    restarted(cont, start, "IPROG", wait_done).await
+}
+
+/// Wait for the device to go through housecleaning after `cause`, and
+/// optionally to configure itself.
+async fn restarted(
+    mut cont: Controller<'_>,
+    start: Instant,
+    cause: &str,
+    wait_done: Option<Duration>,
+) -> Result<ResetStats> {
+    // DONE stays high from the old configuration until housecleaning starts
+    if !poll(cont.reborrow(), IRCapture::DONE, false, INIT_TIMEOUT).await? {
+        bail!("DONE did not go low within {INIT_TIMEOUT:?} after {cause}");
+    }
+    if !poll(cont.reborrow(), IRCapture::INIT, true, INIT_TIMEOUT).await? {
+        bail!("INIT did not go high within {INIT_TIMEOUT:?} after {cause}");
+    }
+    let time_init = start.elapsed();
+
+    let Some(timeout) = wait_done else {
+        return Ok(ResetStats {
+            time_init,
+            time_done: None,
+        });
+    };
+    if !poll(cont.reborrow(), IRCapture::DONE, true, timeout).await? {
+        let msg = format!("DONE did not go high within {timeout:?}");
+        match read_device_register_word(cont, 0, Addr::Bootsts).await {
+            Ok(bootsts) => bail!("{msg}, BOOTSTS {}", BootSts(bootsts)),
+            Err(_) => bail!("{msg}, the device did not configure itself"),
+        }
+    }
+    Ok(ResetStats {
+        time_init,
+        time_done: Some(start.elapsed()),
+    })
+}
+
+/// Wait until `flag` in the IR capture is `set` (or not). `false` on timeout.
+async fn poll(
+    mut cont: Controller<'_>,
+    flag: IRCapture,
+    set: bool,
+    timeout: Duration,
+) -> Result<bool> {
+    let status = async {
+        loop {
+            // as after programming, the device may not respond for a few ms
+            // while it configures, try again until the timeout
+            let Ok(ir) = cont.borrow().capture_ir().await else {
+                continue;
+            };
+            if IRCapture::from_bits_retain(ir as _).intersects(flag) == set {
+                break true;
+            }
+        }
+    };
+    Ok(status.or(nafa_io::timeout(timeout, false)).await)
+}
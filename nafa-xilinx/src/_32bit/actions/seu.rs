@@ -0,0 +1,62 @@
+//! Single event upset (SEU) status of the configuration memory.
+//!
+//! The device can continuously check the CRC of its configuration in the
+//! background (readback CRC, enabled by `COR1.RBCRC_EN`), flagging an error in
+//! STAT and on INIT_B. The frame ECC syndrome of the FRAME_ECC primitive is
+//! only visible to the design, not over JTAG.
+
+use eyre::Result;
+
+use crate::_32bit::{
+    Controller,
+    actions::config,
+    config::{Cor1, Ctl0},
+    io_utils::read_device_register_word,
+    registers::Addr,
+    status::Stat,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeuStatus {
+    pub stat: Stat,
+    pub cor1: Cor1,
+    pub ctl0: Ctl0,
+    /// Expected readback CRC, if set by the bitstream.
+    pub rbcrc_sw: u32,
+}
+
+impl SeuStatus {
+    pub fn rbcrc_enabled(self) -> bool {
+        self.cor1.contains(Cor1::RBCRC_EN)
+    }
+
+    /// What the device does on a readback CRC error.
+    pub fn rbcrc_action(self) -> &'static str {
+        match Cor1::RBCRC_ACTION.get(self.cor1) {
+            0 => "continue",
+            1 => "halt",
+            2 => "correct and halt",
+            _ => "correct and continue",
+        }
+    }
+
+    /// LUT RAM and SRL contents read back as zeros, so they don't change
+    /// between readbacks.
+    pub fn lut_ram_masked(self) -> bool {
+        !self.ctl0.contains(Ctl0::GLUTMASK_B)
+    }
+}
+
+pub async fn status(mut cont: Controller<'_>, slr: u8) -> Result<SeuStatus> {
+    let stat =
+        Stat::from_bits_retain(read_device_register_word(cont.reborrow(), slr, Addr::Stat).await?);
+    let cor1 = config::read::<Cor1>(cont.reborrow(), slr).await?;
+    let ctl0 = config::read::<Ctl0>(cont.reborrow(), slr).await?;
+    let rbcrc_sw = read_device_register_word(cont, slr, Addr::RbcrcSw).await?;
+    Ok(SeuStatus {
+        stat,
+        cor1,
+        ctl0,
+        rbcrc_sw,
+    })
+}
@@ -0,0 +1,173 @@
+//! Compare a readback against the bitstream the device was configured with.
+
+use eyre::{OptionExt as _, Result, bail};
+use nafa_io::{WordOrder, WordsExt as _};
+
+use crate::_32bit::{Controller, actions::readback};
+
+/// A frame that doesn't match, at its first differing word.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Index of the frame in the bitstream's frame data.
+    pub frame: usize,
+    /// Word within the frame.
+    pub word: usize,
+    pub expected: u32,
+    pub actual: u32,
+    /// Differing bits in the whole frame, after masking.
+    pub bits: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// Frames compared, fewer than in the bitstream if the readback was
+    /// shorter.
+    pub frames: usize,
+    /// Words at the start of the readback skipped to line it up with the
+    /// bitstream.
+    pub offset: usize,
+    /// Number of frames that don't match.
+    pub mismatched: usize,
+    /// The first (at most `max`) of them.
+    pub first: Vec<Mismatch>,
+}
+
+/// Frames at the start used to find [`Report::offset`].
+const ALIGN_FRAMES: usize = 32;
+
+/// Read back the configuration memory and compare it against `golden`, the
+/// [frame data](crate::bitstream::frame_data) of the bitstream.
+///
+/// `mask` is the frame data of the matching `.msk` file. Set bits in it are
+/// not compared, i.e. for LUT RAM and block RAM contents that change at
+/// runtime.
+pub async fn run(
+    cont: Controller<'_>,
+    golden: &[u32],
+    mask: Option<&[u32]>,
+    max: usize,
+) -> Result<Report> {
+    let frame = cont.info().family.frame_words();
+    let len = cont.info().readback;
+    let len = len.ok_or_eyre("unsupported device for readback")?;
+    let data = readback::run(cont, len.into()).await?;
+    let words: Vec<u32> = data.words(WordOrder::MSB_FIRST).collect();
+    compare(&words, golden, mask, frame.0, max)
+}
+
+/// [`run`], on a readback already converted to words.
+pub fn compare(
+    readback: &[u32],
+    golden: &[u32],
+    mask: Option<&[u32]>,
+    frame_words: usize,
+    max: usize,
+) -> Result<Report> {
+    if let Some(mask) = mask
+        && mask.len() != golden.len()
+    {
+        bail!(
+            "mask has {} words of frame data, bitstream has {}",
+            mask.len(),
+            golden.len()
+        );
+    }
+    let offset = align(readback, golden, mask, frame_words);
+    let readback = &readback[offset..];
+
+    let frames = golden.len().min(readback.len()) / frame_words;
+    let mut report = Report {
+        frames,
+        offset,
+        mismatched: 0,
+        first: Vec::new(),
+    };
+    for frame in 0..frames {
+        let range = frame * frame_words..(frame + 1) * frame_words;
+        let mut bits = 0;
+        let mut first = None;
+        for idx in range.clone() {
+            let diff = difference(readback[idx], golden[idx], mask.map(|m| m[idx]));
+            if diff != 0 && first.is_none() {
+                first = Some(idx);
+            }
+            bits += diff.count_ones();
+        }
+        let Some(idx) = first else {
+            continue;
+        };
+        report.mismatched += 1;
+        if report.first.len() < max {
+            report.first.push(Mismatch {
+                frame,
+                word: idx - range.start,
+                expected: golden[idx],
+                actual: readback[idx],
+                bits,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Differing bits, ignoring the masked ones.
+fn difference(actual: u32, expected: u32, mask: Option<u32>) -> u32 {
+    (actual ^ expected) & !mask.unwrap_or(0)
+}
+
+/// How many words to skip at the start of `readback`. Readback starts with a
+/// pad frame, which is tried first; other offsets only win if they match the
+/// first frames strictly better.
+fn align(readback: &[u32], golden: &[u32], mask: Option<&[u32]>, frame_words: usize) -> usize {
+    let len = golden.len().min(ALIGN_FRAMES * frame_words);
+    let score = |offset: usize| -> u32 {
+        let readback = readback.get(offset..).unwrap_or_default();
+        (readback.iter().zip(&golden[..len]).enumerate())
+            .map(|(idx, (a, e))| difference(*a, *e, mask.map(|m| m[idx])).count_ones())
+            .sum::<u32>()
+            // missing words count as entirely different
+            + 32 * len.saturating_sub(readback.len()) as u32
+    };
+    let candidates = std::iter::once(frame_words).chain(0..=2 * frame_words);
+    let mut best = (u32::MAX, 0);
+    for offset in candidates {
+        let score = score(offset);
+        if score < best.0 {
+            best = (score, offset);
+        }
+    }
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let frame = 4;
+        let golden: Vec<u32> = (1..=12).collect();
+        // a pad frame, then the frames with one bit flipped in the last
+        let mut readback = vec![0; frame];
+        readback.extend(&golden);
+        readback[frame + 9] ^= 0x100;
+
+        let report = compare(&readback, &golden, None, frame, 10).unwrap();
+        assert_eq!(report.offset, frame);
+        assert_eq!(report.frames, 3);
+        assert_eq!(report.mismatched, 1);
+        let expected = Mismatch {
+            frame: 2,
+            word: 1,
+            expected: 10,
+            actual: 10 ^ 0x100,
+            bits: 1,
+        };
+        assert_eq!(report.first, [expected]);
+
+        let mut mask = vec![0; golden.len()];
+        mask[9] = 0x100;
+        let report = compare(&readback, &golden, Some(&mask), frame, 10).unwrap();
+        assert_eq!(report.mismatched, 0);
+    }
+}
@@ -0,0 +1,153 @@
+//! A minimal virtual I/O core, attached to a `BSCANE2` USER register. Not
+//! Vivado's VIO, which talks to the debug hub instead.
+//!
+//! The data register is laid out as follows, starting with the first bit
+//! shifted:
+//! - 1 bit write strobe. If set on Update-DR, the outputs take the values
+//!   shifted in. Captures as 0.
+//! - the `probe_out` ports, back to back in port order. Captures the current
+//!   output values.
+//! - the `probe_in` ports, the same way. Captures the sampled input values,
+//!   ignored on update.
+//! - padding up to a whole byte, ignored.
+
+use eyre::Result;
+use nafa_io::units::{Bits, Bytes};
+
+use crate::{
+    _32bit::Controller,
+    bscan::{Tunnel, User},
+};
+
+/// Width in bits of each port, at most 64.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    pub outputs: Vec<u32>,
+    pub inputs: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub outputs: Vec<u64>,
+    pub inputs: Vec<u64>,
+}
+
+impl Layout {
+    fn len(&self) -> Bytes<usize> {
+        let bits: u32 = 1 + self.outputs.iter().chain(&self.inputs).sum::<u32>();
+        Bits(bits as usize).bytes_ceil()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(w) = self
+            .outputs
+            .iter()
+            .chain(&self.inputs)
+            .find(|w| !(1..=64).contains(*w))
+        {
+            return Err(eyre::eyre!("vio port width {w} outside of 1..=64"));
+        }
+        Ok(())
+    }
+}
+
+/// Sample the inputs and outputs, leaving the outputs as they are.
+pub async fn read(cont: Controller<'_>, user: u8, layout: &Layout) -> Result<State> {
+    layout.validate()?;
+    let tx = vec![0; layout.len().0];
+    let rx = shift(cont, user, &tx).await?;
+    Ok(unpack(layout, &rx))
+}
+
+/// Drive the outputs, returning the state sampled right before.
+pub async fn write(
+    cont: Controller<'_>,
+    user: u8,
+    layout: &Layout,
+    outputs: &[u64],
+) -> Result<State> {
+    layout.validate()?;
+    if outputs.len() != layout.outputs.len() {
+        return Err(eyre::eyre!(
+            "expected {} output values, got {}",
+            layout.outputs.len(),
+            outputs.len()
+        ));
+    }
+    let mut tx = vec![0; layout.len().0];
+    tx[0] = 1;
+    let mut offset = 1;
+    for (&width, &value) in layout.outputs.iter().zip(outputs) {
+        if width < 64 && value >> width != 0 {
+            return Err(eyre::eyre!("value {value:#x} does not fit in {width} bits"));
+        }
+        put_bits(&mut tx, offset, width, value);
+        offset += width as usize;
+    }
+    let rx = shift(cont, user, &tx).await?;
+    Ok(unpack(layout, &rx))
+}
+
+async fn shift(cont: Controller<'_>, user: u8, tx: &[u8]) -> Result<Vec<u8>> {
+    let mut tunnel = Tunnel::new(cont.consume(), User::new(user)?)?;
+    tunnel.shift(tx, Bytes(tx.len()).into()).await
+}
+
+fn unpack(layout: &Layout, rx: &[u8]) -> State {
+    let mut offset = 1;
+    let mut take = |widths: &[u32]| {
+        let values = widths.iter().map(|&w| {
+            let value = get_bits(rx, offset, w);
+            offset += w as usize;
+            value
+        });
+        values.collect()
+    };
+    let outputs = take(&layout.outputs);
+    let inputs = take(&layout.inputs);
+    State { outputs, inputs }
+}
+
+fn put_bits(buf: &mut [u8], offset: usize, width: u32, value: u64) {
+    for bit in 0..width as usize {
+        if value >> bit & 1 == 1 {
+            let idx = offset + bit;
+            buf[idx / 8] |= 1 << (idx % 8);
+        }
+    }
+}
+
+fn get_bits(buf: &[u8], offset: usize, width: u32) -> u64 {
+    (0..width as usize).fold(0, |acc, bit| {
+        let idx = offset + bit;
+        acc | u64::from(buf[idx / 8] >> (idx % 8) & 1) << bit
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let layout = Layout {
+            outputs: vec![1, 12],
+            inputs: vec![3, 64],
+        };
+        assert_eq!(layout.len(), Bytes(11));
+
+        let mut buf = vec![0; layout.len().0];
+        put_bits(&mut buf, 1, 1, 1);
+        put_bits(&mut buf, 2, 12, 0xabc);
+        put_bits(&mut buf, 14, 3, 0b101);
+        put_bits(&mut buf, 17, 64, u64::MAX - 1);
+        assert_eq!(buf[0], 0b1111_0010);
+        assert_eq!(
+            unpack(&layout, &buf),
+            State {
+                outputs: vec![1, 0xabc],
+                inputs: vec![0b101, u64::MAX - 1],
+            }
+        );
+    }
+}
@@ -1,31 +1,240 @@
-use eyre::Result;
-use nafa_io::{Command, units::Bytes};
+use std::ops::RangeInclusive;
 
-use crate::_32bit::{
-    Controller,
-    commands::{self, master},
-    drp,
+use eyre::{Result, bail};
+use nafa_io::{Command, WordOrder, WordsExt as _, devices::Xilinx32Family as Family, units::Bytes};
+
+use crate::{
+    _32bit::{
+        Controller,
+        commands::{self, master},
+        drp,
+    },
+    dap::MemAp,
 };
 
+/// Run DRP transfers `regs` on the XADC / SYSMON, returning the value each
+/// one read (the low half of the response).
 pub async fn run(
     cont: Controller<'_>,
     regs: impl IntoIterator<Item = drp::Command>,
-) -> Result<&[u8]> {
+) -> Result<Vec<u16>> {
+    run_raw(cont, regs.into_iter().map(drp::Command::to_bits)).await
+}
+
+/// [`run`], with transfers already encoded by [`drp::Command::to_bits_raw`],
+/// i.e. for registers not in [`drp::Addr`].
+pub async fn run_raw(
+    cont: Controller<'_>,
+    regs: impl IntoIterator<Item = u32>,
+) -> Result<Vec<u16>> {
     let num_slr = cont.info().slr;
-    let drp_commands: Vec<[u8; 4]> = regs
-        .into_iter()
-        .map(|c| c.to_bits().to_le_bytes())
-        .collect();
+    let sysmon = drp::Sysmon::of(cont.info().family);
+    let drp_commands: Vec<[u8; 4]> = regs.into_iter().map(u32::to_le_bytes).collect();
 
     let start = [Command::ir(master(commands::SYSMON_DRP, num_slr))];
-    let between = [Command::idle(Bytes(10))];
+    let between = [Command::idle(sysmon.drp_idle())];
     let after = [Command::dr_rx(Bytes(4))];
 
     let drp_commands = drp_commands
         .iter()
         .flat_map(|c| std::iter::once(Command::dr_txrx(c)).chain(between));
 
-    cont.consume()
-        .run(start.into_iter().chain(drp_commands).chain(after))
-        .await
+    let reads = cont
+        .consume()
+        .run_reads(start.into_iter().chain(drp_commands).chain(after))
+        .await?;
+    // each transfer is answered in the next one, the first read is left over
+    // from before
+    let values = reads.iter().skip(1).map(|read| {
+        let word = read.words::<u32>(WordOrder::LSB_FIRST).next();
+        word.unwrap_or_default() as u16
+    });
+    Ok(values.collect())
+}
+
+/// Read every channel enabled in the sequencer, in one batch after reading
+/// which ones are enabled.
+///
+/// With `enable_all`, first switch the sequencer to continuously scan every
+/// channel, including all VAUX inputs. This overrides the configuration of
+/// the design (until the next reconfiguration), and the results of newly
+/// enabled channels are only valid after one full scan.
+pub async fn scan(mut cont: Controller<'_>, enable_all: bool) -> Result<Vec<(drp::Addr, u16)>> {
+    use drp::{Addr, Cmd};
+    let read = |addr| drp::Command {
+        cmd: Cmd::Read,
+        addr,
+        data: 0,
+    };
+    let write = |addr, data| drp::Command {
+        cmd: Cmd::Write,
+        addr,
+        data,
+    };
+
+    if enable_all {
+        let [config1] = run(cont.reborrow(), [read(Addr::Config1)]).await?[..] else {
+            unreachable!("one read")
+        };
+        let config1 = config1 & !drp::SEQ_MODE_MASK | drp::SEQ_MODE_CONTINUOUS;
+        let writes = [
+            write(Addr::SeqChannel0, 0x7f01),
+            write(Addr::SeqChannel1, 0xffff),
+            write(Addr::Config1, config1),
+        ];
+        run(cont.reborrow(), writes).await?;
+    }
+
+    let seq = [read(Addr::SeqChannel0), read(Addr::SeqChannel1)];
+    let [seq0, seq1] = run(cont.reborrow(), seq).await?[..] else {
+        unreachable!("two reads")
+    };
+    let channels = drp::seq_channels(seq0, seq1);
+    let values = run(cont, channels.iter().map(|&addr| read(addr))).await?;
+    Ok(channels.into_iter().zip(values).collect())
+}
+
+/// Write `data` to the DRP register at `addr`, returning the value read
+/// back. Fails if `addr` isn't [writable](drp::writable), unless `unchecked`,
+/// and always if it doesn't fit in the 10-bit address field.
+pub async fn write_reg(cont: Controller<'_>, addr: u16, data: u16, unchecked: bool) -> Result<u16> {
+    if addr > 0x3ff {
+        bail!("DRP address {addr:#x} is out of range, the highest is 0x3ff");
+    }
+    if !unchecked && !drp::writable(addr) {
+        bail!("DRP register {addr:#04x} is not a configuration or alarm register");
+    }
+    let write = drp::Command::to_bits_raw(drp::Cmd::Write as u8, addr, data);
+    let read = drp::Command::to_bits_raw(drp::Cmd::Read as u8, addr, 0);
+    let [_, value] = run_raw(cont, [write, read]).await?[..] else {
+        unreachable!("two transfers")
+    };
+    Ok(value)
+}
+
+/// Read `regs` of the PS SYSMON of a Zynq UltraScale+, through `mem`, the
+/// MEM-AP of the system bus.
+pub async fn run_ps(
+    mem: &mut MemAp<'_, '_>,
+    regs: impl IntoIterator<Item = drp::PsAddr>,
+) -> Result<Vec<u16>> {
+    let mut ret = Vec::new();
+    for reg in regs {
+        ret.push(mem.read_word(reg.address()).await? as u16);
+    }
+    Ok(ret)
+}
+
+/// Die temperature and core supply, to check that a board is powered
+/// properly before configuring it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Supply {
+    /// In °C.
+    pub temperature: f32,
+    /// In V.
+    pub vccint: f32,
+}
+
+/// Junction temperature range, from the lowest (industrial) to the highest
+/// (extended and industrial) grade.
+pub const TEMPERATURE_RANGE: RangeInclusive<f32> = -40.0..=100.0;
+
+/// VCCINT range in the recommended operating conditions, from the lowest
+/// low-voltage speed grade to the highest nominal one (DS181, DS892, DS922).
+pub const fn vccint_range(family: Family) -> RangeInclusive<f32> {
+    match family {
+        Family::S7 => 0.87..=1.05,
+        Family::US => 0.825..=0.979,
+        Family::UP => 0.698..=0.876,
+    }
+}
+
+impl Supply {
+    /// Everything out of range, empty if the device can be configured.
+    pub fn problems(self, family: Family) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !TEMPERATURE_RANGE.contains(&self.temperature) {
+            problems.push(format!(
+                "die temperature {:.1}°C is outside {:.0}..{:.0}°C",
+                self.temperature,
+                TEMPERATURE_RANGE.start(),
+                TEMPERATURE_RANGE.end()
+            ));
+        }
+        let vccint = vccint_range(family);
+        if !vccint.contains(&self.vccint) {
+            problems.push(format!(
+                "VCCINT {:.3}V is outside {:.3}..{:.3}V",
+                self.vccint,
+                vccint.start(),
+                vccint.end()
+            ));
+        }
+        problems
+    }
+}
+
+/// Read the die temperature and VCCINT. Works on an unconfigured device.
+pub async fn supply(cont: Controller<'_>) -> Result<Supply> {
+    use drp::{Addr, Cmd, Transfer};
+    let family = cont.info().family;
+    let read = |addr| drp::Command {
+        cmd: Cmd::Read,
+        addr,
+        data: 0,
+    };
+    let [temperature, vccint] = run(cont, [read(Addr::Temperature), read(Addr::VccInt)]).await?[..]
+    else {
+        unreachable!("two reads")
+    };
+    let convert = |addr: Addr, value| match addr.transfer(family) {
+        Transfer::Exactly(f) => f(value),
+        // the references only differ by a fraction of a degree
+        Transfer::OneOf(fs) => fs[0](value),
+        Transfer::None => unreachable!("{addr:?} has a transfer function"),
+    };
+    Ok(Supply {
+        temperature: convert(Addr::Temperature, temperature),
+        vccint: convert(Addr::VccInt, vccint),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::{
+        devices::Xilinx32Info,
+        fake::{self, FakeDevice},
+        units::Bits,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_write_reg_range() {
+        smol::block_on(async {
+            const XC7A35T: u32 = 0x0362_d093;
+            let device = FakeDevice::new(XC7A35T, Bits(6), 0b001001);
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+            let cont = cont.typed::<Xilinx32Info>().unwrap();
+            // would alias 0x041, the configuration register 1, on the wire
+            let err = write_reg(cont, 0x441, 0, true).await.err().unwrap();
+            assert!(err.to_string().contains("out of range"), "{err}");
+        });
+    }
+
+    #[test]
+    fn test_supply_problems() {
+        let ok = Supply {
+            temperature: 45.0,
+            vccint: 1.0,
+        };
+        assert!(ok.problems(Family::S7).is_empty());
+        // a 7-series core voltage is far too high for UltraScale+
+        assert_eq!(ok.problems(Family::UP).len(), 1);
+        let brownout = Supply {
+            temperature: 45.0,
+            vccint: 0.6,
+        };
+        assert!(brownout.problems(Family::S7)[0].starts_with("VCCINT 0.600V"));
+    }
 }
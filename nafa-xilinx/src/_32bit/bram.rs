@@ -0,0 +1,216 @@
+//! Block RAM contents in the configuration frames.
+//!
+//! The contents of a column of block RAMs are in their own frames (block
+//! type 1 in the frame address), 128 of them per clock region on 7-series.
+//! Which bit of a frame holds which bit of which RAM isn't documented. It is
+//! listed in the logic location file (`.ll`) Vivado writes with
+//! `write_bitstream -logic_location_file`, which [`LogicLocation`] reads.
+//! Without one, [`s7_site`] gives the bits of one RAMB36 in frame order.
+//!
+//! Reading the content frames while the design uses the RAM can return
+//! wrong data, or disturb the RAM on 7-series (UG470, "Readback
+//! Considerations"). Stop the clocks of the RAM, or read after GCAPTURE.
+
+use eyre::{Result, bail, eyre};
+use nafa_io::devices::Xilinx32Family;
+
+/// Block type of the content frames in a frame address.
+pub const CONTENT_BLOCK_TYPE: u32 = 1;
+
+/// Content frames of a BRAM column in one clock region of a 7-series device.
+pub const S7_CONTENT_FRAMES: usize = 128;
+/// RAMB36 sites in a BRAM column of one 7-series clock region.
+pub const S7_SITES: usize = 10;
+/// Words of a 7-series frame for one RAMB36: 320 bits, 128 frames of which
+/// are its 32 Kib of data and 4 Kib of parity.
+const S7_SITE_WORDS: usize = 10;
+/// The word in the middle of a 7-series frame, holding the frame ECC.
+const S7_ECC_WORD: usize = 50;
+
+pub const fn block_type(far: u32) -> u32 {
+    (far >> 23) & 0x7
+}
+
+/// Bits of the frame address for the frame within a column.
+pub const fn minor_mask(family: Xilinx32Family) -> u32 {
+    match family {
+        Xilinx32Family::S7 => 0x7f,
+        Xilinx32Family::US | Xilinx32Family::UP => 0xff,
+    }
+}
+
+/// The 40960 bits of RAMB36 `site` (counted from the start of the frames)
+/// in the 128 content `frames` of a 7-series BRAM column, frame by frame,
+/// LSB first in each byte.
+pub fn s7_site(frames: &[u32], site: usize) -> Result<Vec<u8>> {
+    const FRAME: usize = 101;
+    if frames.len() != S7_CONTENT_FRAMES * FRAME {
+        bail!(
+            "expected {S7_CONTENT_FRAMES} frames of {FRAME} words, got {} words",
+            frames.len()
+        );
+    }
+    if site >= S7_SITES {
+        bail!("no RAMB36 site {site}, a column has {S7_SITES}");
+    }
+    // the ECC word splits the frame in two halves of five sites each
+    let start = site * S7_SITE_WORDS + usize::from(site * S7_SITE_WORDS >= S7_ECC_WORD);
+    let words = (frames.chunks_exact(FRAME)).flat_map(|f| &f[start..start + S7_SITE_WORDS]);
+    Ok(words.flat_map(|w| w.to_le_bytes()).collect())
+}
+
+/// One bit of a block RAM, from a logic location file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RamBit {
+    /// Site of the RAM, i.e. `RAMB36_X0Y12`.
+    pub block: String,
+    pub parity: bool,
+    /// Bit number within the data or the parity bits.
+    pub bit: usize,
+    /// Frame address of the frame holding the bit.
+    pub far: u32,
+    /// Bit within that frame.
+    pub offset: usize,
+}
+
+/// The block RAM bits of a logic location file (`.ll`). Other lines, for
+/// flip-flops and LUT RAM, are skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogicLocation {
+    pub bits: Vec<RamBit>,
+}
+
+impl LogicLocation {
+    /// Lines look like
+    /// `Bit 12345 0x00c00000 67 Block=RAMB36_X0Y12 Ram=B:BIT123`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut bits = Vec::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let err = |msg: &str| eyre!("ll line {}: {msg}", line_no + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let ["Bit", _, far, offset, rest @ ..] = &fields[..] else {
+                continue;
+            };
+            let field = |key: &str| rest.iter().find_map(|f| f.strip_prefix(key));
+            let (Some(block), Some(ram)) = (field("Block="), field("Ram=")) else {
+                continue;
+            };
+            let (_port, name) = ram.split_once(':').ok_or_else(|| err("no ':' in Ram="))?;
+            let (parity, bit) = match name.strip_prefix("PARBIT") {
+                Some(bit) => (true, bit),
+                None => (
+                    false,
+                    name.strip_prefix("BIT")
+                        .ok_or_else(|| err("unknown RAM bit"))?,
+                ),
+            };
+            let far = far
+                .strip_prefix("0x")
+                .ok_or_else(|| err("frame address isn't hex"))?;
+            bits.push(RamBit {
+                block: block.to_owned(),
+                parity,
+                bit: bit.parse().map_err(|_| err("invalid bit number"))?,
+                far: u32::from_str_radix(far, 16).map_err(|_| err("invalid frame address"))?,
+                offset: offset.parse().map_err(|_| err("invalid frame offset"))?,
+            });
+        }
+        Ok(Self { bits })
+    }
+
+    /// The bits of `block`.
+    pub fn block<'a>(&'a self, block: &'a str) -> impl Iterator<Item = &'a RamBit> + 'a {
+        self.bits.iter().filter(move |b| b.block == block)
+    }
+
+    /// The first frame address and number of frames holding `block`, all in
+    /// one column.
+    pub fn frames(&self, block: &str, family: Xilinx32Family) -> Result<(u32, usize)> {
+        let mask = minor_mask(family);
+        let mut fars = self.block(block).map(|b| b.far);
+        let first = fars
+            .next()
+            .ok_or_else(|| eyre!("no bits of {block} in the ll file"))?;
+        let (mut min, mut max) = (first, first);
+        for far in fars {
+            if far & !mask != first & !mask {
+                bail!("{block} spans more than one column, {first:#010x} and {far:#010x}");
+            }
+            (min, max) = (min.min(far), max.max(far));
+        }
+        Ok((min, (max - min) as usize + 1))
+    }
+}
+
+/// The contents of a block RAM.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Contents {
+    /// Bit `n` is bit `n % 8` of byte `n / 8`.
+    pub data: Vec<u8>,
+    pub parity: Vec<u8>,
+}
+
+/// The contents of `block`, from `frames` read from `first_far` on.
+pub fn extract(
+    ll: &LogicLocation,
+    block: &str,
+    first_far: u32,
+    frames: &[u32],
+    frame_words: usize,
+) -> Result<Contents> {
+    let mut contents = Contents::default();
+    for bit in ll.block(block) {
+        let frame = bit.far.wrapping_sub(first_far) as usize;
+        let word = frame * frame_words + bit.offset / 32;
+        let value = *(frames.get(word))
+            .ok_or_else(|| eyre!("frame {:#010x} of {block} wasn't read", bit.far))?;
+        let out = match bit.parity {
+            true => &mut contents.parity,
+            false => &mut contents.data,
+        };
+        if out.len() <= bit.bit / 8 {
+            out.resize(bit.bit / 8 + 1, 0);
+        }
+        out[bit.bit / 8] |= (((value >> (bit.offset % 32)) & 1) as u8) << (bit.bit % 8);
+    }
+    if contents.data.is_empty() {
+        bail!("no bits of {block} in the ll file");
+    }
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_s7_site() {
+        let frames: Vec<u32> = (0..S7_CONTENT_FRAMES * 101).map(|w| w as u32).collect();
+        let site = s7_site(&frames, 5).unwrap();
+        assert_eq!(site.len(), 40960 / 8);
+        // past the ECC word, then the same words of the next frame
+        assert_eq!(site[..4], 51u32.to_le_bytes());
+        assert_eq!(site[40..44], 152u32.to_le_bytes());
+        assert!(s7_site(&frames, S7_SITES).is_err());
+        assert!(s7_site(&frames[..101], 0).is_err());
+    }
+
+    #[test]
+    fn test_logic_location() {
+        let ll = "Revision 3\nBit  1000 0x00800001  33 Block=RAMB36_X0Y0 Ram=B:BIT1\nBit  1001 \
+                  0x00800000   0 Block=RAMB36_X0Y0 Ram=B:BIT0\nBit  1002 0x00800002   5 \
+                  Block=RAMB36_X0Y0 Ram=B:PARBIT0\nBit  1003 0x00400100  12 Block=SLICE_X0Y0 \
+                  Latch=AQ Net=q\nBit  1004 0x00800000   1 Block=RAMB36_X0Y1 Ram=B:BIT0\n";
+        let ll = LogicLocation::parse(ll).unwrap();
+        assert_eq!(ll.bits.len(), 4);
+        let (far, count) = ll.frames("RAMB36_X0Y0", Xilinx32Family::S7).unwrap();
+        assert_eq!((far, count), (0x0080_0000, 3));
+
+        let frame_words = 2;
+        let frames = [0b1, 0, 0, 0b10, 0b10_0000, 0];
+        let contents = extract(&ll, "RAMB36_X0Y0", far, &frames, frame_words).unwrap();
+        assert_eq!(contents.data, [0b11]);
+        assert_eq!(contents.parity, [0b1]);
+        assert!(extract(&ll, "RAMB36_X9Y9", far, &frames, frame_words).is_err());
+    }
+}
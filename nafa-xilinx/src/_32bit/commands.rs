@@ -13,15 +13,43 @@ const DUPLICATED_SKIP_SLR: u32 = 0b100100
     | 0b100100 << (6 * 5);
 
 pub const fn master(val: Master, num_slr: u8) -> u32 {
-    let val = val as u8 as u32;
-    let shift = (num_slr - 1) * 6;
-    DUPLICATED_SKIP_SLR & !(0b111111 << shift) | val << shift
+    master_on(val, num_slr, 0)
+}
+
+/// A [`Master`] instruction sent to `active_slr` instead of the master SLR,
+/// i.e. to read the USERCODE of each SLR.
+pub const fn master_on(val: Master, num_slr: u8, active_slr: u8) -> u32 {
+    on_slr(val as u8, num_slr, active_slr)
 }
 
 pub const fn shifted(val: Shifted, num_slr: u8, active_slr: u8) -> u32 {
-    let val = val as u8 as u32;
+    on_slr(val as u8, num_slr, active_slr)
+}
+
+/// SLR 0 (the master) is closest to TDI, so its instruction is shifted last.
+const fn on_slr(val: u8, num_slr: u8, active_slr: u8) -> u32 {
     let shift = (num_slr - 1 - active_slr) * 6;
-    DUPLICATED_SKIP_SLR & !(0b111111 << shift) | val << shift
+    DUPLICATED_SKIP_SLR & !(0b111111 << shift) | (val as u32) << shift
+}
+
+/// The SLRs not addressed by [`master`], [`master_on`] or [`shifted`] are in
+/// bypass, each adding a bit to the DR. Those between `active_slr` and TDO
+/// come out before the addressed register.
+pub const fn bypass_bits_before(num_slr: u8, active_slr: u8) -> usize {
+    (num_slr - 1 - active_slr) as usize
+}
+
+/// Fill `out` with the bits of `data` (LSB first) from bit `skip` on.
+pub fn skip_bits(data: &[u8], skip: usize, out: &mut [u8]) {
+    let (bytes, bits) = (skip / 8, skip % 8);
+    for (i, out) in out.iter_mut().enumerate() {
+        let lo = data.get(bytes + i).copied().unwrap_or(0);
+        let hi = data.get(bytes + i + 1).copied().unwrap_or(0);
+        *out = match bits {
+            0 => lo,
+            _ => lo >> bits | hi << (8 - bits),
+        };
+    }
 }
 
 #[repr(u8)]
@@ -82,3 +110,21 @@ pub enum Shifted {
     FUSE_RSA    = 0b011000,
     FUSE_SEC    = 0b111011,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_bits() {
+        let data = [0b1010_1101, 0b0000_0011, 0b0000_0001];
+        let skipped = |skip, len| {
+            let mut out = vec![0; len];
+            skip_bits(&data, skip, &mut out);
+            out
+        };
+        assert_eq!(skipped(0, 2), [0b1010_1101, 0b0000_0011]);
+        assert_eq!(skipped(2, 2), [0b1110_1011, 0b0100_0000]);
+        assert_eq!(skipped(9, 1), [0b1000_0001]);
+    }
+}
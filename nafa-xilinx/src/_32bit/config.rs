@@ -0,0 +1,162 @@
+//! Typed configuration option registers: CTL0, COR0, and COR1.
+//!
+//! Single bits are flags, wider fields are read and written through
+//! [`Field`]s, i.e. `Cor0::DONE_CYCLE.get(cor0)`. Change them on a device
+//! with [`actions::config::modify`](super::actions::config::modify).
+
+use bitflags::{Flags, bitflags};
+
+use super::registers::Addr;
+
+/// A field wider than one bit in a register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub shift: u8,
+    pub width: u8,
+}
+
+impl Field {
+    pub const fn new(shift: u8, width: u8) -> Self {
+        Self { shift, width }
+    }
+
+    /// The bits of the field, in place.
+    pub const fn mask(self) -> u32 {
+        ((1 << self.width) - 1) << self.shift
+    }
+
+    pub fn get<R: ConfigRegister>(self, reg: R) -> u32 {
+        (reg.bits() & self.mask()) >> self.shift
+    }
+
+    /// `reg` with the field set to `value`, which is truncated to fit.
+    pub fn set<R: ConfigRegister>(self, reg: R, value: u32) -> R {
+        let bits = (reg.bits() & !self.mask()) | ((value << self.shift) & self.mask());
+        R::from_bits_retain(bits)
+    }
+}
+
+/// A register that can be read and changed with
+/// [`actions::config`](super::actions::config).
+pub trait ConfigRegister: Flags<Bits = u32> + Copy + sealed::Sealed {}
+
+mod sealed {
+    pub trait Sealed {
+        fn addr() -> super::Addr;
+    }
+}
+
+pub(crate) fn addr<R: ConfigRegister>() -> Addr {
+    R::addr()
+}
+
+bitflags! {
+    /// Control register 0 (UG470 table 5-24, UG570 table 9-22).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Ctl0: u32 {
+        /// Deassert to tristate the user I/Os.
+        const GTS_USR_B            = 1 << 0;
+        /// Keep the configuration interface pins after configuration, i.e.
+        /// for SelectMAP readback.
+        const PERSIST              = 1 << 3;
+        /// Bitstream is encrypted.
+        const DEC                  = 1 << 6;
+        /// FAR source for readback, 0 for the FAR register, 1 for EFAR.
+        const FARSRC               = 1 << 7;
+        /// Mask LUT RAM and SRL contents during readback.
+        const GLUTMASK_B           = 1 << 8;
+        const CONFIG_FALLBACK      = 1 << 10;
+        const OVER_TEMP_POWER_DOWN = 1 << 12;
+        /// Use the bottom ICAP instead of the top one.
+        const ICAP_SELECT          = 1 << 30;
+        /// Decrypt with the eFUSE key instead of the BBRAM key.
+        const EFUSE_KEY            = 1 << 31;
+
+        const _ = !0;
+    }
+}
+
+impl Ctl0 {
+    /// Security level: 0 none, 1 readback disabled, 2 and 3 readback and
+    /// reconfiguration disabled.
+    pub const SBITS: Field = Field::new(4, 2);
+}
+
+bitflags! {
+    /// Configuration option register 0 (UG470 table 5-29).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Cor0: u32 {
+        /// Clock the startup sequence only once.
+        const SINGLE      = 1 << 23;
+        /// Actively drive DONE high instead of open drain.
+        const DRIVE_DONE  = 1 << 24;
+        const DONE_PIPE   = 1 << 25;
+        const PWRDWN_STAT = 1 << 27;
+
+        const _ = !0;
+    }
+}
+
+impl Cor0 {
+    /// Startup phase each signal is released in, 7 to keep it.
+    pub const GWE_CYCLE: Field = Field::new(0, 3);
+    pub const GTS_CYCLE: Field = Field::new(3, 3);
+    pub const LOCK_CYCLE: Field = Field::new(6, 3);
+    pub const MATCH_CYCLE: Field = Field::new(9, 3);
+    pub const DONE_CYCLE: Field = Field::new(12, 3);
+    /// Startup clock: 0 CCLK, 1 user clock, 2 JTAG clock.
+    pub const SSCLKSRC: Field = Field::new(15, 2);
+    /// CCLK frequency in master modes.
+    pub const OSCFSEL: Field = Field::new(17, 6);
+}
+
+bitflags! {
+    /// Configuration option register 1 (UG470 table 5-32).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Cor1: u32 {
+        /// Continuously check the readback CRC of the configuration.
+        const RBCRC_EN                   = 1 << 8;
+        const RBCRC_NO_PIN               = 1 << 9;
+        const PERSIST_DEASSERT_AT_DESYNC = 1 << 17;
+
+        const _ = !0;
+    }
+}
+
+impl Cor1 {
+    pub const BPI_PAGE_SIZE: Field = Field::new(0, 2);
+    pub const BPI_1ST_READ_CYCLE: Field = Field::new(2, 2);
+    /// On a readback CRC error: 0 continue, 1 halt, 2 correct and halt, 3
+    /// correct and continue.
+    pub const RBCRC_ACTION: Field = Field::new(15, 2);
+}
+
+macro_rules! config_register {
+    ($($ty:ident => $addr:ident),* $(,)?) => {$(
+        impl sealed::Sealed for $ty {
+            fn addr() -> Addr {
+                Addr::$addr
+            }
+        }
+        impl ConfigRegister for $ty {}
+    )*};
+}
+
+config_register!(Ctl0 => Ctl0, Cor0 => Cor0, Cor1 => Cor1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        let cor0 = Cor0::from_bits_retain(0x0200_3fe5);
+        assert_eq!(Cor0::DONE_CYCLE.get(cor0), 3);
+        assert_eq!(Cor0::GWE_CYCLE.get(cor0), 5);
+        let cor0 = Cor0::DONE_CYCLE.set(cor0, 4);
+        assert_eq!(cor0.bits(), 0x0200_4fe5);
+        assert!(cor0.contains(Cor0::DONE_PIPE));
+        // truncated to the field
+        assert_eq!(Cor0::GWE_CYCLE.set(cor0, 0xf).bits(), 0x0200_4fe7);
+    }
+}
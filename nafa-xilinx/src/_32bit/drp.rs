@@ -1,4 +1,5 @@
-use nafa_io::devices::Xilinx32Family as Family;
+use bitflags::bitflags;
+use nafa_io::{devices::Xilinx32Family as Family, units::Bytes};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Command {
@@ -17,6 +18,46 @@ impl Command {
     }
 }
 
+/// The system monitor of a family: the XADC on 7-series, SYSMONE1 on
+/// UltraScale, and SYSMONE4 on UltraScale+. They share most of the register
+/// map in [`Addr`], but not the transfer functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sysmon {
+    Xadc,
+    Sysmone1,
+    Sysmone4,
+}
+
+impl Sysmon {
+    pub fn of(family: Family) -> Self {
+        match family {
+            Family::S7 => Self::Xadc,
+            Family::US => Self::Sysmone1,
+            Family::UP => Self::Sysmone4,
+        }
+    }
+
+    /// TCKs in Run-Test/Idle after each JTAG DRP command, before the next
+    /// one is shifted in to return its result.
+    ///
+    /// The XADC gives JTAG priority over the fabric DRP port. On SYSMONE1/4,
+    /// JTAG instead waits for a fabric transaction in progress to finish
+    /// (UG580, "JTAG DRP arbitration"), so give it more time.
+    pub fn drp_idle(self) -> Bytes<usize> {
+        match self {
+            Self::Xadc => Bytes(10),
+            Self::Sysmone1 | Self::Sysmone4 => Bytes(32),
+        }
+    }
+
+    /// Whether `addr` is in this block's register map. The user supply
+    /// registers are only on SYSMONE1/4.
+    pub fn has(self, addr: Addr) -> bool {
+        let user = (addr as u16) >= Addr::VUser0 as u16;
+        self != Self::Xadc || !user
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug)]
 pub enum Cmd {
@@ -27,6 +68,9 @@ pub enum Cmd {
 
 /// The descriptions are taken from [UG480] (Series 7). However, the registers
 /// are mostly the same for Ultrascale and Ultrascale+, detailed in [UG580].
+/// On UltraScale+, `0x0d`..`0x0f` hold VCC_PSINTLP, VCC_PSINTFP, and
+/// VCC_PSAUX instead. The VUSER registers are only on UltraScale(+), see
+/// [`Sysmon::has`].
 ///
 /// [UG480], [Table 3-1]: Status Registers (Read Only)
 ///
@@ -228,6 +272,216 @@ pub enum Addr {
     ///
     /// [Flag Register]: https://docs.amd.com/api/khub/maps/qOeib0vlzXa1isUAfuFzOQ/attachments/_mT0t4XmsgJ2qfoNRTv53w-qOeib0vlzXa1isUAfuFzOQ/content#G6.301009
     Flag = 0x3f,
+
+    /// Configuration registers 0-2: averaging and channel for single channel
+    /// mode, sequencer mode and alarm enables, and clock divider.
+    Config0 = 0x40,
+    Config1 = 0x41,
+    Config2 = 0x42,
+
+    /// Channels enabled in the sequencer, see [`SEQ_CHANNELS`].
+    SeqChannel0 = 0x48,
+    SeqChannel1 = 0x49,
+    /// Channels averaged in the sequencer, same bits as `SeqChannel`.
+    SeqAverage0 = 0x4a,
+    SeqAverage1 = 0x4b,
+    /// Channels measured bipolar in the sequencer, same bits as `SeqChannel`.
+    SeqBipolar0 = 0x4c,
+    SeqBipolar1 = 0x4d,
+    /// Channels with extended settling time, same bits as `SeqChannel`.
+    SeqAcquisition0 = 0x4e,
+    SeqAcquisition1 = 0x4f,
+
+    /// The results of the conversions on the user supplies, VUSER0-3 (I/O
+    /// bank supplies chosen with the SYSMON configuration). The 10 MSBs
+    /// correspond to the supply sensor transfer function, with a 3V range for
+    /// HP banks and 6V for HR banks.
+    VUser0 = 0x80,
+    VUser1 = 0x81,
+    VUser2 = 0x82,
+    VUser3 = 0x83,
+
+    /// Maximum VUSER0-3 measurements recorded since power-up or the last
+    /// SYSMON reset.
+    MaxVUser0 = 0xa0,
+    MaxVUser1 = 0xa1,
+    MaxVUser2 = 0xa2,
+    MaxVUser3 = 0xa3,
+
+    /// Minimum VUSER0-3 measurements recorded since power-up or the last
+    /// SYSMON reset.
+    MinVUser0 = 0xa8,
+    MinVUser1 = 0xa9,
+    MinVUser2 = 0xaa,
+    MinVUser3 = 0xab,
+}
+
+/// Base address of the PS SYSMON registers of a Zynq UltraScale+ (in the
+/// AMS block, UG1085 chapter 9). Each [`PsAddr`] is a 32-bit register at
+/// [`PsAddr::address`], with the value in the low half.
+pub const PS_SYSMON_BASE: u32 = 0xffa5_0800;
+
+/// Registers of the PS SYSMON of a Zynq UltraScale+. The register map is that
+/// of a SYSMONE4, but the supply channels measure the PS rails.
+///
+/// Unlike the PL SYSMON, it isn't on the JTAG DRP. It's memory-mapped, read
+/// with [`actions::xadc::run_ps`](super::actions::xadc::run_ps).
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PsAddr {
+    /// Temperature of the low-power domain.
+    TempLpd = 0x00,
+    VccPsIntLp = 0x01,
+    VccPsIntFp = 0x02,
+    VRefP = 0x04,
+    VRefN = 0x05,
+    VccPsAux = 0x06,
+    VccPsDdr = 0x0d,
+    VccPsIo3 = 0x0e,
+    VccPsIo0 = 0x0f,
+    VccPsIo1 = 0x80,
+    VccPsIo2 = 0x81,
+    PsMgtRaVcc = 0x82,
+    PsMgtRaVtt = 0x83,
+    VccPsAdc = 0x84,
+    /// Temperature of the full-power domain.
+    TempFpd = 0x85,
+}
+
+impl PsAddr {
+    pub const ALL: [Self; 15] = [
+        Self::TempLpd,
+        Self::VccPsIntLp,
+        Self::VccPsIntFp,
+        Self::VRefP,
+        Self::VRefN,
+        Self::VccPsAux,
+        Self::VccPsDdr,
+        Self::VccPsIo3,
+        Self::VccPsIo0,
+        Self::VccPsIo1,
+        Self::VccPsIo2,
+        Self::PsMgtRaVcc,
+        Self::PsMgtRaVtt,
+        Self::VccPsAdc,
+        Self::TempFpd,
+    ];
+
+    /// Address of the register in the PS memory map.
+    pub fn address(self) -> u32 {
+        PS_SYSMON_BASE + 4 * self as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TempLpd => "temp_lpd",
+            Self::VccPsIntLp => "vcc_psintlp",
+            Self::VccPsIntFp => "vcc_psintfp",
+            Self::VRefP => "vrefp",
+            Self::VRefN => "vrefn",
+            Self::VccPsAux => "vcc_psaux",
+            Self::VccPsDdr => "vcc_psddr",
+            Self::VccPsIo3 => "vcc_psio3",
+            Self::VccPsIo0 => "vcc_psio0",
+            Self::VccPsIo1 => "vcc_psio1",
+            Self::VccPsIo2 => "vcc_psio2",
+            Self::PsMgtRaVcc => "ps_mgtravcc",
+            Self::PsMgtRaVtt => "ps_mgtravtt",
+            Self::VccPsAdc => "vcc_psadc",
+            Self::TempFpd => "temp_fpd",
+        }
+    }
+
+    pub fn transfer(self) -> Transfer {
+        match self {
+            Self::TempLpd | Self::TempFpd => Transfer::Exactly(temperature_ps),
+            _ => Transfer::Exactly(power_supply_us),
+        }
+    }
+}
+
+/// The status register of each bit in [`Addr::SeqChannel0`] (first 16) and
+/// [`Addr::SeqChannel1`] (last 16). Bit 0 of `SeqChannel0` is calibration,
+/// which has no result register.
+pub const SEQ_CHANNELS: [Option<Addr>; 32] = {
+    use Addr::*;
+    #[rustfmt::skip]
+    let channels = [
+        None, None, None, None, None, None, None, None,
+        Some(Temperature), Some(VccInt), Some(VccAux), Some(VpVn),
+        Some(VRefP), Some(VRefN), Some(VccBram), None,
+        Some(VAuxPVAuxN0), Some(VAuxPVAuxN1), Some(VAuxPVAuxN2), Some(VAuxPVAuxN3),
+        Some(VAuxPVAuxN4), Some(VAuxPVAuxN5), Some(VAuxPVAuxN6), Some(VAuxPVAuxN7),
+        Some(VAuxPVAuxN8), Some(VAuxPVAuxN9), Some(VAuxPVAuxNA), Some(VAuxPVAuxNB),
+        Some(VAuxPVAuxNC), Some(VAuxPVAuxND), Some(VAuxPVAuxNE), Some(VAuxPVAuxNF),
+    ];
+    channels
+};
+
+/// Whether the register at `addr` is safe to write: configuration registers
+/// 0-2, the sequencer registers, and the alarm thresholds (`0x50`..`0x5f`).
+///
+/// The rest are status registers (writing some of them resets the block),
+/// factory test registers (`0x43`..`0x47`), or reserved.
+pub const fn writable(addr: u16) -> bool {
+    matches!(addr, 0x40..=0x42 | 0x48..=0x5f)
+}
+
+/// Sequencer mode in bits 15:12 of [`Addr::Config1`].
+pub const SEQ_MODE_MASK: u16 = 0xf000;
+/// Continuously scan the channels enabled in `SeqChannel`.
+pub const SEQ_MODE_CONTINUOUS: u16 = 0x2000;
+
+/// The registers of the channels enabled in the sequencer, from the values of
+/// [`Addr::SeqChannel0`] and [`Addr::SeqChannel1`].
+pub fn seq_channels(seq0: u16, seq1: u16) -> Vec<Addr> {
+    let bits = u32::from(seq1) << 16 | u32::from(seq0);
+    (SEQ_CHANNELS.iter().enumerate())
+        .filter(|(bit, _)| bits >> bit & 1 != 0)
+        .filter_map(|(_, addr)| *addr)
+        .collect()
+}
+
+bitflags! {
+    /// The alarm bits of [`Addr::Flag`], the same on the XADC and SYSMONE1/4
+    /// (UG480 figure 3-2, UG580 "Flag Register"). Alarms 4-6 are the PS
+    /// supplies of a Zynq.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Flags: u16 {
+        const TEMPERATURE = 1 << 0;
+        const VCC_INT     = 1 << 1;
+        const VCC_AUX     = 1 << 2;
+        const OVER_TEMP   = 1 << 3;
+        const VCC_BRAM    = 1 << 4;
+        const VCC_PINT    = 1 << 5;
+        const VCC_PAUX    = 1 << 6;
+        const VCC_ODDR    = 1 << 7;
+        /// The internal reference failed, and the supply is used instead.
+        const REF         = 1 << 9;
+
+        const _ = !0;
+    }
+}
+
+impl Flags {
+    /// The alarm for the sensor at `addr`, if it has one.
+    pub fn alarm(addr: Addr) -> Option<Self> {
+        Some(match addr {
+            Addr::Temperature => Self::TEMPERATURE,
+            Addr::VccInt => Self::VCC_INT,
+            Addr::VccAux => Self::VCC_AUX,
+            Addr::VccBram => Self::VCC_BRAM,
+            Addr::VccPInt => Self::VCC_PINT,
+            Addr::VccPAux => Self::VCC_PAUX,
+            Addr::VccODdr => Self::VCC_ODDR,
+            _ => return None,
+        })
+    }
+
+    /// Whether the sensor at `addr` is outside its alarm thresholds.
+    pub fn alarmed(self, addr: Addr) -> bool {
+        Self::alarm(addr).is_some_and(|alarm| self.contains(alarm))
+    }
 }
 
 pub enum Transfer {
@@ -277,7 +531,20 @@ impl Addr {
             | Addr::VAuxPVAuxNC
             | Addr::VAuxPVAuxND
             | Addr::VAuxPVAuxNE
-            | Addr::VAuxPVAuxNF => Transfer::OneOf(&[adc_unipolar_s7, adc_bipolar_s7]),
+            | Addr::VAuxPVAuxNF => adc(family),
+
+            Addr::VUser0
+            | Addr::VUser1
+            | Addr::VUser2
+            | Addr::VUser3
+            | Addr::MaxVUser0
+            | Addr::MaxVUser1
+            | Addr::MaxVUser2
+            | Addr::MaxVUser3
+            | Addr::MinVUser0
+            | Addr::MinVUser1
+            | Addr::MinVUser2
+            | Addr::MinVUser3 => user_supply(family),
 
             _ => Transfer::None,
         }
@@ -293,8 +560,6 @@ pub fn temperature(family: Family) -> Transfer {
             |d| linear_scale_10(d, -273.6777, 501.3743 / _2_10), // sysmone1, internal ref
         ]),
         Family::UP => Transfer::OneOf(&[
-            |d| linear_scale_10(d, -273.8195, 502.9098 / _2_10), // sysmone1, external ref
-            |d| linear_scale_10(d, -273.6777, 501.3743 / _2_10), // sysmone1, internal ref
             |d| linear_scale_10(d, -279.4266, 507.5921 / _2_10), // sysmone4, external ref
             |d| linear_scale_10(d, -280.2309, 509.3141 / _2_10), // sysmone4, internal ref
         ]),
@@ -315,6 +580,23 @@ pub fn adc(family: Family) -> Transfer {
     }
 }
 
+pub fn user_supply(family: Family) -> Transfer {
+    match family {
+        Family::S7 => Transfer::None,
+        Family::US | Family::UP => Transfer::OneOf(&[power_supply_us, user_supply_hr_us]),
+    }
+}
+
+/// VUSER on an HR bank, with twice the range of the other supplies.
+pub fn user_supply_hr_us(data: u16) -> f32 {
+    linear_scale_10(data, 0., 0.00586)
+}
+
+/// The PS SYSMON always uses its internal reference.
+pub fn temperature_ps(data: u16) -> f32 {
+    linear_scale_10(data, -280.2309, 509.3141 / 1024.)
+}
+
 pub fn power_supply_us(data: u16) -> f32 {
     linear_scale_10(data, 0., 0.00293)
 }
@@ -362,3 +644,36 @@ fn linear_scale_12_signed(data: u16, base: f32, step: f32) -> f32 {
     let val = (data as i16 >> 4) as f32;
     val.mul_add(step, base)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writable() {
+        assert!(writable(Addr::Config1 as u16));
+        assert!(writable(Addr::SeqChannel0 as u16));
+        assert!(writable(0x50));
+        assert!(!writable(Addr::VccInt as u16));
+        assert!(!writable(0x43));
+        assert!(!writable(0x60));
+    }
+
+    #[test]
+    fn test_seq_channels() {
+        // calibration, temperature, vccint, and vaux 0 and f
+        let channels = seq_channels(0x0301, 0x8001);
+        let channels: Vec<_> = channels.iter().map(|a| *a as u16).collect();
+        assert_eq!(channels, [0x00, 0x01, 0x10, 0x1f]);
+    }
+
+    #[test]
+    fn test_flags() {
+        let flags = Flags::from_bits_retain(0x0019);
+        assert!(flags.alarmed(Addr::Temperature));
+        assert!(flags.alarmed(Addr::VccBram));
+        assert!(!flags.alarmed(Addr::VccInt));
+        assert!(!flags.alarmed(Addr::VpVn));
+        assert!(flags.contains(Flags::OVER_TEMP));
+    }
+}
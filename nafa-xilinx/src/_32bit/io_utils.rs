@@ -1,35 +1,116 @@
-use eyre::Result;
+use eyre::{Result, bail};
 use nafa_io::{
-    Command,
-    units::{Bytes, Words32},
+    Command, WordOrder, WordsExt as _,
+    units::{Bits, Bytes, Words32},
 };
 
 use super::{
     Controller, bitstream_to_wire_order,
     commands::{self, shifted},
-    from_wire_order,
-    registers::{Addr, OpCode, Type1},
+    registers::{Addr, CmdCode, OpCode, Type1},
+    to_wire_order,
 };
-use crate::_32bit::commands::{duplicated, master};
+use crate::_32bit::commands::{duplicated, master_on};
 
 pub async fn read_device_register<'a>(
     cont: Controller<'a>,
     active_slr: u8,
     reg: Type1,
 ) -> Result<&'a [u8]> {
-    let tiny_bitstream =
-        bitstream_to_wire_order([Type1::SYNC, Type1::NOOP, reg.to_raw(), Type1::NOOP, Type1::NOOP]);
+    let tiny_bitstream = register_read_packets(reg);
     let tiny_bitstream = tiny_bitstream.as_flattened();
     let num_slr = cont.info().slr;
 
-    cont.consume()
+    let data = cont
+        .consume()
         .run([
             Command::ir(shifted(commands::CFG_IN, num_slr, active_slr)),
             Command::dr_tx(tiny_bitstream),
             Command::ir(shifted(commands::CFG_OUT, num_slr, active_slr)),
             Command::dr_rx(Bytes::from(reg.word_count.into_())),
         ])
-        .await
+        .await?;
+    Ok(data)
+}
+
+/// Read one word from each of `addrs`, in a single flush.
+///
+/// Each read gets its own CFG_IN/CFG_OUT pair, exactly as
+/// [`read_device_register`] does, only without flushing in between.
+/// Queueing several reads behind one sync and reading all the words back
+/// from CFG_OUT at once doesn't work: on an XC7A35T, that returned the
+/// first register twice, then zeros.
+pub async fn read_device_register_words(
+    cont: Controller<'_>,
+    active_slr: u8,
+    addrs: &[Addr],
+) -> Result<Vec<u32>> {
+    let packets: Vec<_> = (addrs.iter())
+        .map(|&addr| register_read_packets(Type1::new(OpCode::Read, addr, Words32(1))))
+        .collect();
+    let num_slr = cont.info().slr;
+    let commands = packets.iter().flat_map(|packets| {
+        [
+            Command::ir(shifted(commands::CFG_IN, num_slr, active_slr)),
+            Command::dr_tx(packets.as_flattened()),
+            Command::ir(shifted(commands::CFG_OUT, num_slr, active_slr)),
+            Command::dr_rx(Bytes(4)),
+        ]
+    });
+
+    let data = cont.consume().run(commands).await?;
+    if data.len() != 4 * addrs.len() {
+        bail!(
+            "expected {} register words, got {}",
+            addrs.len(),
+            Bytes(data.len())
+        );
+    }
+    Ok(data.words(WordOrder::MSB_FIRST).collect())
+}
+
+fn register_read_packets(reg: Type1) -> [[u8; 4]; 5] {
+    bitstream_to_wire_order([Type1::SYNC, Type1::NOOP, reg.to_raw(), Type1::NOOP, Type1::NOOP])
+}
+
+/// Write `writes` in order, then desync so the configuration logic is left
+/// as it was found.
+pub async fn write_device_registers(
+    cont: Controller<'_>,
+    active_slr: u8,
+    writes: &[(Addr, u32)],
+) -> Result<()> {
+    let packets = (writes.iter())
+        .flat_map(|&(addr, value)| [write_one(addr), value])
+        .chain([write_one(Addr::Cmd), CmdCode::Desync as u32]);
+    send_device_packets(cont, active_slr, packets).await
+}
+
+/// Type 1 header writing one word to `addr`.
+pub fn write_one(addr: Addr) -> u32 {
+    Type1::new(OpCode::Write, addr, Words32(1)).to_raw()
+}
+
+/// Send `packets` to the configuration logic, after a sync word.
+pub async fn send_device_packets(
+    cont: Controller<'_>,
+    active_slr: u8,
+    packets: impl IntoIterator<Item = u32>,
+) -> Result<()> {
+    let packets = [Type1::SYNC, Type1::NOOP]
+        .into_iter()
+        .chain(packets)
+        .chain([Type1::NOOP, Type1::NOOP]);
+    let data: Vec<u8> = packets.flat_map(to_wire_order).collect();
+    let num_slr = cont.info().slr;
+
+    cont.consume()
+        .run([
+            Command::ir(shifted(commands::CFG_IN, num_slr, active_slr)),
+            Command::dr_tx(&data),
+        ])
+        .await?;
+    Ok(())
 }
 
 pub async fn read_device_register_word(
@@ -37,9 +118,11 @@ pub async fn read_device_register_word(
     active_slr: u8,
     addr: Addr,
 ) -> Result<u32> {
-    let data = read_device_register_sized(cont, active_slr, addr).await?;
-    let data = from_wire_order(*data);
-    Ok(data)
+    let data = read_device_register_sized::<4>(cont, active_slr, addr).await?;
+    Ok(data
+        .words(WordOrder::MSB_FIRST)
+        .next()
+        .expect("one word read"))
 }
 
 async fn read_device_register_sized<const N: usize>(
@@ -68,24 +151,43 @@ pub async fn read_jtag_register_duplicated<const N: usize>(
 pub async fn read_jtag_register_master<const N: usize>(
     cont: Controller<'_>,
     inst: commands::Master,
-) -> Result<&[u8; N]> {
-    let num_slr = cont.info().slr;
-    let slice = cont
-        .consume()
-        .run([Command::ir(master(inst, num_slr)), Command::dr_rx(Bytes(N))])
-        .await?;
-    Ok(slice.try_into()?)
+) -> Result<[u8; N]> {
+    read_jtag_register_master_on(cont, 0, inst).await
 }
 
-pub async fn read_jtag_register_shifted<const N: usize>(
+/// [`read_jtag_register_master`] of `active_slr` instead of the master SLR.
+pub async fn read_jtag_register_master_on<const N: usize>(
     cont: Controller<'_>,
     active_slr: u8,
-    inst: commands::Shifted,
-) -> Result<&[u8; N]> {
+    inst: commands::Master,
+) -> Result<[u8; N]> {
     let num_slr = cont.info().slr;
-    let slice = cont
+    read_jtag_register_on_slr(cont, active_slr, master_on(inst, num_slr, active_slr)).await
+}
+
+async fn read_jtag_register_on_slr<const N: usize>(
+    cont: Controller<'_>,
+    active_slr: u8,
+    ir: u32,
+) -> Result<[u8; N]> {
+    let mut ret = [0; N];
+    read_jtag_register_into(cont, active_slr, ir, &mut ret).await?;
+    Ok(ret)
+}
+
+/// Read the register selected by `ir` in `active_slr` into `out`, past the
+/// bypass bits of the SLRs after it.
+pub async fn read_jtag_register_into(
+    cont: Controller<'_>,
+    active_slr: u8,
+    ir: u32,
+    out: &mut [u8],
+) -> Result<()> {
+    let skip = commands::bypass_bits_before(cont.info().slr, active_slr);
+    let data = cont
         .consume()
-        .run([Command::ir(shifted(inst, num_slr, active_slr)), Command::dr_rx(Bytes(N))])
+        .run([Command::ir(ir), Command::dr_rx_bits(Bits(8 * out.len() + skip))])
         .await?;
-    Ok(slice.try_into()?)
+    commands::skip_bits(data, skip, out);
+    Ok(())
 }
@@ -0,0 +1,340 @@
+//! Readback data as frames, in the layout of the bitstream's frame data.
+//!
+//! Readback starts with a pad frame, which is dropped. The pad frames at the
+//! end of each row are kept, so frame `n` of a readback is frame `n` of the
+//! [frame data](crate::bitstream::frame_data) it was configured with, and
+//! two readbacks (or a readback and a bitstream) can be compared frame by
+//! frame.
+//!
+//! With the [`FrameLayout`] of the part, [`Frames::without_pads`] drops the
+//! row pad frames too, leaving only the frames that hold configuration, and
+//! [`readback::frames`](super::actions::readback::frames) reads every SLR
+//! into a [`Readback`].
+
+use std::io::{self, Write};
+
+use eyre::{Result, bail};
+use nafa_io::{WordOrder, WordsExt as _, devices::FrameLayout, units::Bytes};
+
+/// The frames read back from one SLR.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frames {
+    frame_words: usize,
+    words: Vec<u32>,
+}
+
+impl Frames {
+    /// Frames from `data`, a readback as returned by
+    /// [`readback::run`](super::actions::readback::run). A partial frame at
+    /// the end is dropped.
+    pub fn from_readback(data: &[u8], frame_words: usize) -> Result<Self> {
+        let words: Vec<u32> = data.words(WordOrder::MSB_FIRST).collect();
+        Self::from_words(&words, frame_words)
+    }
+
+    /// [`Frames::from_readback`], on a readback already converted to words.
+    pub fn from_words(words: &[u32], frame_words: usize) -> Result<Self> {
+        let Some(words) = words.get(frame_words..) else {
+            bail!(
+                "readback of {} words is shorter than its pad frame",
+                words.len()
+            );
+        };
+        let len = words.len() / frame_words * frame_words;
+        Ok(Self {
+            frame_words,
+            words: words[..len].to_vec(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len() / self.frame_words
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn frame(&self, idx: usize) -> Option<&[u32]> {
+        self.iter().nth(idx)
+    }
+
+    pub fn iter(&self) -> std::slice::ChunksExact<'_, u32> {
+        self.words.chunks_exact(self.frame_words)
+    }
+
+    /// All frames, one after the other.
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    /// The frames without the pad frames after each row, with `rows` the
+    /// rows of this SLR in its [`FrameLayout`]. Frames past the last row are
+    /// dropped.
+    pub fn without_pads(&self, rows: &[u16]) -> Result<Self> {
+        let frames: usize = rows
+            .iter()
+            .map(|&row| usize::from(row) + FrameLayout::ROW_PAD)
+            .sum();
+        if self.len() < frames {
+            bail!(
+                "readback of {} frames is shorter than its {frames} frames of rows",
+                self.len()
+            );
+        }
+        let mut words = Vec::new();
+        let mut start = 0;
+        for &row in rows {
+            let end = start + usize::from(row);
+            words.extend_from_slice(&self.words[start * self.frame_words..end * self.frame_words]);
+            start = end + FrameLayout::ROW_PAD;
+        }
+        Ok(Self {
+            frame_words: self.frame_words,
+            words,
+        })
+    }
+
+    /// Indices of the frames that differ from `other`. Frames only in one of
+    /// them are not compared.
+    pub fn differing<'a>(&'a self, other: &'a Frames) -> impl Iterator<Item = usize> + 'a {
+        (self.iter().zip(other.iter()).enumerate())
+            .filter(|(_, (a, b))| a != b)
+            .map(|(idx, _)| idx)
+    }
+}
+
+/// The frames of every SLR, SLR 0 (the master) first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Readback {
+    pub slrs: Vec<Frames>,
+}
+
+impl Readback {
+    /// A readback from the raw `readbacks` of each SLR, as returned by
+    /// [`readback::run_slr`](super::actions::readback::run_slr), without any
+    /// pad frames.
+    pub fn from_slrs<'a>(
+        readbacks: impl IntoIterator<Item = &'a [u8]>,
+        frame_words: usize,
+        layout: &FrameLayout,
+    ) -> Result<Self> {
+        let readbacks: Vec<_> = readbacks.into_iter().collect();
+        if readbacks.len() != layout.slrs.len() {
+            bail!(
+                "{} readbacks for {} SLRs",
+                readbacks.len(),
+                layout.slrs.len()
+            );
+        }
+        let slrs = (readbacks.into_iter().zip(layout.slrs.iter()))
+            .map(|(data, rows)| Frames::from_readback(data, frame_words)?.without_pads(rows))
+            .collect::<Result<_>>()?;
+        Ok(Self { slrs })
+    }
+
+    /// The frame words of every SLR, big endian, like the frame data of a
+    /// `.bin`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        (self.slrs.iter())
+            .flat_map(|slr| slr.words())
+            .flat_map(|w| w.to_be_bytes())
+            .collect()
+    }
+
+    /// Write the frames like a `.rbd` (or `.rbt`): a short text header, then
+    /// one word per line as ASCII `0`/`1`.
+    pub fn write_rbd(&self, mut w: impl Write, part: &str) -> io::Result<()> {
+        let words = self.slrs.iter().map(|slr| slr.words().len()).sum::<usize>();
+        write_rbd_header(&mut w, part, words)?;
+        for word in self.slrs.iter().flat_map(|slr| slr.words()) {
+            writeln!(w, "{word:032b}")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_rbd_header(mut w: impl Write, part: &str, words: usize) -> io::Result<()> {
+    writeln!(w, "Xilinx ASCII Readback Data")?;
+    writeln!(w, "Created by nafa")?;
+    writeln!(w, "Part:\t{part}")?;
+    writeln!(w, "Bits:\t{}", words * 32)
+}
+
+/// Writes a readback of one SLR as [`Readback::to_bytes`] or
+/// [`Readback::write_rbd`] would, as it is read: for streaming a readback to
+/// a file with a [`WriteBuffer`](nafa_io::buffers::WriteBuffer), without
+/// holding all of it.
+pub struct FrameWriter<W> {
+    out: W,
+    frame_bytes: usize,
+    /// Bytes of the pad frame not seen yet.
+    skip: usize,
+    /// Part of a frame, until the rest of it arrives.
+    pending: Vec<u8>,
+    /// Frames left to write of those promised in the `.rbd` header.
+    rbd_frames: Option<usize>,
+}
+
+impl<W: Write> FrameWriter<W> {
+    /// Big endian words, like [`Readback::to_bytes`].
+    pub fn frames(out: W, frame_words: usize) -> Self {
+        Self {
+            out,
+            frame_bytes: 4 * frame_words,
+            skip: 4 * frame_words,
+            pending: Vec::new(),
+            rbd_frames: None,
+        }
+    }
+
+    /// Like [`Readback::write_rbd`]. The header is written right away, so
+    /// it needs `len`, the length of the whole readback.
+    pub fn rbd(mut out: W, frame_words: usize, part: &str, len: Bytes<usize>) -> io::Result<Self> {
+        let frames = (len.0 / 4).saturating_sub(frame_words) / frame_words;
+        write_rbd_header(&mut out, part, frames * frame_words)?;
+        Ok(Self {
+            rbd_frames: Some(frames),
+            ..Self::frames(out, frame_words)
+        })
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let mut words = frame.words(WordOrder::MSB_FIRST);
+        match &mut self.rbd_frames {
+            None => {
+                let bytes: Vec<u8> = words.flat_map(u32::to_be_bytes).collect();
+                self.out.write_all(&bytes)
+            }
+            Some(0) => Ok(()),
+            Some(left) => {
+                *left -= 1;
+                words.try_for_each(|word| writeln!(self.out, "{word:032b}"))
+            }
+        }
+    }
+
+    /// Flush `W` and return it. A partial frame at the end is dropped, as by
+    /// [`Frames::from_readback`].
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(left @ 1..) = self.rbd_frames {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("readback ended {left} frames short of the .rbd header"),
+            ));
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> Write for FrameWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len();
+        let skipped = self.skip.min(buf.len());
+        (self.skip, buf) = (self.skip - skipped, &buf[skipped..]);
+
+        let mut pending = std::mem::take(&mut self.pending);
+        let buf = match pending.is_empty() {
+            true => buf,
+            false => {
+                pending.extend_from_slice(buf);
+                &pending[..]
+            }
+        };
+        let mut frames = buf.chunks_exact(self.frame_bytes);
+        for frame in &mut frames {
+            self.write_frame(frame)?;
+        }
+        self.pending = frames.remainder().to_vec();
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        // a pad frame, three frames, then part of a fourth
+        let words: Vec<u32> = (0..18).collect();
+        let frames = Frames::from_words(&words, 4).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames.frame(1), Some(&[8, 9, 10, 11][..]));
+        assert_eq!(frames.frame(3), None);
+        assert!(Frames::from_words(&words[..3], 4).is_err());
+
+        let mut other = words.clone();
+        other[13] ^= 1;
+        let other = Frames::from_words(&other, 4).unwrap();
+        assert_eq!(frames.differing(&other).collect::<Vec<_>>(), [2]);
+
+        let readback = Readback { slrs: vec![frames] };
+        let mut rbd = Vec::new();
+        readback.write_rbd(&mut rbd, "xc7a35t").unwrap();
+        let rbd = String::from_utf8(rbd).unwrap();
+        assert!(rbd.contains("Bits:\t384\n"));
+        let data = crate::bitstream::load(rbd.as_bytes(), None).unwrap();
+        assert_eq!(data, readback.to_bytes());
+    }
+
+    #[test]
+    fn test_without_pads() {
+        // a pad frame, rows of 2 and 1 frames each followed by 2 pad frames,
+        // then a frame past the last row
+        let words: Vec<u32> = (0..9).flat_map(|frame| [frame, frame]).collect();
+        let frames = Frames::from_words(&words, 2).unwrap();
+        let stripped = frames.without_pads(&[2, 1]).unwrap();
+        assert_eq!(stripped.words(), [1, 1, 2, 2, 5, 5]);
+        assert!(frames.without_pads(&[2, 3]).is_err());
+
+        let layout = FrameLayout {
+            slrs: vec![vec![2, 1].into(), vec![1].into()].into(),
+        };
+        let raw = |words: &[u32]| -> Vec<u8> {
+            (words.iter())
+                .flat_map(|&w| super::super::to_wire_order(w))
+                .collect()
+        };
+        let (slr0, slr1) = (raw(&words), raw(&[7, 7, 8, 8, 9, 9, 9, 9]));
+        let readback = Readback::from_slrs([&slr0[..], &slr1[..]], 2, &layout).unwrap();
+        assert_eq!(readback.slrs[0], stripped);
+        assert_eq!(readback.slrs[1].words(), [8, 8]);
+        assert!(Readback::from_slrs([&slr0[..]], 2, &layout).is_err());
+    }
+
+    #[test]
+    fn test_frame_writer() {
+        // as read from the device, in pieces that don't line up with frames
+        let words: Vec<u32> = (0..18).collect();
+        let raw: Vec<u8> = words
+            .iter()
+            .flat_map(|&w| super::super::to_wire_order(w))
+            .collect();
+        let readback = Readback {
+            slrs: vec![Frames::from_readback(&raw, 4).unwrap()],
+        };
+        let stream = |mut w: FrameWriter<Vec<u8>>| {
+            for piece in raw.chunks(7) {
+                w.write_all(piece).unwrap();
+            }
+            w.finish().unwrap()
+        };
+
+        let frames = stream(FrameWriter::frames(Vec::new(), 4));
+        assert_eq!(frames, readback.to_bytes());
+
+        let rbd = FrameWriter::rbd(Vec::new(), 4, "xc7a35t", Bytes(raw.len())).unwrap();
+        let mut expected = Vec::new();
+        readback.write_rbd(&mut expected, "xc7a35t").unwrap();
+        assert_eq!(stream(rbd), expected);
+
+        let short = FrameWriter::rbd(Vec::new(), 4, "xc7a35t", Bytes(2 * raw.len())).unwrap();
+        assert!(short.finish().is_err());
+    }
+}
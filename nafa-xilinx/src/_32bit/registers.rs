@@ -57,6 +57,30 @@ pub enum Addr {
     Bspi = 31,
 }
 
+/// Values written to [`Addr::Cmd`].
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(unused)]
+pub enum CmdCode {
+    Null = 0,
+    Wcfg = 1,
+    Mfw = 2,
+    Lfrm = 3,
+    Rcfg = 4,
+    Start = 5,
+    Rcap = 6,
+    Rcrc = 7,
+    Aghigh = 8,
+    Switch = 9,
+    Grestore = 10,
+    Shutdown = 11,
+    Gcapture = 12,
+    Desync = 13,
+    Iprog = 15,
+    Crcc = 16,
+    Ltimer = 17,
+}
+
 impl Type1 {
     /// ```text
     /// [31:29] header type
@@ -0,0 +1,436 @@
+//! SPI configuration flash, reached through the FPGA.
+//!
+//! The FPGA has to be running a JTAG-to-SPI bridge first, i.e. one of the
+//! `bscan_spi` bitstreams also used by OpenOCD's `jtagspi` driver, loaded with
+//! `program`. The bridge sits behind USER1: each DR shift is a marker bit, the
+//! transfer length in bits minus one (32 bits), then the bytes on MOSI. MISO
+//! comes back one clock late. Everything is MSB first.
+//!
+//! Flash geometry is read from SFDP (JESD216) where the flash has it, falling
+//! back to the JEDEC ID and the common 4 KiB / 64 KiB erase commands.
+
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use eyre::{Result, bail, ensure, eyre};
+use nafa_io::Command;
+
+use crate::_32bit::{
+    Controller,
+    commands::{self, master},
+};
+
+const READ_ID: u8 = 0x9f;
+const READ_SFDP: u8 = 0x5a;
+const READ_STATUS: u8 = 0x05;
+const WRITE_ENABLE: u8 = 0x06;
+const READ: u8 = 0x03;
+const READ_4B: u8 = 0x13;
+
+/// Status register bit set while an erase or write is in progress.
+const STATUS_BUSY: u8 = 0x01;
+/// Longest a single erase may take, generous for 64 KiB sectors.
+const ERASE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bytes read per transfer, i.e. between progress updates.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Anything that can run SPI transfers: send `write`, then clock in `read`
+/// bytes, all with chip select held.
+pub trait SpiBus {
+    fn transfer(&mut self, write: &[u8], read: usize) -> impl Future<Output = Result<Vec<u8>>>;
+}
+
+/// [`SpiBus`] through a `bscan_spi` bridge design.
+pub struct JtagSpi<'a> {
+    cont: Controller<'a>,
+}
+
+impl<'a> JtagSpi<'a> {
+    pub fn new(cont: Controller<'a>) -> Self {
+        Self { cont }
+    }
+}
+
+impl SpiBus for JtagSpi<'_> {
+    async fn transfer(&mut self, write: &[u8], read: usize) -> Result<Vec<u8>> {
+        let num_slr = self.cont.info().slr;
+        let tdi = bridge_frame(write, read)?;
+        let tdo = (self.cont.borrow())
+            .run([Command::ir(master(commands::USER1, num_slr)), Command::dr_txrx(&tdi)])
+            .await?;
+        Ok(bridge_response(tdo, write.len(), read))
+    }
+}
+
+/// Bits between the end of MOSI data and the first bit of MISO.
+const MISO_DELAY: usize = 1;
+
+/// DR contents for one bridge transfer, in shift order.
+fn bridge_frame(write: &[u8], read: usize) -> Result<Vec<u8>> {
+    let bits = 8 * (write.len() + read);
+    let len = u32::try_from(bits - 1).map_err(|_| eyre!("spi transfer of {bits} bits"))?;
+    let total = 1 + 32 + bits + MISO_DELAY;
+    let mut tdi = vec![0; total.div_ceil(8)];
+    let mut set = |idx: usize, value: bool| tdi[idx / 8] |= u8::from(value) << (idx % 8);
+    set(0, true);
+    for idx in 0..32 {
+        set(1 + idx, len >> (31 - idx) & 1 == 1);
+    }
+    for (byte_idx, byte) in write.iter().enumerate() {
+        for idx in 0..8 {
+            set(33 + byte_idx * 8 + idx, byte >> (7 - idx) & 1 == 1);
+        }
+    }
+    Ok(tdi)
+}
+
+/// MISO bytes of a transfer from [`bridge_frame`].
+fn bridge_response(tdo: &[u8], write: usize, read: usize) -> Vec<u8> {
+    let start = 33 + 8 * write + MISO_DELAY;
+    let bit = |idx: usize| tdo.get(idx / 8).is_some_and(|b| b >> (idx % 8) & 1 == 1);
+    (0..read)
+        .map(|byte_idx| {
+            (0..8).fold(0, |acc, idx| {
+                acc | u8::from(bit(start + byte_idx * 8 + idx)) << (7 - idx)
+            })
+        })
+        .collect()
+}
+
+/// One erase command the flash supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EraseType {
+    pub size: usize,
+    pub opcode: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlashInfo {
+    /// Manufacturer, memory type, and capacity bytes.
+    pub jedec_id: [u8; 3],
+    /// Size in bytes.
+    pub size: usize,
+    /// Smallest first.
+    pub erase: Vec<EraseType>,
+    /// Uses 4-byte addresses, for flashes over 16 MiB.
+    pub four_byte: bool,
+    /// Geometry came from SFDP, instead of guessed from the JEDEC ID.
+    pub sfdp: bool,
+}
+
+impl FlashInfo {
+    /// Parse the start of SFDP, the header and basic parameter table.
+    fn from_sfdp(jedec_id: [u8; 3], sfdp: &[u8]) -> Option<Self> {
+        let dword = |offset: usize| -> Option<u32> {
+            Some(u32::from_le_bytes(
+                sfdp.get(offset..offset + 4)?.try_into().ok()?,
+            ))
+        };
+        if sfdp.get(..4)? != b"SFDP" {
+            return None;
+        }
+        // first parameter header is always the basic table
+        let pointer = dword(12)? & 0x00ff_ffff;
+        let table = |idx: usize| dword(pointer as usize + 4 * idx);
+
+        let density = table(1)?;
+        let bits = match density & (1 << 31) {
+            0 => u64::from(density) + 1,
+            _ => 1u64.checked_shl(density & 0x7fff_ffff)?,
+        };
+        let size = usize::try_from(bits / 8).ok()?;
+        let four_byte = (table(0)? >> 17) & 0x3 != 0 && size > 1 << 24;
+
+        let erase_words = [table(7)?, table(8)?];
+        let mut erase: Vec<_> = erase_words
+            .iter()
+            .flat_map(|word| [word & 0xffff, word >> 16])
+            .filter_map(|pair| {
+                let (exponent, opcode) = (pair & 0xff, (pair >> 8) as u8);
+                (exponent != 0).then(|| EraseType {
+                    size: 1 << exponent,
+                    opcode: if four_byte { erase_4b(opcode) } else { opcode },
+                })
+            })
+            .collect();
+        erase.sort_by_key(|e| e.size);
+        Some(Self {
+            jedec_id,
+            size,
+            erase,
+            four_byte,
+            sfdp: true,
+        })
+    }
+
+    /// Most flashes encode log2 of the size in the last ID byte.
+    fn from_jedec_id(jedec_id: [u8; 3]) -> Result<Self> {
+        let size = 1usize
+            .checked_shl(u32::from(jedec_id[2]))
+            .filter(|size| (1 << 16..=1 << 30).contains(size))
+            .ok_or_else(|| eyre!("unknown flash size, jedec id {:02X?}", jedec_id))?;
+        let four_byte = size > 1 << 24;
+        let erase = [(4096, 0x20), (65536, 0xd8)].map(|(size, opcode)| EraseType {
+            size,
+            opcode: if four_byte { erase_4b(opcode) } else { opcode },
+        });
+        Ok(Self {
+            jedec_id,
+            size,
+            erase: erase.to_vec(),
+            four_byte,
+            sfdp: false,
+        })
+    }
+}
+
+/// The 4-byte address variant of a standard erase opcode.
+fn erase_4b(opcode: u8) -> u8 {
+    match opcode {
+        0x20 => 0x21,
+        0x52 => 0x5c,
+        0xd8 => 0xdc,
+        other => other,
+    }
+}
+
+pub struct Flash<B> {
+    bus: B,
+    info: FlashInfo,
+}
+
+impl<B: SpiBus> Flash<B> {
+    /// Identify the flash behind `bus`.
+    pub async fn new(mut bus: B) -> Result<Self> {
+        let id = bus.transfer(&[READ_ID], 3).await?;
+        let jedec_id: [u8; 3] = id.try_into().expect("read 3 bytes");
+        if jedec_id == [0xff; 3] || jedec_id == [0; 3] {
+            bail!("no flash responding, is the bridge bitstream loaded?");
+        }
+        // address, then a dummy byte
+        let sfdp = bus.transfer(&[READ_SFDP, 0, 0, 0, 0], 256).await?;
+        let info = match FlashInfo::from_sfdp(jedec_id, &sfdp) {
+            Some(info) => info,
+            None => FlashInfo::from_jedec_id(jedec_id)?,
+        };
+        Ok(Self { bus, info })
+    }
+
+    pub fn info(&self) -> &FlashInfo {
+        &self.info
+    }
+
+    fn command(&self, opcode: u8, addr: usize) -> Vec<u8> {
+        let addr = (addr as u32).to_be_bytes();
+        let addr = if self.info.four_byte {
+            &addr[..]
+        } else {
+            &addr[1..]
+        };
+        [&[opcode], addr].concat()
+    }
+
+    fn check_range(&self, addr: usize, len: usize) -> Result<()> {
+        ensure!(
+            addr.checked_add(len)
+                .is_some_and(|end| end <= self.info.size),
+            "range {addr:#x}+{len:#x} past the end of the {:#x} byte flash",
+            self.info.size
+        );
+        Ok(())
+    }
+
+    /// Read `len` bytes from `addr`, calling `progress` with the bytes read
+    /// so far.
+    pub async fn read(
+        &mut self,
+        addr: usize,
+        len: usize,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<Vec<u8>> {
+        self.check_range(addr, len)?;
+        let opcode = if self.info.four_byte { READ_4B } else { READ };
+        let mut ret = Vec::with_capacity(len);
+        while ret.len() < len {
+            let chunk = READ_CHUNK.min(len - ret.len());
+            let cmd = self.command(opcode, addr + ret.len());
+            ret.extend(self.bus.transfer(&cmd, chunk).await?);
+            progress(ret.len());
+        }
+        Ok(ret)
+    }
+
+    /// Erase `len` bytes from `addr`, which have to line up with the smallest
+    /// erase size. Uses the largest erase that fits at each step.
+    pub async fn erase(
+        &mut self,
+        addr: usize,
+        len: usize,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<()> {
+        self.check_range(addr, len)?;
+        let smallest = self
+            .info
+            .erase
+            .first()
+            .ok_or_else(|| eyre!("flash has no erase"))?;
+        ensure!(
+            addr.is_multiple_of(smallest.size) && len.is_multiple_of(smallest.size),
+            "erase range {addr:#x}+{len:#x} isn't aligned to {:#x} byte sectors",
+            smallest.size
+        );
+        let mut done = 0;
+        while done < len {
+            let at = addr + done;
+            let erase = (self.info.erase.iter().rev())
+                .find(|e| at.is_multiple_of(e.size) && e.size <= len - done)
+                .copied()
+                .expect("smallest erase always fits");
+            self.bus.transfer(&[WRITE_ENABLE], 0).await?;
+            let cmd = self.command(erase.opcode, at);
+            self.bus.transfer(&cmd, 0).await?;
+            self.wait_idle(ERASE_TIMEOUT).await?;
+            done += erase.size;
+            progress(done);
+        }
+        Ok(())
+    }
+
+    async fn wait_idle(&mut self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            let status = self.bus.transfer(&[READ_STATUS], 1).await?;
+            if status[0] & STATUS_BUSY == 0 {
+                return Ok(());
+            }
+            if start.elapsed() > timeout {
+                bail!("flash still busy after {timeout:?}");
+            }
+        }
+    }
+
+    /// Compare the flash at `addr` against `expected`, returning the offset
+    /// of the first difference.
+    pub async fn verify(
+        &mut self,
+        addr: usize,
+        expected: &[u8],
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<Option<usize>> {
+        let data = self.read(addr, expected.len(), progress).await?;
+        Ok(data.iter().zip(expected).position(|(a, e)| a != e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1 MiB flash with 4 KiB and 64 KiB erase, and SFDP.
+    struct FakeFlash {
+        data: Vec<u8>,
+        write_enabled: bool,
+        erases: Vec<(u8, usize)>,
+    }
+
+    impl FakeFlash {
+        fn sfdp() -> Vec<u8> {
+            let mut sfdp = vec![0xff; 256];
+            sfdp[..8].copy_from_slice(b"SFDP\x06\x01\x00\xff");
+            // basic table at 0x30, 9 dwords
+            sfdp[8..16].copy_from_slice(&[0x00, 0x06, 0x01, 9, 0x30, 0, 0, 0xff]);
+            let table: [u32; 9] = [0xfff1_20e5, (1 << 23) - 1, 0, 0, 0, 0, 0, 0xd810_200c, 0];
+            for (idx, word) in table.iter().enumerate() {
+                sfdp[0x30 + 4 * idx..][..4].copy_from_slice(&word.to_le_bytes());
+            }
+            sfdp
+        }
+    }
+
+    impl SpiBus for FakeFlash {
+        async fn transfer(&mut self, write: &[u8], read: usize) -> Result<Vec<u8>> {
+            let addr =
+                |bytes: &[u8]| (bytes[1..4].iter()).fold(0, |acc, b| acc << 8 | usize::from(*b));
+            Ok(match write[0] {
+                READ_ID => vec![0xef, 0x40, 0x14],
+                READ_SFDP => Self::sfdp()[..read].to_vec(),
+                READ_STATUS => vec![0],
+                WRITE_ENABLE => {
+                    self.write_enabled = true;
+                    vec![]
+                }
+                READ => self.data[addr(write)..][..read].to_vec(),
+                op @ (0x20 | 0xd8) => {
+                    assert!(std::mem::take(&mut self.write_enabled));
+                    let size = if op == 0x20 { 4096 } else { 65536 };
+                    let at = addr(write);
+                    self.data[at..at + size].fill(0xff);
+                    self.erases.push((op, at));
+                    vec![]
+                }
+                op => panic!("unexpected opcode {op:#x}"),
+            })
+        }
+    }
+
+    #[test]
+    fn test_bridge_frame() {
+        // marker, then 15 (two bytes, minus one), then 0x9f
+        let tdi = bridge_frame(&[0x9f], 1).unwrap();
+        let bits: String = (0..1 + 32 + 16 + 1)
+            .map(|idx| {
+                if tdi[idx / 8] >> (idx % 8) & 1 == 1 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect();
+        let expected = ["1", &format!("{:032b}", 15), "10011111", "0", "00000000"];
+        assert_eq!(bits, expected.concat());
+
+        // MISO of 0xa5 after the delay bit
+        let mut tdo = vec![0; tdi.len()];
+        for (idx, bit) in "10100101".chars().enumerate() {
+            let idx = 33 + 8 + 1 + idx;
+            tdo[idx / 8] |= u8::from(bit == '1') << (idx % 8);
+        }
+        assert_eq!(bridge_response(&tdo, 1, 1), [0xa5]);
+    }
+
+    #[test]
+    fn test_flash() {
+        smol::block_on(async {
+            let fake = FakeFlash {
+                data: (0..1 << 20).map(|x| x as u8).collect(),
+                write_enabled: false,
+                erases: vec![],
+            };
+            let mut flash = Flash::new(fake).await.unwrap();
+            let info = flash.info();
+            assert!(info.sfdp);
+            assert_eq!(info.size, 1 << 20);
+            let sizes: Vec<_> = info.erase.iter().map(|e| e.size).collect();
+            assert_eq!(sizes, [4096, 65536]);
+
+            let read = flash.read(0x1000, 4, &mut |_| {}).await.unwrap();
+            assert_eq!(read, [0, 1, 2, 3]);
+
+            // one 4 KiB sector to reach alignment, then a 64 KiB block
+            flash.erase(0xf000, 0x11000, &mut |_| {}).await.unwrap();
+            assert_eq!(flash.bus.erases, [(0x20, 0xf000), (0xd8, 0x10000)]);
+            let expected = vec![0xff; 0x11000];
+            let mismatch = flash.verify(0xf000, &expected, &mut |_| {}).await;
+            assert_eq!(mismatch.unwrap(), None);
+            assert_eq!(
+                flash.verify(0, &[0, 1, 9], &mut |_| {}).await.unwrap(),
+                Some(2)
+            );
+
+            assert!(flash.erase(0x800, 0x1000, &mut |_| {}).await.is_err());
+            assert!(flash.read(1 << 20, 1, &mut |_| {}).await.is_err());
+        });
+    }
+}
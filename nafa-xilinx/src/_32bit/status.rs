@@ -0,0 +1,217 @@
+//! Decoding of the configuration status registers.
+
+use std::fmt;
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// The STAT register. These bits are the same on 7-series and
+    /// UltraScale(+) (UG470 table 5-25, UG570 table 9-23).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Stat: u32 {
+        const CRC_ERROR         = 1 << 0;
+        const DECRYPTOR_ENABLED = 1 << 1;
+        const MMCM_LOCK         = 1 << 2;
+        const DCI_MATCH         = 1 << 3;
+        const EOS               = 1 << 4;
+        const GTS_CFG_B         = 1 << 5;
+        const GWE               = 1 << 6;
+        const GHIGH_B           = 1 << 7;
+        const INIT_COMPLETE     = 1 << 11;
+        const INIT_B            = 1 << 12;
+        const RELEASE_DONE      = 1 << 13;
+        const DONE              = 1 << 14;
+        const ID_ERROR          = 1 << 15;
+        const DEC_ERROR         = 1 << 16;
+        const OVER_TEMP         = 1 << 17;
+
+        const _ = !0;
+    }
+}
+
+impl Stat {
+    /// Set once startup released DONE, the global write enable, and the I/Os.
+    pub const STARTED: Self = Self::DONE.union(Self::GWE).union(Self::GTS_CFG_B);
+
+    pub fn started(self) -> bool {
+        self.contains(Self::STARTED)
+    }
+
+    /// The mode pins, as sampled at power on.
+    pub fn mode(self) -> u8 {
+        (self.bits() >> 8 & 0x7) as u8
+    }
+
+    /// Phase of the startup sequence, 0 before startup.
+    pub fn startup_state(self) -> u8 {
+        (self.bits() >> 18 & 0x7) as u8
+    }
+
+    /// Everything in this status that would keep the device from starting,
+    /// most likely cause first.
+    pub fn problems(self) -> Vec<&'static str> {
+        let errors = [
+            (
+                Self::ID_ERROR,
+                "IDCODE in the bitstream doesn't match the device",
+            ),
+            (Self::CRC_ERROR, "CRC error in the bitstream"),
+            (Self::DEC_ERROR, "decryption failed, wrong key?"),
+            (Self::OVER_TEMP, "over-temperature alarm"),
+        ];
+        let missing = [
+            (Self::INIT_B, "INIT_B is low"),
+            (Self::DONE, "DONE is low"),
+            (Self::GWE, "global write enable not released"),
+            (Self::GTS_CFG_B, "I/Os still tristated"),
+        ];
+        let errors = errors.into_iter().filter(|(flag, _)| self.contains(*flag));
+        let missing = missing
+            .into_iter()
+            .filter(|(flag, _)| !self.contains(*flag));
+        errors.chain(missing).map(|(_, msg)| msg).collect()
+    }
+}
+
+/// The raw value, then what's wrong with it, i.e.
+/// `0x0000_1001 (startup phase 0: DONE is low, ...)`.
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#010x} (startup phase {}",
+            self.bits(),
+            self.startup_state()
+        )?;
+        match &self.problems()[..] {
+            [] => write!(f, ", started)"),
+            problems => write!(f, ": {})", problems.join(", ")),
+        }
+    }
+}
+
+bitflags! {
+    /// One configuration attempt, as recorded in [`BootSts`].
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct BootStatus: u8 {
+        /// The rest of the bits are meaningful.
+        const VALID      = 1 << 0;
+        const FALLBACK   = 1 << 1;
+        /// Started by IPROG (i.e. MultiBoot) rather than power on or PROG_B.
+        const IPROG      = 1 << 2;
+        const WATCHDOG   = 1 << 3;
+        const ID_ERROR   = 1 << 4;
+        const CRC_ERROR  = 1 << 5;
+        /// BPI address counter wrapped around.
+        const WRAP_ERROR = 1 << 6;
+        /// HMAC (7-series) or other authentication failure.
+        const AUTH_ERROR = 1 << 7;
+    }
+}
+
+impl BootStatus {
+    /// Errors that ended this attempt.
+    pub fn errors(self) -> Vec<&'static str> {
+        let errors = [
+            (Self::ID_ERROR, "IDCODE error"),
+            (Self::CRC_ERROR, "CRC error"),
+            (Self::WATCHDOG, "watchdog timeout"),
+            (Self::WRAP_ERROR, "address wraparound"),
+            (Self::AUTH_ERROR, "authentication error"),
+        ];
+        (errors.into_iter())
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, msg)| msg)
+            .collect()
+    }
+}
+
+/// The BOOTSTS register: what happened during the last two configuration
+/// attempts, i.e. why a board didn't boot from flash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BootSts(pub u32);
+
+impl BootSts {
+    /// The most recent attempt.
+    pub fn latest(self) -> BootStatus {
+        BootStatus::from_bits_retain(self.0 as u8)
+    }
+
+    /// The attempt before, i.e. the one that triggered fallback.
+    pub fn previous(self) -> BootStatus {
+        BootStatus::from_bits_retain((self.0 >> 8) as u8)
+    }
+
+    /// Explanation if something went wrong, `None` if the last configuration
+    /// was clean (or there wasn't one).
+    pub fn problem(self) -> Option<String> {
+        let latest = self.latest();
+        if !latest.contains(BootStatus::VALID) {
+            return None;
+        }
+        let errors = latest.errors();
+        if latest.contains(BootStatus::FALLBACK) {
+            let cause = match &self.previous().errors()[..] {
+                [] => "unknown error".to_string(),
+                errors => errors.join(", "),
+            };
+            let mut msg = format!("fallback triggered: {cause} on first bitstream");
+            if !errors.is_empty() {
+                msg += &format!(", then {} on the fallback bitstream", errors.join(", "));
+            }
+            return Some(msg);
+        }
+        (!errors.is_empty()).then(|| format!("configuration failed: {}", errors.join(", ")))
+    }
+}
+
+/// The raw value, then the [problem](BootSts::problem) if any.
+impl fmt::Display for BootSts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x}", self.0)?;
+        match self.problem() {
+            Some(problem) => write!(f, " ({problem})"),
+            None if self.latest().contains(BootStatus::VALID) => write!(f, " (ok)"),
+            None => write!(f, " (no configuration recorded)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stat() {
+        // a 7-series in master SPI mode, after a successful program
+        let stat = Stat::from_bits_retain(0x4010_79fc);
+        assert!(stat.started());
+        assert_eq!(stat.mode(), 0b001);
+        assert_eq!(stat.startup_state(), 4);
+        assert_eq!(stat.to_string(), "0x401079fc (startup phase 4, started)");
+
+        let stat = Stat::from_bits_retain(0x0000_9101);
+        assert!(!stat.started());
+        assert_eq!(
+            stat.to_string(),
+            "0x00009101 (startup phase 0: IDCODE in the bitstream doesn't match the device, CRC \
+             error in the bitstream, DONE is low, global write enable not released, I/Os still \
+             tristated)"
+        );
+    }
+
+    #[test]
+    fn test_bootsts() {
+        assert_eq!(BootSts(0x0000_0001).problem(), None);
+        // IDCODE error, then fallback to the golden image
+        let bootsts = BootSts(0x0000_1103);
+        assert_eq!(
+            bootsts.problem().unwrap(),
+            "fallback triggered: IDCODE error on first bitstream"
+        );
+        assert_eq!(
+            BootSts(0x0000_0021).to_string(),
+            "0x00000021 (configuration failed: CRC error)"
+        );
+    }
+}
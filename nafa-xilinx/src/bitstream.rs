@@ -0,0 +1,474 @@
+//! Configuration files in the formats Vivado writes.
+//!
+//! Everything is loaded into the same form as a `.bin`: configuration bytes in
+//! file order, MSB of each byte first, as expected by the configuration logic
+//! (and before [`crate::_32bit::to_wire_order`]-style reversal for JTAG).
+
+use eyre::{OptionExt as _, Result, bail, eyre};
+
+use crate::_32bit::{
+    crc::Crc,
+    registers::{Addr, CmdCode, OpCode, Type1},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// `write_bitstream`, a header followed by the configuration data.
+    Bit,
+    /// `write_bitstream -bin_file`, only the configuration data.
+    Bin,
+    /// `write_bitstream -raw_bitfile`, one 32-bit word per line as ASCII
+    /// `0`/`1`, after a short text header. Also reads `.rbd` readback data.
+    Rbt,
+    /// `write_cfgmem -format mcs`, a flash image as Intel HEX records.
+    Mcs,
+}
+
+/// Magic at the start of a `.bit` header: a length-prefixed field, then the
+/// length of the next one.
+const BIT_MAGIC: [u8; 13] =
+    [0x00, 0x09, 0x0f, 0xf0, 0x0f, 0xf0, 0x0f, 0xf0, 0x0f, 0xf0, 0x00, 0x00, 0x01];
+
+impl Format {
+    /// Guess the format from the contents. Anything not recognized is taken
+    /// as a `.bin`.
+    pub fn detect(data: &[u8]) -> Self {
+        let text = std::str::from_utf8(&data[..data.len().min(256)]).ok();
+        let first_line = text.and_then(|t| t.lines().next()).unwrap_or("").trim();
+        if data.starts_with(&BIT_MAGIC) {
+            Self::Bit
+        } else if first_line.starts_with(':')
+            && first_line.len() > 1
+            && first_line[1..].bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            Self::Mcs
+        } else if first_line.starts_with("Xilinx ASCII") || is_rbt_word(first_line) {
+            Self::Rbt
+        } else {
+            Self::Bin
+        }
+    }
+}
+
+/// The header of a `.bit`, as written by Vivado.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BitHeader {
+    /// Design name, followed by `;UserID=...;Version=...`.
+    pub design: String,
+    /// Part the bitstream is for, i.e. `7a35tcpg236`.
+    pub part: String,
+    pub date: String,
+    pub time: String,
+}
+
+impl BitHeader {
+    /// The header of `data`, `None` if it isn't a `.bit`.
+    pub fn parse(data: &[u8]) -> Result<Option<Self>> {
+        if !data.starts_with(&BIT_MAGIC) {
+            return Ok(None);
+        }
+        let mut header = Self::default();
+        let mut rest = &data[BIT_MAGIC.len()..];
+        // the magic ends with the length of the one-byte field holding the
+        // first key
+        let mut key = take(&mut rest, 1)?[0];
+        while key != b'e' {
+            let len = u16::from_be_bytes(take(&mut rest, 2)?.try_into().expect("2 bytes"));
+            let value = take(&mut rest, len.into())?;
+            let value = String::from_utf8_lossy(value.strip_suffix(b"\0").unwrap_or(value));
+            match key {
+                b'a' => header.design = value.into_owned(),
+                b'b' => header.part = value.into_owned(),
+                b'c' => header.date = value.into_owned(),
+                b'd' => header.time = value.into_owned(),
+                _ => tracing::warn!("skipping unknown .bit header field {:?}", key as char),
+            }
+            key = take(&mut rest, 1)?[0];
+        }
+        Ok(Some(header))
+    }
+}
+
+/// The first `len` bytes of `data`, advancing it past them.
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    let (head, rest) = (data.split_at_checked(len)).ok_or_eyre(".bit header is truncated")?;
+    *data = rest;
+    Ok(head)
+}
+
+/// Load `data` as `format`, or the detected format if `None`.
+pub fn load(data: &[u8], format: Option<Format>) -> Result<Vec<u8>> {
+    match format.unwrap_or_else(|| Format::detect(data)) {
+        // the header comes before the sync word, and is skipped by the
+        // configuration logic like any other padding
+        Format::Bit | Format::Bin => Ok(data.to_vec()),
+        Format::Rbt => parse_rbt(data),
+        Format::Mcs => parse_mcs(data),
+    }
+}
+
+/// The frame data of a loaded bitstream (or mask file): the words of the
+/// first write to FDRI, after the sync word.
+///
+/// Only the first write is returned. That's every frame for an uncompressed
+/// bitstream, and the frames of the SLR read back first for SSI devices.
+pub fn frame_data(config: &[u8]) -> Result<Vec<u32>> {
+    let words = words(config)?;
+    for packet in Packets::new(&words) {
+        let packet = packet?;
+        if packet.op == OpCode::Write as u32
+            && packet.addr == Addr::Fdri as u32
+            && !packet.data.is_empty()
+        {
+            return Ok(packet.data.to_vec());
+        }
+    }
+    bail!("no frame data (FDRI write) in bitstream")
+}
+
+/// Check every CRC write of a loaded bitstream against the CRC of the
+/// register writes before it, as the configuration logic does. Returns how
+/// many were checked: a bitstream written with CRC disabled has none.
+///
+/// Only the packets to the device the bitstream is loaded into are checked.
+/// The bitstreams of other SLRs, nested in those, carry their own CRC, which
+/// is checked by the device.
+pub fn check_crc(config: &[u8]) -> Result<usize> {
+    let words = words(config)?;
+    let mut crc = Crc::new(0);
+    let mut checked = 0;
+    for packet in Packets::new(&words) {
+        let packet = packet?;
+        if packet.op != OpCode::Write as u32 {
+            continue;
+        }
+        if packet.addr == Addr::Crc as u32 {
+            for &expected in packet.data {
+                checked += 1;
+                if crc.value() != expected {
+                    bail!(
+                        "CRC mismatch at check {checked}: bitstream has {expected:#010x}, its \
+                         contents give {:#010x}",
+                        crc.value()
+                    );
+                }
+                crc = Crc::new(0);
+            }
+            continue;
+        }
+        for &word in packet.data {
+            crc.update(packet.addr as u8, word);
+            if packet.addr == Addr::Cmd as u32 && word == CmdCode::Rcrc as u32 {
+                crc = Crc::new(0);
+            }
+        }
+    }
+    Ok(checked)
+}
+
+/// The words of a loaded bitstream, from its first sync word on.
+fn words(config: &[u8]) -> Result<Vec<u32>> {
+    let sync = Type1::SYNC.to_be_bytes();
+    let start = (config.windows(4))
+        .position(|w| w == sync)
+        .ok_or_eyre("no sync word in bitstream")?;
+    let words = config[start..]
+        .chunks_exact(4)
+        .map(|w| u32::from_be_bytes(w.try_into().expect("chunks of 4")))
+        .collect();
+    Ok(words)
+}
+
+/// One configuration packet: the words `data` for register `addr`.
+struct Packet<'a> {
+    op: u32,
+    addr: u32,
+    data: &'a [u32],
+}
+
+/// The packets in a bitstream. Like the configuration logic, words between a
+/// DESYNC and the next sync word are skipped.
+struct Packets<'a> {
+    words: &'a [u32],
+    idx: usize,
+    synced: bool,
+    /// Op and register of the last type 1 packet, used by type 2 packets.
+    last: (u32, u32),
+}
+
+impl<'a> Packets<'a> {
+    fn new(words: &'a [u32]) -> Self {
+        Self {
+            words,
+            idx: 0,
+            synced: false,
+            last: (OpCode::Noop as u32, 0),
+        }
+    }
+
+    fn fail(&mut self, err: eyre::Report) -> Option<Result<Packet<'a>>> {
+        self.idx = self.words.len();
+        Some(Err(err))
+    }
+}
+
+impl<'a> Iterator for Packets<'a> {
+    type Item = Result<Packet<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.synced {
+            let sync = (self.words[self.idx..].iter()).position(|&w| w == Type1::SYNC)?;
+            self.idx += sync + 1;
+            self.synced = true;
+        }
+        let header = *self.words.get(self.idx)?;
+        self.idx += 1;
+        let idx = self.idx;
+        let op = (header >> 27) & 0x3;
+        let (op, addr, count) = match header >> 29 {
+            1 => {
+                self.last = (op, (header >> 13) & 0x3fff);
+                (op, self.last.1, (header & 0x7ff) as usize)
+            }
+            2 => (self.last.0, self.last.1, (header & 0x03ff_ffff) as usize),
+            _ => return self.fail(eyre!("invalid packet header {header:#010x} at word {idx}")),
+        };
+        let Some(data) = self.words.get(idx..idx + count) else {
+            return self.fail(eyre!(
+                "packet at word {idx} runs past the end of the bitstream"
+            ));
+        };
+        self.idx += count;
+        if op == OpCode::Write as u32
+            && addr == Addr::Cmd as u32
+            && data.contains(&(CmdCode::Desync as u32))
+        {
+            self.synced = false;
+        }
+        Some(Ok(Packet { op, addr, data }))
+    }
+}
+
+fn is_rbt_word(line: &str) -> bool {
+    line.len() == 32 && line.bytes().all(|b| b == b'0' || b == b'1')
+}
+
+fn parse_rbt(data: &[u8]) -> Result<Vec<u8>> {
+    let text = std::str::from_utf8(data).map_err(|e| eyre!("rbt is not text: {e}"))?;
+    let mut ret = Vec::new();
+    let mut expected_bits = None;
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if is_rbt_word(line) {
+            let word = u32::from_str_radix(line, 2).expect("checked to be binary");
+            ret.extend(word.to_be_bytes());
+            continue;
+        }
+        if !ret.is_empty() && !line.is_empty() {
+            bail!("rbt line {}: expected 32 bits, got {line:?}", line_no + 1);
+        }
+        if let Some(bits) = line.strip_prefix("Bits:") {
+            expected_bits =
+                Some(bits.trim().parse::<usize>().map_err(|e| {
+                    eyre!("rbt line {}: invalid bit count {bits:?}: {e}", line_no + 1)
+                })?);
+        }
+    }
+    if let Some(bits) = expected_bits
+        && bits != ret.len() * 8
+    {
+        bail!(
+            "rbt header says {bits} bits, but has {} bits of data",
+            ret.len() * 8
+        );
+    }
+    Ok(ret)
+}
+
+/// The image from the lowest address in the file to the highest, with gaps
+/// filled with `0xff` (erased flash).
+fn parse_mcs(data: &[u8]) -> Result<Vec<u8>> {
+    let text = std::str::from_utf8(data).map_err(|e| eyre!("mcs is not text: {e}"))?;
+    let mut chunks: Vec<(u32, Vec<u8>)> = Vec::new();
+    let mut base = 0u32;
+    let mut eof = false;
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |msg: &str| eyre!("mcs line {}: {msg}", line_no + 1);
+        if eof {
+            return Err(err("data after end-of-file record"));
+        }
+        let record = line
+            .strip_prefix(':')
+            .and_then(|hex| hex::decode(hex).ok())
+            .ok_or_else(|| err("not an Intel HEX record"))?;
+        let [len, addr_hi, addr_lo, kind, rest @ ..] = &record[..] else {
+            return Err(err("record too short"));
+        };
+        let Some((payload, _checksum)) = rest.split_last_chunk::<1>() else {
+            return Err(err("record too short"));
+        };
+        if payload.len() != usize::from(*len) {
+            return Err(err("record length doesn't match its data"));
+        }
+        if record.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0 {
+            return Err(err("bad checksum"));
+        }
+        let offset = u32::from(u16::from_be_bytes([*addr_hi, *addr_lo]));
+        match (kind, payload) {
+            (0x00, _) => chunks.push((base + offset, payload.to_vec())),
+            (0x01, _) => eof = true,
+            (0x02, [hi, lo]) => base = u32::from(u16::from_be_bytes([*hi, *lo])) << 4,
+            (0x04, [hi, lo]) => base = u32::from(u16::from_be_bytes([*hi, *lo])) << 16,
+            // start addresses, meaningless for a flash image
+            (0x03 | 0x05, _) => {}
+            _ => return Err(err(&format!("unsupported record type {kind:#04x}"))),
+        }
+    }
+    if !eof {
+        bail!("mcs has no end-of-file record");
+    }
+
+    let start = chunks.iter().map(|(addr, _)| *addr).min().unwrap_or(0);
+    let mut ret = Vec::new();
+    for (addr, payload) in chunks {
+        let offset = (addr - start) as usize;
+        let end = offset + payload.len();
+        if ret.len() < end {
+            ret.resize(end, 0xff);
+        }
+        ret[offset..end].copy_from_slice(&payload);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYNC: [u8; 4] = [0xaa, 0x99, 0x55, 0x66];
+
+    #[test]
+    fn test_detect() {
+        let mut bit = BIT_MAGIC.to_vec();
+        bit.extend(SYNC);
+        assert_eq!(Format::detect(&bit), Format::Bit);
+        assert_eq!(Format::detect(&SYNC), Format::Bin);
+        assert_eq!(Format::detect(b":0400000"), Format::Mcs);
+        assert_eq!(
+            Format::detect(b"Xilinx ASCII Bitstream\nCreated by"),
+            Format::Rbt
+        );
+    }
+
+    #[test]
+    fn test_bit_header() {
+        let mut bit = BIT_MAGIC.to_vec();
+        for (key, value) in [
+            (b'a', &b"top;UserID=0XFFFFFFFF;Version=2024.1\0"[..]),
+            (b'b', b"7a35tcpg236\0"),
+            (b'c', b"2024/06/01\0"),
+            (b'd', b"12:00:00\0"),
+            (b'z', b"from a newer Vivado\0"),
+        ] {
+            bit.push(key);
+            bit.extend((value.len() as u16).to_be_bytes());
+            bit.extend(value);
+        }
+        bit.push(b'e');
+        bit.extend(4u32.to_be_bytes());
+        bit.extend(SYNC);
+
+        let header = BitHeader::parse(&bit).unwrap().unwrap();
+        assert_eq!(header.part, "7a35tcpg236");
+        assert_eq!(header.time, "12:00:00");
+        assert!(header.design.starts_with("top;"));
+        assert_eq!(BitHeader::parse(&SYNC).unwrap(), None);
+        assert!(BitHeader::parse(&bit[..40]).is_err());
+    }
+
+    #[test]
+    fn test_frame_data() {
+        let words = [
+            0xffff_ffff,
+            Type1::SYNC,
+            Type1::NOOP,
+            // write FAR, one word
+            0x3000_2001,
+            0,
+            // write FDRI, count in the type 2 packet
+            0x3000_4000,
+            0x5000_0003,
+            1,
+            2,
+            3,
+            Type1::NOOP,
+        ];
+        let config: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        assert_eq!(frame_data(&config).unwrap(), [1, 2, 3]);
+        assert!(frame_data(&config[..config.len() - 8]).is_err());
+    }
+
+    #[test]
+    fn test_check_crc() {
+        let mut crc = Crc::new(0);
+        crc.update(Addr::Far as u8, 0);
+        for word in [1, 2, 3] {
+            crc.update(Addr::Fdri as u8, word);
+        }
+        let mut words = vec![
+            Type1::SYNC,
+            Type1::NOOP,
+            // write CMD RCRC
+            0x3000_8001,
+            CmdCode::Rcrc as u32,
+            Type1::NOOP,
+            0x3000_2001,
+            0,
+            0x3000_4000,
+            0x5000_0003,
+            1,
+            2,
+            3,
+            // write CRC
+            0x3000_0001,
+            crc.value(),
+            // write CMD DESYNC, then padding
+            0x3000_8001,
+            CmdCode::Desync as u32,
+            0xffff_ffff,
+        ];
+        let config =
+            |words: &[u32]| -> Vec<u8> { words.iter().flat_map(|w| w.to_be_bytes()).collect() };
+        assert_eq!(check_crc(&config(&words)).unwrap(), 1);
+
+        words[10] ^= 1 << 4;
+        assert!(check_crc(&config(&words)).is_err());
+    }
+
+    #[test]
+    fn test_rbt() {
+        let rbt = "Xilinx ASCII Bitstream\nDesign name: top\nBits: \
+                   64\n11111111111111111111111111111111\n10101010100110010101010101100110\n";
+        assert_eq!(
+            load(rbt.as_bytes(), None).unwrap(),
+            [0xff, 0xff, 0xff, 0xff, 0xaa, 0x99, 0x55, 0x66]
+        );
+        let short = rbt.replace("Bits: 64", "Bits: 96");
+        assert!(load(short.as_bytes(), None).is_err());
+    }
+
+    #[test]
+    fn test_mcs() {
+        let mcs = ":020000040000FA\n:04000000AA995566FE\n:02000600FFFFFA\n:00000001FF\n";
+        assert_eq!(
+            load(mcs.as_bytes(), None).unwrap(),
+            [0xaa, 0x99, 0x55, 0x66, 0xff, 0xff, 0xff, 0xff]
+        );
+        let bad = mcs.replace("FE", "FD");
+        assert!(load(bad.as_bytes(), None).is_err());
+    }
+}
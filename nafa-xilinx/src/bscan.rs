@@ -0,0 +1,159 @@
+//! Shifting data through the USER1-USER4 registers, i.e. to talk to a
+//! `BSCANE2` (or `BSCANE2`-based debug hub, soft processor debug module, ...)
+//! in a configured design.
+//!
+//! The FPGA only connects the DR to the fabric while the matching USER
+//! instruction is in IR. [`Tunnel`] loads it once, then shifts exactly as
+//! many bits as asked for each time, so the design sees the same number of
+//! SHIFT clocks.
+
+use eyre::{Result, eyre};
+use nafa_io::{Controller, jtag::State, units::Bits};
+
+use crate::{
+    _32bit::commands::{self, Master},
+    ir::IrEncoder,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum User {
+    User1,
+    User2,
+    User3,
+    User4,
+}
+
+impl User {
+    /// `USER{n}`, for `n` in 1-4.
+    pub fn new(n: u8) -> Result<Self> {
+        match n {
+            1 => Ok(Self::User1),
+            2 => Ok(Self::User2),
+            3 => Ok(Self::User3),
+            4 => Ok(Self::User4),
+            _ => Err(eyre!("no USER{n} register, expected 1-4")),
+        }
+    }
+
+    const fn inst(self) -> Master {
+        match self {
+            Self::User1 => Master::USER1,
+            Self::User2 => Master::USER2,
+            Self::User3 => Master::USER3,
+            Self::User4 => Master::USER4,
+        }
+    }
+}
+
+/// One USER register of the active device. On SSI devices, the register of
+/// the master SLR.
+pub struct Tunnel<'a> {
+    cont: &'a mut Controller,
+    ir: u32,
+    /// The other SLRs are in bypass, between the master and TDO.
+    bypass: usize,
+    selected: bool,
+}
+
+impl<'a> Tunnel<'a> {
+    pub fn new(cont: &'a mut Controller, user: User) -> Result<Self> {
+        let encoder = IrEncoder::new(cont.info())
+            .ok_or_else(|| eyre!("no USER registers on {}", cont.info().name))?;
+        Ok(Self {
+            ir: encoder.master(user.inst()),
+            bypass: encoder.bypass_bits_before(0),
+            cont,
+            selected: false,
+        })
+    }
+
+    /// Shift the first `len` bits of `tdi` (LSB of the first byte first)
+    /// through the register, returning the `len` bits shifted out in the
+    /// same format.
+    pub async fn shift(&mut self, tdi: &[u8], len: Bits<usize>) -> Result<Vec<u8>> {
+        self.select().await?;
+        // the bypass bits come out first, so `tdi` goes in after as many
+        // padding bits
+        let total = len.0 + self.bypass;
+        let mut padded = vec![0; total.div_ceil(8)];
+        for i in (0..len.0).filter(|i| tdi[i / 8] >> (i % 8) & 1 == 1) {
+            let j = i + self.bypass;
+            padded[j / 8] |= 1 << (j % 8);
+        }
+        let data = (self.cont)
+            .shift_dr(&padded, Bits(total), State::RunTestIdle)
+            .await?;
+        let mut tdo = vec![0; len.0.div_ceil(8)];
+        commands::skip_bits(data, self.bypass, &mut tdo);
+        Ok(tdo)
+    }
+
+    /// [`Tunnel::shift`], ignoring what's shifted out.
+    pub async fn write(&mut self, tdi: &[u8], len: Bits<usize>) -> Result<()> {
+        self.shift(tdi, len).await?;
+        Ok(())
+    }
+
+    /// [`Tunnel::shift`] with zeros, returning what's shifted out.
+    pub async fn read(&mut self, len: Bits<usize>) -> Result<Vec<u8>> {
+        let tdi = vec![0; len.0.div_ceil(8)];
+        self.shift(&tdi, len).await
+    }
+
+    async fn select(&mut self) -> Result<()> {
+        if !self.selected {
+            let ir = self.ir.to_le_bytes();
+            self.cont.shift_ir(&ir, State::RunTestIdle).await?;
+            self.selected = true;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::fake::{self, FakeDevice};
+
+    use super::*;
+
+    #[test]
+    fn test_tunnel() {
+        smol::block_on(async {
+            const XC7A35T: u32 = 0x0362_d093;
+            let device = FakeDevice::new(XC7A35T, Bits(6), 0b001001).register(
+                0b000011,
+                Bits(13),
+                &[0x34, 0x12],
+            );
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+
+            let mut tunnel = Tunnel::new(&mut cont, User::new(2).unwrap()).unwrap();
+            let old = tunnel.shift(&[0xff, 0xff], Bits(13)).await.unwrap();
+            assert_eq!(old, [0x34, 0x12]);
+            assert_eq!(tunnel.read(Bits(13)).await.unwrap(), [0xff, 0x1f]);
+            assert!(User::new(5).is_err());
+        });
+    }
+
+    #[test]
+    fn test_tunnel_slr() {
+        smol::block_on(async {
+            const XCVU9P: u32 = 0x04b3_1093;
+            let enc = IrEncoder::Slr { num_slr: 3 };
+            let idcode = enc.duplicated(commands::IDCODE);
+            let user2 = enc.master(Master::USER2);
+            // the two bypass bits of SLR 1 and 2 come first
+            let device = FakeDevice::new(XCVU9P, Bits(18), idcode).register(
+                user2,
+                Bits(15),
+                &(0x1234_u16 << 2 | 0b11).to_le_bytes(),
+            );
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+
+            let mut tunnel = Tunnel::new(&mut cont, User::User2).unwrap();
+            let old = tunnel.shift(&[0xff, 0xff], Bits(13)).await.unwrap();
+            assert_eq!(old, [0x34, 0x12]);
+            assert_eq!(tunnel.read(Bits(13)).await.unwrap(), [0xff, 0x1f]);
+        });
+    }
+}
@@ -0,0 +1,80 @@
+//! XC9500XL CPLDs, programmed through their in-system configuration (ISC)
+//! instructions from a JEDEC fuse map (see [`jedec`]), instead of configured
+//! with a bitstream.
+//!
+//! The fuses are written and read a row at a time: 108 sectors of 15 rows,
+//! each row one byte per function block (only the low 6 bits in the last 6
+//! rows of a sector), following the order of the fuses in the JEDEC file.
+
+use nafa_io::{controller::TypedController, devices::XilinxCpldInfo};
+
+pub mod isp;
+pub mod jedec;
+
+pub type Controller<'a> = TypedController<'a, XilinxCpldInfo>;
+
+/// From `xc9572xl_vq64.bsd`.
+#[repr(u8)]
+#[expect(non_camel_case_types)]
+#[allow(unused, clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug)]
+#[rustfmt::skip]
+pub enum Inst {
+    BYPASS      = 0xff,
+    IDCODE      = 0xfe,
+    USERCODE    = 0xfd,
+    ISC_ENABLE  = 0xe8,
+    ISC_ERASE   = 0xed,
+    ISC_PROGRAM = 0xea,
+    ISC_READ    = 0xee,
+    ISC_NOOP    = 0xe0,
+    ISC_DISABLE = 0xf0,
+}
+
+pub const SECTORS: usize = 108;
+pub const ROWS_PER_SECTOR: usize = 15;
+
+/// Fuses in each function block's byte of row `row` (within a sector).
+pub const fn row_width(row: usize) -> usize {
+    if row < 9 { 8 } else { 6 }
+}
+
+/// Fuses per function block, i.e. the JEDEC file of a device with `n`
+/// function blocks has `n * FUSES_PER_FB` fuses.
+pub const FUSES_PER_FB: usize = SECTORS * (9 * 8 + 6 * 6);
+
+/// ISC address of `row` of `sector`: 5 rows to a group of 8 addresses, 3
+/// groups to a sector of 32.
+pub const fn address(sector: usize, row: usize) -> u16 {
+    (sector * 0x20 + (row / 5) * 0x08 + row % 5) as u16
+}
+
+/// Index of the first fuse of `row` of `sector` in the JEDEC file, for a
+/// device with `function_blocks`. Each function block's fuses follow the
+/// previous one's.
+pub const fn first_fuse(function_blocks: usize, sector: usize, row: usize) -> usize {
+    let mut offset = 0;
+    let mut r = 0;
+    while r < row {
+        offset += row_width(r);
+        r += 1;
+    }
+    function_blocks * (sector * (FUSES_PER_FB / SECTORS) + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(4 * FUSES_PER_FB, 46656);
+        assert_eq!(address(0, 4), 0x04);
+        assert_eq!(address(0, 5), 0x08);
+        assert_eq!(address(1, 14), 0x34);
+        assert_eq!(first_fuse(4, 0, 9), 4 * 72);
+        assert_eq!(first_fuse(4, 1, 0), 4 * 108);
+        let last = first_fuse(4, SECTORS - 1, ROWS_PER_SECTOR - 1) + 4 * 6;
+        assert_eq!(last, 46656);
+    }
+}
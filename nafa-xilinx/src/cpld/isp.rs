@@ -0,0 +1,274 @@
+//! The erase, program, and verify flow of the XC9500XL.
+//!
+//! Between [`enable`] and [`disable`], the ISC data register is 2 control
+//! bits, one byte per function block, and a 16-bit address, all shifted in
+//! LSB first. Programming loads each row of a sector into the device, and
+//! the last one (with both control bits set) programs the whole sector.
+//! Reading is pipelined: each shift sets the address and returns the row
+//! addressed by the previous one.
+
+use std::time::Duration;
+
+use eyre::{Result, bail};
+use nafa_io::{Command, jtag::State, units::Bits};
+
+use super::{
+    Controller, FUSES_PER_FB, Inst, ROWS_PER_SECTOR, SECTORS, address, first_fuse, jedec::Jedec,
+    row_width,
+};
+
+/// Rows programmed or read back, for progress.
+pub const ROWS: usize = SECTORS * ROWS_PER_SECTOR;
+
+/// Value of the 6-bit register behind ISC_ENABLE.
+const ENABLE: u8 = 0x15;
+/// Control bits: load a row, or (with the second bit) act on the sector.
+const LOAD: u8 = 0b01;
+const EXECUTE: u8 = 0b11;
+
+const ERASE_TIME: Duration = Duration::from_millis(400);
+const PROGRAM_TIME: Duration = Duration::from_millis(20);
+/// For the device to load its configuration after [`Inst::ISC_DISABLE`].
+const DISABLE_TIME: Duration = Duration::from_micros(100);
+
+async fn ir(cont: &mut Controller<'_>, inst: Inst) -> Result<()> {
+    cont.borrow()
+        .shift_ir(&[inst as u8], State::RunTestIdle)
+        .await?;
+    Ok(())
+}
+
+/// Shift the ISC data register, returning the bytes of each function block.
+async fn shift(cont: &mut Controller<'_>, control: u8, row: &[u8], addr: u16) -> Result<Vec<u8>> {
+    let fields = [(u32::from(control), 2)]
+        .into_iter()
+        .chain(row.iter().map(|&b| (u32::from(b), 8)))
+        .chain([(u32::from(addr), 16)]);
+    let len = 2 + 8 * row.len() + 16;
+    let mut tdi = vec![0; len.div_ceil(8)];
+    let mut pos = 0;
+    for (value, width) in fields {
+        for i in 0..width {
+            tdi[pos / 8] |= ((value >> i & 1) as u8) << (pos % 8);
+            pos += 1;
+        }
+    }
+
+    let tdo = (cont.borrow())
+        .shift_dr(&tdi, Bits(len), State::RunTestIdle)
+        .await?;
+    let bit = |pos: usize| tdo[pos / 8] >> (pos % 8) & 1;
+    let data = (0..row.len())
+        .map(|fb| (0..8).fold(0, |acc, i| acc | bit(2 + 8 * fb + i) << i))
+        .collect();
+    Ok(data)
+}
+
+/// The bytes of `row` of `sector`, one per function block.
+fn row_data(jed: &Jedec, function_blocks: usize, sector: usize, row: usize) -> Vec<u8> {
+    let width = row_width(row);
+    let first = first_fuse(function_blocks, sector, row);
+    (0..function_blocks)
+        .map(|fb| {
+            let fuses = &jed.fuses[first + fb * width..][..width];
+            (fuses.iter().enumerate()).fold(0, |acc, (i, &f)| acc | u8::from(f) << i)
+        })
+        .collect()
+}
+
+/// The bits of a function block's byte that are fuses in `row`.
+const fn row_mask(row: usize) -> u8 {
+    u8::MAX >> (8 - row_width(row))
+}
+
+fn check_size(cont: &Controller<'_>, jed: &Jedec) -> Result<usize> {
+    let function_blocks = usize::from(cont.info().function_blocks);
+    let expected = function_blocks * FUSES_PER_FB;
+    if jed.fuses.len() != expected {
+        bail!(
+            "JEDEC file has {} fuses, device needs {expected}",
+            jed.fuses.len()
+        );
+    }
+    Ok(function_blocks)
+}
+
+/// Enter ISC mode. The outputs are tristated until [`disable`].
+pub async fn enable(mut cont: Controller<'_>) -> Result<()> {
+    ir(&mut cont, Inst::ISC_ENABLE).await?;
+    (cont.borrow())
+        .shift_dr(&[ENABLE], Bits(6), State::RunTestIdle)
+        .await?;
+    cont.borrow().run([Command::idle_clocks(1)]).await?;
+    Ok(())
+}
+
+/// Leave ISC mode, starting the device with its (new) fuses.
+pub async fn disable(mut cont: Controller<'_>) -> Result<()> {
+    ir(&mut cont, Inst::ISC_DISABLE).await?;
+    cont.borrow().run([Command::wait(DISABLE_TIME)]).await?;
+    ir(&mut cont, Inst::BYPASS).await?;
+    Ok(())
+}
+
+/// Erase every sector. Needs [`enable`] first.
+pub async fn erase(mut cont: Controller<'_>) -> Result<()> {
+    let function_blocks = usize::from(cont.info().function_blocks);
+    cont.borrow().progress_phase("erase");
+    ir(&mut cont, Inst::ISC_ERASE).await?;
+    shift(&mut cont, EXECUTE, &vec![0xff; function_blocks], 0).await?;
+    cont.borrow().run([Command::wait(ERASE_TIME)]).await?;
+    Ok(())
+}
+
+/// Program the fuses of `jed`, calling `progress` with the number of rows
+/// done. Needs [`enable`] and [`erase`] first.
+pub async fn program(
+    mut cont: Controller<'_>,
+    jed: &Jedec,
+    mut progress: impl FnMut(usize),
+) -> Result<()> {
+    let function_blocks = check_size(&cont, jed)?;
+    cont.borrow().progress_phase("program");
+    ir(&mut cont, Inst::ISC_PROGRAM).await?;
+    for sector in 0..SECTORS {
+        for row in 0..ROWS_PER_SECTOR {
+            let data = row_data(jed, function_blocks, sector, row);
+            let last = row == ROWS_PER_SECTOR - 1;
+            let control = if last { EXECUTE } else { LOAD };
+            shift(&mut cont, control, &data, address(sector, row)).await?;
+            let wait = match last {
+                true => Command::wait(PROGRAM_TIME),
+                false => Command::idle_clocks(1),
+            };
+            cont.borrow().run([wait]).await?;
+        }
+        progress((sector + 1) * ROWS_PER_SECTOR);
+    }
+    Ok(())
+}
+
+/// Read every row back and compare it with `jed`, failing on the first row
+/// that differs. Needs [`enable`] first.
+pub async fn verify(
+    mut cont: Controller<'_>,
+    jed: &Jedec,
+    mut progress: impl FnMut(usize),
+) -> Result<()> {
+    let function_blocks = check_size(&cont, jed)?;
+    cont.borrow().progress_phase("verify");
+    ir(&mut cont, Inst::ISC_READ).await?;
+    let rows = (0..SECTORS).flat_map(|sector| (0..ROWS_PER_SECTOR).map(move |row| (sector, row)));
+    let mut previous: Option<(usize, usize)> = None;
+    let zeros = vec![0; function_blocks];
+    // one more shift at the end, for the data of the last row
+    for (i, next) in rows.map(Some).chain([None]).enumerate() {
+        let addr = next.map_or(0, |(sector, row)| address(sector, row));
+        let data = shift(&mut cont, EXECUTE, &zeros, addr).await?;
+        cont.borrow().run([Command::idle_clocks(1)]).await?;
+
+        if let Some((sector, row)) = previous {
+            let expected = row_data(jed, function_blocks, sector, row);
+            let mask = row_mask(row);
+            for (fb, (&actual, &expected)) in data.iter().zip(&expected).enumerate() {
+                if actual & mask != expected {
+                    bail!(
+                        "verify failed at sector {sector} row {row} function block {fb}: read \
+                         {actual:#04x}, expected {expected:#04x}"
+                    );
+                }
+            }
+            progress(i);
+        }
+        previous = next;
+    }
+    Ok(())
+}
+
+/// Erase, program, and (if `verify_after`) verify `jed`, leaving ISC mode
+/// even if one of them fails. `progress` counts rows programmed, then
+/// [`ROWS`] more for the ones verified.
+pub async fn run(
+    mut cont: Controller<'_>,
+    jed: &Jedec,
+    mut progress: impl FnMut(usize),
+    verify_after: bool,
+) -> Result<()> {
+    check_size(&cont, jed)?;
+    enable(cont.reborrow()).await?;
+    let ret = async {
+        erase(cont.reborrow()).await?;
+        program(cont.reborrow(), jed, &mut progress).await?;
+        if verify_after {
+            verify(cont.reborrow(), jed, |n| progress(ROWS + n)).await?;
+        }
+        Ok(())
+    }
+    .await;
+    let disabled = disable(cont).await;
+    ret.and(disabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::{
+        devices::XilinxCpldInfo,
+        fake::{self, FakeDevice},
+    };
+
+    use super::*;
+
+    const XC9536XL: u32 = 0x0960_2093;
+    const FUNCTION_BLOCKS: usize = 2;
+
+    fn blank() -> Jedec {
+        Jedec {
+            fuses: vec![false; FUNCTION_BLOCKS * FUSES_PER_FB],
+            device: None,
+        }
+    }
+
+    #[test]
+    fn test_row_data() {
+        assert_eq!(row_mask(0), 0xff);
+        assert_eq!(row_mask(9), 0x3f);
+
+        let mut jed = blank();
+        let first = first_fuse(FUNCTION_BLOCKS, 1, 9);
+        for fuse in [0, 5, 6 + 1] {
+            jed.fuses[first + fuse] = true;
+        }
+        assert_eq!(row_data(&jed, FUNCTION_BLOCKS, 1, 9), [0b10_0001, 0b10]);
+        assert_eq!(row_data(&jed, FUNCTION_BLOCKS, 1, 8), [0, 0]);
+    }
+
+    #[test]
+    fn test_verify() {
+        smol::block_on(async {
+            // the ISC register reads back what was last shifted in, so every
+            // row reads as the zeros shifted in to address the next one
+            let len = Bits(2 + 8 * FUNCTION_BLOCKS + 16);
+            let device = FakeDevice::new(XC9536XL, Bits(8), Inst::IDCODE as u32).register(
+                Inst::ISC_READ as u32,
+                len,
+                &[0; 5],
+            );
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+            let mut cont = cont.typed::<XilinxCpldInfo>().unwrap();
+
+            let mut rows = 0;
+            verify(cont.reborrow(), &blank(), |n| rows = n)
+                .await
+                .unwrap();
+            assert_eq!(rows, ROWS);
+
+            let mut jed = blank();
+            jed.fuses[first_fuse(FUNCTION_BLOCKS, 3, 2) + 8 + 4] = true;
+            let err = verify(cont, &jed, |_| ()).await.unwrap_err();
+            assert!(
+                err.to_string().contains("sector 3 row 2 function block 1"),
+                "{err}"
+            );
+        });
+    }
+}
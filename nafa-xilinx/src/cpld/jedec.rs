@@ -0,0 +1,164 @@
+//! JEDEC fuse map files (JESD3-C), as written by the CPLD fitter. Only the
+//! fields needed to program a device are read: the fuse count (`QF`), the
+//! default fuse state (`F`), the fuse lists (`L`), the fuse checksum (`C`),
+//! and the `N DEVICE` note.
+
+use eyre::{Result, bail, eyre};
+
+const STX: char = '\x02';
+const ETX: char = '\x03';
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Jedec {
+    /// The state of each fuse, by fuse number.
+    pub fuses: Vec<bool>,
+    /// Part the file was fitted for, i.e. `XC9572XL-10-VQ64`.
+    pub device: Option<String>,
+}
+
+impl Jedec {
+    pub fn parse(data: &str) -> Result<Self> {
+        let data = match data.split_once(STX) {
+            Some((_, rest)) => rest,
+            None => data,
+        };
+        let data = match data.split_once(ETX) {
+            Some((text, _)) => text,
+            None => data,
+        };
+
+        let mut parser = Parser::default();
+        let mut fields = data.split('*');
+        // the design specification, free text ending in the first `*`. Some
+        // fitters leave out STX, so the first field shares it
+        if let Some(header) = fields.next() {
+            for line in header.lines() {
+                let _ = parser.field(line);
+            }
+        }
+        for field in fields {
+            parser.field(field)?;
+        }
+        parser.finish()
+    }
+
+    /// Sum of the fuses taken 8 at a time (the lowest-numbered fuse as the
+    /// LSB of each byte), as in the `C` field.
+    pub fn checksum(&self) -> u16 {
+        (self.fuses.chunks(8))
+            .map(|byte| {
+                (byte.iter().enumerate()).fold(0u16, |acc, (i, &f)| acc | u16::from(f) << i)
+            })
+            .fold(0, u16::wrapping_add)
+    }
+}
+
+#[derive(Default)]
+struct Parser {
+    fuses: Option<Vec<Option<bool>>>,
+    default: Option<bool>,
+    checksum: Option<u16>,
+    device: Option<String>,
+}
+
+impl Parser {
+    fn field(&mut self, field: &str) -> Result<()> {
+        let field = field.trim();
+        let Some(kind) = field.chars().next() else {
+            return Ok(());
+        };
+        let rest = &field[kind.len_utf8()..];
+        match kind {
+            'Q' if rest.starts_with('F') => {
+                let count = rest[1..]
+                    .trim()
+                    .parse()
+                    .map_err(|_| eyre!("invalid QF {rest:?}"))?;
+                self.fuses = Some(vec![None; count]);
+            }
+            'F' => self.default = Some(parse_bit(rest.trim())?),
+            'L' => {
+                let (start, bits) = (rest.split_once(char::is_whitespace))
+                    .ok_or_else(|| eyre!("fuse list without fuses"))?;
+                let start: usize = start.parse().map_err(|_| eyre!("invalid L {start:?}"))?;
+                let Some(fuses) = &mut self.fuses else {
+                    bail!("fuse list before QF");
+                };
+                let bits = bits.chars().filter(|c| !c.is_whitespace());
+                let count = fuses.len();
+                for (i, bit) in bits.enumerate() {
+                    let fuse = (fuses.get_mut(start + i))
+                        .ok_or_else(|| eyre!("fuse {} past QF{count}", start + i))?;
+                    *fuse = Some(parse_bit(&bit.to_string())?);
+                }
+            }
+            'C' => {
+                let sum = u16::from_str_radix(rest.trim(), 16);
+                self.checksum = Some(sum.map_err(|_| eyre!("invalid C {rest:?}"))?);
+            }
+            'N' => {
+                if let Some(device) = rest.trim().strip_prefix("DEVICE") {
+                    self.device = Some(device.trim().to_string());
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Jedec> {
+        let Some(fuses) = self.fuses else {
+            bail!("no fuse count (QF) in JEDEC file");
+        };
+        let default = self.default;
+        let fuses = (fuses.into_iter().enumerate())
+            .map(|(i, f)| {
+                f.or(default)
+                    .ok_or_else(|| eyre!("fuse {i} not set, and no default"))
+            })
+            .collect::<Result<_>>()?;
+        let jed = Jedec {
+            fuses,
+            device: self.device,
+        };
+        if let Some(expected) = self.checksum {
+            let actual = jed.checksum();
+            if actual != expected {
+                bail!("fuse checksum {actual:04X} does not match {expected:04X} in file");
+            }
+        }
+        Ok(jed)
+    }
+}
+
+fn parse_bit(s: &str) -> Result<bool> {
+    match s {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => bail!("invalid fuse state {s:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let file = "Programmer Jedec Bit Map\nDate Extracted: today\n\nQF20*\nQP44*\nF0*\nN \
+                    DEVICE XC9536XL-5-VQ44*\nL0000 10100000\n11*\nL0016 0001*\nC0010*\n\x030000";
+        let jed = Jedec::parse(file).unwrap();
+        assert_eq!(jed.device.as_deref(), Some("XC9536XL-5-VQ44"));
+        assert_eq!(jed.fuses.len(), 20);
+        let set: Vec<_> = (jed.fuses.iter().enumerate())
+            .filter_map(|(i, &f)| f.then_some(i))
+            .collect();
+        assert_eq!(set, [0, 2, 8, 9, 19]);
+        // 0x05 + 0x03 + 0x08
+        assert_eq!(jed.checksum(), 0x10);
+
+        assert!(Jedec::parse(&file.replace("C0010", "C0011")).is_err());
+        assert!(Jedec::parse(&file.replace("L0016 0001", "L0018 0001")).is_err());
+        assert!(Jedec::parse(&file.replace("F0*", "")).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+//! The ARM Debug Access Port (ADIv5 JTAG-DP) in front of the PS of a Zynq,
+//! giving access to PS memory and registers through its MEM-APs.
+//!
+//! On a Zynq-7000 the DAP is a TAP of its own next to the PL. On a Zynq
+//! UltraScale+ it's only on the chain once the PS has been told to add it,
+//! which [`nafa_io::detect_chain`] does.
+//!
+//! Every access is a 35-bit scan through DPACC or APACC. The result of a read
+//! only comes out of the _next_ scan, so reads are finished with a read of
+//! RDBUFF, which has no side effects.
+
+use eyre::{Result, bail};
+use nafa_io::{Controller, jtag::State, units::Bits};
+
+pub mod boot;
+pub mod cpu;
+
+/// JEDEC manufacturer id of ARM, as in the DAP IDCODE.
+const ARM: u16 = 0x23b;
+
+/// JTAG-DP instructions, 4 bits.
+#[rustfmt::skip]
+mod ir {
+    pub const ABORT: u8 = 0b1000;
+    pub const DPACC: u8 = 0b1010;
+    pub const APACC: u8 = 0b1011;
+}
+
+/// How many times a scan is repeated while the DAP answers WAIT.
+const WAIT_RETRIES: usize = 100;
+
+/// MEM-AP for the system bus on both the Zynq-7000 (AHB-AP) and the Zynq
+/// UltraScale+ (AXI-AP).
+pub const SYSTEM_AP: u8 = 0;
+
+/// APB-AP for the CoreSight debug components, i.e. the CPU debug registers.
+pub const DEBUG_AP: u8 = 1;
+
+/// DP registers, by A[3:2].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DpReg {
+    CtrlStat = 0x4,
+    Select = 0x8,
+    RdBuff = 0xc,
+}
+
+/// MEM-AP registers, by address within the AP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ApReg {
+    Csw = 0x00,
+    Tar = 0x04,
+    Drw = 0x0c,
+    Base = 0xf8,
+    Idr = 0xfc,
+}
+
+bitflags::bitflags! {
+    /// DP CTRL/STAT.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct CtrlStat: u32 {
+        const STICKYORUN   = 1 << 1;
+        const STICKYCMP    = 1 << 4;
+        const STICKYERR    = 1 << 5;
+        const CDBGPWRUPREQ = 1 << 28;
+        const CDBGPWRUPACK = 1 << 29;
+        const CSYSPWRUPREQ = 1 << 30;
+        const CSYSPWRUPACK = 1 << 31;
+
+        const _ = !0;
+    }
+}
+
+/// MEM-AP CSW: 32-bit accesses, TAR incremented after each one.
+const CSW_SIZE_WORD: u32 = 0b010;
+const CSW_ADDRINC_SINGLE: u32 = 0b01 << 4;
+const CSW_SIZE_ADDRINC_MASK: u32 = 0x3f;
+
+/// TAR auto-increment is only guaranteed within a 1 KiB block.
+const AUTOINC_WRAP: u32 = 0x400;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Ack {
+    OkFault,
+    Wait,
+    Other(u8),
+}
+
+/// One DPACC/APACC request, as shifted into the 35-bit DR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Request {
+    read: bool,
+    /// Register address, only bits [3:2] are sent.
+    addr: u8,
+    data: u32,
+}
+
+impl Request {
+    const LEN: Bits<usize> = Bits(35);
+
+    fn encode(self) -> [u8; 5] {
+        let raw = (u64::from(self.data) << 3)
+            | (u64::from(self.addr >> 2) & 0b11) << 1
+            | u64::from(self.read);
+        let bytes = raw.to_le_bytes();
+        [bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]]
+    }
+
+    /// The ACK and read result of the previous request.
+    fn decode(tdo: &[u8]) -> (Ack, u32) {
+        let mut bytes = [0; 8];
+        bytes[..5].copy_from_slice(&tdo[..5]);
+        let raw = u64::from_le_bytes(bytes);
+        let ack = match raw & 0b111 {
+            0b010 => Ack::OkFault,
+            0b001 => Ack::Wait,
+            other => Ack::Other(other as u8),
+        };
+        (ack, (raw >> 3) as u32)
+    }
+}
+
+pub struct Dap<'a> {
+    cont: &'a mut Controller,
+    /// Last instruction loaded, to skip loading it again.
+    ir: Option<u8>,
+    /// Last value written to SELECT.
+    select: Option<u32>,
+}
+
+impl<'a> Dap<'a> {
+    /// Select the ARM DAP on the chain, unless it's already the active device,
+    /// and power up the debug and system domains.
+    pub async fn new(cont: &'a mut Controller) -> Result<Self> {
+        let is_dap =
+            |idcode: nafa_io::jtag::IdCode, irlen| idcode.manufacturer() == ARM && irlen == Bits(4);
+        if !is_dap(cont.idcode(), cont.info().irlen) {
+            let Some(idx) = cont
+                .chain()
+                .position(|(idcode, info)| is_dap(*idcode, info.irlen))
+            else {
+                bail!("no ARM DAP on the chain");
+            };
+            cont.select(idx)?;
+        }
+
+        let mut dap = Self {
+            cont,
+            ir: None,
+            select: None,
+        };
+        dap.power_up().await?;
+        Ok(dap)
+    }
+
+    async fn power_up(&mut self) -> Result<()> {
+        let req = CtrlStat::CDBGPWRUPREQ | CtrlStat::CSYSPWRUPREQ;
+        let ack = CtrlStat::CDBGPWRUPACK | CtrlStat::CSYSPWRUPACK;
+        // sticky bits are cleared by writing 1
+        let clear = CtrlStat::STICKYORUN | CtrlStat::STICKYCMP | CtrlStat::STICKYERR;
+        self.write_dp(DpReg::CtrlStat, (req | clear).bits()).await?;
+        for _ in 0..WAIT_RETRIES {
+            let stat = CtrlStat::from_bits_retain(self.read_dp(DpReg::CtrlStat).await?);
+            if stat.contains(ack) {
+                return Ok(());
+            }
+        }
+        bail!("DAP did not power up the debug domain");
+    }
+
+    async fn scan(&mut self, ir: u8, req: Request) -> Result<u32> {
+        if self.ir != Some(ir) {
+            self.cont.shift_ir(&[ir], State::RunTestIdle).await?;
+            self.ir = Some(ir);
+        }
+        let tdi = req.encode();
+        for _ in 0..WAIT_RETRIES {
+            let tdo = (self.cont)
+                .shift_dr(&tdi, Request::LEN, State::RunTestIdle)
+                .await?;
+            match Request::decode(tdo) {
+                (Ack::OkFault, data) => return Ok(data),
+                (Ack::Wait, _) => continue,
+                (Ack::Other(ack), _) => bail!("invalid ACK {ack:#05b} from DAP"),
+            }
+        }
+        bail!("DAP still busy after {WAIT_RETRIES} retries");
+    }
+
+    /// Fetch the result of the last read, posted to RDBUFF.
+    async fn rdbuff(&mut self) -> Result<u32> {
+        let req = Request {
+            read: true,
+            addr: DpReg::RdBuff as u8,
+            data: 0,
+        };
+        self.scan(ir::DPACC, req).await
+    }
+
+    pub async fn read_dp(&mut self, reg: DpReg) -> Result<u32> {
+        let req = Request {
+            read: true,
+            addr: reg as u8,
+            data: 0,
+        };
+        self.scan(ir::DPACC, req).await?;
+        self.rdbuff().await
+    }
+
+    pub async fn write_dp(&mut self, reg: DpReg, data: u32) -> Result<()> {
+        let req = Request {
+            read: false,
+            addr: reg as u8,
+            data,
+        };
+        self.scan(ir::DPACC, req).await?;
+        Ok(())
+    }
+
+    /// Point SELECT at the bank of `addr` in `ap`.
+    async fn select(&mut self, ap: u8, addr: u8) -> Result<()> {
+        let select = u32::from(ap) << 24 | u32::from(addr & 0xf0);
+        if self.select != Some(select) {
+            self.write_dp(DpReg::Select, select).await?;
+            self.select = Some(select);
+        }
+        Ok(())
+    }
+
+    pub async fn read_ap(&mut self, ap: u8, addr: u8) -> Result<u32> {
+        self.select(ap, addr).await?;
+        let req = Request {
+            read: true,
+            addr,
+            data: 0,
+        };
+        self.scan(ir::APACC, req).await?;
+        self.rdbuff().await
+    }
+
+    pub async fn write_ap(&mut self, ap: u8, addr: u8, data: u32) -> Result<()> {
+        self.select(ap, addr).await?;
+        let req = Request {
+            read: false,
+            addr,
+            data,
+        };
+        self.scan(ir::APACC, req).await?;
+        Ok(())
+    }
+
+    /// Fail if an AP access since the last check faulted, clearing the error.
+    async fn check_sticky(&mut self) -> Result<()> {
+        let stat = CtrlStat::from_bits_retain(self.read_dp(DpReg::CtrlStat).await?);
+        let sticky = stat & (CtrlStat::STICKYORUN | CtrlStat::STICKYERR);
+        if sticky.is_empty() {
+            return Ok(());
+        }
+        let keep = stat & (CtrlStat::CDBGPWRUPREQ | CtrlStat::CSYSPWRUPREQ);
+        self.write_dp(DpReg::CtrlStat, (keep | sticky).bits())
+            .await?;
+        bail!("AP access faulted, CTRL/STAT {:08X}", stat.bits());
+    }
+
+    /// Abort the current AP transaction, i.e. one stuck on a bus that doesn't
+    /// answer.
+    pub async fn abort(&mut self) -> Result<()> {
+        self.cont.shift_ir(&[ir::ABORT], State::RunTestIdle).await?;
+        self.ir = Some(ir::ABORT);
+        let tdi = Request {
+            read: false,
+            addr: 0,
+            data: 1,
+        }
+        .encode();
+        (self.cont)
+            .shift_dr(&tdi, Request::LEN, State::RunTestIdle)
+            .await?;
+        Ok(())
+    }
+
+    /// Use `ap` as a MEM-AP, setting it up for 32-bit accesses.
+    pub async fn mem_ap(&mut self, ap: u8) -> Result<MemAp<'_, 'a>> {
+        let csw = self.read_ap(ap, ApReg::Csw as u8).await?;
+        let csw = (csw & !CSW_SIZE_ADDRINC_MASK) | CSW_SIZE_WORD | CSW_ADDRINC_SINGLE;
+        self.write_ap(ap, ApReg::Csw as u8, csw).await?;
+        self.check_sticky().await?;
+        Ok(MemAp { dap: self, ap })
+    }
+}
+
+/// A MEM-AP, accessing memory of its bus one 32-bit word at a time.
+pub struct MemAp<'d, 'a> {
+    dap: &'d mut Dap<'a>,
+    ap: u8,
+}
+
+impl MemAp<'_, '_> {
+    fn check_aligned(addr: u32) -> Result<()> {
+        if !addr.is_multiple_of(4) {
+            bail!("address {addr:#010x} is not word aligned");
+        }
+        Ok(())
+    }
+
+    /// Read `buf.len()` words starting at `addr`.
+    pub async fn read(&mut self, addr: u32, buf: &mut [u32]) -> Result<()> {
+        Self::check_aligned(addr)?;
+        let mut addr = addr;
+        let mut rest = buf;
+        while !rest.is_empty() {
+            let block = ((AUTOINC_WRAP - addr % AUTOINC_WRAP) / 4) as usize;
+            let (chunk, tail) = rest.split_at_mut(block.min(rest.len()));
+            self.read_block(addr, chunk).await?;
+            addr = addr.wrapping_add(4 * chunk.len() as u32);
+            rest = tail;
+        }
+        self.dap.check_sticky().await
+    }
+
+    /// Read words within one auto-increment block.
+    async fn read_block(&mut self, addr: u32, buf: &mut [u32]) -> Result<()> {
+        self.dap.write_ap(self.ap, ApReg::Tar as u8, addr).await?;
+        self.dap.select(self.ap, ApReg::Drw as u8).await?;
+        let req = Request {
+            read: true,
+            addr: ApReg::Drw as u8,
+            data: 0,
+        };
+        // each scan returns the word read by the one before
+        self.dap.scan(ir::APACC, req).await?;
+        let len = buf.len();
+        for word in &mut buf[..len - 1] {
+            *word = self.dap.scan(ir::APACC, req).await?;
+        }
+        buf[len - 1] = self.dap.rdbuff().await?;
+        Ok(())
+    }
+
+    /// Write `data` starting at `addr`.
+    pub async fn write(&mut self, addr: u32, data: &[u32]) -> Result<()> {
+        Self::check_aligned(addr)?;
+        let mut addr = addr;
+        let mut rest = data;
+        while !rest.is_empty() {
+            let block = ((AUTOINC_WRAP - addr % AUTOINC_WRAP) / 4) as usize;
+            let (chunk, tail) = rest.split_at(block.min(rest.len()));
+            self.dap.write_ap(self.ap, ApReg::Tar as u8, addr).await?;
+            for word in chunk {
+                self.dap.write_ap(self.ap, ApReg::Drw as u8, *word).await?;
+            }
+            addr = addr.wrapping_add(4 * chunk.len() as u32);
+            rest = tail;
+        }
+        self.dap.check_sticky().await
+    }
+
+    pub async fn read_word(&mut self, addr: u32) -> Result<u32> {
+        let mut buf = [0];
+        self.read(addr, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    pub async fn write_word(&mut self, addr: u32, data: u32) -> Result<()> {
+        self.write(addr, &[data]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let req = Request {
+            read: true,
+            addr: DpReg::RdBuff as u8,
+            data: 0xdead_beef,
+        };
+        let tdi = req.encode();
+        assert_eq!(tdi[0] & 0b111, 0b111);
+        assert_eq!(Request::decode(&tdi).1, 0xdead_beef);
+
+        assert_eq!(Request::decode(&[0b010, 0, 0, 0, 0]), (Ack::OkFault, 0));
+        assert_eq!(Request::decode(&[0b001, 0, 0, 0, 0]), (Ack::Wait, 0));
+        assert_eq!(
+            Request::decode(&[0b1000_0010, 0, 0, 0, 0b0000_0111]),
+            (Ack::OkFault, 0xe000_0010)
+        );
+    }
+}
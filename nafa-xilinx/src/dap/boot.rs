@@ -0,0 +1,263 @@
+//! Boot a Zynq PS over JTAG: load an ELF or raw binary into OCM or DDR
+//! through the system MEM-AP, then start a core at its entry point.
+//!
+//! Only the memory the image is loaded into has to be working, nothing is
+//! initialized here. Loading into DDR needs the FSBL (or an init script) to
+//! have set up the DDR controller first.
+
+use eyre::{OptionExt as _, Result, bail, eyre};
+
+use super::{
+    Dap, SYSTEM_AP,
+    cpu::{self, Ps},
+};
+
+/// A loadable segment: bytes to place at a physical address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub addr: u32,
+    pub data: Vec<u8>,
+}
+
+/// What to load, and where to start.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    pub entry: u32,
+}
+
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+const PT_LOAD: u32 = 1;
+
+/// Largest segment loaded, `.bss` included. It's all written over JTAG, so a
+/// bigger one is much more likely a corrupt header than a real image.
+const MAX_SEGMENT: usize = 256 << 20;
+
+impl Image {
+    pub fn is_elf(data: &[u8]) -> bool {
+        data.starts_with(&ELF_MAGIC)
+    }
+
+    /// A raw binary, loaded at `addr` and started from its first byte.
+    pub fn from_bin(data: &[u8], addr: u32) -> Self {
+        Self {
+            segments: vec![Segment {
+                addr,
+                data: data.to_vec(),
+            }],
+            entry: addr,
+        }
+    }
+
+    /// The `PT_LOAD` segments of a little-endian ELF32 (Cortex-A9) or ELF64
+    /// (Cortex-A53), each at its physical address, with its `.bss` part
+    /// zeroed.
+    pub fn from_elf(data: &[u8]) -> Result<Self> {
+        if !Self::is_elf(data) {
+            bail!("not an ELF file");
+        }
+        let wide = match data.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            class => bail!("unknown ELF class {class:?}"),
+        };
+        if data.get(5) != Some(&1) {
+            bail!("only little-endian ELF files are supported");
+        }
+
+        let field = |offset: usize, len: usize| -> Result<u64> {
+            let bytes = (offset.checked_add(len))
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| eyre!("ELF truncated at {offset:#x}"))?;
+            let mut buf = [0; 8];
+            buf[..len].copy_from_slice(bytes);
+            Ok(u64::from_le_bytes(buf))
+        };
+        let addr = |value: u64| {
+            u32::try_from(value).map_err(|_| eyre!("address {value:#x} is out of 32-bit range"))
+        };
+
+        let word = if wide { 8 } else { 4 };
+        let entry = addr(field(0x18, word)?)?;
+        let phoff = field(0x18 + word, word)? as usize;
+        let (phentsize, phnum) = match wide {
+            true => (field(0x36, 2)?, field(0x38, 2)?),
+            false => (field(0x2a, 2)?, field(0x2c, 2)?),
+        };
+
+        let mut segments = Vec::new();
+        for i in 0..phnum as usize {
+            let ph = (i.checked_mul(phentsize as usize))
+                .and_then(|entry| phoff.checked_add(entry))
+                .ok_or_eyre("ELF program header table out of range")?;
+            if field(ph, 4)? as u32 != PT_LOAD {
+                continue;
+            }
+            let [offset, paddr, filesz, memsz] = match wide {
+                true => [0x08, 0x18, 0x20, 0x28].map(|o| field(ph + o, 8)),
+                false => [0x04, 0x0c, 0x10, 0x14].map(|o| field(ph + o, 4)),
+            };
+            let (offset, filesz, memsz) = (offset? as usize, filesz? as usize, memsz? as usize);
+            let size = memsz.max(filesz);
+            if size > MAX_SEGMENT {
+                bail!("ELF segment of {size:#x} bytes is larger than {MAX_SEGMENT:#x}");
+            }
+            let mut contents = (offset.checked_add(filesz))
+                .and_then(|end| data.get(offset..end))
+                .ok_or_eyre("ELF segment runs past the end of the file")?
+                .to_vec();
+            contents.resize(size, 0);
+            if contents.is_empty() {
+                continue;
+            }
+            segments.push(Segment {
+                addr: addr(paddr?)?,
+                data: contents,
+            });
+        }
+        if segments.is_empty() {
+            bail!("no loadable segments in ELF");
+        }
+        Ok(Self { segments, entry })
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|s| s.data.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Write `segment` to memory, calling `progress` with the bytes written so
+/// far. A partial last word is merged with what's in memory.
+pub async fn write_segment(
+    dap: &mut Dap<'_>,
+    segment: &Segment,
+    mut progress: impl FnMut(usize),
+) -> Result<()> {
+    /// Bytes per MEM-AP write, between progress updates.
+    const CHUNK: usize = 4096;
+
+    if !segment.addr.is_multiple_of(4) {
+        bail!("segment at {:#010x} is not word aligned", segment.addr);
+    }
+    let mut mem = dap.mem_ap(SYSTEM_AP).await?;
+    let mut words: Vec<u32> = (segment.data.chunks_exact(4))
+        .map(|w| u32::from_le_bytes(w.try_into().expect("chunks of 4")))
+        .collect();
+    let tail = segment.data.chunks_exact(4).remainder();
+    if !tail.is_empty() {
+        let addr = segment.addr + 4 * words.len() as u32;
+        let mut last = mem.read_word(addr).await?.to_le_bytes();
+        last[..tail.len()].copy_from_slice(tail);
+        words.push(u32::from_le_bytes(last));
+    }
+
+    let mut addr = segment.addr;
+    for chunk in words.chunks(CHUNK / 4) {
+        mem.write(addr, chunk).await?;
+        addr += 4 * chunk.len() as u32;
+        progress((addr - segment.addr) as usize);
+    }
+    Ok(())
+}
+
+/// Where the A53 of a Zynq UltraScale+ starts out of reset:
+/// `APU.RVBARADDR<n>L`, followed by the `H` half.
+const ZU_RVBAR: u32 = 0xfd5c_0040;
+
+/// `ldr pc, [pc, #-4]`, followed by the address to jump to.
+const A9_TRAMPOLINE: u32 = 0xe51f_f004;
+
+/// Hold `core` in reset, so it doesn't run while its memory is loaded.
+pub async fn hold(dap: &mut Dap<'_>, ps: Ps, core: u8) -> Result<()> {
+    cpu::set_reset(dap, ps, core, true).await
+}
+
+/// Release `core` from reset, starting at `image`'s entry point.
+///
+/// An A53 starts from its reset vector address register. An A9 always
+/// starts at address 0, so unless `image` covers that, a jump to the entry
+/// point is written there (in OCM, or DDR if it has been remapped low).
+pub async fn start(dap: &mut Dap<'_>, ps: Ps, core: u8, image: &Image) -> Result<()> {
+    let entry = image.entry;
+    let mut mem = dap.mem_ap(SYSTEM_AP).await?;
+    match ps {
+        Ps::Zynq7000 { .. } => {
+            let covers_zero = (image.segments.iter()).any(|s| s.addr == 0 && s.data.len() >= 8);
+            if entry != 0 && !covers_zero {
+                mem.write(0, &[A9_TRAMPOLINE, entry]).await?;
+            }
+        }
+        Ps::ZynqUs { .. } => {
+            let rvbar = ZU_RVBAR + 8 * u32::from(core);
+            mem.write(rvbar, &[entry, 0]).await?;
+        }
+    }
+    cpu::set_reset(dap, ps, core, false).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elf32() {
+        // ELF header, one PT_LOAD of 6 bytes (4 more in .bss) at 0x100 in
+        // the file, loaded at 0x0010_0000
+        let mut elf = vec![0; 0x106];
+        elf[..6].copy_from_slice(&[0x7f, b'E', b'L', b'F', 1, 1]);
+        elf[0x18..0x1c].copy_from_slice(&0x0010_0004u32.to_le_bytes());
+        elf[0x1c..0x20].copy_from_slice(&0x34u32.to_le_bytes());
+        elf[0x2a..0x2c].copy_from_slice(&32u16.to_le_bytes());
+        elf[0x2c..0x2e].copy_from_slice(&1u16.to_le_bytes());
+        let ph = 0x34;
+        for (offset, value) in
+            [(0x00, 1), (0x04, 0x100), (0x0c, 0x0010_0000), (0x10, 6), (0x14, 10)]
+        {
+            elf[ph + offset..ph + offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+        }
+        elf[0x100..].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let image = Image::from_elf(&elf).unwrap();
+        assert_eq!(image.entry, 0x0010_0004);
+        assert_eq!(
+            image.segments,
+            [Segment {
+                addr: 0x0010_0000,
+                data: vec![1, 2, 3, 4, 5, 6, 0, 0, 0, 0],
+            }]
+        );
+
+        // .bss larger than anything that could be loaded
+        let mut huge = elf.clone();
+        huge[ph + 0x14..ph + 0x18].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Image::from_elf(&huge).is_err());
+
+        elf.truncate(0x103);
+        assert!(Image::from_elf(&elf).is_err());
+        assert!(Image::from_elf(b"not an elf").is_err());
+    }
+
+    #[test]
+    fn test_elf64_overflow() {
+        // offsets near u64::MAX, which wrap if added unchecked
+        let mut elf = vec![0; 0x40 + 56];
+        elf[..6].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1]);
+        elf[0x20..0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+        elf[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        elf[0x38..0x3a].copy_from_slice(&2u16.to_le_bytes());
+        assert!(Image::from_elf(&elf).is_err());
+
+        // a PT_LOAD whose file offset wraps
+        elf[0x20..0x28].copy_from_slice(&0x40u64.to_le_bytes());
+        elf[0x38..0x3a].copy_from_slice(&1u16.to_le_bytes());
+        let ph = 0x40;
+        elf[ph..ph + 4].copy_from_slice(&PT_LOAD.to_le_bytes());
+        elf[ph + 0x08..ph + 0x10].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+        elf[ph + 0x20..ph + 0x28].copy_from_slice(&4u64.to_le_bytes());
+        assert!(Image::from_elf(&elf).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Halt, resume, and reset the application cores of a Zynq PS through their
+//! debug registers, i.e. to stop the PS from driving AXI into the PL while it
+//! is reconfigured.
+//!
+//! The Cortex-A9 of a Zynq-7000 (ARMv7 debug) is halted and restarted through
+//! DBGDRCR directly. The Cortex-A53 of a Zynq UltraScale+ (ARMv8 external
+//! debug) only takes halt and restart requests from its cross trigger
+//! interface (CTI).
+
+use std::fmt::{self, Display, Formatter};
+
+use eyre::{Result, bail};
+use nafa_io::{
+    Controller,
+    devices::{Specific, Xilinx32Family},
+};
+
+use super::{DEBUG_AP, Dap, SYSTEM_AP};
+
+/// Written to the lock access registers to allow writes to the others.
+const UNLOCK_KEY: u32 = 0xc5ac_ce55;
+
+/// How many times the core status is polled after a halt or restart request.
+const POLL_RETRIES: usize = 100;
+
+/// Offsets in the debug register block of a core, the same for ARMv7 and
+/// ARMv8 where both have them.
+#[rustfmt::skip]
+mod dbg {
+    pub const DSCR: u32 = 0x088; // DBGDSCRext / EDSCR
+    pub const DRCR: u32 = 0x090; // DBGDRCR / EDRCR
+    pub const OSLAR: u32 = 0x300;
+    pub const PRSR: u32 = 0x314; // DBGPRSR / EDPRSR
+    pub const LAR: u32 = 0xfb0;
+
+    /// DBGDSCR
+    pub const DSCR_HALTED: u32 = 1 << 0;
+    pub const DSCR_RESTARTED: u32 = 1 << 1;
+    pub const DSCR_HDBGEN: u32 = 1 << 14;
+
+    /// DBGDRCR
+    pub const DRCR_HRQ: u32 = 1 << 0;
+    pub const DRCR_RRQ: u32 = 1 << 1;
+    pub const DRCR_CSE: u32 = 1 << 2;
+
+    /// DBGPRSR / EDPRSR
+    pub const PRSR_PU: u32 = 1 << 0;
+    pub const PRSR_HALTED: u32 = 1 << 4;
+}
+
+/// Offsets in the CTI of an ARMv8 core.
+#[rustfmt::skip]
+mod cti {
+    pub const CONTROL: u32 = 0x000;
+    pub const INTACK: u32 = 0x010;
+    pub const APPPULSE: u32 = 0x01c;
+    pub const OUTEN0: u32 = 0x0a0;
+    pub const OUTEN1: u32 = 0x0a4;
+    pub const GATE: u32 = 0x140;
+    pub const LAR: u32 = 0xfb0;
+
+    /// Channel driving the debug request trigger (output 0).
+    pub const CH_HALT: u32 = 1 << 0;
+    /// Channel driving the restart trigger (output 1).
+    pub const CH_RESTART: u32 = 1 << 1;
+}
+
+/// The processing system, and where its cores are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ps {
+    /// Cortex-A9, one or two.
+    Zynq7000 { cores: u8 },
+    /// Cortex-A53 (APU), two on CG parts and four on the others.
+    ZynqUs { cores: u8 },
+}
+
+impl Ps {
+    /// The PS of the Zynq on the chain, from its PL TAP.
+    pub fn detect(cont: &Controller) -> Option<Self> {
+        cont.chain().find_map(|(_, info)| match &info.specific {
+            Specific::XilinxZynq(z) => Some(Self::ZynqUs { cores: z.ps_cores }),
+            Specific::Xilinx32(x) => {
+                let cores = x.ps_cores?;
+                Some(match x.family {
+                    Xilinx32Family::UP => Self::ZynqUs { cores },
+                    _ => Self::Zynq7000 { cores },
+                })
+            }
+            _ => None,
+        })
+    }
+
+    pub const fn cores(self) -> u8 {
+        match self {
+            Self::Zynq7000 { cores } | Self::ZynqUs { cores } => cores,
+        }
+    }
+
+    /// Debug registers of `core`, on the debug APB.
+    const fn debug_base(self, core: u8) -> u32 {
+        match self {
+            Self::Zynq7000 { .. } => 0x8009_0000 + 0x2000 * core as u32,
+            Self::ZynqUs { .. } => 0x8041_0000 + 0x10_0000 * core as u32,
+        }
+    }
+
+    /// CTI of `core`, on the debug APB.
+    const fn cti_base(self, core: u8) -> u32 {
+        match self {
+            Self::Zynq7000 { .. } => 0x8009_8000 + 0x1000 * core as u32,
+            Self::ZynqUs { .. } => 0x8042_0000 + 0x10_0000 * core as u32,
+        }
+    }
+
+    /// Register holding the reset of each core in its low bits, on the system
+    /// bus. Writes to the SLCR of a Zynq-7000 need [`SLCR_UNLOCK`] first.
+    const fn reset_ctrl(self) -> u32 {
+        match self {
+            Self::Zynq7000 { .. } => 0xf800_0244, // A9_CPU_RST_CTRL
+            Self::ZynqUs { .. } => 0xfd1a_0104,   // CRF_APB.RST_FPD_APU
+        }
+    }
+
+    fn check_core(self, core: u8) -> Result<()> {
+        if core >= self.cores() {
+            bail!("no core {core}, {self:?} has {}", self.cores());
+        }
+        Ok(())
+    }
+}
+
+/// SLCR_UNLOCK of a Zynq-7000, and the key it takes.
+const SLCR_UNLOCK: (u32, u32) = (0xf800_0008, 0xdf0d);
+
+/// Also stop the clock of a Zynq-7000 core while holding it in reset.
+const A9_CLKSTOP_SHIFT: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoreState {
+    /// Powered down, or held in reset. The debug registers can't be reached.
+    Off,
+    Running,
+    Halted,
+}
+
+impl Display for CoreState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Running => write!(f, "running"),
+            Self::Halted => write!(f, "halted"),
+        }
+    }
+}
+
+async fn read_debug(dap: &mut Dap<'_>, addr: u32) -> Result<u32> {
+    dap.mem_ap(DEBUG_AP).await?.read_word(addr).await
+}
+
+async fn write_debug(dap: &mut Dap<'_>, addr: u32, data: u32) -> Result<()> {
+    dap.mem_ap(DEBUG_AP).await?.write_word(addr, data).await
+}
+
+pub async fn state(dap: &mut Dap<'_>, ps: Ps, core: u8) -> Result<CoreState> {
+    ps.check_core(core)?;
+    let base = ps.debug_base(core);
+    let prsr = read_debug(dap, base + dbg::PRSR).await?;
+    if prsr & dbg::PRSR_PU == 0 {
+        return Ok(CoreState::Off);
+    }
+    let halted = match ps {
+        Ps::Zynq7000 { .. } => read_debug(dap, base + dbg::DSCR).await? & dbg::DSCR_HALTED != 0,
+        Ps::ZynqUs { .. } => prsr & dbg::PRSR_HALTED != 0,
+    };
+    Ok(match halted {
+        true => CoreState::Halted,
+        false => CoreState::Running,
+    })
+}
+
+/// Unlock the debug registers (and CTI) of `core` for writing.
+async fn unlock(dap: &mut Dap<'_>, ps: Ps, core: u8) -> Result<()> {
+    let base = ps.debug_base(core);
+    write_debug(dap, base + dbg::LAR, UNLOCK_KEY).await?;
+    write_debug(dap, base + dbg::OSLAR, 0).await?;
+    if matches!(ps, Ps::ZynqUs { .. }) {
+        let cti = ps.cti_base(core);
+        write_debug(dap, cti + cti::LAR, UNLOCK_KEY).await?;
+        write_debug(dap, cti + cti::CONTROL, 1).await?;
+        // keep the triggers to this core, instead of halting every core
+        write_debug(dap, cti + cti::GATE, 0).await?;
+        write_debug(dap, cti + cti::OUTEN0, cti::CH_HALT).await?;
+        write_debug(dap, cti + cti::OUTEN1, cti::CH_RESTART).await?;
+    }
+    Ok(())
+}
+
+async fn wait_state(dap: &mut Dap<'_>, ps: Ps, core: u8, want: CoreState) -> Result<()> {
+    for _ in 0..POLL_RETRIES {
+        if state(dap, ps, core).await? == want {
+            return Ok(());
+        }
+    }
+    bail!("core {core} did not become {want}");
+}
+
+/// Halt `core` in debug state. Fails if it's off.
+pub async fn halt(dap: &mut Dap<'_>, ps: Ps, core: u8) -> Result<()> {
+    match state(dap, ps, core).await? {
+        CoreState::Off => bail!("core {core} is off"),
+        CoreState::Halted => return Ok(()),
+        CoreState::Running => (),
+    }
+    unlock(dap, ps, core).await?;
+    let base = ps.debug_base(core);
+    match ps {
+        Ps::Zynq7000 { .. } => {
+            let dscr = read_debug(dap, base + dbg::DSCR).await?;
+            write_debug(dap, base + dbg::DSCR, dscr | dbg::DSCR_HDBGEN).await?;
+            write_debug(dap, base + dbg::DRCR, dbg::DRCR_HRQ).await?;
+        }
+        Ps::ZynqUs { .. } => {
+            let cti = ps.cti_base(core);
+            write_debug(dap, cti + cti::APPPULSE, cti::CH_HALT).await?;
+        }
+    }
+    wait_state(dap, ps, core, CoreState::Halted).await
+}
+
+/// Let a halted `core` run again from where it stopped.
+pub async fn resume(dap: &mut Dap<'_>, ps: Ps, core: u8) -> Result<()> {
+    match state(dap, ps, core).await? {
+        CoreState::Off => bail!("core {core} is off"),
+        CoreState::Running => return Ok(()),
+        CoreState::Halted => (),
+    }
+    unlock(dap, ps, core).await?;
+    let base = ps.debug_base(core);
+    match ps {
+        Ps::Zynq7000 { .. } => {
+            let restart = dbg::DRCR_CSE | dbg::DRCR_RRQ;
+            write_debug(dap, base + dbg::DRCR, restart).await?;
+            for _ in 0..POLL_RETRIES {
+                let dscr = read_debug(dap, base + dbg::DSCR).await?;
+                if dscr & dbg::DSCR_RESTARTED != 0 {
+                    return Ok(());
+                }
+            }
+            bail!("core {core} did not restart");
+        }
+        Ps::ZynqUs { .. } => {
+            let cti = ps.cti_base(core);
+            // the halt trigger stays asserted until acknowledged
+            write_debug(dap, cti + cti::INTACK, cti::CH_HALT).await?;
+            write_debug(dap, base + dbg::DRCR, dbg::DRCR_CSE).await?;
+            write_debug(dap, cti + cti::APPPULSE, cti::CH_RESTART).await?;
+            wait_state(dap, ps, core, CoreState::Running).await
+        }
+    }
+}
+
+/// Put `core` in reset, or take it out again. Out of reset, the core starts
+/// from its reset vector (the boot ROM, or as set up by the FSBL).
+pub async fn set_reset(dap: &mut Dap<'_>, ps: Ps, core: u8, reset: bool) -> Result<()> {
+    ps.check_core(core)?;
+    let mut mem = dap.mem_ap(SYSTEM_AP).await?;
+    let mask = match ps {
+        Ps::Zynq7000 { .. } => {
+            mem.write_word(SLCR_UNLOCK.0, SLCR_UNLOCK.1).await?;
+            (1 << core) | (1 << (A9_CLKSTOP_SHIFT + u32::from(core)))
+        }
+        Ps::ZynqUs { .. } => 1 << core,
+    };
+    let addr = ps.reset_ctrl();
+    let ctrl = mem.read_word(addr).await?;
+    if reset {
+        return mem.write_word(addr, ctrl | mask).await;
+    }
+    // release the reset before starting the clock again
+    let rst = 1 << core;
+    mem.write_word(addr, ctrl & !rst).await?;
+    mem.write_word(addr, ctrl & !mask).await
+}
+
+/// Put `core` through a reset.
+pub async fn reset(dap: &mut Dap<'_>, ps: Ps, core: u8) -> Result<()> {
+    set_reset(dap, ps, core, true).await?;
+    set_reset(dap, ps, core, false).await
+}
+
+/// Halt every core that is powered up, returning which ones were.
+pub async fn halt_all(dap: &mut Dap<'_>, ps: Ps) -> Result<Vec<u8>> {
+    let mut halted = Vec::new();
+    for core in 0..ps.cores() {
+        if state(dap, ps, core).await? == CoreState::Off {
+            continue;
+        }
+        halt(dap, ps, core).await?;
+        halted.push(core);
+    }
+    Ok(halted)
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::{
+        fake::{self, FakeDevice},
+        units::Bits,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        smol::block_on(async {
+            let detect = async |idcode| {
+                let device = FakeDevice::new(idcode, Bits(6), 0b001001);
+                Ps::detect(&fake::controller(vec![device]).await.unwrap())
+            };
+            assert_eq!(detect(0x362d093).await, None); // xc7a35t
+            assert_eq!(detect(0x3723093).await, Some(Ps::Zynq7000 { cores: 1 })); // xc7z007s
+            assert_eq!(detect(0x3727093).await, Some(Ps::Zynq7000 { cores: 2 })); // xc7z020
+            assert_eq!(detect(0x4a42093).await, Some(Ps::ZynqUs { cores: 2 })); // xczu3cg
+            assert_eq!(detect(0x4a57093).await, Some(Ps::ZynqUs { cores: 4 })); // xczu17eg
+        });
+    }
+}
@@ -0,0 +1,140 @@
+//! Device DNA, the factory-programmed unique ID of each die.
+//!
+//! Spartan-6 and 7-series have a 57-bit DNA, read with ISC_DNA/XSC_DNA
+//! between ISC_ENABLE and ISC_DISABLE, most significant bit first.
+//! UltraScale(+) have a 96-bit DNA in the FUSE_DNA eFUSE register, least
+//! significant bit first, one per SLR. Either way, [`Dna`] holds it as the
+//! number `DNA_PORT` in the fabric would give.
+
+use std::fmt::{self, Display, Formatter};
+
+use eyre::{OptionExt as _, Result, bail};
+use nafa_io::{
+    Command, Controller,
+    devices::{Specific, Xilinx32Family, Xilinx32Info, XilinxZynqInfo},
+    units::{Bits, Bytes},
+};
+
+use crate::{
+    _16bit,
+    _32bit::{
+        actions::efuse::{self, Fuse},
+        commands::{Duplicated, Master},
+    },
+    ir::IrEncoder,
+    zynq,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dna {
+    pub len: Bits<u8>,
+    pub value: u128,
+}
+
+impl Dna {
+    /// The first `len` bits of `data` (LSB of the first byte first), the
+    /// first one as the MSB.
+    pub fn from_msb_first(data: &[u8], len: Bits<u8>) -> Self {
+        let value = (0..usize::from(len.0))
+            .map(|i| data[i / 8] >> (i % 8) & 1)
+            .fold(0, |acc, bit| acc << 1 | u128::from(bit));
+        Self { len, value }
+    }
+
+    /// The first `len` bits of `data` (LSB of the first byte first), the
+    /// first one as the LSB.
+    pub fn from_lsb_first(data: &[u8], len: Bits<u8>) -> Self {
+        let value = (0..usize::from(len.0))
+            .map(|i| data[i / 8] >> (i % 8) & 1)
+            .enumerate()
+            .fold(0, |acc, (i, bit)| acc | u128::from(bit) << i);
+        Self { len, value }
+    }
+}
+
+/// Upper case hex, with as many digits as the DNA needs.
+impl Display for Dna {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let digits = usize::from(self.len.0).div_ceil(4);
+        write!(f, "{:0digits$X}", self.value)
+    }
+}
+
+const SHORT: Bits<u8> = Bits(57);
+const LONG: Bits<u8> = Bits(96);
+/// TCKs in Run-Test/Idle around ISC_ENABLE and ISC_DISABLE.
+const ISC_CLOCKS: usize = 64;
+
+/// The DNA of the active device: one for Spartan-6 and 7-series (from the
+/// master SLR), or one per SLR for UltraScale(+).
+pub async fn read(cont: &mut Controller) -> Result<Vec<Dna>> {
+    let info = cont.info();
+    match &info.specific {
+        Specific::Xilinx16(_) => Ok(vec![Dna::from_msb_first(&read_16bit(cont).await?, SHORT)]),
+        Specific::Xilinx32(x) if matches!(x.family, Xilinx32Family::S7) => {
+            let enc = IrEncoder::new(info).ok_or_eyre("no instruction encoding")?;
+            let enable = enc.duplicated(Duplicated::ISC_ENABLE);
+            let disable = enc.duplicated(Duplicated::ISC_DISABLE);
+            let data = isc_dna(cont, enable, enc.master(Master::XSC_DNA), disable).await?;
+            Ok(vec![Dna::from_msb_first(&data, SHORT)])
+        }
+        Specific::Xilinx32(_) => {
+            let mut cont = cont.typed::<Xilinx32Info>().expect("checked above");
+            let mut ret = Vec::new();
+            for slr in 0..cont.info().slr {
+                let data = efuse::read(cont.reborrow(), slr, Fuse::Dna).await?;
+                let data = data.ok_or_eyre("no FUSE_DNA register")?;
+                ret.push(Dna::from_lsb_first(&data, LONG));
+            }
+            Ok(ret)
+        }
+        Specific::XilinxZynq(_) => {
+            let mut cont = cont.typed::<XilinxZynqInfo>().expect("checked above");
+            zynq::io_utils::enable_pl(cont.reborrow()).await?;
+            let data = zynq::fuse_dna(cont).await?;
+            Ok(vec![Dna::from_lsb_first(&data, LONG)])
+        }
+        _ => bail!("no device DNA for {}", info.name),
+    }
+}
+
+/// The DNA of a Spartan-6, as shifted out of ISC_DNA.
+pub(crate) async fn read_16bit(cont: &mut Controller) -> Result<[u8; 8]> {
+    use _16bit::commands::{ISC_DISABLE, ISC_DNA, ISC_ENABLE, ir};
+    isc_dna(cont, ir(ISC_ENABLE), ir(ISC_DNA), ir(ISC_DISABLE)).await
+}
+
+/// The DNA register is only readable between ISC_ENABLE and ISC_DISABLE.
+async fn isc_dna(cont: &mut Controller, enable: u32, dna: u32, disable: u32) -> Result<[u8; 8]> {
+    let data = cont
+        .run([
+            Command::ir(enable),
+            Command::idle_clocks(ISC_CLOCKS),
+            Command::ir(dna),
+            Command::dr_rx(Bytes(8)),
+            Command::ir(disable),
+            Command::idle_clocks(ISC_CLOCKS),
+        ])
+        .await?;
+    Ok(data.try_into()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dna() {
+        // first bit shifted out is the MSB
+        let mut data = [0; 8];
+        data[0] = 0b1;
+        data[7] = 0b1000_0000;
+        let short = Dna::from_msb_first(&data, SHORT);
+        assert_eq!(short.value, 1 << 56);
+        assert_eq!(short.to_string(), "100000000000000");
+
+        let long = Dna::from_lsb_first(&[0x21, 0x43, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80], LONG);
+        assert_eq!(long.value, 0x4321 | 1 << 95);
+        assert_eq!(long.to_string(), "800000000000000000004321");
+    }
+}
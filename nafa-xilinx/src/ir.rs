@@ -0,0 +1,166 @@
+//! Instruction encoding for the configuration TAPs of each family.
+//!
+//! A configuration instruction isn't always a single 6-bit value. SSI devices
+//! have one TAP per SLR in series, each needing its own copy (or BYPASS), and
+//! the Zynq UltraScale+ puts the PL TAP behind the PS TAP in one 12-bit IR.
+//! [`IrEncoder`] builds the full IR value from the 6-bit PL instruction.
+
+use nafa_io::{
+    devices::{DeviceInfo, Specific},
+    units::Bits,
+};
+
+use crate::_32bit::commands;
+pub use crate::_32bit::commands::{Duplicated, Master, Shifted};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrEncoder {
+    /// 6 bits per SLR, with the SLRs in series: 7-series, UltraScale(+). The
+    /// PL of a Zynq-7000 is also this, as a TAP of its own next to the ARM
+    /// DAP.
+    Slr { num_slr: u8 },
+    /// Zynq UltraScale+: 12 bits, the PS instruction in the upper 6 and the
+    /// PL instruction in the lower 6.
+    ///
+    /// The PS routes the lower half to the PL TAP with [`PS_TO_PL`] in the
+    /// upper half. Instructions for the PS itself keep the PL TAP in BYPASS.
+    ZynqUs,
+}
+
+/// PS instruction of a Zynq UltraScale+ that passes the lower 6 bits on to
+/// the PL TAP.
+pub const PS_TO_PL: u32 = 0b100100;
+
+/// PS-only instructions of a Zynq UltraScale+, from `xczu9eg_ffvc900.bsd`.
+#[repr(u8)]
+#[expect(non_camel_case_types)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug)]
+#[rustfmt::skip]
+pub enum Ps {
+    JTAG_CTRL    = 0b100000,
+    JTAG_STATUS  = 0b011111,
+    ERROR_STATUS = 0b111110,
+    PMU_MDM      = 0b000011,
+    FUSE_USER_PS = 0b001000,
+}
+
+/// Value for [`Ps::JTAG_CTRL`] adding both the PL TAP (bit 0) and the ARM DAP
+/// (bit 1) to the chain.
+pub const JTAG_CTRL_ENABLE_ALL: [u8; 4] = [0b0000_0011, 0, 0, 0];
+
+impl IrEncoder {
+    /// The encoder for `info`, if it's a device with a PL configuration TAP.
+    pub fn new(info: &DeviceInfo) -> Option<Self> {
+        match &info.specific {
+            Specific::Xilinx32(info) => Some(Self::Slr { num_slr: info.slr }),
+            Specific::XilinxZynq(_) if info.irlen == Bits(12) => Some(Self::ZynqUs),
+            _ => None,
+        }
+    }
+
+    pub const fn irlen(self) -> Bits<u8> {
+        match self {
+            Self::Slr { num_slr } => Bits(6 * num_slr),
+            Self::ZynqUs => Bits(12),
+        }
+    }
+
+    /// An instruction every SLR gets at once.
+    pub const fn duplicated(self, inst: Duplicated) -> u32 {
+        match self {
+            Self::Slr { num_slr } => commands::duplicated(inst) & slr_mask(num_slr),
+            // the boundary scan and identification instructions are shared
+            // with the PS, the rest only go to the PL
+            Self::ZynqUs => match inst {
+                Duplicated::IDCODE => 0b001001_001001,
+                Duplicated::BYPASS => 0b111111_111111,
+                Duplicated::EXTEST => 0b100110_100110,
+                Duplicated::SAMPLE => 0b111111_000001,
+                Duplicated::HIGHZ_IO => 0b001010_001010,
+                Duplicated::EXTEST_PULSE => 0b100110_111100,
+                Duplicated::EXTEST_TRAIN => 0b100110_111101,
+                _ => PS_TO_PL << 6 | inst as u32,
+            },
+        }
+    }
+
+    /// An instruction only the master SLR gets.
+    pub const fn master(self, inst: Master) -> u32 {
+        match self {
+            Self::Slr { num_slr } => commands::master(inst, num_slr) & slr_mask(num_slr),
+            Self::ZynqUs => PS_TO_PL << 6 | inst as u32,
+        }
+    }
+
+    /// An instruction only `active_slr` gets.
+    pub const fn shifted(self, inst: Shifted, active_slr: u8) -> u32 {
+        match self {
+            Self::Slr { num_slr } => {
+                commands::shifted(inst, num_slr, active_slr) & slr_mask(num_slr)
+            }
+            Self::ZynqUs => PS_TO_PL << 6 | inst as u32,
+        }
+    }
+
+    /// Bits shifted out of the DR before the register selected in
+    /// `active_slr`, see [`commands::bypass_bits_before`].
+    pub const fn bypass_bits_before(self, active_slr: u8) -> usize {
+        match self {
+            Self::Slr { num_slr } => commands::bypass_bits_before(num_slr, active_slr),
+            Self::ZynqUs => 0,
+        }
+    }
+
+    /// An instruction for the PS, with the PL in BYPASS. `None` if there's
+    /// no PS in this TAP.
+    pub const fn ps(self, inst: Ps) -> Option<u32> {
+        match self {
+            Self::Slr { .. } => None,
+            Self::ZynqUs => Some((inst as u32) << 6 | 0b111111),
+        }
+    }
+
+    /// The IR and DR to write to make the PL TAP reachable, if it isn't
+    /// always. On a Zynq UltraScale+, the PL TAP is only in the chain after
+    /// setting it in JTAG_CTRL (which is also where the ARM DAP is enabled, so
+    /// both are).
+    pub const fn enable_pl(self) -> Option<(u32, [u8; 4])> {
+        match self.ps(Ps::JTAG_CTRL) {
+            Some(ir) => Some((ir, JTAG_CTRL_ENABLE_ALL)),
+            None => None,
+        }
+    }
+}
+
+/// The bits of the IR of `num_slr` SLRs, the rest of the encoding in
+/// [`commands`] is BYPASS for SLRs that aren't there.
+const fn slr_mask(num_slr: u8) -> u32 {
+    (1 << (6 * num_slr as u32)) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_32bit::commands::{CFG_IN, IDCODE, JPROGRAM, USERCODE};
+
+    #[test]
+    fn test_encoder() {
+        let single = IrEncoder::Slr { num_slr: 1 };
+        assert_eq!(single.irlen(), Bits(6));
+        assert_eq!(single.shifted(CFG_IN, 0), 0b000101);
+
+        let ssi = IrEncoder::Slr { num_slr: 3 };
+        assert_eq!(ssi.irlen(), Bits(18));
+        assert_eq!(ssi.master(USERCODE), 0b001000_100100_100100);
+        assert_eq!(ssi.shifted(CFG_IN, 2), 0b100100_100100_000101);
+
+        // against the constants from the BSDL file
+        let zu = IrEncoder::ZynqUs;
+        assert_eq!(zu.duplicated(IDCODE), 0b001001_001001);
+        assert_eq!(zu.duplicated(JPROGRAM), 0b100100_001011);
+        assert_eq!(zu.shifted(CFG_IN, 0), 0b100100_000101);
+        assert_eq!(zu.ps(Ps::JTAG_CTRL), Some(0b100000_111111));
+        assert_eq!(single.enable_pl(), None);
+    }
+}
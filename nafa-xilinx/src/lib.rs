@@ -1,2 +1,12 @@
+pub mod _16bit;
 pub mod _32bit;
+pub mod bitstream;
+pub mod bscan;
+pub mod cpld;
+pub mod dap;
+pub mod dna;
+pub mod ir;
+pub mod ltx;
+pub mod prom;
+pub mod virtex;
 pub mod zynq;
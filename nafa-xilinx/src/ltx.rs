@@ -0,0 +1,241 @@
+//! Vivado debug probe files (`.ltx`).
+//!
+//! These map net names of a design to the ports of its debug cores (ILA, VIO),
+//! so the cores can be driven by name instead of by bit offset. Only the JSON
+//! format written by Vivado 2017.1 and later is supported.
+
+use eyre::{Context, Result};
+use facet::Facet;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProbeFile {
+    pub cores: Vec<DebugCore>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugCore {
+    pub name: String,
+    pub kind: CoreKind,
+    pub probes: Vec<Probe>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CoreKind {
+    Ila,
+    Vio,
+    /// Anything else, with the `spec` string as written by Vivado.
+    Other(String),
+}
+
+/// A net (or bus) connected to some bits of a debug core port.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    pub name: String,
+    /// Index of the core port, i.e. `3` for `probe3` / `probe_in3`.
+    pub port: u32,
+    /// Highest bit of the port used by this probe, inclusive.
+    pub msb: u32,
+    /// Lowest bit of the port used by this probe.
+    pub lsb: u32,
+    pub direction: Direction,
+}
+
+/// Direction as seen from the design: VIO outputs are driven over JTAG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+impl Probe {
+    pub fn width(&self) -> u32 {
+        self.msb - self.lsb + 1
+    }
+}
+
+impl ProbeFile {
+    pub fn parse(data: &str) -> Result<Self> {
+        let raw: RawFile = facet_json::from_str(data).wrap_err("invalid ltx file")?;
+        let mut cores = Vec::new();
+        for set in raw.ltx_root.ltx_data {
+            for core in set.debug_cores {
+                cores.push(core.try_into()?);
+            }
+        }
+        Ok(Self { cores })
+    }
+
+    pub fn read(path: &std::path::Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&data)
+    }
+
+    /// Find a probe by its full net name, i.e. `top/counter_reg`.
+    pub fn find(&self, name: &str) -> Option<(&DebugCore, &Probe)> {
+        self.cores
+            .iter()
+            .find_map(|c| Some((c, c.probes.iter().find(|p| p.name == name)?)))
+    }
+}
+
+impl TryFrom<RawCore> for DebugCore {
+    type Error = eyre::Report;
+
+    fn try_from(raw: RawCore) -> Result<Self> {
+        let spec = raw.spec.to_ascii_lowercase();
+        let kind = if spec.contains("ila") {
+            CoreKind::Ila
+        } else if spec.contains("vio") {
+            CoreKind::Vio
+        } else {
+            CoreKind::Other(raw.spec)
+        };
+
+        let mut probes = Vec::new();
+        for port in raw.ports {
+            let direction = match port.direction.as_deref() {
+                Some("out") => Direction::Out,
+                _ => Direction::In,
+            };
+            for probe in port.probes {
+                let (port, msb, lsb) = parse_map(&probe.map)
+                    .wrap_err_with(|| format!("in probe {} of core {}", probe.name, raw.name))?;
+                probes.push(Probe {
+                    name: probe.name,
+                    port,
+                    msb,
+                    lsb,
+                    direction,
+                });
+            }
+        }
+        Ok(Self {
+            name: raw.name,
+            kind,
+            probes,
+        })
+    }
+}
+
+/// `probe3[7:0]`, `probe_out1[2]`, or `probe0` (single bit).
+fn parse_map(map: &str) -> Result<(u32, u32, u32)> {
+    let (port, bits) = match map.split_once('[') {
+        Some((port, rest)) => {
+            let bits = rest
+                .strip_suffix(']')
+                .ok_or_else(|| eyre::eyre!("no ']' in {map:?}"))?;
+            (port, Some(bits))
+        }
+        None => (map.trim(), None),
+    };
+    let digits = port.trim_start_matches(|c: char| !c.is_ascii_digit());
+    let port = digits
+        .parse()
+        .wrap_err_with(|| format!("no port index in {map:?}"))?;
+    let (msb, lsb) = match bits {
+        None => (0, 0),
+        Some(bits) => match bits.split_once(':') {
+            Some((msb, lsb)) => (msb.parse()?, lsb.parse()?),
+            None => {
+                let bit = bits.parse()?;
+                (bit, bit)
+            }
+        },
+    };
+    if msb < lsb {
+        return Err(eyre::eyre!("reversed bit range in {map:?}"));
+    }
+    Ok((port, msb, lsb))
+}
+
+#[derive(Facet)]
+struct RawFile {
+    ltx_root: RawRoot,
+}
+
+#[derive(Facet)]
+struct RawRoot {
+    ltx_data: Vec<RawData>,
+}
+
+#[derive(Facet)]
+struct RawData {
+    #[facet(default)]
+    debug_cores: Vec<RawCore>,
+}
+
+#[derive(Facet)]
+struct RawCore {
+    name: String,
+    #[facet(default)]
+    spec: String,
+    #[facet(default)]
+    ports: Vec<RawPort>,
+}
+
+#[derive(Facet)]
+struct RawPort {
+    #[facet(default)]
+    direction: Option<String>,
+    #[facet(default)]
+    probes: Vec<RawProbe>,
+}
+
+#[derive(Facet)]
+struct RawProbe {
+    name: String,
+    map: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = r#"{
+          "ltx_root": {
+            "version": 2,
+            "minor_version": 0,
+            "ltx_data": [{
+              "name": "EDA_PROBESET",
+              "active": 1,
+              "debug_cores": [{
+                "name": "hw_ila_1",
+                "spec": "labtools_ila_v6",
+                "ports": [{
+                  "name": "probe0",
+                  "direction": "in",
+                  "probes": [
+                    {"name": "top/counter", "map": "probe0[7:0]", "type": "data_trigger"},
+                    {"name": "top/valid", "map": "probe1[0]"}
+                  ]
+                }]
+              }, {
+                "name": "hw_vio_1",
+                "spec": "labtools_vio_v3",
+                "ports": [{
+                  "name": "probe_out0",
+                  "direction": "out",
+                  "probes": [{"name": "top/enable", "map": "probe_out0"}]
+                }]
+              }]
+            }]
+          }
+        }"#;
+        let file = ProbeFile::parse(data).unwrap();
+        assert_eq!(file.cores.len(), 2);
+        assert_eq!(file.cores[0].kind, CoreKind::Ila);
+
+        let (core, probe) = file.find("top/counter").unwrap();
+        assert_eq!(core.name, "hw_ila_1");
+        assert_eq!((probe.port, probe.msb, probe.lsb), (0, 7, 0));
+        assert_eq!(probe.width(), 8);
+
+        let (core, probe) = file.find("top/enable").unwrap();
+        assert_eq!(core.kind, CoreKind::Vio);
+        assert_eq!(probe.direction, Direction::Out);
+        assert_eq!((probe.port, probe.width()), (0, 1));
+    }
+}
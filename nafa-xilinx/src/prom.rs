@@ -0,0 +1,285 @@
+//! XCFxxS platform flash PROMs (XCF01S, XCF02S, XCF04S) and XC18V00 ISP PROMs
+//! (XC18V512 to XC18V04): erase, program, and verify through their ISC
+//! instructions.
+//!
+//! The PROM is written and read in [blocks](block) of 2048 or 4096 bits. The
+//! XCFxxS count up on their own from the address set once with
+//! [`Inst::ISC_ADDRESS_SHIFT`], while the XC18V00 take the address of each
+//! block, and read it back with [`Inst::XC18V_VERIFY`]. Either sends its
+//! contents to the FPGA in the order they were shifted in, so each byte is
+//! shifted MSB first, as when configuring over JTAG.
+//!
+//! The XCFxxP parts have a 16-bit IR and design revisions, and aren't
+//! supported.
+
+use std::time::Duration;
+
+use eyre::{Result, bail};
+use nafa_io::{
+    Command,
+    controller::TypedController,
+    devices::{XilinxPromFamily, XilinxPromInfo},
+    units::{Bits, Bytes},
+};
+
+pub type Controller<'a> = TypedController<'a, XilinxPromInfo>;
+
+/// From `xcf04s_vo20.bsd`.
+#[repr(u8)]
+#[expect(non_camel_case_types)]
+#[allow(unused, clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug)]
+#[rustfmt::skip]
+pub enum Inst {
+    BYPASS            = 0xff,
+    IDCODE            = 0xfe,
+    USERCODE          = 0xfd,
+    /// `serase` in iMPACT's SVF: run once after programming, with the
+    /// address of the whole array, to finish it.
+    SERASE            = 0x0a,
+    ISC_ENABLE        = 0xe8,
+    ISC_PROGRAM       = 0xea,
+    ISC_ADDRESS_SHIFT = 0xeb,
+    ISC_ERASE         = 0xec,
+    ISC_DATA_SHIFT    = 0xed,
+    XSC_READ          = 0xef,
+    ISC_DISABLE       = 0xf0,
+    /// `fvfy1` in `xc18v04_vq44.bsd`: XC18V00 only, reads the block at the
+    /// address set with [`Inst::ISC_ADDRESS_SHIFT`].
+    XC18V_VERIFY      = 0xf8,
+}
+
+const fn ir(inst: Inst) -> u32 {
+    inst as u8 as u32
+}
+
+/// Size of a block written with [`Inst::ISC_PROGRAM`] or read back: 4096 bits
+/// on the XC18V02 and XC18V04, 2048 on the others.
+pub fn block(info: &XilinxPromInfo) -> Bytes<usize> {
+    match info.family {
+        XilinxPromFamily::Xc18v if info.size.0 >= 2 << 17 => Bytes(4096 / 8),
+        XilinxPromFamily::XcfS | XilinxPromFamily::Xc18v => Bytes(2048 / 8),
+    }
+}
+
+/// Address of the block at `idx`, for the XC18V00. Addresses count 128-bit
+/// rows.
+fn xc18v_address(block: Bytes<usize>, idx: usize) -> [u8; 2] {
+    let rows = block.0 / (128 / 8);
+    u16::try_from(idx * rows)
+        .expect("address fits in 16 bits for PROMs up to 4 Mbit")
+        .to_le_bytes()
+}
+
+/// Value of the 6-bit register behind ISC_ENABLE.
+const ENABLE: u32 = 0x34;
+/// Address selecting the whole array, for erasing and finishing.
+const ALL: [u8; 2] = 0x0001u16.to_le_bytes();
+
+const ERASE_TIME: Duration = Duration::from_secs(15);
+const PROGRAM_TIME: Duration = Duration::from_millis(14);
+const FINISH_TIME: Duration = Duration::from_millis(37);
+/// For the PROM to leave ISC mode, after [`Inst::ISC_DISABLE`].
+const DISABLE_TIME: Duration = Duration::from_millis(110);
+/// TCKs in Run-Test/Idle between XSC_READ and reading the block.
+const READ_CLOCKS: usize = 50;
+
+/// Wire order of an image: each byte reversed, so it's shifted MSB first.
+/// The last [block] is padded with `0xff`, as erased.
+pub fn to_wire_order(image: &[u8], block: Bytes<usize>) -> Vec<u8> {
+    let mut data: Vec<u8> = image.iter().map(|b| b.reverse_bits()).collect();
+    data.resize(data.len().next_multiple_of(block.0), 0xff);
+    data
+}
+
+fn check_size(cont: &Controller<'_>, data: &[u8]) -> Result<()> {
+    let size = cont.info().size;
+    if data.len() > size.0 {
+        bail!(
+            "image of {} bytes does not fit in PROM of {size}",
+            data.len()
+        );
+    }
+    Ok(())
+}
+
+/// Enter ISC mode. The PROM stops driving the FPGA until [`disable`].
+pub async fn enable(mut cont: Controller<'_>) -> Result<()> {
+    cont.borrow()
+        .run([Command::ir(ir(Inst::ISC_ENABLE)), Command::dr_tx_bits(ENABLE, Bits(6))])
+        .await?;
+    Ok(())
+}
+
+/// Leave ISC mode.
+pub async fn disable(mut cont: Controller<'_>) -> Result<()> {
+    cont.borrow()
+        .run([
+            Command::ir(ir(Inst::ISC_DISABLE)),
+            Command::wait(DISABLE_TIME),
+            Command::ir(ir(Inst::BYPASS)),
+        ])
+        .await?;
+    Ok(())
+}
+
+/// Erase the whole PROM. Needs [`enable`] first.
+pub async fn erase(mut cont: Controller<'_>) -> Result<()> {
+    cont.borrow().progress_phase("erase");
+    cont.borrow()
+        .run([
+            Command::ir(ir(Inst::ISC_ADDRESS_SHIFT)),
+            Command::dr_tx(&ALL),
+            Command::idle_clocks(2),
+            Command::ir(ir(Inst::ISC_ERASE)),
+            Command::wait(ERASE_TIME),
+        ])
+        .await?;
+    Ok(())
+}
+
+/// Program `data` (in [wire order](to_wire_order)) from the start of the
+/// PROM, reporting progress for each block. Needs [`enable`] and [`erase`]
+/// first.
+pub async fn program(mut cont: Controller<'_>, data: &[u8]) -> Result<()> {
+    check_size(&cont, data)?;
+    let info = cont.info().clone();
+    let size = block(&info);
+    cont.borrow().progress_phase("program");
+    for (i, block) in data.chunks(size.0).enumerate() {
+        let address = match info.family {
+            XilinxPromFamily::XcfS if i != 0 => None,
+            XilinxPromFamily::XcfS => Some([0, 0]),
+            XilinxPromFamily::Xc18v => Some(xc18v_address(size, i)),
+        };
+        let mut commands =
+            vec![Command::ir(ir(Inst::ISC_DATA_SHIFT)), Command::dr_tx_with_notification(block)];
+        if let Some(address) = &address {
+            commands.extend([
+                Command::ir(ir(Inst::ISC_ADDRESS_SHIFT)),
+                Command::dr_tx(address),
+                Command::idle_clocks(1),
+            ]);
+        }
+        commands.extend([Command::ir(ir(Inst::ISC_PROGRAM)), Command::wait(PROGRAM_TIME)]);
+        cont.borrow().run(commands).await?;
+    }
+
+    cont.borrow()
+        .run([
+            Command::ir(ir(Inst::ISC_ADDRESS_SHIFT)),
+            Command::dr_tx(&ALL),
+            Command::idle_clocks(1),
+            Command::ir(ir(Inst::SERASE)),
+            Command::wait(FINISH_TIME),
+        ])
+        .await?;
+    Ok(())
+}
+
+/// Read back the first `data.len()` bytes of the PROM (in wire order), and
+/// compare them with `data`. Needs [`enable`] first.
+pub async fn verify(mut cont: Controller<'_>, data: &[u8]) -> Result<()> {
+    check_size(&cont, data)?;
+    let info = cont.info().clone();
+    let size = block(&info);
+    cont.borrow().progress_phase("verify");
+    if info.family == XilinxPromFamily::XcfS {
+        cont.borrow()
+            .run([
+                Command::ir(ir(Inst::ISC_ADDRESS_SHIFT)),
+                Command::dr_tx(&[0, 0]),
+                Command::idle_clocks(1),
+            ])
+            .await?;
+    }
+    for (i, block) in data.chunks(size.0).enumerate() {
+        let address = xc18v_address(size, i);
+        let mut commands = vec![];
+        let read = match info.family {
+            XilinxPromFamily::XcfS => Inst::XSC_READ,
+            XilinxPromFamily::Xc18v => {
+                commands.extend([
+                    Command::ir(ir(Inst::ISC_ADDRESS_SHIFT)),
+                    Command::dr_tx(&address),
+                    Command::idle_clocks(1),
+                ]);
+                Inst::XC18V_VERIFY
+            }
+        };
+        commands.extend([
+            Command::ir(ir(read)),
+            Command::idle_clocks(READ_CLOCKS),
+            Command::dr_rx_with_notification(Bytes(block.len())),
+        ]);
+        let read = cont.borrow().run(commands).await?;
+        if let Some(offset) = (read.iter().zip(block)).position(|(a, b)| a != b) {
+            let addr = i * size.0 + offset;
+            bail!(
+                "verify failed at byte {addr:#x}: read {:#04x}, expected {:#04x}",
+                read[offset].reverse_bits(),
+                block[offset].reverse_bits(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Erase, program, and (if `verify_after`) verify `data`, leaving ISC mode
+/// even if one of them fails.
+pub async fn run(mut cont: Controller<'_>, data: &[u8], verify_after: bool) -> Result<()> {
+    check_size(&cont, data)?;
+    enable(cont.reborrow()).await?;
+    let ret = async {
+        erase(cont.reborrow()).await?;
+        program(cont.reborrow(), data).await?;
+        if verify_after {
+            verify(cont.reborrow(), data).await?;
+        }
+        Ok(())
+    }
+    .await;
+    let disabled = disable(cont).await;
+    ret.and(disabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use nafa_io::fake::{self, FakeDevice};
+
+    use super::*;
+
+    #[test]
+    fn test_to_wire_order() {
+        let data = to_wire_order(&[0xaa, 0x99, 0x55, 0x66], Bytes(256));
+        assert_eq!(data.len(), 256);
+        assert_eq!(data[..4], [0x55, 0x99, 0xaa, 0x66]);
+        assert!(data[4..].iter().all(|&b| b == 0xff));
+        assert!(to_wire_order(&[], Bytes(256)).is_empty());
+    }
+
+    #[test]
+    fn test_xc18v() {
+        smol::block_on(async {
+            const XC18V04: u32 = 0x0502_6093;
+            let block = Bytes(4096 / 8);
+            let erased = vec![0xff; block.0];
+            let device = FakeDevice::new(XC18V04, Bits(8), ir(Inst::IDCODE))
+                .register(ir(Inst::ISC_DATA_SHIFT), Bits(4096), &erased)
+                .register(ir(Inst::ISC_ADDRESS_SHIFT), Bits(16), &[0, 0])
+                .constant(ir(Inst::XC18V_VERIFY), Bits(4096), &erased);
+            let mut cont = fake::controller(vec![device]).await.unwrap();
+            let mut cont = cont.typed::<XilinxPromInfo>().unwrap();
+            assert_eq!(super::block(cont.info()), block);
+            assert_eq!(xc18v_address(block, 3), [0x60, 0]);
+
+            let data = to_wire_order(&[0x12; 600], block);
+            assert_eq!(data.len(), 2 * block.0);
+            program(cont.reborrow(), &data).await.unwrap();
+
+            verify(cont.reborrow(), &[0xff; 1024]).await.unwrap();
+            let err = verify(cont, &data).await.unwrap_err();
+            assert!(err.to_string().contains("at byte 0x0"), "{err}");
+        });
+    }
+}
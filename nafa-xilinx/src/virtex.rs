@@ -0,0 +1,69 @@
+//! Virtex-4, Virtex-5, and Virtex-6. Their configuration logic takes the same
+//! 32-bit packets as the later families, but the TAP has a 10-bit (or 14-bit,
+//! on Virtex-4 FX with two PowerPCs) IR: the 6-bit instruction of
+//! [`crate::_32bit`] in the low bits, padded with ones. Virtex-4 also has its
+//! own register addresses, see [`registers::Reg::addr`].
+
+use nafa_io::{controller::TypedController, devices::XilinxVirtexInfo};
+
+use crate::_32bit::IRCapture;
+
+pub mod actions;
+mod io_utils;
+pub(crate) mod registers;
+
+pub type Controller<'a> = TypedController<'a, XilinxVirtexInfo>;
+
+pub use crate::_32bit::to_wire_order;
+
+/// 6-bit configuration instructions, as on the 32-bit families.
+#[repr(u8)]
+#[expect(non_camel_case_types)]
+#[allow(unused, clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug)]
+#[rustfmt::skip]
+pub enum Inst {
+    BYPASS    = 0b111111,
+    IDCODE    = 0b001001,
+    USERCODE  = 0b001000,
+    CFG_OUT   = 0b000100,
+    CFG_IN    = 0b000101,
+    USER1     = 0b000010,
+    USER2     = 0b000011,
+    USER3     = 0b100010,
+    USER4     = 0b100011,
+    JPROGRAM  = 0b001011,
+    JSTART    = 0b001100,
+    JSHUTDOWN = 0b001101,
+}
+
+/// The full IR value of `inst` for an IR of `irlen` bits.
+pub const fn ir(inst: Inst, irlen: u8) -> u32 {
+    let pad = (1u32 << irlen) - 1;
+    pad & !0x3f | inst as u8 as u32
+}
+
+/// The status bits of the configuration TAP, from a captured IR of `irlen`
+/// bits (see [`nafa_io::Controller::capture_ir`] for the order).
+pub(crate) fn ir_capture(capture: u32, irlen: u8) -> IRCapture {
+    IRCapture::from_bits_retain((capture >> (irlen - 6)) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ir() {
+        assert_eq!(ir(Inst::IDCODE, 10), 0x3c9);
+        assert_eq!(ir(Inst::CFG_IN, 10), 0x3c5);
+        assert_eq!(ir(Inst::IDCODE, 14), 0x3fc9);
+        assert_eq!(ir(Inst::BYPASS, 14), 0x3fff);
+
+        let capture = 0b001001 << 4 | 0b1111;
+        assert_eq!(
+            ir_capture(capture, 10),
+            IRCapture::DONE | IRCapture::ISC_DONE
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod info;
+pub mod program;
+pub mod readback;
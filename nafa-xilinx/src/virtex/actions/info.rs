@@ -0,0 +1,44 @@
+use eyre::Result;
+use facet::Facet;
+
+use crate::virtex::{
+    Controller, Inst,
+    io_utils::{read_device_register_word as device_register, read_jtag_register as jtag_register},
+    registers::Reg,
+};
+
+#[derive(Facet)]
+pub struct Virtex {
+    pub jtag: VirtexJtag,
+    pub registers: VirtexRegisters,
+}
+
+#[derive(Facet)]
+pub struct VirtexJtag {
+    pub idcode: [u8; 4],
+    pub usercode: [u8; 4],
+}
+
+#[derive(Facet)]
+pub struct VirtexRegisters {
+    pub stat: u32,
+    pub ctl0: u32,
+    pub cor0: u32,
+    pub idcode: u32,
+}
+
+impl Virtex {
+    pub async fn read(mut cont: Controller<'_>) -> Result<Self> {
+        let jtag = VirtexJtag {
+            idcode: *jtag_register(cont.reborrow(), Inst::IDCODE).await?,
+            usercode: *jtag_register(cont.reborrow(), Inst::USERCODE).await?,
+        };
+        let registers = VirtexRegisters {
+            stat: device_register(cont.reborrow(), Reg::Stat).await?,
+            ctl0: device_register(cont.reborrow(), Reg::Ctl0).await?,
+            cor0: device_register(cont.reborrow(), Reg::Cor0).await?,
+            idcode: device_register(cont.reborrow(), Reg::Idcode).await?,
+        };
+        Ok(Self { jtag, registers })
+    }
+}
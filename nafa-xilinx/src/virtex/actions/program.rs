@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use eyre::{Result, bail};
+use nafa_io::{Command, units::Bytes};
+
+use crate::{
+    _32bit::IRCapture,
+    virtex::{
+        Controller, Inst,
+        io_utils::{irlen, read_device_register_word},
+        ir, ir_capture,
+        registers::Reg,
+    },
+};
+
+pub struct ProgramStats {
+    pub time_shutdown: Duration,
+    pub time_program: Duration,
+    pub time_startup: Duration,
+}
+
+/// TCKs in Run-Test/Idle after JSTART, for the startup sequence to finish.
+const STARTUP_CLOCKS: Bytes<usize> = Bytes(2000 / 8);
+/// How long housecleaning after JPROGRAM may take.
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Clear the configuration, shift in `data` (in wire order), and run the
+/// startup sequence. Fails with STAT if DONE didn't go high.
+pub async fn run(mut cont: Controller<'_>, data: &[u8]) -> Result<ProgramStats> {
+    let irlen = irlen(&mut cont);
+    let start = Instant::now();
+    cont.borrow().progress_phase("shutdown");
+    cont.borrow()
+        .run([Command::ir(ir(Inst::JPROGRAM, irlen))])
+        .await?;
+    loop {
+        let capture = ir_capture(cont.borrow().capture_ir().await?, irlen);
+        if capture.contains(IRCapture::INIT) {
+            break;
+        }
+        if start.elapsed() > INIT_TIMEOUT {
+            bail!("INIT did not go high after JPROGRAM");
+        }
+    }
+    let end_shutdown = Instant::now();
+
+    cont.borrow().progress_phase("program");
+    cont.borrow()
+        .run([Command::ir(ir(Inst::CFG_IN, irlen)), Command::dr_tx_with_notification(data)])
+        .await?;
+    let end_program = Instant::now();
+
+    cont.borrow().progress_phase("startup");
+    cont.borrow()
+        .run([Command::ir(ir(Inst::JSTART, irlen)), Command::idle(STARTUP_CLOCKS)])
+        .await?;
+    let capture = ir_capture(cont.borrow().capture_ir().await?, irlen);
+    if !capture.contains(IRCapture::DONE) {
+        match read_device_register_word(cont.reborrow(), Reg::Stat).await {
+            Ok(stat) => bail!("DONE did not go high, STAT {stat:#010x}"),
+            Err(_) => bail!("DONE did not go high, and STAT could not be read"),
+        }
+    }
+
+    Ok(ProgramStats {
+        time_shutdown: end_shutdown - start,
+        time_program: end_program - end_shutdown,
+        time_startup: end_program.elapsed(),
+    })
+}
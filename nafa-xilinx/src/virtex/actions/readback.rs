@@ -0,0 +1,53 @@
+use eyre::{OptionExt as _, Result};
+use nafa_io::{
+    Command,
+    units::{Bytes, Words32},
+};
+
+use crate::virtex::{
+    Controller, Inst,
+    io_utils::irlen,
+    ir,
+    registers::{CmdCode, NOOP, OpCode, Reg, SYNC, type1, type2},
+    to_wire_order,
+};
+
+/// Read back `words` words of configuration memory, starting at frame 0, in
+/// wire order.
+pub async fn run(mut cont: Controller<'_>, words: Words32<usize>) -> Result<&[u8]> {
+    let family = cont.info().family;
+    let addr = |reg: Reg| {
+        reg.addr(family)
+            .ok_or_eyre("register not present on this family")
+    };
+    let irlen = irlen(&mut cont);
+    let count = u32::try_from(words.0)?;
+
+    let packets = [
+        SYNC,
+        NOOP,
+        type1(OpCode::Write, addr(Reg::Cmd)?, 1),
+        CmdCode::Rcrc as u32,
+        NOOP,
+        NOOP,
+        type1(OpCode::Write, addr(Reg::Far)?, 1),
+        0,
+        type1(OpCode::Write, addr(Reg::Cmd)?, 1),
+        CmdCode::Rcfg as u32,
+        type1(OpCode::Read, addr(Reg::Fdro)?, 0),
+        type2(OpCode::Read, count),
+    ]
+    .into_iter()
+    .chain([NOOP; 32]);
+    let readback: Vec<u8> = packets.flat_map(to_wire_order).collect();
+
+    Ok(cont
+        .consume()
+        .run([
+            Command::ir(ir(Inst::CFG_IN, irlen)),
+            Command::dr_tx(&readback),
+            Command::ir(ir(Inst::CFG_OUT, irlen)),
+            Command::dr_rx_with_notification(Bytes::from(words)),
+        ])
+        .await?)
+}
@@ -0,0 +1,51 @@
+use eyre::{OptionExt as _, Result};
+use nafa_io::{Command, WordOrder, WordsExt as _, units::Bytes};
+
+use super::{
+    Controller, Inst, ir,
+    registers::{NOOP, OpCode, Reg, SYNC, type1},
+    to_wire_order,
+};
+
+pub fn irlen(cont: &mut Controller<'_>) -> u8 {
+    cont.borrow().info().irlen.0
+}
+
+/// Read `count` words of `reg` through CFG_IN/CFG_OUT, in wire order.
+pub async fn read_device_register(mut cont: Controller<'_>, reg: Reg, count: u32) -> Result<&[u8]> {
+    let family = cont.info().family;
+    let addr = reg
+        .addr(family)
+        .ok_or_eyre("register not present on this family")?;
+    let irlen = irlen(&mut cont);
+    let packets = [SYNC, NOOP, type1(OpCode::Read, addr, count), NOOP, NOOP];
+    let tiny_bitstream: Vec<u8> = packets.into_iter().flat_map(to_wire_order).collect();
+
+    let data = cont
+        .consume()
+        .run([
+            Command::ir(ir(Inst::CFG_IN, irlen)),
+            Command::dr_tx(&tiny_bitstream),
+            Command::ir(ir(Inst::CFG_OUT, irlen)),
+            Command::dr_rx(Bytes(4 * count as usize)),
+        ])
+        .await?;
+    Ok(data)
+}
+
+pub async fn read_device_register_word(cont: Controller<'_>, reg: Reg) -> Result<u32> {
+    let data = read_device_register(cont, reg, 1).await?;
+    (data.words(WordOrder::MSB_FIRST).next()).ok_or_eyre("no register word read")
+}
+
+pub async fn read_jtag_register<const N: usize>(
+    mut cont: Controller<'_>,
+    inst: Inst,
+) -> Result<&[u8; N]> {
+    let irlen = irlen(&mut cont);
+    let slice = cont
+        .consume()
+        .run([Command::ir(ir(inst, irlen)), Command::dr_rx(Bytes(N))])
+        .await?;
+    Ok(slice.try_into()?)
+}
@@ -0,0 +1,64 @@
+//! Configuration registers, by name rather than address, since Virtex-4
+//! (UG071) numbers them differently from Virtex-5/6 (UG191, UG360).
+
+use nafa_io::devices::VirtexFamily;
+
+pub use crate::_32bit::registers::{CmdCode, OpCode, type2};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(unused)]
+pub enum Reg {
+    Crc,
+    Far,
+    Fdri,
+    Fdro,
+    Cmd,
+    Ctl0,
+    Mask,
+    Stat,
+    Lout,
+    Cor0,
+    Mfwr,
+    Idcode,
+    /// Virtex-5/6 only.
+    Cor1,
+    /// Virtex-5/6 only.
+    Wbstar,
+    /// Virtex-5/6 only.
+    Bootsts,
+}
+
+impl Reg {
+    pub const fn addr(self, family: VirtexFamily) -> Option<u32> {
+        use VirtexFamily as F;
+        let addr = match (self, family) {
+            (Self::Crc, _) => 0,
+            (Self::Far, _) => 1,
+            (Self::Fdri, _) => 2,
+            (Self::Fdro, _) => 3,
+            (Self::Cmd, _) => 4,
+            (Self::Ctl0, _) => 5,
+            (Self::Mask, _) => 6,
+            (Self::Stat, _) => 7,
+            (Self::Lout, _) => 8,
+            (Self::Cor0, _) => 9,
+            (Self::Mfwr, _) => 10,
+            (Self::Idcode, F::V4) => 14,
+            (Self::Idcode, F::V5 | F::V6) => 12,
+            (Self::Cor1, F::V5 | F::V6) => 14,
+            (Self::Wbstar, F::V5 | F::V6) => 16,
+            (Self::Bootsts, F::V5 | F::V6) => 22,
+            (Self::Cor1 | Self::Wbstar | Self::Bootsts, F::V4) => return None,
+        };
+        Some(addr)
+    }
+}
+
+/// Type 1 packet header, laid out as on the later families.
+pub const fn type1(op: OpCode, addr: u32, word_count: u32) -> u32 {
+    let opcode = ((op as u8 & 0x3) as u32) << 27;
+    1 << 29 | opcode | (addr & 0x3fff) << 13 | (word_count & 0x7ff)
+}
+
+pub const SYNC: u32 = 0xaa99_5566;
+pub const NOOP: u32 = 0x2000_0000;
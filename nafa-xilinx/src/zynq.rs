@@ -2,12 +2,20 @@
 //!
 //! These are _almost_ the same as the rest of the devices. However, they have
 //! an IRLEN of 12 instead of 6 --- there's a processor and FPGA stuck together
-//! acting as a single device.
+//! acting as a single device. The instructions are built with
+//! [`IrEncoder::ZynqUs`](crate::ir::IrEncoder::ZynqUs).
 
-use nafa_io::{controller::TypedController, devices::Xilinx32Info};
+use eyre::Result;
+use nafa_io::{controller::TypedController, devices::XilinxZynqInfo};
 
 pub mod actions;
 mod commands;
-mod io_utils;
+pub(crate) mod io_utils;
 
-pub type Controller<'a> = TypedController<'a, Xilinx32Info>;
+pub type Controller<'a> = TypedController<'a, XilinxZynqInfo>;
+
+/// The 96-bit FUSE_DNA, for [`crate::dna`] and the info. Needs the PL TAP on
+/// the chain.
+pub(crate) async fn fuse_dna(cont: Controller<'_>) -> Result<[u8; 12]> {
+    Ok(*io_utils::read_jtag_register_sized(cont, commands::FUSE_DNA).await?)
+}
@@ -7,9 +7,10 @@ use crate::{
         registers::Addr,
     },
     zynq::{
-        Controller, commands,
+        self, Controller, commands,
         io_utils::{
-            read_device_register_word as device_register, read_jtag_register_sized as jtag_register,
+            enable_pl, read_device_register_word as device_register,
+            read_jtag_register_sized as jtag_register,
         },
     },
 };
@@ -30,7 +31,7 @@ pub struct ZPJtag {
     pub jstatus: [u8; 4],
     pub xsc_dna: [u8; 12],
     pub fuse_key: [u8; 32],
-    pub fuse_dna: [u8; 8],
+    pub fuse_dna: [u8; 12],
     pub fuse_cntl: [u8; 4],
     pub fuse_user_ps: [u8; 4],
     pub user1: [u8; 4],
@@ -43,6 +44,7 @@ pub struct ZPJtag {
 
 impl ZP {
     pub async fn read(mut cont: Controller<'_>) -> Result<Self> {
+        enable_pl(cont.reborrow()).await?;
         let jtag = ZPJtag {
             idcode_ps: *jtag_register(cont.reborrow(), commands::IDCODE).await?,
             idcode_pl: *jtag_register(cont.reborrow(), commands::IDCODE_PL).await?,
@@ -52,7 +54,7 @@ impl ZP {
             jstatus: *jtag_register(cont.reborrow(), commands::JSTATUS).await?,
             xsc_dna: *jtag_register(cont.reborrow(), commands::XSC_DNA).await?,
             fuse_key: *jtag_register(cont.reborrow(), commands::FUSE_KEY).await?,
-            fuse_dna: *jtag_register(cont.reborrow(), commands::FUSE_DNA).await?,
+            fuse_dna: zynq::fuse_dna(cont.reborrow()).await?,
             fuse_cntl: *jtag_register(cont.reborrow(), commands::FUSE_CNTL).await?,
             fuse_user_ps: *jtag_register(cont.reborrow(), commands::FUSE_USER_PS).await?,
             user1: *jtag_register(cont.reborrow(), commands::USER1).await?,
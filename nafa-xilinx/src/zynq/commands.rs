@@ -1,45 +1,59 @@
 pub use self::internal::*;
 
-// from xczu9eg_ffvc900.bsd
+// from xczu9eg_ffvc900.bsd. The PL instructions are the same as on the other
+// 32-bit devices, encoded by [`IrEncoder::ZynqUs`].
 #[rustfmt::skip]
 #[allow(unused)]
 mod internal {
-    pub const IDCODE:       u32 = 0b_001001_001001; // PS IDCODE, DEVICE_ID reg
-    pub const IDCODE_PL:    u32 = 0b_100100_100101; // PRIVATE, PL IDCODE, DEVICE_ID reg
-    pub const IDCODE_PSPL:  u32 = 0b_001001_100101; // PRIVATE, PS AND PL IDCODES, DEVICE_ID reg
-    pub const BYPASS:       u32 = 0b_111111_111111; // PS BYPASS, BYPASS reg
-    pub const EXTEST:       u32 = 0b_100110_100110; // BOUNDARY reg
-    pub const SAMPLE:       u32 = 0b_111111_000001; // BOUNDARY reg
-    pub const PRELOAD:      u32 = 0b_111111_000001; // BOUNDARY reg, Same as SAMPLE
-    pub const USERCODE:     u32 = 0b_100100_001000; // PL USER CODE, DEVICE_ID reg
-    pub const HIGHZ_IO:     u32 = 0b_001010_001010; // PRIVATE, BYPASS reg
-    pub const JTAG_STATUS:  u32 = 0b_011111_111111; // PRIVATE, STATUS from PS
-    pub const JSTATUS:      u32 = 0b_100100_100001; // PRIVATE, STATUS from PL
-    pub const EXTEST_PULSE: u32 = 0b_100110_111100; // BOUNDARY reg
-    pub const EXTEST_TRAIN: u32 = 0b_100110_111101; // BOUNDARY reg
-    pub const ISC_ENABLE:   u32 = 0b_100100_010000; // PRIVATE, ISC_CONFIG
-    pub const ISC_PROGRAM:  u32 = 0b_100100_010001; // PRIVATE, ISC_PDATA
-    pub const ISC_PROG_SEC: u32 = 0b_100100_010010; // PRIVATE
-    pub const ISC_NOOP:     u32 = 0b_100100_010100; // PRIVATE, ISC_DEFAULT
-    pub const ISC_DISABLE:  u32 = 0b_100100_010110; // PRIVATE, ISC_CONFIG
-    pub const ISC_READ:     u32 = 0b_100100_010101; // PRIVATE, ISC_CONFIG
-    pub const XSC_DNA:      u32 = 0b_100100_010111; // PRIVATE, DNA reg
-    pub const CFG_IN:       u32 = 0b_100100_000101; // PRIVATE
-    pub const CFG_OUT:      u32 = 0b_100100_000100; // PRIVATE
-    pub const JPROGRAM:     u32 = 0b_100100_001011; // PRIVATE
-    pub const JSTART:       u32 = 0b_100100_001100; // PRIVATE
-    pub const JSHUTDOWN:    u32 = 0b_100100_001101; // PRIVATE
-    pub const FUSE_CTS:     u32 = 0b_100100_110000; // PRIVATE
-    pub const FUSE_KEY:     u32 = 0b_100100_110001; // PRIVATE
-    pub const FUSE_DNA:     u32 = 0b_100100_110010; // PRIVATE
-    pub const FUSE_CNTL:    u32 = 0b_100100_110100; // PRIVATE
-    pub const FUSE_USER_PS: u32 = 0b_001000_111111; // PRIVATE, PS USER CODE, DEVICE_ID reg
-    pub const USER1:        u32 = 0b_100100_000010; // PRIVATE, Not available until after configuration
-    pub const USER2:        u32 = 0b_100100_000011; // PRIVATE, Not available until after configuration
-    pub const USER3:        u32 = 0b_100100_100010; // PRIVATE, Not available until after configuration
-    pub const USER4:        u32 = 0b_100100_100011; // PRIVATE, Not available until after configuration
-    pub const SYSMON_DRP:   u32 = 0b_100100_110111; // PRIVATE
-    pub const JTAG_CTRL:    u32 = 0b_100000_111111; // PRIVATE, JTAG_CTRL reg
-    pub const ERROR_STATUS: u32 = 0b_111110_111111; // PRIVATE, PMU ERROR_STATUS reg
-    pub const PMU_MDM:      u32 = 0b_000011_111111; // PRIVATE
+    use crate::{
+        _32bit::commands as pl,
+        ir::{IrEncoder, Ps},
+    };
+
+    const ZU: IrEncoder = IrEncoder::ZynqUs;
+    const fn ps(inst: Ps) -> u32 {
+        match ZU.ps(inst) {
+            Some(inst) => inst,
+            None => unreachable!(),
+        }
+    }
+
+    pub const IDCODE:       u32 = ZU.duplicated(pl::IDCODE);       // PS IDCODE, DEVICE_ID reg
+    pub const IDCODE_PL:    u32 = 0b_100100_100101;                // PRIVATE, PL IDCODE, DEVICE_ID reg
+    pub const IDCODE_PSPL:  u32 = 0b_001001_100101;                // PRIVATE, PS AND PL IDCODES, DEVICE_ID reg
+    pub const BYPASS:       u32 = ZU.duplicated(pl::BYPASS);       // PS BYPASS, BYPASS reg
+    pub const EXTEST:       u32 = ZU.duplicated(pl::EXTEST);       // BOUNDARY reg
+    pub const SAMPLE:       u32 = ZU.duplicated(pl::SAMPLE);       // BOUNDARY reg
+    pub const PRELOAD:      u32 = ZU.duplicated(pl::SAMPLE);       // BOUNDARY reg, Same as SAMPLE
+    pub const USERCODE:     u32 = ZU.master(pl::USERCODE);         // PL USER CODE, DEVICE_ID reg
+    pub const HIGHZ_IO:     u32 = ZU.duplicated(pl::HIGHZ_IO);     // PRIVATE, BYPASS reg
+    pub const JTAG_STATUS:  u32 = ps(Ps::JTAG_STATUS);             // PRIVATE, STATUS from PS
+    pub const JSTATUS:      u32 = ZU.duplicated(pl::JSTATUS);      // PRIVATE, STATUS from PL
+    pub const EXTEST_PULSE: u32 = ZU.duplicated(pl::EXTEST_PULSE); // BOUNDARY reg
+    pub const EXTEST_TRAIN: u32 = ZU.duplicated(pl::EXTEST_TRAIN); // BOUNDARY reg
+    pub const ISC_ENABLE:   u32 = ZU.duplicated(pl::ISC_ENABLE);   // PRIVATE, ISC_CONFIG
+    pub const ISC_PROGRAM:  u32 = ZU.duplicated(pl::ISC_PROGRAM);  // PRIVATE, ISC_PDATA
+    pub const ISC_PROG_SEC: u32 = ZU.duplicated(pl::XSC_PROG_SEC); // PRIVATE
+    pub const ISC_NOOP:     u32 = ZU.duplicated(pl::ISC_NOOP);     // PRIVATE, ISC_DEFAULT
+    pub const ISC_DISABLE:  u32 = ZU.duplicated(pl::ISC_DISABLE);  // PRIVATE, ISC_CONFIG
+    pub const ISC_READ:     u32 = ZU.duplicated(pl::ISC_READ);     // PRIVATE, ISC_CONFIG
+    pub const XSC_DNA:      u32 = ZU.master(pl::XSC_DNA);          // PRIVATE, DNA reg
+    pub const CFG_IN:       u32 = ZU.shifted(pl::CFG_IN, 0);       // PRIVATE
+    pub const CFG_OUT:      u32 = ZU.shifted(pl::CFG_OUT, 0);      // PRIVATE
+    pub const JPROGRAM:     u32 = ZU.duplicated(pl::JPROGRAM);     // PRIVATE
+    pub const JSTART:       u32 = ZU.duplicated(pl::JSTART);       // PRIVATE
+    pub const JSHUTDOWN:    u32 = ZU.duplicated(pl::JSHUTDOWN);    // PRIVATE
+    pub const FUSE_CTS:     u32 = ZU.shifted(pl::FUSE_CTS, 0);     // PRIVATE
+    pub const FUSE_KEY:     u32 = ZU.shifted(pl::FUSE_KEY, 0);     // PRIVATE
+    pub const FUSE_DNA:     u32 = ZU.shifted(pl::FUSE_DNA, 0);     // PRIVATE
+    pub const FUSE_CNTL:    u32 = ZU.shifted(pl::FUSE_CNTL, 0);    // PRIVATE
+    pub const FUSE_USER_PS: u32 = ps(Ps::FUSE_USER_PS);            // PRIVATE, PS USER CODE, DEVICE_ID reg
+    pub const USER1:        u32 = ZU.master(pl::USER1);            // PRIVATE, Not available until after configuration
+    pub const USER2:        u32 = ZU.master(pl::USER2);            // PRIVATE, Not available until after configuration
+    pub const USER3:        u32 = ZU.master(pl::USER3);            // PRIVATE, Not available until after configuration
+    pub const USER4:        u32 = ZU.master(pl::USER4);            // PRIVATE, Not available until after configuration
+    pub const SYSMON_DRP:   u32 = ZU.master(pl::SYSMON_DRP);       // PRIVATE
+    pub const JTAG_CTRL:    u32 = ps(Ps::JTAG_CTRL);               // PRIVATE, JTAG_CTRL reg
+    pub const ERROR_STATUS: u32 = ps(Ps::ERROR_STATUS);            // PRIVATE, PMU ERROR_STATUS reg
+    pub const PMU_MDM:      u32 = ps(Ps::PMU_MDM);                 // PRIVATE
 }
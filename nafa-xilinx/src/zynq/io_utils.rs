@@ -1,34 +1,52 @@
 use eyre::Result;
 use nafa_io::{
-    Command,
+    Command, WordOrder, WordsExt as _,
     units::{Bytes, Words32},
 };
 
 use super::{Controller, commands};
-use crate::_32bit::{
-    bitstream_to_wire_order, from_wire_order,
-    registers::{Addr, OpCode, Type1},
+use crate::{
+    _32bit::{
+        bitstream_to_wire_order,
+        registers::{Addr, OpCode, Type1},
+    },
+    ir::IrEncoder,
 };
 
+/// Add the PL TAP to the chain, see [`IrEncoder::enable_pl`].
+pub(crate) async fn enable_pl(cont: Controller<'_>) -> Result<()> {
+    let Some((ir, dr)) = IrEncoder::ZynqUs.enable_pl() else {
+        return Ok(());
+    };
+    cont.consume()
+        .run([Command::ir(ir), Command::dr_tx(&dr)])
+        .await?;
+    Ok(())
+}
+
 pub async fn read_device_register(cont: Controller<'_>, reg: Type1) -> Result<&[u8]> {
     let tiny_bitstream =
         bitstream_to_wire_order([Type1::SYNC, Type1::NOOP, reg.to_raw(), Type1::NOOP, Type1::NOOP]);
     let tiny_bitstream = tiny_bitstream.as_flattened();
 
-    cont.consume()
+    let data = cont
+        .consume()
         .run([
             Command::ir(commands::CFG_IN),
             Command::dr_tx(tiny_bitstream),
             Command::ir(commands::CFG_OUT),
             Command::dr_rx(Bytes::from(reg.word_count.into_())),
         ])
-        .await
+        .await?;
+    Ok(data)
 }
 
 pub(crate) async fn read_device_register_word(cont: Controller<'_>, addr: Addr) -> Result<u32> {
-    let data = read_device_register_sized(cont, addr).await?;
-    let data = from_wire_order(*data);
-    Ok(data)
+    let data = read_device_register_sized::<4>(cont, addr).await?;
+    Ok(data
+        .words(WordOrder::MSB_FIRST)
+        .next()
+        .expect("one word read"))
 }
 
 pub(crate) async fn read_device_register_sized<const N: usize>(
@@ -50,9 +68,11 @@ pub(crate) async fn read_jtag_register(
     inst: u32,
     len: Bytes<usize>,
 ) -> Result<&[u8]> {
-    cont.consume()
+    let data = cont
+        .consume()
         .run([Command::ir(inst), Command::dr_rx(len)])
-        .await
+        .await?;
+    Ok(data)
 }
 
 pub(crate) async fn read_jtag_register_sized<const N: usize>(
@@ -0,0 +1,170 @@
+//! Hardware-in-the-loop checks against a real board, described by a fixture in
+//! `tests/hil/<board>.toml`:
+//!
+//! ```text
+//! cargo test -p nafa-xilinx --features hil -- --board arty
+//! ```
+//!
+//! The board must be the only one plugged in. Set `NAFA_HIL_BITSTREAM` to a
+//! `.bin` for the board to also check programming.
+//!
+//! Without `--board`, nothing is run, so `--all-features` works without
+//! hardware.
+
+#[path = "../examples/common/mod.rs"]
+mod common;
+
+use std::{path::PathBuf, process::ExitCode};
+
+use eyre::{OptionExt as _, Result, bail};
+use facet::Facet;
+use nafa_io::{Controller, devices::Xilinx32Info};
+use nafa_xilinx::_32bit::{
+    actions,
+    drp::{Addr, Cmd, Command},
+};
+
+#[derive(Facet)]
+struct Fixture {
+    chain: Vec<ChainEntry>,
+    #[facet(default)]
+    xadc: Option<Xadc>,
+}
+
+#[derive(Facet)]
+struct ChainEntry {
+    idcode: String,
+    #[facet(default)]
+    name: Option<String>,
+}
+
+#[derive(Facet)]
+struct Xadc {
+    #[facet(default)]
+    temperature: Option<Range>,
+    #[facet(default)]
+    vccint: Option<Range>,
+    #[facet(default)]
+    vccaux: Option<Range>,
+}
+
+#[derive(Facet)]
+struct Range {
+    min: f32,
+    max: f32,
+}
+
+fn main() -> ExitCode {
+    // the rest are libtest flags cargo passes along, i.e. `--nocapture`
+    let mut args = std::env::args().skip_while(|a| a != "--board");
+    let Some(board) = args.nth(1) else {
+        println!("no --board given, skipping hardware-in-the-loop tests");
+        return ExitCode::SUCCESS;
+    };
+
+    match smol::block_on(run(&board)) {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(failed) => {
+            println!("\n{failed} check(s) failed on {board}");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            println!("{e:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns the number of failed checks.
+async fn run(board: &str) -> Result<usize> {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "hil", board]
+        .iter()
+        .collect();
+    let path = path.with_extension("toml");
+    let fixture: Fixture = facet_toml::from_str(&std::fs::read_to_string(&path)?)
+        .map_err(|e| eyre::eyre!("invalid fixture {}: {e}", path.display()))?;
+
+    let mut cont = common::open().await?;
+    let mut failed = 0;
+    let mut check = |name: &str, result: Result<()>| {
+        match &result {
+            Ok(()) => println!("test {board}::{name} ... ok"),
+            Err(e) => println!("test {board}::{name} ... FAILED\n    {e}"),
+        }
+        failed += usize::from(result.is_err());
+    };
+
+    check("chain", check_chain(&cont, &fixture.chain));
+    check("info", check_info(&mut cont).await);
+    if let Some(xadc) = &fixture.xadc {
+        check("xadc", check_xadc(&mut cont, xadc).await);
+    }
+    if let Some(bitstream) = std::env::var_os("NAFA_HIL_BITSTREAM") {
+        check("program", check_program(&mut cont, bitstream.into()).await);
+    }
+    Ok(failed)
+}
+
+fn check_chain(cont: &Controller, expected: &[ChainEntry]) -> Result<()> {
+    let found: Vec<_> = cont.chain().map(|(idcode, _)| *idcode).collect();
+    if found.len() != expected.len() {
+        bail!("expected {} devices, found {}", expected.len(), found.len());
+    }
+    for (idx, (idcode, entry)) in found.iter().zip(expected).enumerate() {
+        let hex = entry.idcode.trim_start_matches("0x");
+        let want = nafa_io::jtag::IdCode::new(u32::from_str_radix(hex, 16)?);
+        if idcode.strip_version() != want.strip_version() {
+            let name = entry.name.as_deref().unwrap_or("?");
+            bail!(
+                "device {idx}: expected {:08X} ({name}), found {:08X}",
+                want.code(),
+                idcode.code(),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn typed(cont: &mut Controller) -> Result<nafa_xilinx::_32bit::Controller<'_>> {
+    cont.typed::<Xilinx32Info>()
+        .ok_or_eyre("selected device is not xilinx")
+}
+
+async fn check_info(cont: &mut Controller) -> Result<()> {
+    actions::info::run(typed(cont)?).await?;
+    Ok(())
+}
+
+async fn check_xadc(cont: &mut Controller, xadc: &Xadc) -> Result<()> {
+    let sensors = [
+        ("temperature", Addr::Temperature, &xadc.temperature),
+        ("vccint", Addr::VccInt, &xadc.vccint),
+        ("vccaux", Addr::VccAux, &xadc.vccaux),
+    ];
+    let cont = typed(cont)?;
+    let family = cont.info().family;
+    let regs = sensors.map(|(_, addr, _)| Command {
+        cmd: Cmd::Read,
+        addr,
+        data: 0,
+    });
+    let values = actions::xadc::run(cont, regs).await?;
+
+    for ((name, addr, range), raw) in sensors.iter().zip(values) {
+        let Some(range) = range else { continue };
+        let value = common::convert(family, *addr, raw).ok_or_eyre("no transfer")?;
+        if !(range.min..=range.max).contains(&value) {
+            bail!("{name} {value:.3} outside of {}..={}", range.min, range.max);
+        }
+    }
+    Ok(())
+}
+
+async fn check_program(cont: &mut Controller, path: PathBuf) -> Result<()> {
+    let data: Vec<u8> = (std::fs::read(path)?.iter())
+        .map(|b| b.reverse_bits())
+        .collect();
+    // fails with the decoded STAT if the device didn't start
+    actions::program::run(typed(cont)?, &data).await?;
+    Ok(())
+}